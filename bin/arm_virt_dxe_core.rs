@@ -46,6 +46,8 @@ static LOGGER: AdvancedLogger<UartPl011> = AdvancedLogger::new(
         TargetFilter { target: "gcd_measure", log_level: log::LevelFilter::Off, hw_filter_override: None },
         TargetFilter { target: "allocations", log_level: log::LevelFilter::Off, hw_filter_override: None },
         TargetFilter { target: "efi_memory_map", log_level: log::LevelFilter::Off, hw_filter_override: None },
+        TargetFilter { target: "mm_comm", log_level: log::LevelFilter::Off, hw_filter_override: None },
+        TargetFilter { target: "sw_mmi", log_level: log::LevelFilter::Off, hw_filter_override: None },
     ],
     log::LevelFilter::Info,
     UartPl011::new(PL011_UART_BASE),
@@ -65,6 +67,15 @@ const GICD_BASE: u64 = 0x0800_0000;
 /// Base address of the GIC redistributor on the QEMU Arm Virt machine.
 const GICR_BASE: u64 = 0x080A_0000;
 
+/// Base address of the non-secure buffer TF-A shares with the Standalone MM partition on QEMU
+/// (`PLAT_QEMU_SP_IMAGE_NS_BUF_BASE`).
+#[cfg(feature = "armvirt_standalone_mm")]
+const STANDALONE_MM_NS_BUFFER_BASE: u64 = 0x4010_0000;
+
+/// Size of the non-secure buffer shared with the Standalone MM partition.
+#[cfg(feature = "armvirt_standalone_mm")]
+const STANDALONE_MM_NS_BUFFER_SIZE: u64 = 0x1_0000;
+
 /// The virtio-serial-mmio base address used. This is the only device
 /// so it's the last of 32 0x200 byte blocks start at 0xA000000.
 #[cfg(feature = "build_debugger")]
@@ -77,6 +88,19 @@ static DEBUGGER: patina_debugger::PatinaDebugger<VirtioSerial> =
         .with_transport_init() // Transport init required for virtio
         .with_force_enable(_ENABLE_DEBUGGER);
 
+/// SMBIOS platform identity, version and entry points, shared by the SMBIOS provider and platform components.
+fn smbios_config() -> qemu_resources::smbios::SmbiosPlatformConfig {
+    qemu_resources::smbios::SmbiosPlatformConfig {
+        product_name: "QEMU Arm Virtual Machine",
+        sku_number: "ARMVIRT-STANDARD",
+        baseboard_product: "QEMU Arm Virtual Machine",
+        oem_strings: &[concat!("Patina DXE Core ", env!("CARGO_PKG_VERSION"))],
+        smbios_version: (3, 9),
+        entry_point: qemu_resources::smbios::SmbiosEntryPoint::Bits64,
+        ..Default::default()
+    }
+}
+
 struct ArmVirt;
 
 // Default `MemoryInfo` implementation is sufficient for Arm Virt.
@@ -93,7 +117,7 @@ impl CpuInfo for ArmVirt {
 impl ComponentInfo for ArmVirt {
     fn components(mut add: Add<Component>) {
         add.component(AdvancedLoggerComponent::<UartPl011>::new(&LOGGER));
-        add.component(patina_smbios::component::SmbiosProvider::new(3, 9));
+        add.component(smbios_config().provider());
         add.component(armvirt_services::smbios_platform::ArmVirtSmbiosPlatform::new());
         add.component(patina_test::component::TestRunner::default().with_callback(|test_name, err_msg| {
             log::error!("Test {} failed: {}", test_name, err_msg);
@@ -107,9 +131,23 @@ impl ComponentInfo for ArmVirt {
                | patina::performance::Measurement::StartImage, // Adds start image measurements.
         ));
         add.component(patina_acpi::component::AcpiComponent::default());
+        #[cfg(feature = "armvirt_standalone_mm")]
+        {
+            add.component(armvirt_services::mm_config_provider::ArmVirtMmConfigurationProvider);
+            add.component(armvirt_services::mm_control::ArmVirtPlatformMmControl::new());
+            add.component(patina_mm::component::communicator::MmCommunicator::new());
+        }
     }
 
-    fn configs(_add: Add<Config>) {}
+    fn configs(mut add: Add<Config>) {
+        add.config(smbios_config());
+        #[cfg(feature = "armvirt_standalone_mm")]
+        add.config(armvirt_services::mm_config_provider::ArmVirtMmConfiguration {
+            comm_buffer_base: STANDALONE_MM_NS_BUFFER_BASE,
+            comm_buffer_size: STANDALONE_MM_NS_BUFFER_SIZE,
+            ..Default::default()
+        });
+    }
 }
 
 impl PlatformInfo for ArmVirt {
@@ -18,7 +18,9 @@ use patina::{
 use patina_dxe_core::*;
 use patina_ffs_extractors::CompositeSectionExtractor;
 use patina_stacktrace::StackTrace;
-use qemu_resources::q35::timer;
+use qemu_resources::q35::{component::service as q35_services, smi_features, timer};
+extern crate alloc;
+use alloc::vec;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
@@ -40,6 +42,8 @@ static LOGGER: SerialLogger<Uart16550> = SerialLogger::new(
         ("efi_memory_map", log::LevelFilter::Off),
         ("gcd_measure", log::LevelFilter::Off),
         ("goblin", log::LevelFilter::Off),
+        ("mm_comm", log::LevelFilter::Off),
+        ("sw_mmi", log::LevelFilter::Off),
     ],
     log::LevelFilter::Info,
     Uart16550::Io { base: 0x402 },
@@ -69,12 +73,31 @@ impl CpuInfo for Ovmf {
 }
 
 impl ComponentInfo for Ovmf {
-    fn configs(_add: Add<Config>) {
-        // Add components and configs later
+    fn configs(mut add: Add<Config>) {
+        add.config(patina_mm::config::MmCommunicationConfiguration {
+            acpi_base: patina_mm::config::AcpiBase::Mmio(0x0), // Actual ACPI base address will be set during boot
+            cmd_port: patina_mm::config::MmiPort::Smi(0xB2),
+            data_port: patina_mm::config::MmiPort::Smi(0xB3),
+            enable_comm_buffer_updates: false,
+            updatable_buffer_id: None,
+            comm_buffers: vec![],
+        });
+        add.config(smi_features::SmiFeatureConfiguration {
+            requested: smi_features::SmiFeatures::BROADCAST, // Negotiated with QEMU during boot
+            ..Default::default()
+        });
     }
 
-    fn components(_add: Add<Component>) {
-        // Add components and configs later
+    fn components(mut add: Add<Component>) {
+        // The MM configuration provider only runs on `SMM_REQUIRE` builds, which produce the SMRAM Memory HOB. Without
+        // it, SMI features are not negotiated and the platform MM control service is not installed, so the SW MMI
+        // manager skips platform MM init. The communicator only has a buffer if the MM IPL publishes the MM
+        // Communication Buffer HOB.
+        add.component(q35_services::ovmf_mm_config_provider::OvmfMmConfigurationProvider);
+        add.component(q35_services::smi_feature_negotiation::QemuQ35SmiFeatureNegotiation::new());
+        add.component(q35_services::mm_control::QemuQ35PlatformMmControl::new());
+        add.component(patina_mm::component::sw_mmi_manager::SwMmiManager::new());
+        add.component(patina_mm::component::communicator::MmCommunicator::new());
     }
 }
 
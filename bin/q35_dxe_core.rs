@@ -24,7 +24,7 @@ extern crate alloc;
 use alloc::vec;
 #[cfg(feature = "exit_on_patina_test_failure")]
 use qemu_exit::QEMUExit;
-use qemu_resources::q35::timer;
+use qemu_resources::q35::{smi_features, timer};
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
@@ -69,6 +69,19 @@ static DEBUGGER: patina_debugger::PatinaDebugger<Uart16550> =
         .with_force_enable(_ENABLE_DEBUGGER)
         .with_log_policy(patina_debugger::DebuggerLoggingPolicy::FullLogging);
 
+/// SMBIOS platform identity, version and entry points, shared by the SMBIOS provider and platform components.
+fn smbios_config() -> qemu_resources::smbios::SmbiosPlatformConfig {
+    qemu_resources::smbios::SmbiosPlatformConfig {
+        product_name: "QEMU Q35 Virtual Machine",
+        sku_number: "Q35-STANDARD",
+        baseboard_product: "QEMU Q35 Virtual Machine",
+        oem_strings: &[concat!("Patina DXE Core ", env!("CARGO_PKG_VERSION"))],
+        smbios_version: (3, 9),
+        entry_point: qemu_resources::smbios::SmbiosEntryPoint::Both, // Older guest tooling reads `_SM_` only
+        ..Default::default()
+    }
+}
+
 struct Q35;
 
 // Default `MemoryInfo` implementation is sufficient for Q35.
@@ -93,22 +106,34 @@ impl ComponentInfo for Q35 {
             updatable_buffer_id: None,
             comm_buffers: vec![],
         });
+        add.config(smi_features::SmiFeatureConfiguration {
+            requested: smi_features::SmiFeatures::BROADCAST, // Negotiated with QEMU during boot
+            ..Default::default()
+        });
+        add.config(smbios_config());
+        add.config(q35_services::mm_supervisor_policy::MmSupervisorPolicyConfiguration {
+            expected_policy: None, // Set to a captured policy blob to fail boot on policy drift
+        });
     }
 
     fn components(mut add: Add<Component>) {
         add.component(AdvancedLoggerComponent::<Uart16550>::new(&LOGGER));
         add.component(q35_services::mm_config_provider::MmConfigurationProvider);
+        add.component(q35_services::smi_feature_negotiation::QemuQ35SmiFeatureNegotiation::new());
         add.component(q35_services::mm_control::QemuQ35PlatformMmControl::new());
         add.component(patina_mm::component::sw_mmi_manager::SwMmiManager::new());
         add.component(patina_mm::component::communicator::MmCommunicator::new());
-        add.component(q35_services::mm_test::QemuQ35MmTest::new());
+        add.component(q35_services::mm_supervisor_client::QemuQ35MmSupervisorClient::new());
+        add.component(q35_services::mm_supervisor_policy::QemuQ35MmSupervisorPolicyAudit::new());
+        add.component(q35_services::mm_variable::QemuQ35MmVariable::new());
+        add.component(q35_services::smram_lock::QemuQ35SmramLock::new());
         add.component(patina_performance::component::Performance::new().with_measurements(
             patina::performance::Measurement::DriverBindingStart     // Adds driver binding start measurements.
                | patina::performance::Measurement::DriverBindingStop // Adds driver binding stop measurements.
                | patina::performance::Measurement::LoadImage         // Adds load image measurements.
                | patina::performance::Measurement::StartImage, // Adds start image measurements.
         ));
-        add.component(patina_smbios::component::SmbiosProvider::new(3, 9));
+        add.component(smbios_config().provider());
         add.component(q35_services::smbios_platform::Q35SmbiosPlatform::new());
         add.component(patina_acpi::component::AcpiComponent::default());
         add.component(patina_test::component::TestRunner::default().with_callback(|test_name, err_msg| {
@@ -0,0 +1,47 @@
+//! Build Script
+//!
+//! Passes the versions of the patina crates the binaries depend on to the SMBIOS firmware inventory, as
+//! `PATINA_CRATE_VERSIONS`: a comma-separated list of `name version` pairs read from `Cargo.toml` and `Cargo.lock`.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo");
+    let manifest_dir = Path::new(&manifest_dir);
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let manifest = fs::read_to_string(manifest_dir.join("Cargo.toml")).unwrap_or_default();
+    let lock = fs::read_to_string(manifest_dir.join("Cargo.lock")).unwrap_or_default();
+
+    let versions: Vec<String> = dependencies(&manifest)
+        .filter(|name| name.starts_with("patina"))
+        .filter_map(|name| locked_version(&lock, name).map(|version| format!("{name} {version}")))
+        .collect();
+    println!("cargo:rustc-env=PATINA_CRATE_VERSIONS={}", versions.join(","));
+}
+
+/// Returns the names of the packages in the `[dependencies]` table of `manifest`.
+fn dependencies(manifest: &str) -> impl Iterator<Item = &str> {
+    manifest
+        .lines()
+        .skip_while(|line| line.trim() != "[dependencies]")
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .filter_map(|line| line.split_once('=').map(|(name, _)| name.trim()))
+        .filter(|name| !name.is_empty() && !name.starts_with('#'))
+}
+
+/// Returns the version of package `name` in `lock`.
+fn locked_version<'a>(lock: &'a str, name: &str) -> Option<&'a str> {
+    let mut lines = lock.lines();
+    lines.find(|line| *line == format!("name = \"{name}\""))?;
+    lines.next()?.strip_prefix("version = \"")?.strip_suffix('"')
+}
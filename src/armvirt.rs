@@ -9,3 +9,10 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 pub mod component;
+pub mod fdt;
+pub mod ffa;
+#[coverage(off)]
+pub mod fw_cfg;
+pub mod processor;
+#[cfg(test)]
+pub mod sim;
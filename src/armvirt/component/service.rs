@@ -8,6 +8,11 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+pub mod mm_config_provider;
+pub mod mm_control;
+#[cfg(any(test, feature = "armvirt_standalone_mm"))]
+#[coverage(off)]
+pub mod mm_test;
 #[coverage(off)]
 pub mod smbios_platform;
 #[coverage(off)]
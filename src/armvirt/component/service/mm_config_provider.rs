@@ -0,0 +1,163 @@
+//! QEMU Arm Virt Management Mode (MM) Configuration Provider
+//!
+//! Produces MM configuration for QEMU Arm Virt builds that run a Standalone MM secure partition under TF-A. The
+//! partition is reached with FF-A direct messages, and requests are exchanged through a non-secure buffer that TF-A
+//! shares with the partition.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use patina::{
+    component::{
+        component,
+        params::{Config, ConfigMut},
+    },
+    error::EfiError,
+};
+use patina_mm::config::{CommunicateBuffer, MmCommunicationConfiguration, MmiPort};
+
+use crate::armvirt::ffa;
+
+/// ID of the single communicate buffer shared with the Standalone MM partition.
+pub const MM_COMM_BUFFER_ID: u8 = 0;
+
+/// FF-A UUID of the edk2 Standalone MM partition, `eaba83d8-baaf-4eaf-8144-f7fdcbe544a7`, as the four words passed to
+/// FFA_PARTITION_INFO_GET.
+pub const STANDALONE_MM_PARTITION_UUID: [u32; 4] = [0xeaba83d8, 0x4eafbaaf, 0xfdf74481, 0xa744e5cb];
+
+/// QEMU Arm Virt MM configuration.
+///
+/// Platform-fixed details of the Standalone MM partition that cannot be discovered at boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArmVirtMmConfiguration {
+    /// FF-A partition ID of the Standalone MM partition.
+    pub partition_id: u16,
+    /// Physical address of the non-secure buffer shared with the partition. Zero if MM is not present.
+    pub comm_buffer_base: u64,
+    /// Size of the shared buffer in bytes.
+    pub comm_buffer_size: u64,
+}
+
+impl Default for ArmVirtMmConfiguration {
+    fn default() -> Self {
+        // The TF-A EL3 SPMC assigns IDs to secure partitions starting at 0x8001.
+        Self { partition_id: 0x8001, comm_buffer_base: 0, comm_buffer_size: 0 }
+    }
+}
+
+/// Responsible for providing MM configuration information on QEMU Arm Virt.
+pub struct ArmVirtMmConfigurationProvider;
+
+#[component]
+impl ArmVirtMmConfigurationProvider {
+    /// Entry point for the QEMU Arm Virt MM Configuration Provider.
+    ///
+    /// Creates the communicate buffer from the shared buffer in the `ArmVirtMmConfiguration` and selects FF-A direct
+    /// requests as the MMI mechanism. The `MmCommunicationConfiguration` is only locked if a shared buffer is
+    /// configured, so MM components are not dispatched on builds without a Standalone MM partition.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::InvalidParameter` if the configured shared buffer cannot be used as a communicate buffer.
+    pub fn entry_point(
+        self,
+        platform_config: Config<ArmVirtMmConfiguration>,
+        mut config_mut: ConfigMut<MmCommunicationConfiguration>,
+    ) -> patina::error::Result<()> {
+        log::debug!("Arm Virt MM Configuration Provider Entry Point");
+
+        if platform_config.comm_buffer_base == 0 {
+            log::info!("No MM communicate buffer is configured. MM communication is not available.");
+            return Ok(());
+        }
+
+        config_mut.cmd_port = MmiPort::Smc(ffa::FFA_MSG_SEND_DIRECT_REQ_64 as u32);
+        config_mut.data_port = MmiPort::Smc(0);
+
+        let size = usize::try_from(platform_config.comm_buffer_size).map_err(|_| EfiError::InvalidParameter)?;
+        // SAFETY: The platform configuration describes non-secure memory that TF-A reserves and shares with the
+        // Standalone MM partition for the lifetime of the firmware.
+        let buffer = unsafe {
+            CommunicateBuffer::from_firmware_region(platform_config.comm_buffer_base, size, MM_COMM_BUFFER_ID, None)
+        }
+        .map_err(|e| {
+            log::error!("Failed to create MM Communicate Buffer: {e:?}");
+            EfiError::InvalidParameter
+        })?;
+
+        log::info!(
+            "MM Communicate Buffer {} configured at {:#X} ({:#X} bytes)",
+            buffer.id(),
+            platform_config.comm_buffer_base,
+            buffer.len()
+        );
+        config_mut.comm_buffers.push(buffer);
+        config_mut.lock();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use patina::component::Storage;
+
+    use super::*;
+    use crate::{
+        armvirt::component::service::mm_control::ArmVirtPlatformMmControl,
+        sim::{allocate_pages, run_component},
+    };
+
+    fn storage_with_config(config: ArmVirtMmConfiguration) -> Storage {
+        let mut storage = Storage::new();
+        storage.add_config(config);
+        storage
+    }
+
+    #[test]
+    fn test_provider_builds_config() {
+        let base = allocate_pages(4);
+        let mut storage = storage_with_config(ArmVirtMmConfiguration {
+            comm_buffer_base: base,
+            comm_buffer_size: 0x4000,
+            ..Default::default()
+        });
+
+        assert_eq!(run_component(ArmVirtMmConfigurationProvider, &mut storage), Ok(true));
+
+        let config = storage.get_config::<MmCommunicationConfiguration>().unwrap();
+        assert!(
+            matches!(config.cmd_port, MmiPort::Smc(function) if function as u64 == ffa::FFA_MSG_SEND_DIRECT_REQ_64)
+        );
+        assert_eq!(config.comm_buffers.len(), 1);
+        assert_eq!(config.comm_buffers[0].id(), MM_COMM_BUFFER_ID);
+        assert_eq!(config.comm_buffers[0].as_ptr() as u64, base);
+        assert_eq!(config.comm_buffers[0].len(), 0x4000);
+        assert!(!config.comm_buffers[0].has_status_mailbox());
+    }
+
+    #[test]
+    fn test_provider_leaves_config_unlocked_without_buffer() {
+        let mut storage = storage_with_config(ArmVirtMmConfiguration::default());
+
+        assert_eq!(run_component(ArmVirtMmConfigurationProvider, &mut storage), Ok(true));
+        // Components that need the locked configuration are not dispatched.
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Ok(false));
+    }
+
+    #[test]
+    fn test_provider_rejects_unaligned_buffer() {
+        let mut storage = storage_with_config(ArmVirtMmConfiguration {
+            comm_buffer_base: 0x4010_0800,
+            comm_buffer_size: 0x1000,
+            ..Default::default()
+        });
+
+        assert_eq!(run_component(ArmVirtMmConfigurationProvider, &mut storage), Err(EfiError::InvalidParameter));
+    }
+}
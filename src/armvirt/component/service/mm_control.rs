@@ -0,0 +1,290 @@
+//! QEMU Arm Virt Platform Management Mode (MM) Control
+//!
+//! Provides platform-specific MM control for QEMU Arm Virt. MMIs are raised with FF-A direct request messages sent
+//! through the SMC conduit to the Standalone MM secure partition.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use patina::{
+    component::{
+        component,
+        params::{Commands, Config},
+        service::IntoService,
+    },
+    error::EfiError,
+};
+use patina_mm::{
+    config::MmCommunicationConfiguration,
+    service::{SwMmiTrigger, platform_mm_control::PlatformMmControl},
+};
+
+use crate::armvirt::{
+    component::service::mm_config_provider::{ArmVirtMmConfiguration, STANDALONE_MM_PARTITION_UUID},
+    ffa,
+};
+
+/// Standalone MM return codes in the first register of a direct response.
+mod mm_return {
+    pub const SUCCESS: i32 = 0;
+    pub const NOT_SUPPORTED: i32 = -1;
+    pub const INVALID_PARAMETER: i32 = -2;
+    pub const DENIED: i32 = -3;
+    pub const NO_MEMORY: i32 = -5;
+}
+
+/// The QEMU Arm Virt platform-specific MM control component.
+///
+/// Produces both the `PlatformMmControl` service and the `SwMmiTrigger` service used by the `MmCommunicator`. Each
+/// MMI is an FF-A direct request carrying the physical address of the communicate buffer in its first message
+/// register, as the edk2 Standalone MM FF-A entry point expects.
+#[derive(IntoService, Default)]
+#[service(dyn PlatformMmControl, dyn SwMmiTrigger)]
+pub struct ArmVirtPlatformMmControl {
+    partition_id: u16,
+    caller_id: u16,
+    comm_buffer: u64,
+}
+
+#[component]
+impl ArmVirtPlatformMmControl {
+    /// Creates a new instance of the QEMU Arm Virt platform MM control component.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Entry point for the QEMU Arm Virt platform MM control component.
+    ///
+    /// Depends on the locked `MmCommunicationConfiguration`, so it is only dispatched when the MM configuration
+    /// provider found a communicate buffer. Discovers the FF-A endpoints and installs the services.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::NotFound` if no communicate buffer is configured.
+    /// - Any error returned by [`PlatformMmControl::init`] or FFA_ID_GET.
+    pub fn entry_point(
+        mut self,
+        config: Config<MmCommunicationConfiguration>,
+        platform_config: Config<ArmVirtMmConfiguration>,
+        mut commands: Commands,
+    ) -> patina::error::Result<()> {
+        log::debug!("Arm Virt Platform MM Control Entry Point");
+
+        let Some(comm_buffer) = config.comm_buffers.first() else {
+            log::error!("No MM communicate buffer is configured");
+            return Err(EfiError::NotFound);
+        };
+        self.comm_buffer = comm_buffer.as_ptr() as u64;
+        self.partition_id = platform_config.partition_id;
+
+        self.init()?;
+        self.caller_id = ffa::id_get().inspect_err(|e| log::error!("FFA_ID_GET failed: {e:?}"))?;
+        log::info!("FF-A endpoints: caller {:#06X}, Standalone MM {:#06X}", self.caller_id, self.partition_id);
+
+        commands.add_service(self);
+
+        Ok(())
+    }
+}
+
+impl PlatformMmControl for ArmVirtPlatformMmControl {
+    /// Checks that FF-A is available and that the Standalone MM partition exists.
+    ///
+    /// Partition discovery needs FF-A 1.1. With FF-A 1.0 the configured partition ID is used without checking.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::Unsupported` if FF-A is not implemented or its version is not supported.
+    /// - `EfiError::NotFound` if no Standalone MM partition is present.
+    fn init(&self) -> patina::error::Result<()> {
+        let version = ffa::version().inspect_err(|_| log::error!("FF-A is not available"))?;
+        log::debug!("FF-A version {}.{}", version >> 16, version & 0xFFFF);
+
+        if version < ffa::FFA_VERSION_1_1 {
+            log::warn!("FF-A 1.0 cannot count partitions. Assuming partition {:#06X} is present.", self.partition_id);
+            return Ok(());
+        }
+
+        match ffa::partition_count(STANDALONE_MM_PARTITION_UUID) {
+            Ok(0) | Err(ffa::FfaError::InvalidParameters) => {
+                log::error!("The Standalone MM partition is not present");
+                Err(EfiError::NotFound)
+            }
+            Ok(count) => {
+                log::debug!("Found {count} Standalone MM partition(s)");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// SAFETY: The service is only installed after the entry point has confirmed that the Standalone MM partition is
+//         reachable, and the communicate buffer address comes from the locked MM configuration.
+unsafe impl SwMmiTrigger for ArmVirtPlatformMmControl {
+    fn trigger_sw_mmi(&self, cmd_port_value: u8, data_port_value: u8) -> patina::error::Result<()> {
+        log::trace!(target: "sw_mmi", "FF-A MMI (cmd {cmd_port_value:#04X}, data {data_port_value:#04X}) to {:#06X}", self.partition_id);
+
+        let response = ffa::send_direct_request(self.caller_id, self.partition_id, [self.comm_buffer, 0, 0, 0, 0])
+            .inspect_err(|e| log::error!(target: "sw_mmi", "FF-A direct request failed: {e:?}"))?;
+
+        match response[0] as u32 as i32 {
+            mm_return::SUCCESS => Ok(()),
+            mm_return::NOT_SUPPORTED => Err(EfiError::Unsupported),
+            mm_return::INVALID_PARAMETER => Err(EfiError::InvalidParameter),
+            mm_return::DENIED => Err(EfiError::AccessDenied),
+            mm_return::NO_MEMORY => Err(EfiError::OutOfResources),
+            other => {
+                log::error!(target: "sw_mmi", "Standalone MM returned {other}");
+                Err(EfiError::DeviceError)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use std::{boxed::Box, rc::Rc, vec};
+
+    use core::cell::Cell;
+    use patina::component::Storage;
+
+    use super::*;
+    use crate::{
+        armvirt::{component::service::mm_config_provider::ArmVirtMmConfigurationProvider, sim},
+        sim::{allocate_pages, run_component},
+    };
+
+    const CALLER_ID: u16 = 0x0001;
+    const PARTITION_ID: u16 = 0x8001;
+
+    /// Creates storage with a configured communicate buffer and an SPMC hosting the Standalone MM partition.
+    fn setup() -> (Storage, u64) {
+        sim::reset();
+        sim::with_spmc(|spmc| {
+            spmc.caller_id = CALLER_ID;
+            spmc.partitions = vec![(STANDALONE_MM_PARTITION_UUID, PARTITION_ID)];
+        });
+
+        let base = allocate_pages(1);
+        let mut storage = Storage::new();
+        storage.add_config(ArmVirtMmConfiguration {
+            partition_id: PARTITION_ID,
+            comm_buffer_base: base,
+            comm_buffer_size: 0x1000,
+        });
+        assert_eq!(run_component(ArmVirtMmConfigurationProvider, &mut storage), Ok(true));
+        (storage, base)
+    }
+
+    fn set_handler(handler: impl FnMut([u64; 5]) -> [u64; 5] + 'static) {
+        sim::with_spmc(|spmc| spmc.handler = Some(Box::new(handler)));
+    }
+
+    fn calls_to(function: u64) -> usize {
+        sim::with_spmc(|spmc| spmc.calls.iter().filter(|call| call[0] == function).count())
+    }
+
+    #[test]
+    fn test_control_installs_services() {
+        let (mut storage, base) = setup();
+        let received = Rc::new(Cell::new(0));
+        let received_clone = received.clone();
+        set_handler(move |message| {
+            received_clone.set(message[0]);
+            [0; 5]
+        });
+
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Ok(true));
+        assert!(storage.get_service::<dyn PlatformMmControl>().is_some());
+
+        let trigger = storage.get_service::<dyn SwMmiTrigger>().unwrap();
+        assert_eq!(trigger.trigger_sw_mmi(0xFF, 0), Ok(()));
+        assert_eq!(received.get(), base);
+    }
+
+    #[test]
+    fn test_control_fails_without_ffa() {
+        let (mut storage, _) = setup();
+        sim::with_spmc(|spmc| spmc.version = None);
+
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Err(EfiError::Unsupported));
+        assert!(storage.get_service::<dyn SwMmiTrigger>().is_none());
+    }
+
+    #[test]
+    fn test_control_rejects_other_major_version() {
+        let (mut storage, _) = setup();
+        sim::with_spmc(|spmc| spmc.version = Some(2 << 16));
+
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Err(EfiError::Unsupported));
+    }
+
+    #[test]
+    fn test_control_fails_without_partition() {
+        let (mut storage, _) = setup();
+        sim::with_spmc(|spmc| spmc.partitions.clear());
+
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Err(EfiError::NotFound));
+    }
+
+    #[test]
+    fn test_control_skips_partition_discovery_on_ffa_1_0() {
+        let (mut storage, _) = setup();
+        sim::with_spmc(|spmc| spmc.version = Some(ffa::FFA_VERSION_1_0));
+
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Ok(true));
+        assert_eq!(calls_to(ffa::FFA_PARTITION_INFO_GET), 0);
+    }
+
+    #[test]
+    fn test_trigger_resumes_interrupted_requests() {
+        let (mut storage, _) = setup();
+        set_handler(|_| [0; 5]);
+        sim::with_spmc(|spmc| spmc.interrupts = 3);
+
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Ok(true));
+        let trigger = storage.get_service::<dyn SwMmiTrigger>().unwrap();
+
+        assert_eq!(trigger.trigger_sw_mmi(0xFF, 0), Ok(()));
+        assert_eq!(calls_to(ffa::FFA_RUN), 3);
+    }
+
+    #[test]
+    fn test_trigger_maps_mm_return_codes() {
+        let (mut storage, _) = setup();
+        let status = Rc::new(Cell::new(0i32));
+        let status_clone = status.clone();
+        set_handler(move |_| [status_clone.get() as u32 as u64, 0, 0, 0, 0]);
+
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Ok(true));
+        let trigger = storage.get_service::<dyn SwMmiTrigger>().unwrap();
+
+        for (code, expected) in [
+            (mm_return::NOT_SUPPORTED, EfiError::Unsupported),
+            (mm_return::INVALID_PARAMETER, EfiError::InvalidParameter),
+            (mm_return::DENIED, EfiError::AccessDenied),
+            (mm_return::NO_MEMORY, EfiError::OutOfResources),
+            (-4, EfiError::DeviceError),
+        ] {
+            status.set(code);
+            assert_eq!(trigger.trigger_sw_mmi(0xFF, 0), Err(expected), "MM return code {code}");
+        }
+    }
+
+    #[test]
+    fn test_trigger_reports_busy_partition() {
+        let (mut storage, _) = setup();
+
+        assert_eq!(run_component(ArmVirtPlatformMmControl::new(), &mut storage), Ok(true));
+        let trigger = storage.get_service::<dyn SwMmiTrigger>().unwrap();
+
+        assert_eq!(trigger.trigger_sw_mmi(0xFF, 0), Err(EfiError::NotReady));
+    }
+}
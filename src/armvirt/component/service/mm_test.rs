@@ -0,0 +1,134 @@
+//! QEMU Arm Virt Management Mode (MM) Test
+//!
+//! Verifies that MM interfaces are working as expected on QEMU Arm Virt with a Standalone MM secure partition. By
+//! exercising a MM communication transaction over FF-A to the Standalone MM variable handler.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use patina::component::service::Service;
+use patina_mm::service::MmCommunication;
+use patina_test::{patina_test, u_assert, u_assert_eq, u_assert_ne};
+use r_efi::efi;
+
+use crate::{
+    armvirt::component::service::mm_config_provider::MM_COMM_BUFFER_ID,
+    mm_variable::{COMMUNICATE_HEADER_SIZE, MM_VARIABLE_HANDLER_GUID, QUERY_VARIABLE_INFO_SIZE, function},
+};
+
+/// Queries variable storage information from Standalone MM and validates the response.
+///
+/// Uses the `MmCommunication` service to send a QueryVariableInfo request for non-volatile variables to the
+/// Standalone MM variable handler. Every MMI is an FF-A direct request to the secure partition, so a valid response
+/// shows the whole path through TF-A works.
+#[patina_test]
+fn armvirt_mm_variable_query_test(mm_comm: Service<dyn MmCommunication>) -> patina_test::error::Result {
+    log::debug!("MM Test - Testing MM Communication over FF-A");
+
+    let mut message = [0u8; COMMUNICATE_HEADER_SIZE + QUERY_VARIABLE_INFO_SIZE];
+    message[..8].copy_from_slice(&function::QUERY_VARIABLE_INFO.to_le_bytes());
+    let attributes = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+    message[COMMUNICATE_HEADER_SIZE + 24..COMMUNICATE_HEADER_SIZE + 28].copy_from_slice(&attributes.to_le_bytes());
+
+    let response = match mm_comm.communicate(MM_COMM_BUFFER_ID, &message, MM_VARIABLE_HANDLER_GUID.as_guid()) {
+        Ok(response) => response,
+        Err(status) => {
+            log::error!("MM Communication failed: {status:?}");
+            return Err("MM Communication to Standalone MM failed");
+        }
+    };
+    log::trace!("  Response: {} bytes", response.len());
+
+    u_assert!(response.len() >= message.len(), "MM variable response is too small for the query info");
+
+    let read_u64 = |offset: usize| u64::from_le_bytes(response[offset..offset + 8].try_into().unwrap());
+    u_assert_eq!(read_u64(0), function::QUERY_VARIABLE_INFO, "MM variable response is for a different function");
+    u_assert_eq!(
+        read_u64(8),
+        efi::Status::SUCCESS.as_usize() as u64,
+        "MM variable handler returned an error for QueryVariableInfo"
+    );
+
+    let maximum_storage_size = read_u64(COMMUNICATE_HEADER_SIZE);
+    let remaining_storage_size = read_u64(COMMUNICATE_HEADER_SIZE + 8);
+    let maximum_variable_size = read_u64(COMMUNICATE_HEADER_SIZE + 16);
+    log::info!(
+        "MM Variable Storage: {maximum_storage_size:#X} bytes, {remaining_storage_size:#X} remaining, \
+         {maximum_variable_size:#X} per variable",
+    );
+
+    u_assert_ne!(maximum_storage_size, 0, "Maximum variable storage size should be non-zero");
+    u_assert_ne!(maximum_variable_size, 0, "Maximum variable size should be non-zero");
+    u_assert!(
+        remaining_storage_size <= maximum_storage_size,
+        "Remaining variable storage should not exceed the maximum"
+    );
+
+    log::debug!("MM Test complete");
+    Ok(())
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use patina_mm::component::communicator::Status;
+
+    use super::*;
+    use crate::sim::ScriptedMmCommunication;
+
+    /// Answers a QueryVariableInfo request the way the MM variable handler does.
+    fn variable_handler(
+        status: efi::Status,
+        maximum: u64,
+        remaining: u64,
+    ) -> impl FnOnce(&mut [u8]) -> Result<(), Status> {
+        move |buffer| {
+            buffer[8..16].copy_from_slice(&(status.as_usize() as u64).to_le_bytes());
+            let info = &mut buffer[COMMUNICATE_HEADER_SIZE..];
+            info[..8].copy_from_slice(&maximum.to_le_bytes());
+            info[8..16].copy_from_slice(&remaining.to_le_bytes());
+            info[16..24].copy_from_slice(&0x2000u64.to_le_bytes());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_query_passes() {
+        let mm_comm = ScriptedMmCommunication::new().then(variable_handler(efi::Status::SUCCESS, 0x10000, 0x8000));
+        let requests = mm_comm.requests();
+
+        assert_eq!(armvirt_mm_variable_query_test(mm_comm.into_service()), Ok(()));
+
+        let requests = requests.borrow();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].id, MM_COMM_BUFFER_ID);
+        assert_eq!(requests[0].recipient, MM_VARIABLE_HANDLER_GUID);
+        assert_eq!(requests[0].data[..8], function::QUERY_VARIABLE_INFO.to_le_bytes());
+    }
+
+    #[test]
+    fn test_query_rejects_error_status() {
+        let mm_comm =
+            ScriptedMmCommunication::new().then(variable_handler(efi::Status::INVALID_PARAMETER, 0x10000, 0x8000));
+
+        assert!(armvirt_mm_variable_query_test(mm_comm.into_service()).is_err());
+    }
+
+    #[test]
+    fn test_query_rejects_empty_storage() {
+        let mm_comm = ScriptedMmCommunication::new().then(variable_handler(efi::Status::SUCCESS, 0, 0));
+
+        assert!(armvirt_mm_variable_query_test(mm_comm.into_service()).is_err());
+    }
+
+    #[test]
+    fn test_query_reports_communication_failure() {
+        let mm_comm = ScriptedMmCommunication::new().then(|_| Err(Status::SwMmiFailed));
+
+        assert!(armvirt_mm_variable_query_test(mm_comm.into_service()).is_err());
+    }
+}
@@ -1,5 +1,17 @@
 //! Arm Virt SMBIOS Platform Component
 //!
+//! The Type 4 and Type 7 processor records describe the boot processor, read from its system registers by
+//! [`processor`](crate::armvirt::processor), with one core per CPU in the fw_cfg boot CPU count.
+//!
+//! The system UUID comes from the fw_cfg UUID item, or from a fallback persisted in a UEFI variable and seeded from
+//! the virtual counter. Slot and onboard device records are built from the PCI root bus, read through the ECAM window
+//! of the generic PCI host bridge in the device tree. The window moves with the machine options, such as `highmem`,
+//! and is not described by the HOB list, so the component adds the window of the root bus to the GCD and maps it as
+//! device memory. The PCI records are skipped if the device tree has no PCI host bridge or the window cannot be mapped.
+//!
+//! The persisted UUID fallback and the boot status are kept in UEFI variables, so the records are added and the table
+//! is published once the Variable Write architectural protocol is installed.
+//!
 //! ## License
 //!
 //! Copyright (c) Microsoft Corporation.
@@ -8,30 +20,40 @@
 //!
 
 extern crate alloc;
-use alloc::{string::String, vec};
+use alloc::format;
 
 use patina::{
-    component::{component, service::Service},
-    error::Result,
+    boot_services::StandardBootServices,
+    component::{component, hob::Hob, params::Config, service::Service},
+    error::{EfiError, Result},
+    pi::dxe_services::{DXE_SERVICES_TABLE_GUID, DxeServicesTable, GcdMemoryType},
+    runtime_services::StandardRuntimeServices,
 };
 use patina_smbios::{
-    service::{SMBIOS_HANDLE_PI_RESERVED, Smbios, SmbiosExt, SmbiosTableHeader},
-    smbios_record::{
-        Type0PlatformFirmwareInformation, Type1SystemInformation, Type2BaseboardInformation, Type3SystemEnclosure,
-        Type4ProcessorInformation, Type7CacheInformation, Type16PhysicalMemoryArray, Type17MemoryDevice,
-        Type19MemoryArrayMappedAddress,
-    },
+    service::{SMBIOS_HANDLE_PI_RESERVED, Smbios, SmbiosHandle, SmbiosTableHeader},
     smbios_types::{
-        AssociativityField, BiosCharacteristics, BiosCharacteristicsExt1, BiosCharacteristicsExt2, BoardType,
-        BootUpState, CacheConfiguration, CacheErrorCorrectionType, CacheSize, CacheSize2, CacheSramTypeData,
-        ExtendedBiosRomSize, FeatureFlags, MemoryArrayErrorCorrectionType, MemoryArrayLocation, MemoryArrayUse,
-        MemoryCapability, MemoryDeviceAttributes, MemoryDeviceTechnology, MemoryDeviceType, MemoryDeviceTypeDetails,
-        MemoryFormFactor, PowerSupplyState, ProcessorCharacteristics, ProcessorFamilyData, ProcessorInformationStatus,
-        ProcessorTypeData, ProcessorUpgrade, ProcessorVoltage, SecurityStatus, SystemCacheType, ThermalState,
-        WakeUpType,
+        ProcessorCharacteristics, ProcessorFamilyData, ProcessorInformationStatus, ProcessorTypeData, ProcessorUpgrade,
+        ProcessorVoltage,
     },
 };
 
+use crate::{
+    armvirt::{
+        fdt::{Fdt, FdtHob, PCI_HOST_ECAM_GENERIC},
+        fw_cfg::{self, FW_CFG_BASE, FwCfg},
+        processor::ProcessorInfo,
+    },
+    smbios::{
+        PciRootBus, QemuSmbiosTables, SmbiosMemoryMap, SmbiosPlatformBuilder, SmbiosPlatformConfig, StringPool,
+        boot_status, configuration_table, pci, qemu, record::Type4ProcessorInformation, uuid,
+    },
+    variable_services,
+};
+use r_efi::efi;
+
+/// Size of the ECAM window of one bus: 32 devices of 8 functions, with 4 KiB of configuration space each
+const ECAM_BUS_SIZE: u64 = 0x10_0000;
+
 /// Arm Virt platform SMBIOS record provider.
 #[derive(Default)]
 pub struct ArmVirtSmbiosPlatform;
@@ -43,348 +65,350 @@ impl ArmVirtSmbiosPlatform {
         Self
     }
 
-    fn entry_point(self, smbios: Service<dyn Smbios>) -> Result<()> {
+    /// Registers the records to be added and published once the variable services are installed.
+    fn entry_point(
+        self,
+        smbios: Service<dyn Smbios>,
+        config: Config<SmbiosPlatformConfig>,
+        fdt_hob: Option<Hob<FdtHob>>,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+    ) -> Result<()> {
         log::debug!("=== Arm Virt SMBIOS Platform Component ===");
 
-        let (major, minor) = smbios.version();
-        log::trace!("SMBIOS Version: {}.{}", major, minor);
-
-        let bios_info = Type0PlatformFirmwareInformation {
-            header: SmbiosTableHeader::new(0, 0, SMBIOS_HANDLE_PI_RESERVED),
-            vendor: 1,
-            firmware_version: 2,
-            bios_starting_address_segment: 0xE800,
-            firmware_release_date: 3,
-            firmware_rom_size: 0xFF,
-            characteristics: BiosCharacteristics::new().with_pci_supported(true),
-            characteristics_ext1: BiosCharacteristicsExt1::new()
-                .with_acpi_supported(true)
-                .with_usb_legacy_supported(true)
-                .with_smart_battery_supported(true),
-            characteristics_ext2: BiosCharacteristicsExt2::new()
-                .with_bios_boot_specification_supported(true)
-                .with_uefi_spec_supported(true),
-            system_bios_major_release: 1,
-            system_bios_minor_release: 0,
-            embedded_controller_major_release: 0xFF,
-            embedded_controller_minor_release: 0xFF,
-            extended_bios_rom_size: ExtendedBiosRomSize::new(),
-            string_pool: vec![
-                String::from("Patina Firmware"),
-                String::from(env!("CARGO_PKG_VERSION")),
-                String::from(option_env!("BUILD_DATE").unwrap_or("01/01/1970")),
-            ],
-        };
-
-        // Type 0 and Type 1 are required per SMBIOS spec Section 6.2. Propagate errors
-        // to avoid publishing an incompliant table.
-        let type0_handle = smbios.add_record(None, &bios_info).map_err(|e| {
-            log::error!("Failed to add required Type 0 (BIOS Info): {:?}", e);
-            e
-        })?;
-        log::trace!("  Type 0 (BIOS Info) - Handle 0x{:04X}", type0_handle);
-
-        let system_info = Type1SystemInformation {
-            header: SmbiosTableHeader::new(1, 0, SMBIOS_HANDLE_PI_RESERVED),
-            manufacturer: 1,
-            product_name: 2,
-            version: 3,
-            serial_number: 4,
-            uuid: [0; 16],
-            wake_up_type: WakeUpType::PowerSwitch,
-            sku_number: 5,
-            family: 6,
-            string_pool: vec![
-                String::from("QEMU"),
-                String::from("Arm Virtual Machine"),
-                String::from("1.0"),
-                String::from("VM-001"),
-                String::from("ARMVIRT-STANDARD"),
-                String::from("Virtual Machine Family"),
-            ],
+        let platform = PlatformRecords {
+            smbios,
+            config: *config,
+            ecam_base: map_root_bus_ecam(&boot_services, fdt_hob.as_deref()),
+            boot_services: boot_services.clone(),
+            runtime_services,
         };
+        variable_services::on_variable_write(&boot_services, platform, PlatformRecords::publish_callback).map_err(
+            |status| {
+                log::error!("Failed to wait for the variable services: {status:?}");
+                EfiError::from(status)
+            },
+        )
+    }
+}
 
-        let type1_handle = smbios.add_record(None, &system_info).map_err(|e| {
-            log::error!("Failed to add required Type 1 (System Info): {:?}", e);
-            e
-        })?;
-        log::trace!("  Type 1 (System Info) - Handle 0x{:04X}", type1_handle);
-
-        let enclosure_info = Type3SystemEnclosure {
-            header: SmbiosTableHeader::new(3, 0, SMBIOS_HANDLE_PI_RESERVED),
-            manufacturer: 1,
-            enclosure_type: 0x03,
-            version: 2,
-            serial_number: 3,
-            asset_tag_number: 4,
-            bootup_state: BootUpState::Safe,
-            power_supply_state: PowerSupplyState::Safe,
-            thermal_state: ThermalState::Safe,
-            security_status: SecurityStatus::Unknown,
-            oem_defined: 0x00000000,
-            height: 0x00,
-            number_of_power_cords: 0x01,
-            contained_element_count: 0x00,
-            contained_element_record_length: 0x00,
-            string_pool: vec![
-                String::from("Example Corporation"),
-                String::from("Example Chassis v1.0"),
-                String::from("CHASSIS-99999"),
-                String::from("ASSET-CHASSIS-001"),
-            ],
-        };
+/// What the component needs to add and publish the records once the variable services are installed.
+struct PlatformRecords {
+    smbios: Service<dyn Smbios>,
+    config: SmbiosPlatformConfig,
+    ecam_base: Option<u64>,
+    boot_services: StandardBootServices,
+    runtime_services: StandardRuntimeServices,
+}
 
-        let mut type3_handle = 0xFFFF;
-        match smbios.add_record(None, &enclosure_info) {
-            Ok(handle) => {
-                log::trace!("  Type 3 (System Enclosure) - Handle 0x{:04X}", handle);
-                type3_handle = handle;
-            }
-            Err(e) => log::warn!("  Failed to add Type 3: {:?}", e),
+impl PlatformRecords {
+    /// Adds the platform records and publishes the SMBIOS table, logging any failure.
+    fn publish_callback(&'static self) {
+        if let Err(e) = self.publish() {
+            log::error!("Failed to publish the SMBIOS table: {e:?}");
         }
+    }
 
-        let baseboard_info = Type2BaseboardInformation {
-            header: SmbiosTableHeader::new(2, 0, SMBIOS_HANDLE_PI_RESERVED),
-            manufacturer: 1,
-            product: 2,
-            version: 3,
-            serial_number: 4,
-            asset_tag: 5,
-            feature_flags: FeatureFlags::new().with_hosting_board(true),
-            location_in_chassis: 6,
-            chassis_handle: type3_handle,
-            board_type: BoardType::Motherboard,
-            contained_object_handles: 0,
-            string_pool: vec![
-                String::from("Example Corporation"),
-                String::from("Example Baseboard"),
-                String::from("1.0"),
-                String::from("MB-67890"),
-                String::from("ASSET-MB-001"),
-                String::from("Main Board Slot"),
-            ],
+    /// Adds the platform records and publishes the SMBIOS table.
+    ///
+    /// ## Errors
+    ///
+    /// - The error returned by the SMBIOS service if a required record could not be added or the table could not be
+    ///   published.
+    fn publish(&self) -> Result<()> {
+        let config = &self.config;
+        let (major, minor) = config.smbios_version;
+        log::trace!("SMBIOS Version: {}.{} ({:?} entry point)", major, minor, config.entry_point);
+
+        // SAFETY: The Arm Virt machine places the fw_cfg registers at FW_CFG_BASE in the device memory region.
+        let fw_cfg = unsafe { FwCfg::new(FW_CFG_BASE) };
+        let qemu_tables = match &fw_cfg {
+            Some(fw_cfg) => {
+                let read = |name| fw_cfg.find_file(name).map(|file| fw_cfg.read_file(&file)).unwrap_or_default();
+                QemuSmbiosTables::parse(&read(qemu::ANCHOR_FILE), &read(qemu::TABLES_FILE))
+            }
+            None => QemuSmbiosTables::default(),
         };
 
-        match smbios.add_record(None, &baseboard_info) {
-            Ok(handle) => log::trace!("  Type 2 (Base Board Info) - Handle 0x{:04X}", handle),
-            Err(e) => log::warn!("  Failed to add Type 2: {:?}", e),
+        let mut fw_cfg_uuid = [0u8; 16];
+        let mut cpus = [0u8; 2];
+        if let Some(fw_cfg) = &fw_cfg {
+            fw_cfg.read_item(fw_cfg::KEY_UUID, &mut fw_cfg_uuid);
+            fw_cfg.read_item(fw_cfg::KEY_NB_CPUS, &mut cpus);
+        }
+        let cpus = u16::from_le_bytes(cpus).max(1);
+        let system_uuid = uuid::resolve(fw_cfg_uuid, &qemu_tables, || {
+            uuid::persisted_fallback(&self.runtime_services, virtual_count)
+        });
+        if let Some(uuid) = system_uuid {
+            log::info!("System UUID: {uuid}");
         }
 
-        // Type 7: Cache Information - L1 and L2 caches for the virtual processor
-        let l1_cache = Type7CacheInformation {
-            header: SmbiosTableHeader::new(7, 0, SMBIOS_HANDLE_PI_RESERVED),
-            socket_designation: 1,
-            cache_configuration: CacheConfiguration::new()
-                .with_cache_level(0)
-                .with_enabled_disabled(true)
-                .with_operational_mode(1), // L1, enabled, write-back
-            maximum_cache_size: CacheSize::new().with_max_size(64), // 64 KB
-            installed_size: CacheSize::new().with_max_size(64),
-            supported_sram_type: CacheSramTypeData::new().with_unknown(true),
-            current_sram_type: CacheSramTypeData::new().with_unknown(true),
-            cache_speed: 0,
-            error_correction_type: CacheErrorCorrectionType::SingleBitEcc,
-            system_cache_type: SystemCacheType::Unified,
-            associativity: AssociativityField::FullyAssociative,
-            maximum_cache_size2: CacheSize2::new().with_max_size(64),
-            installed_size2: CacheSize2::new().with_max_size(64),
-            string_pool: vec![String::from("L1 Cache")],
-        };
+        let previous_boot = boot_status::track_boot(&self.boot_services, &self.runtime_services);
+        log::info!("Previous boot status: {previous_boot:#04X}");
 
-        let mut l1_cache_handle = 0xFFFF;
-        match smbios.add_record(None, &l1_cache) {
-            Ok(handle) => {
-                log::trace!("  Type 7 (L1 Cache) - Handle 0x{:04X}", handle);
-                l1_cache_handle = handle;
-            }
-            Err(e) => log::warn!("  Failed to add Type 7 (L1 Cache): {:?}", e),
-        }
+        // SAFETY: The entry point read the ECAM window of the root bus from the device tree and mapped it as device
+        // memory.
+        let root_bus = self.ecam_base.map(|ecam_base| unsafe { root_bus(ecam_base as usize) });
 
-        let l2_cache = Type7CacheInformation {
-            header: SmbiosTableHeader::new(7, 0, SMBIOS_HANDLE_PI_RESERVED),
-            socket_designation: 1,
-            cache_configuration: CacheConfiguration::new()
-                .with_cache_level(1)
-                .with_enabled_disabled(true)
-                .with_operational_mode(1), // L2, enabled, write-back
-            maximum_cache_size: CacheSize::new().with_max_size(256), // 256 KB
-            installed_size: CacheSize::new().with_max_size(256),
-            supported_sram_type: CacheSramTypeData::new().with_unknown(true),
-            current_sram_type: CacheSramTypeData::new().with_unknown(true),
-            cache_speed: 0,
-            error_correction_type: CacheErrorCorrectionType::SingleBitEcc,
-            system_cache_type: SystemCacheType::Unified,
-            associativity: AssociativityField::FullyAssociative,
-            maximum_cache_size2: CacheSize2::new().with_max_size(256),
-            installed_size2: CacheSize2::new().with_max_size(256),
-            string_pool: vec![String::from("L2 Cache")],
-        };
+        let memory = SmbiosMemoryMap::from_configuration_table(&self.boot_services);
 
-        let mut l2_cache_handle = 0xFFFF;
-        match smbios.add_record(None, &l2_cache) {
-            Ok(handle) => {
-                log::trace!("  Type 7 (L2 Cache) - Handle 0x{:04X}", handle);
-                l2_cache_handle = handle;
-            }
-            Err(e) => log::warn!("  Failed to add Type 7 (L2 Cache): {:?}", e),
-        }
+        let builder =
+            SmbiosPlatformBuilder::new(&self.smbios, config).with_qemu_tables(&qemu_tables).with_uuid(system_uuid);
+        add_records(&builder, &ProcessorInfo::read(), cpus, &memory, root_bus.as_ref(), previous_boot)?;
 
-        // Type 4: Processor Information
-        let processor_info = Type4ProcessorInformation {
-            header: SmbiosTableHeader::new(4, 0, SMBIOS_HANDLE_PI_RESERVED),
-            socket_designation: 1,
-            processor_type: ProcessorTypeData::CentralProcessor,
-            processor_family: 0xFE, // Use processor_family2
-            processor_manufacturer: 2,
-            processor_id: [0u8; 8],
-            processor_version: 3,
-            voltage: ProcessorVoltage::new().with_processor_voltage_indicate_legacy(true),
-            external_clock: 0, // Unknown
-            max_speed: 2000,
-            current_speed: 2000,
-            status: ProcessorInformationStatus::new().with_cpu_status(1).with_cpu_socket_populated(true),
-            processor_upgrade: ProcessorUpgrade::NoUpgrade, // None
-            l1_cache_handle,
-            l2_cache_handle,
-            l3_cache_handle: 0xFFFF, // Not provided
-            serial_number: 4,
-            asset_tag: 5,
-            part_number: 6,
-            core_count: 1,
-            core_enabled: 1,
-            thread_count: 1,
-            processor_characteristics: ProcessorCharacteristics::new().with_capable_64bit(true),
-            processor_family2: ProcessorFamilyData::ARMv8,
-            core_count2: 1,
-            core_enabled2: 1,
-            thread_count2: 1,
-            string_pool: vec![
-                String::from("CPU0"),
-                String::from("QEMU"),
-                String::from("ARMv8 Virtual Processor"),
-                String::from("SN-CPU-001"),
-                String::from("ASSET-CPU-001"),
-                String::from("PN-CPU-001"),
-            ],
-        };
+        builder.publish(&self.boot_services)
+    }
+}
+
+/// Adds every platform record, in table order, from the processor, boot CPU count, memory and PCI root bus of the
+/// machine.
+///
+/// ## Errors
+///
+/// - The error returned by the SMBIOS service if the required Type 0 or Type 1 record could not be added.
+fn add_records(
+    builder: &SmbiosPlatformBuilder,
+    processor: &ProcessorInfo,
+    cpus: u16,
+    memory: &SmbiosMemoryMap,
+    bus: Option<&PciRootBus>,
+    previous_boot: u8,
+) -> Result<()> {
+    let handles = builder.add_system_records()?;
+
+    // The virt machine places every CPU in one socket
+    let cache_handles = builder.add_cache_records(0, &processor.caches);
+    builder.add_record("Type 4 (CPU 0)", &processor_information(builder, processor, cpus, cache_handles));
 
-        match smbios.add_record(None, &processor_info) {
-            Ok(handle) => log::trace!("  Type 4 (Processor Info) - Handle 0x{:04X}", handle),
-            Err(e) => log::warn!("  Failed to add Type 4: {:?}", e),
+    builder.add_memory_records(memory);
+    builder.add_oem_records();
+    builder.add_boot_information(previous_boot);
+    builder.add_firmware_inventory(handles.firmware);
+    if let Some(bus) = bus {
+        builder.add_pci_records(bus);
+    }
+    builder.add_qemu_records();
+    Ok(())
+}
+
+/// Returns the Type 4 Processor Information record of the socket holding `cpus` cores, linked to its L1, L2 and L3
+/// cache records.
+///
+/// QEMU does not model a processor frequency, so the speeds are reported as unknown. The strings QEMU sets, with
+/// `-smbios type=4`, override the ones read from the processor.
+fn processor_information(
+    builder: &SmbiosPlatformBuilder,
+    processor: &ProcessorInfo,
+    cpus: u16,
+    [l1_cache_handle, l2_cache_handle, l3_cache_handle]: [SmbiosHandle; 3],
+) -> Type4ProcessorInformation {
+    let mut strings = StringPool::new();
+    let version = format!("ARMv8 Processor {:#010X}", processor.midr as u32);
+    let qemu_string = |offset, default| builder.qemu_instance_string(4, 0, offset, default);
+
+    Type4ProcessorInformation {
+        header: SmbiosTableHeader::new(4, 0, SMBIOS_HANDLE_PI_RESERVED),
+        socket_designation: strings.add(qemu_string(0x04, "CPU 0")),
+        processor_type: ProcessorTypeData::CentralProcessor,
+        processor_family: 0xFE, // Use processor_family2
+        processor_manufacturer: strings.add(qemu_string(0x07, processor.manufacturer())),
+        processor_id: processor.processor_id(),
+        processor_version: strings.add(qemu_string(0x10, &version)),
+        voltage: ProcessorVoltage::new(),
+        external_clock: 0, // Unknown
+        max_speed: 0,
+        current_speed: 0,
+        status: ProcessorInformationStatus::new().with_cpu_status(1).with_cpu_socket_populated(true),
+        processor_upgrade: ProcessorUpgrade::Other,
+        l1_cache_handle,
+        l2_cache_handle,
+        l3_cache_handle,
+        serial_number: strings.add(qemu_string(0x20, "")),
+        asset_tag: strings.add(qemu_string(0x21, "")),
+        part_number: strings.add(qemu_string(0x22, "")),
+        core_count: cpus.min(0xFF) as u8,
+        core_enabled: cpus.min(0xFF) as u8,
+        thread_count: cpus.min(0xFF) as u8,
+        processor_characteristics: ProcessorCharacteristics::new()
+            .with_capable_64bit(true)
+            .with_multi_core(cpus > 1)
+            .with_execute_protection(true),
+        processor_family2: ProcessorFamilyData::ARMv8,
+        core_count2: cpus,
+        core_enabled2: cpus,
+        thread_count2: cpus,
+        thread_enabled: cpus,
+        socket_type: 0,
+        string_pool: strings.into_strings(),
+    }
+}
+
+/// Returns the address of the ECAM window of the PCI root bus, read from the device tree in `fdt_hob`, after adding
+/// it to the GCD and mapping it as device memory.
+///
+/// The root bus is the first bus of the window. Returns `None`, and the PCI records are skipped, if the device tree
+/// has no enabled generic PCI host bridge or the window cannot be mapped.
+fn map_root_bus_ecam(boot_services: &StandardBootServices, fdt_hob: Option<&FdtHob>) -> Option<u64> {
+    let Some(fdt_hob) = fdt_hob else {
+        log::warn!("No FDT HOB, skipping the PCI records");
+        return None;
+    };
+    // SAFETY: The FDT HOB holds the address of the device tree QEMU passes to the firmware, which is not reclaimed.
+    let Some(fdt) = (unsafe { Fdt::from_hob(fdt_hob) }) else {
+        log::warn!("No device tree at {:#X}, skipping the PCI records", fdt_hob.address);
+        return None;
+    };
+    let Some(window) = fdt.find_compatible_region(PCI_HOST_ECAM_GENERIC) else {
+        log::info!("No PCI host bridge in the device tree, skipping the PCI records");
+        return None;
+    };
+    if window.size < ECAM_BUS_SIZE {
+        log::warn!("The PCI ECAM window {window:X?} does not hold a bus, skipping the PCI records");
+        return None;
+    }
+
+    let dxe_services = match configuration_table(boot_services, &DXE_SERVICES_TABLE_GUID) {
+        Ok(Some(table)) => table as *const DxeServicesTable,
+        Ok(None) => {
+            log::warn!("No DXE Services Table in the Configuration Table, skipping the PCI records");
+            return None;
         }
+        Err(err) => {
+            log::warn!("{err}, skipping the PCI records");
+            return None;
+        }
+    };
+    // SAFETY: The DXE core installs the DXE Services Table in the Configuration Table for the duration of boot
+    // services.
+    let dxe_services = unsafe { &*dxe_services };
 
-        // Type 16: Physical Memory Array
-        let memory_array = Type16PhysicalMemoryArray {
-            header: SmbiosTableHeader::new(16, 0, SMBIOS_HANDLE_PI_RESERVED),
-            location: MemoryArrayLocation::SystemBoard,
-            use_field: MemoryArrayUse::SystemMemory,
-            memory_error_correction: MemoryArrayErrorCorrectionType::NoEcc,
-            maximum_capacity: 0x00100000,            // 1 GB in KB
-            memory_error_information_handle: 0xFFFE, // Not provided
-            number_of_memory_devices: 1,
-            extended_maximum_capacity: 0,
-            string_pool: vec![],
-        };
+    let attributes = efi::MEMORY_UC | efi::MEMORY_XP;
+    let status = (dxe_services.add_memory_space)(GcdMemoryType::MemoryMappedIo, window.base, ECAM_BUS_SIZE, attributes);
+    if status.is_error() {
+        // The pre-DXE phase may have described the window already
+        log::debug!("Adding the PCI ECAM window at {:#X} to the GCD returned {status:?}", window.base);
+    }
+    let status = (dxe_services.set_memory_space_attributes)(window.base, ECAM_BUS_SIZE, attributes);
+    if status.is_error() {
+        log::warn!("Failed to map the PCI ECAM window at {:#X}: {status:?}, skipping the PCI records", window.base);
+        return None;
+    }
 
-        let type16_handle = match smbios.add_record(None, &memory_array) {
-            Ok(handle) => {
-                log::trace!("  Type 16 (Physical Memory Array) - Handle 0x{:04X}", handle);
-                Some(handle)
-            }
-            Err(e) => {
-                log::warn!("  Failed to add Type 16: {:?}", e);
-                None
-            }
-        };
+    log::debug!("PCI root bus ECAM window at {:#X}", window.base);
+    Some(window.base)
+}
 
-        if let Some(type16_handle) = type16_handle {
-            // Type 17: Memory Device
-            let memory_device = Type17MemoryDevice {
-                header: SmbiosTableHeader::new(17, 0, SMBIOS_HANDLE_PI_RESERVED),
-                physical_memory_array_handle: type16_handle,
-                memory_error_information_handle: 0xFFFE, // Not provided
-                total_width: 64,
-                data_width: 64,
-                size: 0x0400, // 1024 MB
-                form_factor: MemoryFormFactor::Dimm,
-                device_set: 0,
-                device_locator: 1,
-                bank_locator: 2,
-                memory_type: MemoryDeviceType::Ddr4,
-                type_detail: MemoryDeviceTypeDetails::new().with_synchronous(true),
-                speed: 3200,
-                manufacturer: 3,
-                serial_number: 4,
-                asset_tag: 5,
-                part_number: 6,
-                attributes: MemoryDeviceAttributes::new().with_rank(1),
-                extended_size: 0,
-                configured_memory_clock_speed: 3200,
-                minimum_voltage: 1200,
-                maximum_voltage: 1200,
-                configured_voltage: 1200,
-                memory_technology: MemoryDeviceTechnology::Unknown,
-                memory_operating_mode_capability: MemoryCapability::new().with_volatile_memory(true),
-                firmware_version: 7,
-                module_manufacturer_id: 0,
-                module_product_id: 0,
-                memory_subsystem_controller_manufacturer_id: 0,
-                memory_subsystem_controller_product_id: 0,
-                non_volatile_size: 0,
-                volatile_size: 0x40000000, // 1 GB
-                cache_size: 0,
-                logical_size: 0,
-                extended_speed: 0,
-                extended_configured_memory_speed: 0,
-                pmic0_manufacturer_id: 0,
-                pmic0_revision_number: 0,
-                rcd_manufacturer_id: 0,
-                rcd_revision_number: 0,
-                string_pool: vec![
-                    String::from("DIMM 0"),
-                    String::from("BANK 0"),
-                    String::from("QEMU"),
-                    String::from("SN-DIMM-001"),
-                    String::from("ASSET-DIMM-001"),
-                    String::from("QEMU-DIMM"),
-                    String::from("v1.0"),
-                ],
-            };
-
-            match smbios.add_record(None, &memory_device) {
-                Ok(handle) => log::trace!("  Type 17 (Memory Device) - Handle 0x{:04X}", handle),
-                Err(e) => log::warn!("  Failed to add Type 17: {:?}", e),
-            }
+/// Enumerates the PCI root bus through the ECAM window at `ecam_base`.
+///
+/// ## Safety
+///
+/// `ecam_base` must be the address of the ECAM window of the root bus, mapped as device memory.
+unsafe fn root_bus(ecam_base: usize) -> PciRootBus {
+    PciRootBus::scan(|device, function, offset| {
+        let address = ecam_base
+            + patina::pci_address!(pci::ROOT_BUS as u32, device as u32, function as u32, offset as u32) as usize;
+        // SAFETY: The caller guarantees that the ECAM window of the root bus is mapped.
+        unsafe { core::ptr::read_volatile(address as *const u32) }
+    })
+}
 
-            // Type 19: Memory Array Mapped Address
-            let memory_mapped = Type19MemoryArrayMappedAddress {
-                header: SmbiosTableHeader::new(19, 0, SMBIOS_HANDLE_PI_RESERVED),
-                starting_address: 0,
-                ending_address: 0x000FFFFF, // 1 GB - 1 in KB
-                memory_array_handle: type16_handle,
-                partition_width: 1,
-                extended_starting_address: 0,
-                extended_ending_address: 0,
-                string_pool: vec![],
-            };
-
-            match smbios.add_record(None, &memory_mapped) {
-                Ok(handle) => log::trace!("  Type 19 (Memory Array Mapped Address) - Handle 0x{:04X}", handle),
-                Err(e) => log::warn!("  Failed to add Type 19: {:?}", e),
-            }
-        } else {
-            log::warn!("  Skipping Type 17 and Type 19 because Type 16 was not added");
+/// Reads the virtual counter, which seeds the fallback system UUID.
+fn virtual_count() -> u64 {
+    #[cfg(target_arch = "aarch64")]
+    {
+        let count: u64;
+        // SAFETY: CNTVCT_EL0 is readable at EL1 and reading it has no side effects.
+        unsafe { core::arch::asm!("mrs {}, cntvct_el0", out(reg) count, options(nomem, nostack)) };
+        count
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    0
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use std::vec;
+
+    use patina_smbios::smbios_types::{AssociativityField, SystemCacheType};
+
+    use super::*;
+    use crate::smbios::{
+        SMBIOS_HANDLE_NONE, SmbiosCache, SystemUuid,
+        golden::{self, RecordingSmbios},
+        pci::tests::FakeConfigSpace,
+    };
+
+    /// A Cortex-A57 with a split 32 KB/48 KB L1 and a 2 MB L2.
+    fn processor() -> ProcessorInfo {
+        ProcessorInfo {
+            midr: 0x411F_D070,
+            caches: vec![
+                SmbiosCache {
+                    level: 1,
+                    cache_type: SystemCacheType::Data,
+                    size: 32 * 1024,
+                    associativity: AssociativityField::SetAssociative2Way,
+                },
+                SmbiosCache {
+                    level: 1,
+                    cache_type: SystemCacheType::Instruction,
+                    size: 48 * 1024,
+                    associativity: AssociativityField::Other,
+                },
+                SmbiosCache {
+                    level: 2,
+                    cache_type: SystemCacheType::Unified,
+                    size: 2 * 1024 * 1024,
+                    associativity: AssociativityField::SetAssociative16Way,
+                },
+            ],
         }
+    }
+
+    #[test]
+    fn test_processor_information_reports_the_cpus() {
+        let smbios = RecordingSmbios::new().into_service();
+        let config = SmbiosPlatformConfig::default();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+
+        let record = processor_information(&builder, &processor(), 4, [0x10, 0x11, SMBIOS_HANDLE_NONE]);
+
+        assert_eq!((record.l1_cache_handle, record.l2_cache_handle, record.l3_cache_handle), (0x10, 0x11, 0xFFFF));
+        assert_eq!((record.core_count, record.thread_count, record.core_count2), (4, 4, 4));
+        assert_eq!((record.max_speed, record.current_speed), (0, 0));
+        assert_eq!((record.serial_number, record.asset_tag, record.part_number), (0, 0, 0));
+        assert_eq!(record.processor_id, [0x70, 0xD0, 0x1F, 0x41, 0, 0, 0, 0]);
+        assert!(record.processor_characteristics.multi_core());
+        assert_eq!(record.string_pool, ["CPU 0", "Arm Limited", "ARMv8 Processor 0x411FD070"]);
+
+        let record = processor_information(&builder, &ProcessorInfo::default(), 1, [SMBIOS_HANDLE_NONE; 3]);
+        assert!(!record.processor_characteristics.multi_core());
+        assert_eq!(record.processor_manufacturer, 0);
+    }
+
+    #[test]
+    fn test_records_match_golden_dump() {
+        let mut pci = FakeConfigSpace::default();
+        pci.add_function(0, 0, 0x06_00_00);
+        pci.add_function(1, 0, 0x02_00_00);
+        pci.add_function(2, 0, 0x01_08_02);
+        pci.add_root_port(3, 0, (4, 1), 2, false);
+
+        let config = SmbiosPlatformConfig { product_name: "Arm Virt Golden", ..Default::default() };
+        let memory = SmbiosMemoryMap::new(Some(0x4000_0000..0x1_4000_0000));
+        let smbios = RecordingSmbios::new();
+        let records = smbios.records();
+        let smbios = smbios.into_service();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config).with_uuid(SystemUuid::from_bytes([0xA5; 16]));
 
-        log::debug!("Publishing SMBIOS table...");
-        let (table_addr, entry_point_addr) = smbios.publish_table().map_err(|e| {
-            log::error!("Failed to publish SMBIOS table: {:?}", e);
-            e
-        })?;
-        log::debug!("SMBIOS table published successfully");
-        log::debug!("  Entry Point: 0x{:X}", entry_point_addr);
-        log::debug!("  Table Data: 0x{:X}", table_addr);
+        add_records(&builder, &processor(), 4, &memory, Some(&pci.scan()), boot_status::NO_ERRORS).unwrap();
 
-        Ok(())
+        golden::assert_golden(
+            "src/armvirt/component/service/testdata/smbios_platform.golden",
+            &golden::dump(&records.borrow()),
+        );
     }
 }
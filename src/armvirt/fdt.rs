@@ -0,0 +1,335 @@
+//! Arm Virt Device Tree
+//!
+//! QEMU describes the Arm Virt machine to the firmware in a flattened device tree, whose address the pre-DXE phase
+//! passes in the FDT HOB. The layout of the machine depends on its options, such as `highmem`, so the addresses of
+//! devices that move with them are read from the device tree rather than fixed in the binary.
+//!
+//! Only what the platform components need is decoded: the nodes of the structure block and the `compatible`,
+//! `status` and `reg` properties.
+//!
+//! ## References
+//!
+//! - [Devicetree Specification v0.4](https://github.com/devicetree-org/devicetree-specification/releases/tag/v0.4),
+//!   chapter 5 (Flattened Devicetree (DTB) Format)
+//! - [Linux host-generic-pci binding](https://www.kernel.org/doc/Documentation/devicetree/bindings/pci/host-generic-pci.txt)
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use patina::component::hob::FromHob;
+
+/// FDT HOB (`gFdtHobGuid`).
+///
+/// Holds the physical address of the flattened device tree QEMU passes to the firmware.
+#[derive(FromHob, Debug, Default, Clone, Copy, zerocopy::FromBytes)]
+#[hob = "16958446-19b7-480b-b047-7485ad3f716d"]
+#[repr(C)]
+pub struct FdtHob {
+    /// Physical address of the flattened device tree
+    pub address: u64,
+}
+
+/// `compatible` value of the generic PCI host bridge with an ECAM window QEMU places on the Arm Virt machine
+pub const PCI_HOST_ECAM_GENERIC: &str = "pci-host-ecam-generic";
+
+/// Magic value at the start of a flattened device tree
+const FDT_MAGIC: u32 = 0xD00D_FEED;
+/// Size of the flattened device tree header up to `size_dt_struct`
+const FDT_HEADER_SIZE: usize = 40;
+/// Structure block token starting a node
+const FDT_BEGIN_NODE: u32 = 1;
+/// Structure block token ending a node
+const FDT_END_NODE: u32 = 2;
+/// Structure block token of a property
+const FDT_PROP: u32 = 3;
+/// Structure block token to ignore
+const FDT_NOP: u32 = 4;
+/// Structure block token ending the structure block
+const FDT_END: u32 = 9;
+/// `#address-cells` of a node that does not set it
+const DEFAULT_ADDRESS_CELLS: u32 = 2;
+/// `#size-cells` of a node that does not set it
+const DEFAULT_SIZE_CELLS: u32 = 1;
+
+/// A memory-mapped region from the `reg` property of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdtRegion {
+    /// Physical address of the region
+    pub base: u64,
+    /// Size of the region in bytes
+    pub size: u64,
+}
+
+/// A flattened device tree.
+#[derive(Debug, Clone, Copy)]
+pub struct Fdt<'a> {
+    structure: &'a [u8],
+    strings: &'a [u8],
+}
+
+/// Properties of a node, recorded while its children are walked.
+#[derive(Default)]
+struct Node<'a> {
+    address_cells: u32,
+    size_cells: u32,
+    compatible: &'a [u8],
+    status: &'a [u8],
+    reg: &'a [u8],
+}
+
+impl<'a> Fdt<'a> {
+    /// Reads the flattened device tree at the address in the FDT HOB.
+    ///
+    /// Returns `None` if the address does not hold a flattened device tree.
+    ///
+    /// ## Safety
+    ///
+    /// `hob.address` must be the address of the device tree passed by the pre-DXE phase, which stays mapped and
+    /// unchanged for the lifetime of the firmware.
+    pub unsafe fn from_hob(hob: &FdtHob) -> Option<Fdt<'static>> {
+        if hob.address == 0 {
+            return None;
+        }
+        // SAFETY: The caller guarantees that the address holds a device tree, which starts with its header.
+        let header = unsafe { core::slice::from_raw_parts(hob.address as *const u8, FDT_HEADER_SIZE) };
+        let total_size = be32(header, 4)? as usize;
+        // SAFETY: The header was read from a device tree and reports the size of the whole device tree.
+        Fdt::new(unsafe { core::slice::from_raw_parts(hob.address as *const u8, total_size) })
+    }
+
+    /// Returns the flattened device tree held in `bytes`, or `None` if the header is not valid.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        if be32(bytes, 0)? != FDT_MAGIC || be32(bytes, 4)? as usize > bytes.len() {
+            return None;
+        }
+        let block = |offset, size| {
+            let offset = be32(bytes, offset)? as usize;
+            bytes.get(offset..offset.checked_add(be32(bytes, size)? as usize)?)
+        };
+        Some(Self { structure: block(8, 36)?, strings: block(12, 32)? })
+    }
+
+    /// Returns the first region in the `reg` property of the first enabled node compatible with `compatible`.
+    pub fn find_compatible_region(&self, compatible: &str) -> Option<FdtRegion> {
+        let mut nodes: Vec<Node<'a>> = Vec::new();
+        let mut offset = 0;
+        loop {
+            let token = be32(self.structure, offset)?;
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name_length = self.structure.get(offset..)?.iter().position(|&byte| byte == 0)?;
+                    offset = align4(offset + name_length + 1);
+                    nodes.push(Node {
+                        address_cells: DEFAULT_ADDRESS_CELLS,
+                        size_cells: DEFAULT_SIZE_CELLS,
+                        ..Default::default()
+                    });
+                }
+                FDT_END_NODE => {
+                    let node = nodes.pop()?;
+                    if node.is_enabled() && node.is_compatible(compatible) {
+                        let parent = nodes.last()?;
+                        return node.first_region(parent.address_cells, parent.size_cells);
+                    }
+                }
+                FDT_PROP => {
+                    let length = be32(self.structure, offset)? as usize;
+                    let name = self.string(be32(self.structure, offset + 4)? as usize)?;
+                    let value = self.structure.get(offset + 8..offset + 8 + length)?;
+                    offset = align4(offset + 8 + length);
+
+                    let node = nodes.last_mut()?;
+                    match name {
+                        b"#address-cells" => node.address_cells = be32(value, 0)?,
+                        b"#size-cells" => node.size_cells = be32(value, 0)?,
+                        b"compatible" => node.compatible = value,
+                        b"status" => node.status = value,
+                        b"reg" => node.reg = value,
+                        _ => {}
+                    }
+                }
+                FDT_NOP => {}
+                FDT_END => return None,
+                _ => {
+                    log::warn!("Unexpected device tree token {token:#X} at offset {:#X}", offset - 4);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Returns the NUL-terminated string at `offset` in the strings block, without the terminator.
+    fn string(&self, offset: usize) -> Option<&'a [u8]> {
+        let string = self.strings.get(offset..)?;
+        Some(&string[..string.iter().position(|&byte| byte == 0)?])
+    }
+}
+
+impl Node<'_> {
+    /// Returns `true` if the node has no `status`, or an `"okay"` one.
+    fn is_enabled(&self) -> bool {
+        matches!(self.status, b"" | b"okay\0" | b"ok\0")
+    }
+
+    /// Returns `true` if one of the NUL-separated strings of `compatible` is `compatible`.
+    fn is_compatible(&self, compatible: &str) -> bool {
+        self.compatible.split(|&byte| byte == 0).any(|value| value == compatible.as_bytes())
+    }
+
+    /// Decodes the first region of `reg`, with the cell counts of the parent node.
+    fn first_region(&self, address_cells: u32, size_cells: u32) -> Option<FdtRegion> {
+        let base = cells(self.reg, 0, address_cells)?;
+        let size = cells(self.reg, address_cells as usize * 4, size_cells)?;
+        Some(FdtRegion { base, size })
+    }
+}
+
+/// Reads the big-endian 32-bit value at `offset`.
+fn be32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Reads the value of one or two big-endian cells at `offset`.
+fn cells(bytes: &[u8], offset: usize, count: u32) -> Option<u64> {
+    match count {
+        1 => be32(bytes, offset).map(u64::from),
+        2 => Some((u64::from(be32(bytes, offset)?) << 32) | u64::from(be32(bytes, offset + 4)?)),
+        _ => None,
+    }
+}
+
+/// Rounds `offset` up to the 4-byte alignment of the structure block tokens.
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use std::vec;
+
+    use super::*;
+
+    /// Builds flattened device trees for the tests.
+    #[derive(Default)]
+    struct FdtBuilder {
+        structure: Vec<u8>,
+        strings: Vec<u8>,
+    }
+
+    impl FdtBuilder {
+        fn token(mut self, token: u32) -> Self {
+            self.structure.extend_from_slice(&token.to_be_bytes());
+            self
+        }
+
+        fn begin_node(self, name: &str) -> Self {
+            let mut builder = self.token(FDT_BEGIN_NODE);
+            builder.structure.extend_from_slice(name.as_bytes());
+            builder.structure.push(0);
+            builder.structure.resize(align4(builder.structure.len()), 0);
+            builder
+        }
+
+        fn end_node(self) -> Self {
+            self.token(FDT_END_NODE)
+        }
+
+        fn property(self, name: &str, value: &[u8]) -> Self {
+            let name_offset = self.strings.len() as u32;
+            let mut builder = self.token(FDT_PROP).token(value.len() as u32).token(name_offset);
+            builder.strings.extend_from_slice(name.as_bytes());
+            builder.strings.push(0);
+            builder.structure.extend_from_slice(value);
+            builder.structure.resize(align4(builder.structure.len()), 0);
+            builder
+        }
+
+        fn cells(self, name: &str, cells: &[u32]) -> Self {
+            let value: Vec<u8> = cells.iter().flat_map(|cell| cell.to_be_bytes()).collect();
+            self.property(name, &value)
+        }
+
+        fn build(self) -> Vec<u8> {
+            let builder = self.token(FDT_END);
+            let structure_offset = FDT_HEADER_SIZE;
+            let strings_offset = structure_offset + builder.structure.len();
+            let total_size = strings_offset + builder.strings.len();
+
+            let mut bytes = vec![0u8; FDT_HEADER_SIZE];
+            for (offset, value) in [
+                (0, FDT_MAGIC),
+                (4, total_size as u32),
+                (8, structure_offset as u32),
+                (12, strings_offset as u32),
+                (32, builder.strings.len() as u32),
+                (36, builder.structure.len() as u32),
+            ] {
+                bytes[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+            }
+            bytes.extend_from_slice(&builder.structure);
+            bytes.extend_from_slice(&builder.strings);
+            bytes
+        }
+    }
+
+    /// Returns a device tree shaped like the one of the Arm Virt machine with `highmem=on`.
+    fn virt_fdt(status: &[u8]) -> Vec<u8> {
+        FdtBuilder::default()
+            .begin_node("")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[2])
+            .begin_node("pl011@9000000")
+            .property("compatible", b"arm,pl011\0arm,primecell\0")
+            .cells("reg", &[0, 0x0900_0000, 0, 0x1000])
+            .end_node()
+            .begin_node("pcie@10000000")
+            .property("compatible", b"pci-host-ecam-generic\0")
+            .property("status", status)
+            .cells("#address-cells", &[3])
+            .cells("#size-cells", &[2])
+            .cells("reg", &[0x40, 0x1000_0000, 0, 0x1000_0000])
+            .end_node()
+            .end_node()
+            .build()
+    }
+
+    #[test]
+    fn test_find_compatible_region() {
+        let bytes = virt_fdt(b"okay\0");
+        let fdt = Fdt::new(&bytes).unwrap();
+
+        assert_eq!(
+            fdt.find_compatible_region(PCI_HOST_ECAM_GENERIC),
+            Some(FdtRegion { base: 0x40_1000_0000, size: 0x1000_0000 })
+        );
+        assert_eq!(fdt.find_compatible_region("arm,primecell"), Some(FdtRegion { base: 0x0900_0000, size: 0x1000 }));
+        assert_eq!(fdt.find_compatible_region("arm,pl031"), None);
+    }
+
+    #[test]
+    fn test_find_compatible_region_skips_disabled_nodes() {
+        let bytes = virt_fdt(b"disabled\0");
+
+        assert_eq!(Fdt::new(&bytes).unwrap().find_compatible_region(PCI_HOST_ECAM_GENERIC), None);
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_headers() {
+        let mut bytes = virt_fdt(b"okay\0");
+        assert!(Fdt::new(&bytes[..FDT_HEADER_SIZE]).is_none());
+
+        bytes[0] = 0;
+        assert!(Fdt::new(&bytes).is_none());
+        assert!(Fdt::new(&[]).is_none());
+    }
+}
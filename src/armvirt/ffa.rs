@@ -0,0 +1,214 @@
+//! QEMU Arm Virt Firmware Framework for Arm (FF-A) Access
+//!
+//! Minimal FF-A ABI support for reaching a Standalone MM secure partition through the SMC conduit. Only the calls
+//! needed for discovery and direct messaging are implemented, using the SMC64 convention where one exists.
+//!
+//! Host unit tests replace the SMC conduit with the scripted conduit in `armvirt::sim`.
+//!
+//! ## References
+//!
+//! - [Arm Firmware Framework for Arm A-profile](https://developer.arm.com/documentation/den0077/latest)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use patina::error::EfiError;
+
+/// FFA_ERROR function ID
+pub const FFA_ERROR: u64 = 0x8400_0060;
+/// FFA_SUCCESS function ID (SMC32)
+pub const FFA_SUCCESS_32: u64 = 0x8400_0061;
+/// FFA_SUCCESS function ID (SMC64)
+pub const FFA_SUCCESS_64: u64 = 0xC400_0061;
+/// FFA_INTERRUPT function ID
+pub const FFA_INTERRUPT: u64 = 0x8400_0062;
+/// FFA_VERSION function ID
+pub const FFA_VERSION: u64 = 0x8400_0063;
+/// FFA_PARTITION_INFO_GET function ID
+pub const FFA_PARTITION_INFO_GET: u64 = 0x8400_0068;
+/// FFA_ID_GET function ID
+pub const FFA_ID_GET: u64 = 0x8400_0069;
+/// FFA_YIELD function ID
+pub const FFA_YIELD: u64 = 0x8400_006C;
+/// FFA_RUN function ID
+pub const FFA_RUN: u64 = 0x8400_006D;
+/// FFA_MSG_SEND_DIRECT_REQ function ID (SMC64)
+pub const FFA_MSG_SEND_DIRECT_REQ_64: u64 = 0xC400_006F;
+/// FFA_MSG_SEND_DIRECT_RESP function ID (SMC64)
+pub const FFA_MSG_SEND_DIRECT_RESP_64: u64 = 0xC400_0070;
+
+/// FF-A version requested by the caller, 1.1
+pub const FFA_VERSION_1_1: u32 = (1 << 16) | 1;
+/// Lowest FF-A version supported, 1.0
+pub const FFA_VERSION_1_0: u32 = 1 << 16;
+
+/// FFA_PARTITION_INFO_GET flag to return only the partition count
+pub const PARTITION_INFO_GET_COUNT_ONLY: u64 = 0x1;
+
+/// Number of times an interrupted direct request is resumed before giving up
+const MAX_RESUME_COUNT: usize = 1024;
+
+/// Registers passed to and returned from an SMC (`x0` to `x7`).
+pub type SmcArgs = [u64; 8];
+
+/// FF-A error status codes returned in `w2` of FFA_ERROR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum FfaError {
+    /// The operation is not supported.
+    NotSupported = -1,
+    /// A parameter is invalid.
+    InvalidParameters = -2,
+    /// Not enough memory.
+    NoMemory = -3,
+    /// The target is busy.
+    Busy = -4,
+    /// The operation was interrupted.
+    Interrupted = -5,
+    /// The operation is not permitted.
+    Denied = -6,
+    /// The operation should be retried.
+    Retry = -7,
+    /// The operation was aborted.
+    Aborted = -8,
+}
+
+impl FfaError {
+    /// Converts the `w2` value of an FFA_ERROR response.
+    pub fn from_status(status: u64) -> Option<Self> {
+        match status as u32 as i32 {
+            -1 => Some(Self::NotSupported),
+            -2 => Some(Self::InvalidParameters),
+            -3 => Some(Self::NoMemory),
+            -4 => Some(Self::Busy),
+            -5 => Some(Self::Interrupted),
+            -6 => Some(Self::Denied),
+            -7 => Some(Self::Retry),
+            -8 => Some(Self::Aborted),
+            _ => None,
+        }
+    }
+}
+
+impl From<FfaError> for EfiError {
+    fn from(error: FfaError) -> Self {
+        match error {
+            FfaError::NotSupported => EfiError::Unsupported,
+            FfaError::InvalidParameters => EfiError::InvalidParameter,
+            FfaError::NoMemory => EfiError::OutOfResources,
+            FfaError::Busy | FfaError::Retry => EfiError::NotReady,
+            FfaError::Interrupted | FfaError::Aborted => EfiError::Aborted,
+            FfaError::Denied => EfiError::AccessDenied,
+        }
+    }
+}
+
+/// Issues an SMC with the given arguments and returns the result registers.
+#[cfg(all(target_arch = "aarch64", not(test)))]
+pub fn smc(args: SmcArgs) -> SmcArgs {
+    let mut regs = args;
+    // SAFETY: The SMC conduit is handled by EL3 firmware. FF-A calls only read and write x0 to x7 and do not access
+    // memory other than buffers the caller explicitly passes by address.
+    unsafe {
+        core::arch::asm!(
+            "smc #0",
+            inout("x0") regs[0],
+            inout("x1") regs[1],
+            inout("x2") regs[2],
+            inout("x3") regs[3],
+            inout("x4") regs[4],
+            inout("x5") regs[5],
+            inout("x6") regs[6],
+            inout("x7") regs[7],
+            options(nostack),
+        );
+    }
+    regs
+}
+
+/// Issues an SMC with the given arguments and returns the result registers.
+///
+/// There is no SMC conduit on this architecture, so every call fails with `NOT_SUPPORTED`.
+#[cfg(all(not(target_arch = "aarch64"), not(test)))]
+pub fn smc(_args: SmcArgs) -> SmcArgs {
+    [FFA_ERROR, 0, FfaError::NotSupported as i32 as u32 as u64, 0, 0, 0, 0, 0]
+}
+
+#[cfg(test)]
+pub use crate::armvirt::sim::smc;
+
+/// Converts an FFA_SUCCESS response to its registers and an FFA_ERROR response to an error.
+fn expect_success(regs: SmcArgs) -> Result<SmcArgs, FfaError> {
+    match regs[0] {
+        FFA_SUCCESS_32 | FFA_SUCCESS_64 => Ok(regs),
+        FFA_ERROR => Err(FfaError::from_status(regs[2]).unwrap_or(FfaError::NotSupported)),
+        other => {
+            log::error!("Unexpected FF-A response {other:#X}");
+            Err(FfaError::NotSupported)
+        }
+    }
+}
+
+/// Negotiates the FF-A version and returns the version implemented by the SPMC.
+///
+/// ## Errors
+///
+/// - `FfaError::NotSupported` if FF-A is not implemented or the version is older than 1.0 or a different major
+///   version.
+pub fn version() -> Result<u32, FfaError> {
+    let regs = smc([FFA_VERSION, FFA_VERSION_1_1 as u64, 0, 0, 0, 0, 0, 0]);
+    let version = regs[0] as u32;
+    if version as i32 == FfaError::NotSupported as i32 {
+        return Err(FfaError::NotSupported);
+    }
+    if version >> 16 != FFA_VERSION_1_0 >> 16 || version < FFA_VERSION_1_0 {
+        log::error!("Unsupported FF-A version {}.{}", version >> 16, version & 0xFFFF);
+        return Err(FfaError::NotSupported);
+    }
+    Ok(version)
+}
+
+/// Returns the FF-A ID of the caller.
+pub fn id_get() -> Result<u16, FfaError> {
+    expect_success(smc([FFA_ID_GET, 0, 0, 0, 0, 0, 0, 0])).map(|regs| regs[2] as u16)
+}
+
+/// Returns the number of partitions with the given UUID, given as the four little-endian words of its byte encoding.
+pub fn partition_count(uuid: [u32; 4]) -> Result<u32, FfaError> {
+    let [w1, w2, w3, w4] = uuid.map(u64::from);
+    expect_success(smc([FFA_PARTITION_INFO_GET, w1, w2, w3, w4, PARTITION_INFO_GET_COUNT_ONLY, 0, 0]))
+        .map(|regs| regs[2] as u32)
+}
+
+/// Sends a 64-bit direct request message from `source` to `destination` and waits for the direct response.
+///
+/// Interrupted and yielded requests are resumed with FFA_RUN. Returns the five message registers (`x3` to `x7`) of
+/// the response.
+///
+/// ## Errors
+///
+/// - Any FF-A error returned for the request.
+/// - `FfaError::Aborted` if the request does not complete after being resumed repeatedly.
+pub fn send_direct_request(source: u16, destination: u16, message: [u64; 5]) -> Result<[u64; 5], FfaError> {
+    let endpoints = ((source as u64) << 16) | destination as u64;
+    let [m0, m1, m2, m3, m4] = message;
+    let mut regs = smc([FFA_MSG_SEND_DIRECT_REQ_64, endpoints, 0, m0, m1, m2, m3, m4]);
+
+    for _ in 0..MAX_RESUME_COUNT {
+        match regs[0] {
+            FFA_MSG_SEND_DIRECT_RESP_64 => return Ok([regs[3], regs[4], regs[5], regs[6], regs[7]]),
+            FFA_INTERRUPT | FFA_YIELD => {
+                log::trace!("FF-A direct request to {destination:#06X} resumed after {:#X}", regs[0]);
+                regs = smc([FFA_RUN, (destination as u64) << 16, 0, 0, 0, 0, 0, 0]);
+            }
+            _ => return expect_success(regs).and(Err(FfaError::NotSupported)),
+        }
+    }
+
+    log::error!("FF-A direct request to {destination:#06X} did not complete");
+    Err(FfaError::Aborted)
+}
@@ -0,0 +1,119 @@
+//! QEMU Arm Virt Firmware Configuration (fw_cfg) Access
+//!
+//! This module provides read access to the QEMU Firmware Configuration device through the MMIO interface of the Arm
+//! Virt machine. The selector register is big-endian, and the data register returns the bytes of the selected item
+//! in order, one per read.
+//!
+//! ## References
+//!
+//! - [QEMU Firmware Configuration (fw_cfg) Device](https://www.qemu.org/docs/master/specs/fw_cfg.html)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{vec, vec::Vec};
+
+/// Base address of the fw_cfg MMIO registers on the Arm Virt machine
+pub const FW_CFG_BASE: usize = 0x0902_0000;
+
+/// Offset of the data register
+const DATA_OFFSET: usize = 0x0;
+/// Offset of the selector register
+const SELECTOR_OFFSET: usize = 0x8;
+
+/// Signature item key
+pub const KEY_SIGNATURE: u16 = 0x0000;
+/// UUID item key
+pub const KEY_UUID: u16 = 0x0002;
+/// Boot CPU count item key
+pub const KEY_NB_CPUS: u16 = 0x0005;
+/// File directory item key
+pub const KEY_FILE_DIR: u16 = 0x0019;
+
+/// Expected value of the signature item
+const SIGNATURE: [u8; 4] = *b"QEMU";
+
+/// Maximum length of a file name in the file directory, including the NUL terminator
+const FILE_NAME_SIZE: usize = 56;
+/// Size of a file directory entry
+const FILE_ENTRY_SIZE: usize = 4 + 2 + 2 + FILE_NAME_SIZE;
+
+/// A file in the fw_cfg file directory.
+#[derive(Debug, Clone, Copy)]
+pub struct FwCfgFile {
+    /// Size of the file in bytes
+    pub size: u32,
+    /// Item key that selects the file
+    pub select: u16,
+}
+
+/// Access to the QEMU fw_cfg device.
+pub struct FwCfg {
+    base: usize,
+}
+
+impl FwCfg {
+    /// Returns fw_cfg access if the device is present at `base`.
+    ///
+    /// ## Safety
+    ///
+    /// `base` must be the address of the fw_cfg MMIO registers, mapped as device memory.
+    pub unsafe fn new(base: usize) -> Option<Self> {
+        let fw_cfg = Self { base };
+
+        let mut signature = [0u8; 4];
+        fw_cfg.read_item(KEY_SIGNATURE, &mut signature);
+        if signature != SIGNATURE {
+            log::debug!("fw_cfg signature not found: {signature:02X?}");
+            return None;
+        }
+
+        Some(fw_cfg)
+    }
+
+    /// Selects the item with the given key and reads `buffer.len()` bytes from the start of the item.
+    pub fn read_item(&self, key: u16, buffer: &mut [u8]) {
+        let selector = (self.base + SELECTOR_OFFSET) as *mut u16;
+        let data = (self.base + DATA_OFFSET) as *const u8;
+
+        // SAFETY: `new` requires the base address of the mapped fw_cfg registers. Reading past the end of an item or
+        // reading a missing item returns zeros.
+        unsafe {
+            core::ptr::write_volatile(selector, key.to_be());
+            for byte in buffer.iter_mut() {
+                *byte = core::ptr::read_volatile(data);
+            }
+        }
+    }
+
+    /// Searches the file directory for a file with the given name.
+    pub fn find_file(&self, name: &str) -> Option<FwCfgFile> {
+        let mut count = [0u8; 4];
+        self.read_item(KEY_FILE_DIR, &mut count);
+        let count = u32::from_be_bytes(count) as usize;
+
+        let mut directory = vec![0u8; 4 + count * FILE_ENTRY_SIZE];
+        self.read_item(KEY_FILE_DIR, &mut directory);
+
+        directory[4..].chunks_exact(FILE_ENTRY_SIZE).find_map(|entry| {
+            let file_name = &entry[8..];
+            let file_name = &file_name[..file_name.iter().position(|&b| b == 0).unwrap_or(FILE_NAME_SIZE)];
+            (file_name == name.as_bytes()).then(|| FwCfgFile {
+                size: u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]),
+                select: u16::from_be_bytes([entry[4], entry[5]]),
+            })
+        })
+    }
+
+    /// Reads the full contents of a file.
+    pub fn read_file(&self, file: &FwCfgFile) -> Vec<u8> {
+        let mut buffer = vec![0u8; file.size as usize];
+        self.read_item(file.select, &mut buffer);
+        buffer
+    }
+}
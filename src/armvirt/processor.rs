@@ -0,0 +1,255 @@
+//! Arm Virt Processor Information
+//!
+//! Reads the identification and cache hierarchy of the boot processor from its system registers. QEMU sets these
+//! from the `-cpu` model, so the SMBIOS processor and cache records describe the processor the machine emulates.
+//!
+//! The cache levels come from `CLIDR_EL1`, and the geometry of each cache from `CCSIDR_EL1`, in its 64-bit layout
+//! when `ID_AA64MMFR2_EL1.CCIDX` reports it. The cache ID registers do not describe how caches are shared between
+//! cores, so the sizes are those of one instance of each cache.
+//!
+//! ## References
+//!
+//! - Arm Architecture Reference Manual for A-profile architecture (DDI 0487), D23.2 AArch64 System Registers
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use patina_smbios::smbios_types::{AssociativityField, SystemCacheType};
+
+use crate::smbios::SmbiosCache;
+
+/// Number of cache levels `CLIDR_EL1` describes
+const CLIDR_LEVELS: u8 = 7;
+/// `CLIDR_EL1.Ctype<n>` value for a level with only an instruction cache
+const CTYPE_INSTRUCTION: u64 = 0b001;
+/// `CLIDR_EL1.Ctype<n>` value for a level with only a data cache
+const CTYPE_DATA: u64 = 0b010;
+/// `CLIDR_EL1.Ctype<n>` value for a level with separate instruction and data caches
+const CTYPE_SEPARATE: u64 = 0b011;
+/// `CLIDR_EL1.Ctype<n>` value for a level with a unified cache
+const CTYPE_UNIFIED: u64 = 0b100;
+
+/// Identification and caches of the boot processor.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessorInfo {
+    /// `MIDR_EL1`, the Main ID Register
+    pub midr: u64,
+    /// Caches, from the innermost level, with a data cache before the instruction cache of the same level
+    pub caches: Vec<SmbiosCache>,
+}
+
+impl ProcessorInfo {
+    /// Reads the processor information of the executing processor.
+    pub fn read() -> Self {
+        let ccidx = (registers::id_aa64mmfr2() >> 20) & 0xF != 0;
+        let caches = decode_caches(registers::clidr(), |level, instruction| {
+            decode_ccsidr(registers::ccsidr(level, instruction), ccidx)
+        });
+        Self { midr: registers::midr(), caches }
+    }
+
+    /// Returns the implementer code, `MIDR_EL1.Implementer`.
+    pub fn implementer(&self) -> u8 {
+        (self.midr >> 24) as u8
+    }
+
+    /// Returns the name of the implementer, or an empty string for an implementer without a known name.
+    pub fn manufacturer(&self) -> &'static str {
+        match self.implementer() {
+            0x41 => "Arm Limited",
+            0x42 => "Broadcom Corporation",
+            0x43 => "Cavium Inc.",
+            0x46 => "Fujitsu Ltd.",
+            0x48 => "HiSilicon Technologies Co. Ltd.",
+            0x4E => "NVIDIA Corporation",
+            0x51 => "Qualcomm Inc.",
+            0x61 => "Apple Inc.",
+            0xC0 => "Ampere Computing",
+            _ => "",
+        }
+    }
+
+    /// Returns the SMBIOS processor ID of an Arm processor without an SoC ID: `MIDR_EL1` followed by zeros.
+    pub fn processor_id(&self) -> [u8; 8] {
+        let mut id = [0u8; 8];
+        id[..4].copy_from_slice(&(self.midr as u32).to_le_bytes());
+        id
+    }
+}
+
+/// Returns the caches of the levels described by `clidr`, reading the geometry of each with `ccsidr`.
+///
+/// `ccsidr` receives the level, starting at 1, and whether the instruction cache is selected, and returns the line
+/// size in bytes, the ways and the sets of the cache.
+fn decode_caches(clidr: u64, ccsidr: impl Fn(u8, bool) -> (u64, u32, u32)) -> Vec<SmbiosCache> {
+    let mut caches = Vec::new();
+    for level in 1..=CLIDR_LEVELS {
+        let selections: &[(SystemCacheType, bool)] = match (clidr >> (3 * (level - 1))) & 0b111 {
+            CTYPE_INSTRUCTION => &[(SystemCacheType::Instruction, true)],
+            CTYPE_DATA => &[(SystemCacheType::Data, false)],
+            CTYPE_SEPARATE => &[(SystemCacheType::Data, false), (SystemCacheType::Instruction, true)],
+            CTYPE_UNIFIED => &[(SystemCacheType::Unified, false)],
+            _ => break,
+        };
+
+        for &(cache_type, instruction) in selections {
+            let (line_size, ways, sets) = ccsidr(level, instruction);
+            let associativity = match sets {
+                1 => AssociativityField::FullyAssociative,
+                _ => SmbiosCache::set_associativity(ways),
+            };
+            caches.push(SmbiosCache { level, cache_type, size: line_size * ways as u64 * sets as u64, associativity });
+        }
+    }
+    caches
+}
+
+/// Decodes `CCSIDR_EL1` into the line size in bytes, the ways and the sets of the cache.
+///
+/// `ccidx` selects the 64-bit layout of `FEAT_CCIDX`.
+fn decode_ccsidr(ccsidr: u64, ccidx: bool) -> (u64, u32, u32) {
+    let line_size = 1u64 << ((ccsidr & 0x7) + 4);
+    let (ways, sets) = match ccidx {
+        true => ((ccsidr >> 3) & 0x1F_FFFF, (ccsidr >> 32) & 0xFF_FFFF),
+        false => ((ccsidr >> 3) & 0x3FF, (ccsidr >> 13) & 0x7FFF),
+    };
+    (line_size, ways as u32 + 1, sets as u32 + 1)
+}
+
+/// Accessors for the processor identification registers.
+#[cfg(target_arch = "aarch64")]
+mod registers {
+    /// Reads `MIDR_EL1`.
+    pub fn midr() -> u64 {
+        let value: u64;
+        // SAFETY: MIDR_EL1 is readable at EL1 and reading it has no side effects.
+        unsafe { core::arch::asm!("mrs {}, midr_el1", out(reg) value, options(nomem, nostack)) };
+        value
+    }
+
+    /// Reads `ID_AA64MMFR2_EL1`.
+    pub fn id_aa64mmfr2() -> u64 {
+        let value: u64;
+        // SAFETY: ID_AA64MMFR2_EL1 is readable at EL1 and reading it has no side effects.
+        unsafe { core::arch::asm!("mrs {}, id_aa64mmfr2_el1", out(reg) value, options(nomem, nostack)) };
+        value
+    }
+
+    /// Reads `CLIDR_EL1`.
+    pub fn clidr() -> u64 {
+        let value: u64;
+        // SAFETY: CLIDR_EL1 is readable at EL1 and reading it has no side effects.
+        unsafe { core::arch::asm!("mrs {}, clidr_el1", out(reg) value, options(nomem, nostack)) };
+        value
+    }
+
+    /// Selects the cache at `level`, starting at 1, in `CSSELR_EL1` and reads its `CCSIDR_EL1`.
+    pub fn ccsidr(level: u8, instruction: bool) -> u64 {
+        let selection = ((level as u64 - 1) << 1) | instruction as u64;
+        let value: u64;
+        // SAFETY: CSSELR_EL1 only selects the cache CCSIDR_EL1 describes. DXE runs on the boot processor with
+        // interrupts that do not use CSSELR_EL1, so the selection is not changed before it is read back.
+        unsafe {
+            core::arch::asm!(
+                "msr csselr_el1, {selection}",
+                "isb",
+                "mrs {value}, ccsidr_el1",
+                selection = in(reg) selection,
+                value = out(reg) value,
+                options(nomem, nostack),
+            )
+        };
+        value
+    }
+}
+
+/// Accessors for the processor identification registers. Hosts other than AArch64 report no identification and no
+/// caches.
+#[cfg(not(target_arch = "aarch64"))]
+mod registers {
+    pub fn midr() -> u64 {
+        0
+    }
+
+    pub fn id_aa64mmfr2() -> u64 {
+        0
+    }
+
+    pub fn clidr() -> u64 {
+        0
+    }
+
+    pub fn ccsidr(_level: u8, _instruction: bool) -> u64 {
+        0
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    /// Encodes a 32-bit `CCSIDR_EL1`.
+    fn ccsidr(line_size: u64, ways: u64, sets: u64) -> u64 {
+        (line_size.trailing_zeros() as u64 - 4) | ((ways - 1) << 3) | ((sets - 1) << 13)
+    }
+
+    #[test]
+    fn test_decode_ccsidr() {
+        assert_eq!(decode_ccsidr(ccsidr(64, 4, 256), false), (64, 4, 256));
+
+        let ccidx = 0b010 | (15 << 3) | (2047 << 32);
+        assert_eq!(decode_ccsidr(ccidx, true), (64, 16, 2048));
+    }
+
+    #[test]
+    fn test_decode_caches() {
+        // Separate L1, unified L2, no L3
+        let clidr = CTYPE_SEPARATE | (CTYPE_UNIFIED << 3);
+        let caches = decode_caches(clidr, |level, instruction| match (level, instruction) {
+            (1, false) => (64, 4, 256),
+            (1, true) => (64, 3, 256),
+            (2, false) => (64, 16, 2048),
+            _ => panic!("Unexpected cache selection"),
+        });
+
+        assert_eq!(caches.len(), 3);
+        assert_eq!((caches[0].level, caches[0].size), (1, 64 * 1024));
+        assert!(matches!(caches[0].cache_type, SystemCacheType::Data));
+        assert!(matches!(caches[0].associativity, AssociativityField::SetAssociative4Way));
+        assert_eq!((caches[1].level, caches[1].size), (1, 48 * 1024));
+        assert!(matches!(caches[1].cache_type, SystemCacheType::Instruction));
+        assert!(matches!(caches[1].associativity, AssociativityField::Other));
+        assert_eq!((caches[2].level, caches[2].size), (2, 2 * 1024 * 1024));
+        assert!(matches!(caches[2].cache_type, SystemCacheType::Unified));
+    }
+
+    #[test]
+    fn test_decode_caches_fully_associative() {
+        let caches = decode_caches(CTYPE_DATA, |_, _| (64, 8, 1));
+
+        assert_eq!(caches.len(), 1);
+        assert!(matches!(caches[0].associativity, AssociativityField::FullyAssociative));
+    }
+
+    #[test]
+    fn test_decode_caches_without_caches() {
+        assert!(decode_caches(0, |_, _| panic!("No cache should be read")).is_empty());
+    }
+
+    #[test]
+    fn test_processor_id_and_manufacturer() {
+        let processor = ProcessorInfo { midr: 0x411F_D070, caches: Vec::new() };
+
+        assert_eq!(processor.processor_id(), [0x70, 0xD0, 0x1F, 0x41, 0, 0, 0, 0]);
+        assert_eq!(processor.manufacturer(), "Arm Limited");
+        assert_eq!(ProcessorInfo::default().manufacturer(), "");
+    }
+}
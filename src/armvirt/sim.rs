@@ -0,0 +1,131 @@
+//! QEMU Arm Virt Simulated FF-A Environment
+//!
+//! A host-only simulation of the Secure Partition Manager Core (SPMC) reached through the SMC conduit, so the FF-A
+//! MM components can be exercised by unit tests. `armvirt::ffa` routes its SMCs to [`smc`] under `cfg(test)`.
+//!
+//! The SPMC state is thread-local, so tests running in parallel do not observe each other.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg(test)]
+
+extern crate std;
+use std::{boxed::Box, cell::RefCell, vec::Vec};
+
+use crate::armvirt::ffa::{self, FfaError, SmcArgs};
+
+std::thread_local! {
+    static SPMC: RefCell<SimulatedSpmc> = RefCell::new(SimulatedSpmc::default());
+}
+
+/// Handles a direct request message sent to a partition and returns the response message.
+pub type DirectRequestHandler = Box<dyn FnMut([u64; 5]) -> [u64; 5]>;
+
+/// Simulated SPMC state.
+pub struct SimulatedSpmc {
+    /// Value returned for FFA_VERSION. `None` if FF-A is not implemented.
+    pub version: Option<u32>,
+    /// FF-A ID of the caller.
+    pub caller_id: u16,
+    /// Partitions as `(uuid, partition_id)` pairs.
+    pub partitions: Vec<([u32; 4], u16)>,
+    /// Number of FFA_INTERRUPT responses returned before each direct response.
+    pub interrupts: usize,
+    /// Handler for direct requests to the partitions.
+    pub handler: Option<DirectRequestHandler>,
+    /// SMCs received, in order.
+    pub calls: Vec<SmcArgs>,
+    pending: Option<PendingResponse>,
+}
+
+/// A direct response that is delayed by interrupts.
+struct PendingResponse {
+    interrupts: usize,
+    endpoints: u64,
+    message: [u64; 5],
+}
+
+impl Default for SimulatedSpmc {
+    fn default() -> Self {
+        Self {
+            version: Some(ffa::FFA_VERSION_1_1),
+            caller_id: 0,
+            partitions: Vec::new(),
+            interrupts: 0,
+            handler: None,
+            calls: Vec::new(),
+            pending: None,
+        }
+    }
+}
+
+impl SimulatedSpmc {
+    fn call(&mut self, args: SmcArgs) -> SmcArgs {
+        self.calls.push(args);
+        match args[0] {
+            ffa::FFA_VERSION => match self.version {
+                Some(version) => [version as u64, 0, 0, 0, 0, 0, 0, 0],
+                None => [FfaError::NotSupported as i32 as u32 as u64, 0, 0, 0, 0, 0, 0, 0],
+            },
+            ffa::FFA_ID_GET => [ffa::FFA_SUCCESS_32, 0, self.caller_id as u64, 0, 0, 0, 0, 0],
+            ffa::FFA_PARTITION_INFO_GET if args[5] == ffa::PARTITION_INFO_GET_COUNT_ONLY => {
+                let uuid = [args[1] as u32, args[2] as u32, args[3] as u32, args[4] as u32];
+                match self.partitions.iter().filter(|(partition, _)| *partition == uuid).count() {
+                    0 => error(FfaError::InvalidParameters),
+                    count => [ffa::FFA_SUCCESS_32, 0, count as u64, 0, 0, 0, 0, 0],
+                }
+            }
+            ffa::FFA_MSG_SEND_DIRECT_REQ_64 => {
+                let (source, destination) = ((args[1] >> 16) as u16, args[1] as u16);
+                if source != self.caller_id || !self.partitions.iter().any(|(_, id)| *id == destination) {
+                    return error(FfaError::InvalidParameters);
+                }
+                let Some(handler) = self.handler.as_mut() else {
+                    return error(FfaError::Busy);
+                };
+                let message = handler([args[3], args[4], args[5], args[6], args[7]]);
+                self.pending = Some(PendingResponse { interrupts: self.interrupts, endpoints: args[1], message });
+                self.respond()
+            }
+            ffa::FFA_RUN if self.pending.is_some() => self.respond(),
+            _ => error(FfaError::NotSupported),
+        }
+    }
+
+    /// Returns FFA_INTERRUPT while interrupts are pending, then the direct response.
+    fn respond(&mut self) -> SmcArgs {
+        let pending = self.pending.as_mut().expect("A direct request is pending");
+        if pending.interrupts > 0 {
+            pending.interrupts -= 1;
+            return [ffa::FFA_INTERRUPT, 0, 0, 0, 0, 0, 0, 0];
+        }
+
+        let PendingResponse { endpoints, message: [m0, m1, m2, m3, m4], .. } = self.pending.take().unwrap();
+        // The response swaps the source and destination of the request.
+        let endpoints = ((endpoints & 0xFFFF) << 16) | (endpoints >> 16);
+        [ffa::FFA_MSG_SEND_DIRECT_RESP_64, endpoints, 0, m0, m1, m2, m3, m4]
+    }
+}
+
+fn error(error: FfaError) -> SmcArgs {
+    [ffa::FFA_ERROR, 0, error as i32 as u32 as u64, 0, 0, 0, 0, 0]
+}
+
+/// Resets the simulated SPMC of the current thread.
+pub fn reset() {
+    SPMC.with(|spmc| *spmc.borrow_mut() = SimulatedSpmc::default());
+}
+
+/// Runs `f` with the simulated SPMC of the current thread.
+pub fn with_spmc<R>(f: impl FnOnce(&mut SimulatedSpmc) -> R) -> R {
+    SPMC.with(|spmc| f(&mut spmc.borrow_mut()))
+}
+
+/// Handles an SMC with the simulated SPMC. Has the same signature as the hardware `armvirt::ffa::smc`.
+pub fn smc(args: SmcArgs) -> SmcArgs {
+    with_spmc(|spmc| spmc.call(args))
+}
@@ -13,5 +13,10 @@
 
 #[cfg(any(feature = "aarch64", test))]
 pub mod armvirt;
+pub mod mm_variable;
 #[cfg(any(feature = "x64", test))]
 pub mod q35;
+#[cfg(test)]
+pub mod sim;
+pub mod smbios;
+pub mod variable_services;
@@ -0,0 +1,49 @@
+//! MM Variable Messages
+//!
+//! Definitions of the messages exchanged with the EDK II Standalone MM variable handler (`VariableStandaloneMm`),
+//! shared by the QEMU platforms. The layouts match the `SMM_VARIABLE_COMMUNICATE_*` structures. Every message starts
+//! with an `SMM_VARIABLE_COMMUNICATE_HEADER` holding the function code and the status returned by the handler.
+//!
+//! ## References
+//!
+//! - [EDK II SmmVariableCommon.h](https://github.com/tianocore/edk2/blob/master/MdeModulePkg/Include/Guid/SmmVariableCommon.h)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::BinaryGuid;
+
+/// GUID of the MM variable handler (`gEfiSmmVariableProtocolGuid`)
+pub const MM_VARIABLE_HANDLER_GUID: BinaryGuid = BinaryGuid::from_string("ED32D533-99E6-4209-9CC0-2D72CDD998A7");
+
+/// MM variable handler function codes (`SMM_VARIABLE_FUNCTION_*`)
+pub mod function {
+    /// `SMM_VARIABLE_FUNCTION_GET_VARIABLE`
+    pub const GET_VARIABLE: u64 = 1;
+    /// `SMM_VARIABLE_FUNCTION_GET_NEXT_VARIABLE_NAME`
+    pub const GET_NEXT_VARIABLE_NAME: u64 = 2;
+    /// `SMM_VARIABLE_FUNCTION_SET_VARIABLE`
+    pub const SET_VARIABLE: u64 = 3;
+    /// `SMM_VARIABLE_FUNCTION_QUERY_VARIABLE_INFO`
+    pub const QUERY_VARIABLE_INFO: u64 = 4;
+    /// `SMM_VARIABLE_FUNCTION_READY_TO_BOOT`
+    pub const READY_TO_BOOT: u64 = 5;
+    /// `SMM_VARIABLE_FUNCTION_EXIT_BOOT_SERVICE`
+    pub const EXIT_BOOT_SERVICE: u64 = 6;
+    /// `SMM_VARIABLE_FUNCTION_GET_PAYLOAD_SIZE`
+    pub const GET_PAYLOAD_SIZE: u64 = 11;
+}
+
+/// Size of `SMM_VARIABLE_COMMUNICATE_HEADER` up to `Data` (`Function`, `ReturnStatus`)
+pub const COMMUNICATE_HEADER_SIZE: usize = 16;
+/// Offset of `Name` in `SMM_VARIABLE_COMMUNICATE_ACCESS_VARIABLE` (`Guid`, `DataSize`, `NameSize`, `Attributes`)
+pub const ACCESS_VARIABLE_HEADER_SIZE: usize = 36;
+/// Offset of `Name` in `SMM_VARIABLE_COMMUNICATE_GET_NEXT_VARIABLE_NAME` (`Guid`, `NameSize`)
+pub const GET_NEXT_VARIABLE_NAME_HEADER_SIZE: usize = 24;
+/// Size of `SMM_VARIABLE_COMMUNICATE_QUERY_VARIABLE_INFO`, including trailing padding
+pub const QUERY_VARIABLE_INFO_SIZE: usize = 32;
+/// Size of `SMM_VARIABLE_COMMUNICATE_GET_PAYLOAD_SIZE`
+pub const GET_PAYLOAD_SIZE_SIZE: usize = 8;
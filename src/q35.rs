@@ -9,5 +9,11 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 pub mod component;
+pub mod cpuid;
+pub mod fw_cfg;
+pub mod hw;
 pub mod registers;
+#[cfg(test)]
+pub mod sim;
+pub mod smi_features;
 pub mod timer;
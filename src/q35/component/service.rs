@@ -9,12 +9,24 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 #[coverage(off)]
+pub mod mm_comm_stress_test;
 pub mod mm_config_provider;
-#[coverage(off)]
 pub mod mm_control;
+pub mod mm_supervisor_client;
+pub mod mm_supervisor_policy;
 #[coverage(off)]
+pub mod mm_supervisor_policy_test;
 pub mod mm_test;
+pub mod mm_variable;
 #[coverage(off)]
+pub mod mm_variable_test;
+pub mod ovmf_mm_config_provider;
 pub mod smbios_platform;
 #[coverage(off)]
 pub mod smbios_test;
+pub mod smi_feature_negotiation;
+pub mod smram_lock;
+#[coverage(off)]
+pub mod smram_lock_test;
+#[coverage(off)]
+pub mod sw_mmi_test;
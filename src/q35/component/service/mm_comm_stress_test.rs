@@ -0,0 +1,242 @@
+//! QEMU Q35 MM Communication Stress Tests
+//!
+//! Exercises the `MmCommunication` service with many back-to-back requests, maximum-size payloads and malformed
+//! input. Every malformed request must fail cleanly, and a well-formed MM Supervisor version request sent afterwards
+//! must return the same result as before, showing that the communication buffer was not corrupted.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{vec, vec::Vec};
+
+use patina::{
+    BinaryGuid,
+    component::{params::Config, service::Service},
+    error::EfiError,
+    management_mode::protocol::mm_supervisor_request::{
+        self, MM_SUPERVISOR_REQUEST_HANDLER_GUID, MmSupervisorRequestHeader, MmSupervisorVersionInfo, RequestType,
+    },
+};
+use patina_mm::{component::communicator::Status, config::MmCommunicationConfiguration, service::MmCommunication};
+use patina_test::{patina_test, u_assert, u_assert_eq};
+use r_efi::efi;
+use zerocopy::IntoBytes;
+
+use crate::q35::component::service::{
+    mm_config_provider::{CommunicateBufferTypeExt, MmCommBufferType},
+    mm_supervisor_client::{map_comm_status, parse_response},
+};
+
+/// Number of requests sent by the back-to-back test
+const BACK_TO_BACK_REQUESTS: usize = 256;
+
+/// A GUID that no MM handler is registered for
+const UNKNOWN_HANDLER_GUID: BinaryGuid = BinaryGuid::from_string("0C4F1B6E-2A7D-4E53-9B18-6F3D7A2C5E91");
+
+/// Version information fields compared between requests: `(version, patch_level, max_supervisor_request_level)`
+type VersionFields = (u32, u32, u64);
+
+/// Sends many version requests back-to-back and expects every response to be valid and identical.
+#[patina_test]
+fn q35_mm_comm_back_to_back_test(mm_comm: Service<dyn MmCommunication>) -> patina_test::error::Result {
+    let expected = version_request(&mm_comm, MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE)?;
+
+    for _ in 0..BACK_TO_BACK_REQUESTS {
+        let version = version_request(&mm_comm, MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE)?;
+        u_assert_eq!(version, expected, "Back-to-back version requests returned different results");
+    }
+
+    Ok(())
+}
+
+/// Sends a version request padded to the full comm buffer capacity, and one byte more.
+#[patina_test]
+fn q35_mm_comm_max_payload_test(
+    mm_comm: Service<dyn MmCommunication>,
+    config: Config<MmCommunicationConfiguration>,
+) -> patina_test::error::Result {
+    let expected = version_request(&mm_comm, MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE)?;
+
+    let Some(capacity) = config
+        .comm_buffers
+        .iter()
+        .find(|buffer| buffer.buffer_type() == Some(MmCommBufferType::User))
+        .map(|buffer| buffer.message_capacity())
+    else {
+        return Err("No user MM communication buffer is configured");
+    };
+    log::debug!("  User comm buffer message capacity: {capacity:#X}");
+
+    let version = version_request(&mm_comm, capacity)?;
+    u_assert_eq!(version, expected, "A maximum-size version request returned a different result");
+
+    let oversized = version_message(capacity + 1);
+    u_assert_eq!(
+        mm_comm
+            .communicate(MmCommBufferType::User.id(), &oversized, MM_SUPERVISOR_REQUEST_HANDLER_GUID.as_guid())
+            .err(),
+        Some(Status::CommBufferTooSmall),
+        "A request larger than the comm buffer should fail with CommBufferTooSmall"
+    );
+
+    check_not_corrupted(&mm_comm, expected)
+}
+
+/// Sends a request to a handler GUID that is not registered and expects MM to report `NotFound`.
+#[patina_test]
+fn q35_mm_comm_unknown_handler_test(
+    mm_comm: Service<dyn MmCommunication>,
+    config: Config<MmCommunicationConfiguration>,
+) -> patina_test::error::Result {
+    let expected = version_request(&mm_comm, MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE)?;
+
+    let message = version_message(MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE);
+    u_assert_eq!(
+        communicate_status(&mm_comm, &config, &message, UNKNOWN_HANDLER_GUID)?,
+        Err(EfiError::NotFound),
+        "A request to an unknown handler should fail with NotFound"
+    );
+
+    check_not_corrupted(&mm_comm, expected)
+}
+
+/// Sends empty, truncated and invalid requests and expects each to be rejected.
+#[patina_test]
+fn q35_mm_comm_malformed_request_test(
+    mm_comm: Service<dyn MmCommunication>,
+    config: Config<MmCommunicationConfiguration>,
+) -> patina_test::error::Result {
+    let expected = version_request(&mm_comm, MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE)?;
+    let user = MmCommBufferType::User.id();
+    let recipient = MM_SUPERVISOR_REQUEST_HANDLER_GUID.as_guid();
+
+    u_assert_eq!(
+        mm_comm.communicate(user, &[], recipient.clone()).err(),
+        Some(Status::InvalidDataBuffer),
+        "An empty request should fail with InvalidDataBuffer"
+    );
+
+    u_assert_eq!(
+        mm_comm.communicate(u8::MAX, &version_message(MmSupervisorRequestHeader::SIZE), recipient.clone()).err(),
+        Some(Status::CommBufferNotFound),
+        "A request to a missing comm buffer should fail with CommBufferNotFound"
+    );
+
+    // Headers truncated at every field boundary, and one byte short of a full header.
+    for length in [4, 8, 12, 16, MmSupervisorRequestHeader::SIZE - 1] {
+        let message = version_message(MmSupervisorRequestHeader::SIZE);
+        u_assert_eq!(
+            communicate_status(&mm_comm, &config, &message[..length], MM_SUPERVISOR_REQUEST_HANDLER_GUID)?,
+            Err(EfiError::InvalidParameter),
+            "A truncated request should fail with InvalidParameter"
+        );
+        check_not_corrupted(&mm_comm, expected)?;
+    }
+
+    // A full-size request with a bad signature.
+    let mut message = version_message(MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE);
+    message[..4].copy_from_slice(&(!mm_supervisor_request::SIGNATURE).to_le_bytes());
+    expect_rejected(&mm_comm, &message)?;
+    check_not_corrupted(&mm_comm, expected)?;
+
+    // A full-size request with an unsupported revision.
+    let mut message = version_message(MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE);
+    message[4..8].copy_from_slice(&(mm_supervisor_request::REVISION + 1).to_le_bytes());
+    expect_rejected(&mm_comm, &message)?;
+    check_not_corrupted(&mm_comm, expected)?;
+
+    // A full-size request with an unknown request type.
+    let mut message = version_message(MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE);
+    message[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    expect_rejected(&mm_comm, &message)?;
+
+    check_not_corrupted(&mm_comm, expected)
+}
+
+/// Builds a version info request of `length` bytes, zero-padded after the header.
+fn version_message(length: usize) -> Vec<u8> {
+    let header = MmSupervisorRequestHeader {
+        signature: mm_supervisor_request::SIGNATURE,
+        revision: mm_supervisor_request::REVISION,
+        request: RequestType::VersionInfo.into(),
+        reserved: 0,
+        result: 0,
+    };
+
+    let mut message = vec![0u8; length.max(MmSupervisorRequestHeader::SIZE)];
+    message[..MmSupervisorRequestHeader::SIZE].copy_from_slice(header.as_bytes());
+    message.truncate(length);
+    message
+}
+
+/// Sends a version info request of `length` bytes and returns the validated version information.
+fn version_request(mm_comm: &Service<dyn MmCommunication>, length: usize) -> Result<VersionFields, &'static str> {
+    let message = version_message(length);
+    let response = mm_comm
+        .communicate(MmCommBufferType::User.id(), &message, MM_SUPERVISOR_REQUEST_HANDLER_GUID.as_guid())
+        .map_err(|status| {
+            log::error!("  Version request of {length:#X} bytes failed: {status:?}");
+            "MM Communication failed for a well-formed version request"
+        })?;
+    u_assert_eq!(response.len(), message.len(), "The response length should match the request length");
+
+    let payload = parse_response(RequestType::VersionInfo, &response)
+        .map_err(|_| "A well-formed version request returned an invalid response")?;
+    let version_info =
+        MmSupervisorVersionInfo::from_bytes(payload).ok_or("The version response is too small for version info")?;
+
+    Ok((version_info.version, version_info.patch_level, version_info.max_supervisor_request_level))
+}
+
+/// Sends `message` to `recipient` through the user comm buffer and returns the status reported by MM.
+///
+/// A failure of the communication itself is mapped with `map_comm_status`. Otherwise the status the MM handler wrote
+/// to the comm buffer status mailbox is returned, since `MmCommunication` returns the response even when the handler
+/// fails.
+fn communicate_status(
+    mm_comm: &Service<dyn MmCommunication>,
+    config: &Config<MmCommunicationConfiguration>,
+    message: &[u8],
+    recipient: BinaryGuid,
+) -> Result<patina::error::Result<()>, &'static str> {
+    if let Err(status) = mm_comm.communicate(MmCommBufferType::User.id(), message, recipient.as_guid()) {
+        return Ok(Err(map_comm_status(status)));
+    }
+
+    let (return_status, _) = config
+        .comm_buffers
+        .iter()
+        .find(|buffer| buffer.buffer_type() == Some(MmCommBufferType::User))
+        .ok_or("No user MM communication buffer is configured")?
+        .get_mm_return_status()
+        .map_err(|_| "The user MM communication buffer has no status mailbox")?;
+
+    Ok(EfiError::status_to_result(efi::Status::from_usize(return_status as usize)))
+}
+
+/// Sends a malformed supervisor request and expects it to fail or to return a response that does not validate.
+fn expect_rejected(mm_comm: &Service<dyn MmCommunication>, message: &[u8]) -> patina_test::error::Result {
+    match mm_comm.communicate(MmCommBufferType::User.id(), message, MM_SUPERVISOR_REQUEST_HANDLER_GUID.as_guid()) {
+        Ok(response) => {
+            u_assert_eq!(response.len(), message.len(), "The response length should match the request length");
+            u_assert!(
+                parse_response(RequestType::VersionInfo, &response).is_err(),
+                "A malformed request should not produce a valid version response"
+            );
+        }
+        Err(status) => log::debug!("  Malformed request failed: {status:?}"),
+    }
+    Ok(())
+}
+
+/// Checks that a well-formed version request still returns `expected`.
+fn check_not_corrupted(mm_comm: &Service<dyn MmCommunication>, expected: VersionFields) -> patina_test::error::Result {
+    let version = version_request(mm_comm, MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE)?;
+    u_assert_eq!(version, expected, "A version request after malformed input returned a different result");
+    Ok(())
+}
@@ -16,10 +16,58 @@ use patina::component::{
 };
 use patina_mm::config::{CommunicateBuffer, MmCommunicationConfiguration};
 
-use crate::q35::registers as register;
+use crate::q35::{hw, registers as register};
 
 extern crate alloc;
 
+/// The kind of a MM Communication Region.
+///
+/// The MM Communicate Region HOB describes the purpose of each region with a raw `buffer_type` value. The numeric
+/// value of each kind is used as the ID of the `CommunicateBuffer` created for the region, so callers of
+/// `MmCommunication::communicate` can select a buffer by purpose with [`MmCommBufferType::id`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MmCommBufferType {
+    /// Buffer used to communicate with user MM handlers.
+    User = 0,
+    /// Buffer used to communicate with the MM Supervisor.
+    Supervisor = 1,
+    /// Buffer used for Generic Hardware Error Source (GHES) reporting.
+    Ghes = 2,
+}
+
+impl MmCommBufferType {
+    /// Returns the communicate buffer ID used for buffers of this kind.
+    pub const fn id(self) -> u8 {
+        self as u8
+    }
+}
+
+impl TryFrom<u64> for MmCommBufferType {
+    type Error = u64;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::User),
+            1 => Ok(Self::Supervisor),
+            2 => Ok(Self::Ghes),
+            other => Err(other),
+        }
+    }
+}
+
+/// Extends `CommunicateBuffer` with the kind of MM Communication Region it was created from.
+pub trait CommunicateBufferTypeExt {
+    /// Returns the kind of this buffer or `None` if the buffer ID does not map to a known kind.
+    fn buffer_type(&self) -> Option<MmCommBufferType>;
+}
+
+impl CommunicateBufferTypeExt for CommunicateBuffer {
+    fn buffer_type(&self) -> Option<MmCommBufferType> {
+        MmCommBufferType::try_from(self.id() as u64).ok()
+    }
+}
+
 /// Responsible for providing MM configuration information to other components. All other MM related components
 /// should be abstracted from MM details by the configuration produced by this component.
 pub struct MmConfigurationProvider;
@@ -73,15 +121,7 @@ impl MmConfigurationProvider {
 
         log::debug!("Incoming MM Configuration: {config_mut:?}");
 
-        let pm_base: *const u16 = (register::PCI_EXPRESS_BASE_ADDRESS as usize
-            + patina::pci_address!(0, 0x1F, 0, register::ich9::PMBASE) as usize)
-            as *const u16;
-        let pm_base_value: u16 = unsafe { core::ptr::read_volatile(pm_base) } & register::ich9::PMBASE_MASK;
-
-        log::info!("ACPI I/O Port Address: {:#X}", pm_base as usize);
-        log::info!("ACPI (PMBASE) I/O Port: {pm_base_value:#X}");
-
-        config_mut.acpi_base = pm_base_value.into();
+        config_mut.acpi_base = read_pm_base().into();
 
         log::info!("Found {} MM Communicate Region HOBs", mm_comm_region_hob.iter().count());
 
@@ -90,16 +130,25 @@ impl MmConfigurationProvider {
             log::debug!("HOB Pages: {:#X}", hob.pages);
             log::debug!("HOB Buffer Type: {:#X}", hob.buffer_type);
 
+            let buffer_type = match MmCommBufferType::try_from(hob.buffer_type) {
+                Ok(buffer_type) => buffer_type,
+                Err(unknown) => {
+                    log::error!("Ignoring MM Communicate Region HOB with unknown buffer type {unknown:#X}");
+                    continue;
+                }
+            };
+
             let buffer = unsafe {
                 CommunicateBuffer::from_raw_parts(
                     hob.address as usize as *mut u8,
                     hob.pages as usize * patina::base::UEFI_PAGE_SIZE,
-                    hob.buffer_type as u8,
+                    buffer_type.id(),
                 )
             };
 
             match buffer {
                 Ok(buffer) => {
+                    log::info!("MM Communicate Buffer {:?} (ID {}) configured", buffer_type, buffer.id());
                     config_mut.comm_buffers.push(buffer);
                 }
                 Err(e) => {
@@ -114,3 +163,116 @@ impl MmConfigurationProvider {
         Ok(())
     }
 }
+
+/// Reads the ACPI PM I/O base address programmed in the ICH9 LPC bridge PMBASE register.
+pub(crate) fn read_pm_base() -> u16 {
+    let pm_base: *const u16 = (register::PCI_EXPRESS_BASE_ADDRESS as usize
+        + patina::pci_address!(0, 0x1F, 0, register::ich9::PMBASE) as usize)
+        as *const u16;
+    let pm_base_value: u16 = unsafe { hw::read_volatile(pm_base) } & register::ich9::PMBASE_MASK;
+
+    log::info!("ACPI I/O Port Address: {:#X}", pm_base as usize);
+    log::info!("ACPI (PMBASE) I/O Port: {pm_base_value:#X}");
+
+    pm_base_value
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+
+    use patina::{component::Storage, error::EfiError};
+    use patina_mm::{component::sw_mmi_manager::SwMmiManager, service::SwMmiTrigger};
+
+    use super::*;
+    use crate::{
+        q35::sim::{self, FakeHobList, MockPlatformMmControl},
+        sim::run_component,
+    };
+
+    const TEST_PM_BASE: u16 = 0x600;
+
+    fn storage_with_hobs(hobs: FakeHobList) -> Storage {
+        sim::reset();
+        sim::with_chipset(|chipset| chipset.set_pm_base(TEST_PM_BASE));
+
+        let mut storage = Storage::new();
+        hobs.install(&mut storage);
+        storage
+    }
+
+    #[test]
+    fn test_buffer_type_round_trip() {
+        for buffer_type in [MmCommBufferType::User, MmCommBufferType::Supervisor, MmCommBufferType::Ghes] {
+            assert_eq!(MmCommBufferType::try_from(buffer_type.id() as u64), Ok(buffer_type));
+        }
+        assert_eq!(MmCommBufferType::try_from(3), Err(3));
+    }
+
+    #[test]
+    fn test_provider_builds_config_from_hobs() {
+        let mut storage = storage_with_hobs(FakeHobList::new().with_comm_region(0, 1).with_comm_region(1, 2));
+
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(true));
+
+        let config = storage.get_config::<MmCommunicationConfiguration>().unwrap();
+        assert_eq!(config.acpi_base.get_io_value(), TEST_PM_BASE);
+        assert_eq!(config.comm_buffers.len(), 2);
+
+        let user = &config.comm_buffers[0];
+        assert_eq!(user.buffer_type(), Some(MmCommBufferType::User));
+        assert_eq!(user.len(), patina::base::UEFI_PAGE_SIZE);
+        assert!(user.message_capacity() < user.len());
+
+        let supervisor = &config.comm_buffers[1];
+        assert_eq!(supervisor.buffer_type(), Some(MmCommBufferType::Supervisor));
+        assert_eq!(supervisor.len(), 2 * patina::base::UEFI_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_provider_skips_unknown_buffer_type() {
+        let mut storage = storage_with_hobs(FakeHobList::new().with_comm_region(7, 1).with_comm_region(2, 1));
+
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(true));
+
+        let config = storage.get_config::<MmCommunicationConfiguration>().unwrap();
+        assert_eq!(config.comm_buffers.len(), 1);
+        assert_eq!(config.comm_buffers[0].buffer_type(), Some(MmCommBufferType::Ghes));
+    }
+
+    #[test]
+    fn test_provider_not_dispatched_without_hobs() {
+        let mut storage = storage_with_hobs(FakeHobList::new());
+
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(false));
+    }
+
+    #[test]
+    fn test_sw_mmi_manager_calls_platform_control() {
+        let mut storage = storage_with_hobs(FakeHobList::new().with_comm_region(0, 1));
+        let control = MockPlatformMmControl::new(Ok(()));
+        let calls = control.calls();
+        storage.add_service(control);
+
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(true));
+        assert_eq!(run_component(SwMmiManager::new(), &mut storage), Ok(true));
+
+        assert_eq!(calls.get(), 1);
+        assert!(storage.get_service::<dyn SwMmiTrigger>().is_some());
+    }
+
+    #[test]
+    fn test_sw_mmi_manager_fails_when_platform_control_fails() {
+        let mut storage = storage_with_hobs(FakeHobList::new().with_comm_region(0, 1));
+        let control = MockPlatformMmControl::new(Err(EfiError::DeviceError));
+        let calls = control.calls();
+        storage.add_service(control);
+
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(true));
+        assert_eq!(run_component(SwMmiManager::new(), &mut storage), Err(EfiError::DeviceError));
+
+        assert_eq!(calls.get(), 1);
+        assert!(storage.get_service::<dyn SwMmiTrigger>().is_none());
+    }
+}
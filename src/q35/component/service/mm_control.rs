@@ -8,14 +8,26 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
-#![cfg(all(target_os = "uefi", target_arch = "x86_64", feature = "x64"))]
+#![cfg(any(test, all(target_os = "uefi", target_arch = "x86_64", feature = "x64")))]
 
 use patina_mm::{config::MmCommunicationConfiguration, service::platform_mm_control::PlatformMmControl};
 
-use crate::q35::registers as register;
-use patina::component::{Storage, component, service::IntoService};
+use crate::q35::{
+    hw::{self, Port},
+    registers as register,
+    smi_features::SmiFeatureConfiguration,
+};
+use patina::{
+    component::{
+        component,
+        params::{Commands, Config},
+        service::IntoService,
+    },
+    error::EfiError,
+};
 
-use x86_64::instructions::port::Port;
+/// SMI_EN bits that must be set for software MMIs to be generated through the APM control port.
+const SMI_EN_REQUIRED: u32 = register::ich9::SMI_EN_APMC_EN | register::ich9::SMI_EN_GBL_SMI_EN;
 
 /// The QEMU Q35 platform-specific MM control component.
 ///
@@ -41,53 +53,264 @@ impl QemuQ35PlatformMmControl {
     ///
     /// Installs an instance of the `PlatformMmControl` service that can be invoked by other components that depend
     /// upon hardware initialization for MMI control.
-    pub fn entry_point(mut self, storage: &mut Storage) -> patina::error::Result<()> {
+    ///
+    /// Depends on the locked `MmCommunicationConfiguration` so the PMBASE I/O port discovered by the MM configuration
+    /// provider is available when the service is installed.
+    ///
+    /// Depends on the locked `SmiFeatureConfiguration` so the service is only installed once SMI features have been
+    /// negotiated with QEMU, and the negotiated features are in effect before `PlatformMmControl::init` enables MMIs.
+    ///
+    /// The service is not installed if the configuration has no communicate buffers. The platform configuration leaves
+    /// them empty and only the MM configuration provider adds them, so without MM there is nothing to initialize.
+    pub fn entry_point(
+        mut self,
+        config: Config<MmCommunicationConfiguration>,
+        smi_features: Config<SmiFeatureConfiguration>,
+        mut commands: Commands,
+    ) -> patina::error::Result<()> {
         log::debug!("Platform MM Control Entry Point");
 
-        self.inner_config = storage
-            .get_config::<MmCommunicationConfiguration>()
-            .expect("Failed to get MM Configuration Config from storage")
-            .clone();
+        if config.comm_buffers.is_empty() {
+            log::info!("MM is not configured. Platform MM Control not installed.");
+            return Ok(());
+        }
+
+        self.inner_config = config.clone();
         log::debug!("PMBASE I/O Port (from config): {:?}", self.inner_config.acpi_base);
+        log::debug!("Negotiated SMI features (from config): [{}]", smi_features.negotiated);
 
-        storage.add_service(self);
+        commands.add_service(self);
 
         Ok(())
     }
+
+    /// Returns a pointer to the ICH9 GEN_PMCON_1 register in PCI Express configuration space.
+    fn gen_pmcon_1() -> *mut u16 {
+        (register::PCI_EXPRESS_BASE_ADDRESS as usize
+            + patina::pci_address!(0, 0x1F, 0, register::ich9::GEN_PMCON_1) as usize) as *mut u16
+    }
 }
 
 impl PlatformMmControl for QemuQ35PlatformMmControl {
     /// Initializes QEMU Q35 for Management Mode (MM).
     ///
     /// After this function completes, the platform hardware enabling required to support MMIs is completed.
+    ///
+    /// The function may be called more than once. If SMI_LOCK is already set in GEN_PMCON_1, no registers are
+    /// written and the function only verifies that the locked SMI_EN value enables software MMIs.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::NotReady` if the PMBASE I/O port has not been configured.
+    /// - `EfiError::AccessDenied` if SMI_EN is locked without the bits required for software MMIs.
+    /// - `EfiError::DeviceError` if the SMI_EN state left by PEI is inconsistent or if SMI_EN or SMI_LOCK do not
+    ///   read back as written.
     fn init(&self) -> patina::error::Result<()> {
         log::debug!("Performing platform-specific MM init...");
 
-        let mut smi_en_port =
-            Port::new(self.inner_config.acpi_base.get_io_value() + register::ich9::PMBASE_OFS_SMI_EN as u16);
+        let pm_base = self.inner_config.acpi_base.get_io_value();
+        if pm_base == 0 {
+            log::error!("PMBASE I/O port is not configured: {:?}", self.inner_config.acpi_base);
+            return Err(EfiError::NotReady);
+        }
+
+        let mut smi_en_port: Port<u32> = Port::new(pm_base + register::ich9::PMBASE_OFS_SMI_EN as u16);
+        let gen_pmcon_1 = Self::gen_pmcon_1();
+
+        // SAFETY: PMBASE was read from the ICH9 LPC bridge and SMI_EN is a 32-bit register at a fixed offset from it.
         let smi_enable_val: u32 = unsafe { smi_en_port.read() };
+        // SAFETY: GEN_PMCON_1 is a 16-bit register in the ICH9 LPC bridge configuration space which is always mapped
+        // through PCI Express MMIO on Q35.
+        let gen_pmcon_1_val: u16 = unsafe { hw::read_volatile(gen_pmcon_1) };
+        log::debug!("SMI_EN: {smi_enable_val:#X}, GEN_PMCON_1: {gen_pmcon_1_val:#X}");
+
+        if gen_pmcon_1_val & register::ich9::GEN_PMCON_1_SMI_LOCK != 0 {
+            if smi_enable_val & SMI_EN_REQUIRED != SMI_EN_REQUIRED {
+                log::error!("SMI_EN is locked without APMC_EN and GBL_SMI_EN set: {smi_enable_val:#X}");
+                return Err(EfiError::AccessDenied);
+            }
+            log::debug!("SMI_LOCK is already set. Skipping MM init register writes.");
+            return Ok(());
+        }
 
         // On Q35, the SMI_EN bit should be set already if Standalone MM was launched in PEI.
-        if smi_enable_val & register::ich9::SMI_EN_APMC_EN != 0 {
-            assert_eq!(
-                smi_enable_val & register::ich9::SMI_EN_GBL_SMI_EN,
-                register::ich9::SMI_EN_GBL_SMI_EN,
-                "SMI Enable bit not set"
-            );
+        if smi_enable_val & register::ich9::SMI_EN_APMC_EN != 0
+            && smi_enable_val & register::ich9::SMI_EN_GBL_SMI_EN == 0
+        {
+            log::error!("APMC_EN is set but GBL_SMI_EN is not set in SMI_EN: {smi_enable_val:#X}");
+            return Err(EfiError::DeviceError);
         }
 
         // In any case, set the SMI_EN bit to enable SMI generation.
-        let smi_enable_val = smi_enable_val | register::ich9::SMI_EN_APMC_EN | register::ich9::SMI_EN_GBL_SMI_EN;
-        unsafe { smi_en_port.write(smi_enable_val) };
+        // SAFETY: See the SMI_EN read above.
+        unsafe { smi_en_port.write(smi_enable_val | SMI_EN_REQUIRED) };
+        // SAFETY: See the SMI_EN read above.
+        let smi_enable_val: u32 = unsafe { smi_en_port.read() };
+        if smi_enable_val & SMI_EN_REQUIRED != SMI_EN_REQUIRED {
+            log::error!("SMI_EN did not retain APMC_EN and GBL_SMI_EN after write: {smi_enable_val:#X}");
+            return Err(EfiError::DeviceError);
+        }
+
+        // Set the SMI Lock bit in the GEN_PMCON_1 register to lock the SMI_EN bits
+        // SAFETY: See the GEN_PMCON_1 read above.
+        unsafe { hw::write_volatile(gen_pmcon_1, gen_pmcon_1_val | register::ich9::GEN_PMCON_1_SMI_LOCK) };
+        // SAFETY: See the GEN_PMCON_1 read above.
+        let gen_pmcon_1_val: u16 = unsafe { hw::read_volatile(gen_pmcon_1) };
+        if gen_pmcon_1_val & register::ich9::GEN_PMCON_1_SMI_LOCK == 0 {
+            log::error!("SMI_LOCK did not take effect in GEN_PMCON_1: {gen_pmcon_1_val:#X}");
+            return Err(EfiError::DeviceError);
+        }
 
-        // Set the SMI Lock bit in the PM1A_CNT register to lock the SMI_EN bits
-        let pm1a_cnt: *mut u16 = (register::PCI_EXPRESS_BASE_ADDRESS as usize
-            + patina::pci_address!(0, 0x1F, 0, register::ich9::GEN_PMCON_1) as usize)
-            as *mut u16;
-        let mut pm1a_cnt_val: u16 = unsafe { core::ptr::read_volatile(pm1a_cnt) };
-        pm1a_cnt_val |= register::ich9::GEN_PMCON_1_SMI_LOCK;
-        unsafe { core::ptr::write_volatile(pm1a_cnt, pm1a_cnt_val) };
+        log::debug!("MM init complete. SMI_EN: {smi_enable_val:#X}, GEN_PMCON_1: {gen_pmcon_1_val:#X}");
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+
+    use patina::component::Storage;
+    use patina_mm::service::platform_mm_control::PlatformMmControl;
+
+    use super::*;
+    use crate::{
+        q35::{
+            component::service::{
+                mm_config_provider::MmConfigurationProvider, smi_feature_negotiation::QemuQ35SmiFeatureNegotiation,
+            },
+            sim::{self, FakeHobList},
+        },
+        sim::run_component,
+    };
+
+    const TEST_PM_BASE: u16 = 0x600;
+    /// An SMI_EN bit that the component does not manage.
+    const SMI_EN_SWSMI_TMR_EN: u32 = 0x40;
+
+    fn control() -> QemuQ35PlatformMmControl {
+        sim::reset();
+        sim::with_chipset(|chipset| chipset.set_pm_base(TEST_PM_BASE));
+        QemuQ35PlatformMmControl {
+            inner_config: MmCommunicationConfiguration { acpi_base: TEST_PM_BASE.into(), ..Default::default() },
+        }
+    }
+
+    #[test]
+    fn test_init_requires_pm_base() {
+        let control = QemuQ35PlatformMmControl::new();
+        assert_eq!(control.init(), Err(EfiError::NotReady));
+    }
+
+    #[test]
+    fn test_init_enables_and_locks_smi() {
+        let control = control();
+
+        assert_eq!(control.init(), Ok(()));
+
+        sim::with_chipset(|chipset| {
+            assert_eq!(chipset.smi_en() & SMI_EN_REQUIRED, SMI_EN_REQUIRED);
+            assert_ne!(chipset.gen_pmcon_1() & register::ich9::GEN_PMCON_1_SMI_LOCK, 0);
+        });
+    }
+
+    #[test]
+    fn test_init_preserves_smi_en_bits_set_by_pei() {
+        let control = control();
+        let smi_en = SMI_EN_REQUIRED | SMI_EN_SWSMI_TMR_EN;
+        sim::with_chipset(|chipset| chipset.set_smi_en(smi_en));
+
+        assert_eq!(control.init(), Ok(()));
+
+        sim::with_chipset(|chipset| assert_eq!(chipset.smi_en(), smi_en));
+    }
+
+    #[test]
+    fn test_init_is_repeatable() {
+        let control = control();
+
+        assert_eq!(control.init(), Ok(()));
+        assert_eq!(control.init(), Ok(()));
+    }
+
+    #[test]
+    fn test_init_skips_writes_when_locked() {
+        let control = control();
+        let smi_en = SMI_EN_REQUIRED | SMI_EN_SWSMI_TMR_EN;
+        sim::with_chipset(|chipset| {
+            chipset.set_smi_en(smi_en);
+            chipset.set_gen_pmcon_1(register::ich9::GEN_PMCON_1_SMI_LOCK);
+            chipset.make_smi_en_read_only();
+            chipset.make_gen_pmcon_1_read_only();
+        });
+
+        assert_eq!(control.init(), Ok(()));
+
+        sim::with_chipset(|chipset| assert_eq!(chipset.smi_en(), smi_en));
+    }
+
+    #[test]
+    fn test_init_rejects_locked_smi_en_without_required_bits() {
+        let control = control();
+        sim::with_chipset(|chipset| {
+            chipset.set_smi_en(register::ich9::SMI_EN_APMC_EN);
+            chipset.set_gen_pmcon_1(register::ich9::GEN_PMCON_1_SMI_LOCK);
+        });
+
+        assert_eq!(control.init(), Err(EfiError::AccessDenied));
+    }
+
+    #[test]
+    fn test_init_rejects_apmc_without_global_enable() {
+        let control = control();
+        sim::with_chipset(|chipset| chipset.set_smi_en(register::ich9::SMI_EN_APMC_EN));
+
+        assert_eq!(control.init(), Err(EfiError::DeviceError));
+        sim::with_chipset(|chipset| assert_eq!(chipset.gen_pmcon_1() & register::ich9::GEN_PMCON_1_SMI_LOCK, 0));
+    }
+
+    #[test]
+    fn test_init_detects_ignored_smi_en_write() {
+        let control = control();
+        sim::with_chipset(|chipset| chipset.make_smi_en_read_only());
+
+        assert_eq!(control.init(), Err(EfiError::DeviceError));
+        sim::with_chipset(|chipset| assert_eq!(chipset.gen_pmcon_1() & register::ich9::GEN_PMCON_1_SMI_LOCK, 0));
+    }
+
+    #[test]
+    fn test_init_detects_ignored_smi_lock_write() {
+        let control = control();
+        sim::with_chipset(|chipset| chipset.make_gen_pmcon_1_read_only());
+
+        assert_eq!(control.init(), Err(EfiError::DeviceError));
+    }
+
+    #[test]
+    fn test_entry_point_installs_service() {
+        let _ = control();
+        let mut storage = Storage::new();
+        FakeHobList::new().with_comm_region(0, 1).install(&mut storage);
+
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(true));
+        assert_eq!(run_component(QemuQ35SmiFeatureNegotiation::new(), &mut storage), Ok(true));
+        assert_eq!(run_component(QemuQ35PlatformMmControl::new(), &mut storage), Ok(true));
+
+        let service = storage.get_service::<dyn PlatformMmControl>().unwrap();
+        assert_eq!(service.init(), Ok(()));
+    }
+
+    #[test]
+    fn test_entry_point_skips_unconfigured_mm() {
+        let _ = control();
+        let mut storage = Storage::new();
+        storage.add_config(MmCommunicationConfiguration::default());
+        storage.add_config(SmiFeatureConfiguration::default());
+
+        assert_eq!(run_component(QemuQ35PlatformMmControl::new(), &mut storage), Ok(true));
+
+        assert!(storage.get_service::<dyn PlatformMmControl>().is_none());
+    }
+}
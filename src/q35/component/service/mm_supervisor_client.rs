@@ -0,0 +1,592 @@
+//! QEMU Q35 MM Supervisor Client
+//!
+//! Provides a typed client for the MM Supervisor request interface. Requests are sent to the MM Supervisor through
+//! the `MmCommunication` service and every response is validated before its payload is returned to the caller.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use patina::{
+    component::{
+        component,
+        params::{Commands, Config},
+        service::{IntoService, Service},
+    },
+    error::EfiError,
+    management_mode::protocol::mm_supervisor_request::{
+        self, MM_SUPERVISOR_REQUEST_HANDLER_GUID, MmSupervisorRequestHeader, MmSupervisorUnblockMemoryParams,
+        MmSupervisorVersionInfo, RequestType,
+    },
+};
+use patina_mm::{
+    component::communicator::Status as MmCommStatus, config::MmCommunicationConfiguration, service::MmCommunication,
+};
+use r_efi::efi;
+use zerocopy::FromBytes;
+
+use crate::q35::component::service::mm_config_provider::{CommunicateBufferTypeExt, MmCommBufferType};
+
+/// Number of MM communication buffers that can be replaced with a comm buffer update request.
+pub const MM_OPEN_BUFFER_COUNT: usize = 3;
+
+/// MM Supervisor Comm Buffer Update Request Payload
+///
+/// Describes the new location of each MM communication buffer, indexed by [`MmCommBufferType`], and the new MM core
+/// private data region. Layout of the `MM_SUPERVISOR_COMM_UPDATE_BUFFER` payload.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct MmSupervisorCommUpdateBuffer {
+    /// New communication buffers, indexed by buffer type.
+    pub new_comm_buffers: [efi::MemoryDescriptor; MM_OPEN_BUFFER_COUNT],
+    /// New MM core private data region.
+    pub new_mm_core_data: efi::MemoryDescriptor,
+}
+
+/// MM Supervisor Secure Policy Header
+///
+/// The header at the start of the policy blob returned by a fetch policy request. Layout of
+/// `SMM_SUPV_SECURE_POLICY_DATA_V1_0`.
+#[derive(Debug, Clone, Copy, zerocopy::FromBytes, zerocopy::Immutable, zerocopy::KnownLayout)]
+#[repr(C)]
+pub struct MmSupervisorPolicyHeader {
+    /// Minor version of the policy format.
+    pub version_minor: u16,
+    /// Major version of the policy format.
+    pub version_major: u16,
+    /// Total size of the policy blob in bytes, including this header.
+    pub size: u32,
+    /// Offset of the legacy memory policy from the start of the blob.
+    pub memory_policy_offset: u32,
+    /// Number of legacy memory policy descriptors.
+    pub memory_policy_count: u32,
+    /// Policy flags.
+    pub flags: u32,
+    /// Policy capabilities.
+    pub capabilities: u32,
+    /// Reserved.
+    pub reserved: u64,
+    /// Offset of the policy root descriptors from the start of the blob.
+    pub policy_root_offset: u32,
+    /// Number of policy root descriptors.
+    pub policy_root_count: u32,
+}
+
+impl MmSupervisorPolicyHeader {
+    /// Size of the header in bytes.
+    pub const SIZE: usize = core::mem::size_of::<Self>();
+}
+
+/// MM Supervisor Client Service
+///
+/// Sends requests to the MM Supervisor. All functions validate the response header (signature, revision and request
+/// type) and map a failing `result` to the corresponding `EfiError`.
+///
+/// ## Errors
+///
+/// - `EfiError::ProtocolError` if the response is malformed.
+/// - The `EfiError` matching the `result` status reported by the MM Supervisor.
+/// - The `EfiError` mapped from the MM communication failure if the request could not be delivered.
+pub trait MmSupervisorClient {
+    /// Returns the MM Supervisor version information.
+    fn version_info(&self) -> patina::error::Result<MmSupervisorVersionInfo>;
+
+    /// Returns the MM Supervisor security policy blob, starting with an [`MmSupervisorPolicyHeader`].
+    fn fetch_policy(&self) -> patina::error::Result<Vec<u8>>;
+
+    /// Requests that the MM Supervisor move the MM communication buffers to new locations.
+    fn update_comm_buffer(&self, update: &MmSupervisorCommUpdateBuffer) -> patina::error::Result<()>;
+
+    /// Requests that the MM Supervisor map a memory region into the MM address space.
+    fn unblock_memory(&self, params: &MmSupervisorUnblockMemoryParams) -> patina::error::Result<()>;
+}
+
+/// QEMU Q35 MM Supervisor Client
+///
+/// Installs the [`MmSupervisorClient`] service. Requests are sent through the MM communication buffer of the
+/// configured [`MmCommBufferType`].
+#[derive(IntoService)]
+#[service(dyn MmSupervisorClient)]
+pub struct QemuQ35MmSupervisorClient {
+    buffer_type: MmCommBufferType,
+    message_capacity: usize,
+    mm_comm: Option<Service<dyn MmCommunication>>,
+}
+
+impl Default for QemuQ35MmSupervisorClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[component]
+impl QemuQ35MmSupervisorClient {
+    /// Creates a new instance of the QEMU Q35 MM Supervisor Client component that uses the user buffer.
+    pub fn new() -> Self {
+        Self { buffer_type: MmCommBufferType::User, message_capacity: 0, mm_comm: None }
+    }
+
+    /// Selects the MM communication buffer used for supervisor requests.
+    pub fn with_buffer_type(mut self, buffer_type: MmCommBufferType) -> Self {
+        self.buffer_type = buffer_type;
+        self
+    }
+
+    /// Entry point for the MM Supervisor Client component.
+    ///
+    /// Looks up the configured MM communication buffer to size policy requests and installs the
+    /// [`MmSupervisorClient`] service.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::NotFound` if no MM communication buffer of the configured type exists.
+    fn entry_point(
+        mut self,
+        config: Config<MmCommunicationConfiguration>,
+        mm_comm: Service<dyn MmCommunication>,
+        mut commands: Commands,
+    ) -> patina::error::Result<()> {
+        log::debug!("MM Supervisor Client Entry Point");
+
+        let buffer = config.comm_buffers.iter().find(|buffer| buffer.buffer_type() == Some(self.buffer_type));
+        let Some(buffer) = buffer else {
+            log::error!("No MM Communicate Buffer of type {:?} for MM Supervisor requests", self.buffer_type);
+            return Err(EfiError::NotFound);
+        };
+
+        self.message_capacity = buffer.message_capacity();
+        self.mm_comm = Some(mm_comm);
+        log::debug!("MM Supervisor requests use buffer {:?} ({:#X} bytes)", self.buffer_type, self.message_capacity);
+
+        commands.add_service(self);
+
+        Ok(())
+    }
+
+    /// Sends a request with the given payload and returns the validated response payload.
+    ///
+    /// `response_size` is the number of payload bytes reserved for the response. The request payload is zero-padded
+    /// to this size.
+    fn request(&self, request: RequestType, payload: &[u8], response_size: usize) -> patina::error::Result<Vec<u8>> {
+        let mm_comm = self.mm_comm.as_ref().ok_or(EfiError::NotReady)?;
+
+        let header = MmSupervisorRequestHeader {
+            signature: mm_supervisor_request::SIGNATURE,
+            revision: mm_supervisor_request::REVISION,
+            request: request.into(),
+            reserved: 0,
+            result: 0,
+        };
+
+        let mut message = Vec::with_capacity(MmSupervisorRequestHeader::SIZE + payload.len().max(response_size));
+        message.extend_from_slice(zerocopy::IntoBytes::as_bytes(&header));
+        message.extend_from_slice(payload);
+        message.resize(MmSupervisorRequestHeader::SIZE + payload.len().max(response_size), 0);
+
+        let response = mm_comm
+            .communicate(self.buffer_type.id(), &message, MM_SUPERVISOR_REQUEST_HANDLER_GUID.as_guid())
+            .map_err(|status| {
+                log::error!("MM Supervisor request {request:?} failed to communicate: {status:?}");
+                map_comm_status(status)
+            })?;
+
+        parse_response(request, &response).map(|payload| payload.to_vec())
+    }
+}
+
+impl MmSupervisorClient for QemuQ35MmSupervisorClient {
+    fn version_info(&self) -> patina::error::Result<MmSupervisorVersionInfo> {
+        let payload = self.request(RequestType::VersionInfo, &[], MmSupervisorVersionInfo::SIZE)?;
+
+        MmSupervisorVersionInfo::from_bytes(&payload).ok_or_else(|| {
+            log::error!("MM Supervisor version info response is too small: {} bytes", payload.len());
+            EfiError::ProtocolError
+        })
+    }
+
+    fn fetch_policy(&self) -> patina::error::Result<Vec<u8>> {
+        let response_size = self.message_capacity.saturating_sub(MmSupervisorRequestHeader::SIZE);
+        let mut payload = self.request(RequestType::FetchPolicy, &[], response_size)?;
+
+        let header = MmSupervisorPolicyHeader::read_from_prefix(&payload).map(|(header, _)| header).map_err(|_| {
+            log::error!("MM Supervisor policy response is too small: {} bytes", payload.len());
+            EfiError::ProtocolError
+        })?;
+
+        let size = header.size as usize;
+        if size < MmSupervisorPolicyHeader::SIZE || size > payload.len() {
+            log::error!("MM Supervisor policy size {size:#X} is invalid for a {:#X} byte response", payload.len());
+            return Err(EfiError::ProtocolError);
+        }
+
+        payload.truncate(size);
+        Ok(payload)
+    }
+
+    fn update_comm_buffer(&self, update: &MmSupervisorCommUpdateBuffer) -> patina::error::Result<()> {
+        let mut payload = Vec::with_capacity(core::mem::size_of::<MmSupervisorCommUpdateBuffer>());
+        for descriptor in update.new_comm_buffers.iter().chain(core::iter::once(&update.new_mm_core_data)) {
+            push_memory_descriptor(&mut payload, descriptor);
+        }
+
+        self.request(RequestType::CommUpdate, &payload, 0).map(|_| ())
+    }
+
+    fn unblock_memory(&self, params: &MmSupervisorUnblockMemoryParams) -> patina::error::Result<()> {
+        let mut payload = Vec::with_capacity(MmSupervisorUnblockMemoryParams::SIZE);
+        push_memory_descriptor(&mut payload, &params.memory_descriptor);
+        payload.extend_from_slice(params.identifier_guid.as_bytes());
+
+        self.request(RequestType::UnblockMem, &payload, 0).map(|_| ())
+    }
+}
+
+/// Validates an MM Supervisor response and returns the payload that follows the response header.
+///
+/// ## Errors
+///
+/// - `EfiError::ProtocolError` if the response is smaller than the header, the signature or revision is invalid, or
+///   the response is for a different request type.
+/// - The `EfiError` matching the `result` status reported by the MM Supervisor.
+pub fn parse_response(request: RequestType, response: &[u8]) -> patina::error::Result<&[u8]> {
+    let Some(header) = MmSupervisorRequestHeader::from_bytes(response) else {
+        log::error!("MM Supervisor response is too small for the header: {} bytes", response.len());
+        return Err(EfiError::ProtocolError);
+    };
+
+    if !header.is_valid() {
+        log::error!(
+            "MM Supervisor response has an invalid signature ({:#010X}) or revision ({})",
+            header.signature,
+            header.revision
+        );
+        return Err(EfiError::ProtocolError);
+    }
+
+    if header.request != u32::from(request) {
+        log::error!("MM Supervisor response is for request {:#X}, expected {request:?}", header.request);
+        return Err(EfiError::ProtocolError);
+    }
+
+    EfiError::status_to_result(efi::Status::from_usize(header.result as usize)).inspect_err(|e| {
+        log::error!("MM Supervisor request {request:?} returned {:#X} ({e:?})", header.result);
+    })?;
+
+    Ok(&response[MmSupervisorRequestHeader::SIZE..])
+}
+
+/// Maps an MM communication failure to the closest `EfiError`.
+pub fn map_comm_status(status: MmCommStatus) -> EfiError {
+    match status {
+        MmCommStatus::NoCommBuffer | MmCommStatus::CommBufferNotFound => EfiError::NotFound,
+        MmCommStatus::CommBufferTooSmall => EfiError::BufferTooSmall,
+        MmCommStatus::InvalidDataBuffer => EfiError::InvalidParameter,
+        MmCommStatus::SwMmiServiceNotAvailable => EfiError::NotReady,
+        MmCommStatus::CommBufferInitError | MmCommStatus::SwMmiFailed => EfiError::DeviceError,
+        MmCommStatus::InvalidResponse => EfiError::ProtocolError,
+    }
+}
+
+/// Appends an `EFI_MEMORY_DESCRIPTOR` to `buffer` in its C layout, with the padding after `Type` zeroed.
+fn push_memory_descriptor(buffer: &mut Vec<u8>, descriptor: &efi::MemoryDescriptor) {
+    buffer.extend_from_slice(&descriptor.r#type.to_le_bytes());
+    buffer.extend_from_slice(&[0u8; 4]);
+    buffer.extend_from_slice(&descriptor.physical_start.to_le_bytes());
+    buffer.extend_from_slice(&descriptor.virtual_start.to_le_bytes());
+    buffer.extend_from_slice(&descriptor.number_of_pages.to_le_bytes());
+    buffer.extend_from_slice(&descriptor.attribute.to_le_bytes());
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use patina::{BinaryGuid, component::Storage};
+    use zerocopy::IntoBytes;
+
+    use super::*;
+    use crate::{
+        q35::{component::service::mm_config_provider::MmConfigurationProvider, sim::FakeHobList},
+        sim::{ScriptedMmCommunication, run_component},
+    };
+
+    /// Message capacity of the comm buffer used by the test clients
+    const MESSAGE_CAPACITY: usize = 0x100;
+
+    fn client(mm_comm: ScriptedMmCommunication) -> QemuQ35MmSupervisorClient {
+        QemuQ35MmSupervisorClient {
+            buffer_type: MmCommBufferType::Supervisor,
+            message_capacity: MESSAGE_CAPACITY,
+            mm_comm: Some(mm_comm.into_service()),
+        }
+    }
+
+    /// Builds a response header for `request` with the given header `signature` and `result`.
+    fn response_header(request: RequestType, signature: u32, result: u64) -> MmSupervisorRequestHeader {
+        MmSupervisorRequestHeader {
+            signature,
+            revision: mm_supervisor_request::REVISION,
+            request: request.into(),
+            reserved: 0,
+            result,
+        }
+    }
+
+    /// Builds a version info response with the given header `signature` and `result`.
+    fn version_response(signature: u32, result: u64) -> Vec<u8> {
+        let mut response = response_header(RequestType::VersionInfo, signature, result).as_bytes().to_vec();
+        response.extend_from_slice(
+            MmSupervisorVersionInfo { version: 0x10000, patch_level: 0, max_supervisor_request_level: 3 }.as_bytes(),
+        );
+        response
+    }
+
+    /// Returns a scripted handler that answers a fetch policy request with a policy header claiming `size` bytes.
+    fn policy_handler(size: u32) -> impl FnOnce(&mut [u8]) -> Result<(), MmCommStatus> {
+        move |buffer| {
+            let (header, payload) = buffer.split_at_mut(MmSupervisorRequestHeader::SIZE);
+            payload[4..8].copy_from_slice(&size.to_le_bytes());
+            header.copy_from_slice(
+                response_header(RequestType::FetchPolicy, mm_supervisor_request::SIGNATURE, 0).as_bytes(),
+            );
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_response_accepts_version_info() {
+        let response = version_response(mm_supervisor_request::SIGNATURE, 0);
+
+        let payload = parse_response(RequestType::VersionInfo, &response).unwrap();
+        assert_eq!(MmSupervisorVersionInfo::from_bytes(payload).map(|info| info.version), Some(0x10000));
+    }
+
+    #[test]
+    fn test_parse_response_rejects_bad_signature() {
+        let response = version_response(!mm_supervisor_request::SIGNATURE, 0);
+
+        assert_eq!(parse_response(RequestType::VersionInfo, &response), Err(EfiError::ProtocolError));
+    }
+
+    #[test]
+    fn test_parse_response_rejects_error_result() {
+        let response = version_response(mm_supervisor_request::SIGNATURE, efi::Status::UNSUPPORTED.as_usize() as u64);
+
+        assert_eq!(parse_response(RequestType::VersionInfo, &response), Err(EfiError::Unsupported));
+    }
+
+    #[test]
+    fn test_parse_response_rejects_other_request() {
+        let response = version_response(mm_supervisor_request::SIGNATURE, 0);
+
+        assert_eq!(parse_response(RequestType::FetchPolicy, &response), Err(EfiError::ProtocolError));
+    }
+
+    #[test]
+    fn test_parse_response_rejects_truncated_header() {
+        let response = version_response(mm_supervisor_request::SIGNATURE, 0);
+
+        assert_eq!(
+            parse_response(RequestType::VersionInfo, &response[..MmSupervisorRequestHeader::SIZE - 1]),
+            Err(EfiError::ProtocolError)
+        );
+    }
+
+    #[test]
+    fn test_request_sends_header_to_supervisor_handler() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            buffer.copy_from_slice(&version_response(mm_supervisor_request::SIGNATURE, 0));
+            Ok(())
+        });
+        let requests = mm_comm.requests();
+
+        let version_info = client(mm_comm).version_info().unwrap();
+        assert_eq!(version_info.version, 0x10000);
+        assert_eq!(version_info.max_supervisor_request_level, 3);
+
+        let requests = requests.borrow();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].id, MmCommBufferType::Supervisor.id());
+        assert_eq!(requests[0].recipient, MM_SUPERVISOR_REQUEST_HANDLER_GUID);
+        assert_eq!(requests[0].data.len(), MmSupervisorRequestHeader::SIZE + MmSupervisorVersionInfo::SIZE);
+        let header = MmSupervisorRequestHeader::from_bytes(&requests[0].data).unwrap();
+        assert!(header.is_valid());
+        assert_eq!(header.request, u32::from(RequestType::VersionInfo));
+        assert_eq!(header.result, 0);
+    }
+
+    #[test]
+    fn test_request_maps_communication_failure() {
+        let mm_comm = ScriptedMmCommunication::new().then(|_| Err(MmCommStatus::SwMmiFailed));
+
+        assert_eq!(client(mm_comm).version_info().map(|_| ()), Err(EfiError::DeviceError));
+    }
+
+    #[test]
+    fn test_request_requires_mm_communication() {
+        let client = QemuQ35MmSupervisorClient::new();
+
+        assert_eq!(client.version_info().map(|_| ()), Err(EfiError::NotReady));
+    }
+
+    #[test]
+    fn test_fetch_policy_truncates_to_policy_size() {
+        let mm_comm = ScriptedMmCommunication::new().then(policy_handler(0x40));
+        let requests = mm_comm.requests();
+
+        let policy = client(mm_comm).fetch_policy().unwrap();
+        assert_eq!(policy.len(), 0x40);
+        assert_eq!(requests.borrow()[0].data.len(), MESSAGE_CAPACITY);
+    }
+
+    #[test]
+    fn test_fetch_policy_rejects_invalid_size() {
+        let response_size = (MESSAGE_CAPACITY - MmSupervisorRequestHeader::SIZE) as u32;
+        for size in [0, MmSupervisorPolicyHeader::SIZE as u32 - 1, response_size + 1] {
+            let mm_comm = ScriptedMmCommunication::new().then(policy_handler(size));
+
+            assert_eq!(client(mm_comm).fetch_policy(), Err(EfiError::ProtocolError), "size {size:#X}");
+        }
+
+        let mm_comm = ScriptedMmCommunication::new().then(policy_handler(response_size));
+        assert_eq!(client(mm_comm).fetch_policy().map(|policy| policy.len()), Ok(response_size as usize));
+    }
+
+    #[test]
+    fn test_push_memory_descriptor_layout() {
+        let descriptor = efi::MemoryDescriptor {
+            r#type: 0x0102_0304,
+            physical_start: 0x1000,
+            virtual_start: 0x2000,
+            number_of_pages: 3,
+            attribute: 0x8000_0000_0000_0008,
+        };
+
+        let mut buffer = Vec::new();
+        push_memory_descriptor(&mut buffer, &descriptor);
+
+        assert_eq!(buffer.len(), core::mem::size_of::<efi::MemoryDescriptor>());
+        assert_eq!(buffer[0..4], 0x0102_0304u32.to_le_bytes());
+        assert_eq!(buffer[4..8], [0; 4]);
+        assert_eq!(buffer[8..16], 0x1000u64.to_le_bytes());
+        assert_eq!(buffer[16..24], 0x2000u64.to_le_bytes());
+        assert_eq!(buffer[24..32], 3u64.to_le_bytes());
+        assert_eq!(buffer[32..40], 0x8000_0000_0000_0008u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_unblock_memory_sends_descriptor_and_guid() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            buffer[..MmSupervisorRequestHeader::SIZE].copy_from_slice(
+                response_header(RequestType::UnblockMem, mm_supervisor_request::SIGNATURE, 0).as_bytes(),
+            );
+            Ok(())
+        });
+        let requests = mm_comm.requests();
+        let params = MmSupervisorUnblockMemoryParams {
+            memory_descriptor: efi::MemoryDescriptor {
+                r#type: 4,
+                physical_start: 0x1000,
+                virtual_start: 0,
+                number_of_pages: 1,
+                attribute: 0,
+            },
+            identifier_guid: BinaryGuid::from_string("0C4F1B6E-2A7D-4E53-9B18-6F3D7A2C5E91"),
+        };
+
+        assert_eq!(client(mm_comm).unblock_memory(&params), Ok(()));
+
+        let payload = requests.borrow()[0].data[MmSupervisorRequestHeader::SIZE..].to_vec();
+        let mut expected = Vec::new();
+        push_memory_descriptor(&mut expected, &params.memory_descriptor);
+        expected.extend_from_slice(params.identifier_guid.as_bytes());
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    fn test_update_comm_buffer_sends_all_descriptors() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            buffer[..MmSupervisorRequestHeader::SIZE].copy_from_slice(
+                response_header(RequestType::CommUpdate, mm_supervisor_request::SIGNATURE, 0).as_bytes(),
+            );
+            Ok(())
+        });
+        let requests = mm_comm.requests();
+        let descriptor = |pages| efi::MemoryDescriptor {
+            r#type: 6,
+            physical_start: 0x10_0000,
+            virtual_start: 0,
+            number_of_pages: pages,
+            attribute: 0,
+        };
+        let update = MmSupervisorCommUpdateBuffer {
+            new_comm_buffers: [descriptor(1), descriptor(2), descriptor(3)],
+            new_mm_core_data: descriptor(4),
+        };
+
+        assert_eq!(client(mm_comm).update_comm_buffer(&update), Ok(()));
+
+        let payload = requests.borrow()[0].data[MmSupervisorRequestHeader::SIZE..].to_vec();
+        let size = core::mem::size_of::<efi::MemoryDescriptor>();
+        assert_eq!(payload.len(), size * (MM_OPEN_BUFFER_COUNT + 1));
+        for (index, chunk) in payload.chunks(size).enumerate() {
+            assert_eq!(chunk[24..32], (index as u64 + 1).to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn test_map_comm_status() {
+        for (status, error) in [
+            (MmCommStatus::NoCommBuffer, EfiError::NotFound),
+            (MmCommStatus::CommBufferNotFound, EfiError::NotFound),
+            (MmCommStatus::CommBufferTooSmall, EfiError::BufferTooSmall),
+            (MmCommStatus::InvalidDataBuffer, EfiError::InvalidParameter),
+            (MmCommStatus::SwMmiServiceNotAvailable, EfiError::NotReady),
+            (MmCommStatus::CommBufferInitError, EfiError::DeviceError),
+            (MmCommStatus::SwMmiFailed, EfiError::DeviceError),
+            (MmCommStatus::InvalidResponse, EfiError::ProtocolError),
+        ] {
+            assert_eq!(map_comm_status(status), error, "{status:?}");
+        }
+    }
+
+    #[test]
+    fn test_entry_point_installs_service() {
+        let mut storage = Storage::new();
+        FakeHobList::new().with_comm_region(1, 1).install(&mut storage);
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(true));
+        storage.add_service(ScriptedMmCommunication::new());
+
+        assert_eq!(
+            run_component(
+                QemuQ35MmSupervisorClient::new().with_buffer_type(MmCommBufferType::Supervisor),
+                &mut storage
+            ),
+            Ok(true)
+        );
+
+        assert!(storage.get_service::<dyn MmSupervisorClient>().is_some());
+    }
+
+    #[test]
+    fn test_entry_point_requires_buffer_type() {
+        let mut storage = Storage::new();
+        FakeHobList::new().with_comm_region(0, 1).install(&mut storage);
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(true));
+        storage.add_service(ScriptedMmCommunication::new());
+
+        assert_eq!(
+            run_component(
+                QemuQ35MmSupervisorClient::new().with_buffer_type(MmCommBufferType::Supervisor),
+                &mut storage
+            ),
+            Err(EfiError::NotFound)
+        );
+        assert!(storage.get_service::<dyn MmSupervisorClient>().is_none());
+    }
+}
@@ -0,0 +1,729 @@
+//! QEMU Q35 MM Supervisor Policy Audit
+//!
+//! Fetches the MM Supervisor security policy at boot, decodes it into a readable report and optionally compares it
+//! against an expected policy. The policy controls which memory, I/O ports, MSRs, privileged instructions and save
+//! state fields MM handlers can access, so a report in the boot log is evidence of the policy that was enforced.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{format, vec::Vec};
+use core::fmt;
+
+use patina::{
+    component::{component, params::Config, service::Service},
+    error::EfiError,
+};
+use zerocopy::FromBytes;
+
+use crate::q35::component::service::mm_supervisor_client::{MmSupervisorClient, MmSupervisorPolicyHeader};
+
+/// Policy resource attributes (`SECURE_POLICY_RESOURCE_ATTR_*`)
+pub mod attribute {
+    /// The resource can be read.
+    pub const READ: u32 = 1 << 0;
+    /// The resource can be written.
+    pub const WRITE: u32 = 1 << 1;
+    /// The resource can be executed.
+    pub const EXECUTE: u32 = 1 << 2;
+    /// Accesses must use the exact width of the descriptor.
+    pub const STRICT_WIDTH: u32 = 1 << 3;
+    /// The resource can be read when the save state condition is met.
+    pub const COND_READ: u32 = 1 << 4;
+    /// The resource can be written when the save state condition is met.
+    pub const COND_WRITE: u32 = 1 << 5;
+}
+
+/// MM Supervisor policy audit configuration.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MmSupervisorPolicyConfiguration {
+    /// The policy blob the MM Supervisor is expected to report, as returned by a fetch policy request. If set, any
+    /// difference between the decoded policies fails the audit.
+    pub expected_policy: Option<&'static [u8]>,
+}
+
+/// Whether the descriptors of a policy root list the allowed or the denied resources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyAccess {
+    /// Only the listed resources may be accessed.
+    Allow,
+    /// All resources except the listed ones may be accessed.
+    Deny,
+}
+
+/// A privileged instruction controlled by the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyInstruction {
+    /// `CLI`
+    Cli,
+    /// `WBINVD`
+    Wbinvd,
+    /// `HLT`
+    Hlt,
+    /// An instruction index this decoder does not know.
+    Unknown(u16),
+}
+
+/// A save state field controlled by the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveStateField {
+    /// `RAX`
+    Rax,
+    /// The I/O trap information.
+    IoTrap,
+    /// A save state field index this decoder does not know.
+    Unknown(u32),
+}
+
+/// The condition under which a save state access applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveStateCondition {
+    /// The access always applies.
+    Unconditional,
+    /// The access applies when the MMI was raised by an I/O read.
+    IoRead,
+    /// The access applies when the MMI was raised by an I/O write.
+    IoWrite,
+    /// A condition this decoder does not know.
+    Unknown(u32),
+}
+
+/// A decoded policy descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyEntry {
+    /// A physical memory range.
+    Memory {
+        /// Base address of the range.
+        base: u64,
+        /// Size of the range in bytes.
+        size: u64,
+        /// Access attributes, a combination of the [`attribute`] flags.
+        attributes: u32,
+    },
+    /// A range of I/O ports.
+    Io {
+        /// First port of the range.
+        port: u16,
+        /// Number of ports in the range.
+        width: u16,
+        /// Access attributes, a combination of the [`attribute`] flags.
+        attributes: u16,
+    },
+    /// A range of MSRs.
+    Msr {
+        /// First MSR of the range.
+        address: u32,
+        /// Number of MSRs in the range.
+        size: u16,
+        /// Access attributes, a combination of the [`attribute`] flags.
+        attributes: u16,
+    },
+    /// A privileged instruction.
+    Instruction {
+        /// The instruction.
+        instruction: PolicyInstruction,
+        /// Access attributes, a combination of the [`attribute`] flags.
+        attributes: u16,
+    },
+    /// A save state field.
+    SaveState {
+        /// The save state field.
+        field: SaveStateField,
+        /// Access attributes, a combination of the [`attribute`] flags.
+        attributes: u32,
+        /// The condition under which the access applies.
+        condition: SaveStateCondition,
+    },
+}
+
+/// A policy rule: a descriptor and whether its root allows or denies it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolicyRule {
+    /// Whether the root of the descriptor allows or denies it.
+    pub access: PolicyAccess,
+    /// The descriptor.
+    pub entry: PolicyEntry,
+}
+
+/// A difference between the reported and the expected policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyDrift {
+    /// The reported policy has a rule that is not expected.
+    Unexpected(PolicyRule),
+    /// An expected rule is missing from the reported policy.
+    Missing(PolicyRule),
+}
+
+/// A policy root: the descriptors of one resource type that share an access type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyRoot {
+    /// Whether the descriptors list the allowed or the denied resources.
+    pub access: PolicyAccess,
+    /// The descriptors of the root.
+    pub entries: Vec<PolicyEntry>,
+}
+
+/// A decoded MM Supervisor security policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupervisorPolicy {
+    /// Major version of the policy format.
+    pub version_major: u16,
+    /// Minor version of the policy format.
+    pub version_minor: u16,
+    /// Policy flags.
+    pub flags: u32,
+    /// Policy capabilities.
+    pub capabilities: u32,
+    /// The policy roots, in the order they appear in the blob.
+    pub roots: Vec<PolicyRoot>,
+}
+
+/// Policy root descriptor (`SMM_SUPV_POLICY_ROOT_V1`)
+#[derive(Debug, Clone, Copy, zerocopy::FromBytes, zerocopy::Immutable, zerocopy::KnownLayout)]
+#[repr(C)]
+struct PolicyRootDescriptor {
+    version: u32,
+    policy_root_size: u32,
+    descriptor_type: u32,
+    offset: u32,
+    count: u32,
+    access_attr: u8,
+    reserved: [u8; 3],
+}
+
+/// Policy descriptor types (`SMM_SUPV_SECURE_POLICY_DESCRIPTOR_TYPE_*`) and their descriptor sizes
+mod descriptor {
+    pub const MEMORY: u32 = 1;
+    pub const IO: u32 = 2;
+    pub const MSR: u32 = 3;
+    pub const INSTRUCTION: u32 = 4;
+    pub const SAVE_STATE: u32 = 5;
+
+    pub const MEMORY_SIZE: usize = 24;
+    pub const IO_SIZE: usize = 8;
+    pub const MSR_SIZE: usize = 8;
+    pub const INSTRUCTION_SIZE: usize = 8;
+    pub const SAVE_STATE_SIZE: usize = 16;
+}
+
+/// Supported major version of the policy format
+const POLICY_VERSION_MAJOR: u16 = 1;
+/// `SMM_SUPV_ACCESS_ATTR_DENY`
+const ACCESS_ATTR_DENY: u8 = 1;
+
+impl SupervisorPolicy {
+    /// Decodes a policy blob returned by a fetch policy request.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::Unsupported` if the policy major version is not supported.
+    /// - `EfiError::ProtocolError` if the blob is truncated, a root or descriptor lies outside the blob, or a root has
+    ///   an unknown descriptor type.
+    pub fn parse(blob: &[u8]) -> patina::error::Result<Self> {
+        let (header, _) = MmSupervisorPolicyHeader::read_from_prefix(blob).map_err(|_| {
+            log::error!("MM Supervisor policy is too small for the header: {} bytes", blob.len());
+            EfiError::ProtocolError
+        })?;
+
+        if header.version_major != POLICY_VERSION_MAJOR {
+            log::error!(
+                "MM Supervisor policy version {}.{} is not supported",
+                header.version_major,
+                header.version_minor
+            );
+            return Err(EfiError::Unsupported);
+        }
+
+        let size = header.size as usize;
+        let Some(blob) = blob.get(..size).filter(|_| size >= MmSupervisorPolicyHeader::SIZE) else {
+            log::error!("MM Supervisor policy size {size:#X} is invalid for a {:#X} byte blob", blob.len());
+            return Err(EfiError::ProtocolError);
+        };
+
+        if header.memory_policy_count != 0 {
+            log::warn!("Ignoring {} legacy memory policy descriptors", header.memory_policy_count);
+        }
+
+        let roots = (0..header.policy_root_count as usize)
+            .map(|index| {
+                let offset = header.policy_root_offset as usize + index * core::mem::size_of::<PolicyRootDescriptor>();
+                let root = blob.get(offset..).and_then(|bytes| PolicyRootDescriptor::read_from_prefix(bytes).ok());
+                let Some((root, _)) = root else {
+                    log::error!("MM Supervisor policy root {index} at {offset:#X} is outside the policy");
+                    return Err(EfiError::ProtocolError);
+                };
+                Self::parse_root(blob, &root)
+            })
+            .collect::<patina::error::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            version_major: header.version_major,
+            version_minor: header.version_minor,
+            flags: header.flags,
+            capabilities: header.capabilities,
+            roots,
+        })
+    }
+
+    fn parse_root(blob: &[u8], root: &PolicyRootDescriptor) -> patina::error::Result<PolicyRoot> {
+        let descriptor_size = match root.descriptor_type {
+            descriptor::MEMORY => descriptor::MEMORY_SIZE,
+            descriptor::IO => descriptor::IO_SIZE,
+            descriptor::MSR => descriptor::MSR_SIZE,
+            descriptor::INSTRUCTION => descriptor::INSTRUCTION_SIZE,
+            descriptor::SAVE_STATE => descriptor::SAVE_STATE_SIZE,
+            other => {
+                log::error!("MM Supervisor policy root has unknown descriptor type {other}");
+                return Err(EfiError::ProtocolError);
+            }
+        };
+
+        let start = root.offset as usize;
+        let end = (root.count as usize).checked_mul(descriptor_size).and_then(|size| start.checked_add(size));
+        let Some(descriptors) = end.and_then(|end| blob.get(start..end)) else {
+            log::error!(
+                "MM Supervisor policy descriptors at {start:#X} ({} entries) are outside the policy",
+                root.count
+            );
+            return Err(EfiError::ProtocolError);
+        };
+
+        let entries = descriptors
+            .chunks_exact(descriptor_size)
+            .map(|bytes| {
+                let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+                let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+                let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+                match root.descriptor_type {
+                    descriptor::MEMORY => {
+                        PolicyEntry::Memory { base: u64_at(0), size: u64_at(8), attributes: u32_at(16) }
+                    }
+                    descriptor::IO => PolicyEntry::Io { port: u16_at(0), width: u16_at(2), attributes: u16_at(4) },
+                    descriptor::MSR => PolicyEntry::Msr { address: u32_at(0), size: u16_at(4), attributes: u16_at(6) },
+                    descriptor::INSTRUCTION => PolicyEntry::Instruction {
+                        instruction: match u16_at(0) {
+                            0 => PolicyInstruction::Cli,
+                            1 => PolicyInstruction::Wbinvd,
+                            2 => PolicyInstruction::Hlt,
+                            other => PolicyInstruction::Unknown(other),
+                        },
+                        attributes: u16_at(2),
+                    },
+                    _ => PolicyEntry::SaveState {
+                        field: match u32_at(0) {
+                            0 => SaveStateField::Rax,
+                            1 => SaveStateField::IoTrap,
+                            other => SaveStateField::Unknown(other),
+                        },
+                        attributes: u32_at(4),
+                        condition: match u32_at(8) {
+                            0 => SaveStateCondition::Unconditional,
+                            1 => SaveStateCondition::IoRead,
+                            2 => SaveStateCondition::IoWrite,
+                            other => SaveStateCondition::Unknown(other),
+                        },
+                    },
+                }
+            })
+            .collect();
+
+        let access = if root.access_attr == ACCESS_ATTR_DENY { PolicyAccess::Deny } else { PolicyAccess::Allow };
+        Ok(PolicyRoot { access, entries })
+    }
+
+    /// Returns every rule in the policy.
+    pub fn rules(&self) -> impl Iterator<Item = PolicyRule> + '_ {
+        self.roots.iter().flat_map(|root| root.entries.iter().map(|&entry| PolicyRule { access: root.access, entry }))
+    }
+
+    /// Returns the rules that differ from `expected`, ignoring the order of roots and descriptors.
+    pub fn drift(&self, expected: &Self) -> Vec<PolicyDrift> {
+        let unexpected = self.rules().filter(|rule| !expected.rules().any(|r| r == *rule)).map(PolicyDrift::Unexpected);
+        let missing = expected.rules().filter(|rule| !self.rules().any(|r| r == *rule)).map(PolicyDrift::Missing);
+        unexpected.chain(missing).collect()
+    }
+}
+
+/// Formats policy attribute bits as a compact string, such as `RW` or `R(cond)`.
+struct Attributes(u32);
+
+impl fmt::Display for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (attribute::READ, "R"),
+            (attribute::WRITE, "W"),
+            (attribute::EXECUTE, "X"),
+            (attribute::STRICT_WIDTH, " strict-width"),
+            (attribute::COND_READ, " cond-read"),
+            (attribute::COND_WRITE, " cond-write"),
+        ];
+        if self.0 & 0x3F == 0 {
+            f.write_str("-")?;
+        }
+        for (bit, name) in flags {
+            if self.0 & bit != 0 {
+                f.write_str(name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PolicyEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Memory { base, size, attributes } => {
+                write!(
+                    f,
+                    "Memory {base:#018X}-{:#018X} {}",
+                    base.wrapping_add(size).wrapping_sub(1),
+                    Attributes(attributes)
+                )
+            }
+            Self::Io { port, width, attributes } => {
+                write!(f, "I/O {port:#06X} width {width} {}", Attributes(attributes.into()))
+            }
+            Self::Msr { address, size, attributes } => {
+                write!(f, "MSR {address:#010X} count {size} {}", Attributes(attributes.into()))
+            }
+            Self::Instruction { instruction, attributes } => {
+                write!(f, "Instruction {instruction:?} {}", Attributes(attributes.into()))
+            }
+            Self::SaveState { field, attributes, condition } => {
+                write!(f, "Save State {field:?} {} when {condition:?}", Attributes(attributes))
+            }
+        }
+    }
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {}", self.access, self.entry)
+    }
+}
+
+impl fmt::Display for SupervisorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "MM Supervisor Policy v{}.{} (flags {:#X}, capabilities {:#X}, {} roots)",
+            self.version_major,
+            self.version_minor,
+            self.flags,
+            self.capabilities,
+            self.roots.len()
+        )?;
+        for (index, root) in self.roots.iter().enumerate() {
+            writeln!(f, "  Root {index}: {:?} list, {} descriptors", root.access, root.entries.len())?;
+            for entry in &root.entries {
+                writeln!(f, "    {entry}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Logs every line of `report` at info level, so each line gets its own log prefix.
+fn log_report(report: &str) {
+    for line in report.lines() {
+        log::info!("{line}");
+    }
+}
+
+/// Fetches, decodes and audits the MM Supervisor policy.
+///
+/// Used by the boot-time audit component and the policy patina test.
+///
+/// ## Errors
+///
+/// - Any error returned while fetching or decoding the policy.
+/// - `EfiError::SecurityViolation` if the policy differs from the expected policy.
+/// - `EfiError::InvalidParameter` if the expected policy cannot be decoded.
+pub fn audit_policy(
+    client: &dyn MmSupervisorClient,
+    config: &MmSupervisorPolicyConfiguration,
+) -> patina::error::Result<SupervisorPolicy> {
+    let blob = client.fetch_policy().inspect_err(|e| log::error!("Failed to fetch the MM Supervisor policy: {e:?}"))?;
+    let policy = SupervisorPolicy::parse(&blob)?;
+    log_report(&format!("{policy}"));
+
+    let Some(expected) = config.expected_policy else {
+        log::info!("No expected MM Supervisor policy is configured. Skipping the comparison.");
+        return Ok(policy);
+    };
+
+    let expected = SupervisorPolicy::parse(expected).map_err(|e| {
+        log::error!("The expected MM Supervisor policy could not be decoded: {e:?}");
+        EfiError::InvalidParameter
+    })?;
+
+    let drift = policy.drift(&expected);
+    if !drift.is_empty() {
+        for difference in &drift {
+            match difference {
+                PolicyDrift::Unexpected(rule) => log::error!("MM Supervisor policy drift: unexpected {rule}"),
+                PolicyDrift::Missing(rule) => log::error!("MM Supervisor policy drift: missing {rule}"),
+            }
+        }
+        log::error!("MM Supervisor policy differs from the expected policy in {} rules", drift.len());
+        return Err(EfiError::SecurityViolation);
+    }
+
+    log::info!("MM Supervisor policy matches the expected policy");
+    Ok(policy)
+}
+
+/// QEMU Q35 MM Supervisor Policy Audit
+///
+/// Logs the MM Supervisor policy report at boot and fails if it drifted from the configured expected policy.
+#[derive(Default)]
+pub struct QemuQ35MmSupervisorPolicyAudit;
+
+#[component]
+impl QemuQ35MmSupervisorPolicyAudit {
+    /// Creates a new instance of the QEMU Q35 MM Supervisor Policy Audit component.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Entry point for the MM Supervisor Policy Audit component.
+    ///
+    /// ## Errors
+    ///
+    /// - Any error returned by [`audit_policy`].
+    fn entry_point(
+        self,
+        client: Service<dyn MmSupervisorClient>,
+        config: Config<MmSupervisorPolicyConfiguration>,
+    ) -> patina::error::Result<()> {
+        log::debug!("MM Supervisor Policy Audit Entry Point");
+        audit_policy(*client, &config).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use std::{vec, vec::Vec};
+
+    use super::*;
+    use crate::q35::component::service::mm_supervisor_client::MmSupervisorCommUpdateBuffer;
+    use patina::management_mode::protocol::mm_supervisor_request::{
+        MmSupervisorUnblockMemoryParams, MmSupervisorVersionInfo,
+    };
+
+    /// An MM Supervisor client that returns a fixed policy.
+    struct FixedPolicyClient(patina::error::Result<Vec<u8>>);
+
+    impl MmSupervisorClient for FixedPolicyClient {
+        fn version_info(&self) -> patina::error::Result<MmSupervisorVersionInfo> {
+            Err(EfiError::Unsupported)
+        }
+
+        fn fetch_policy(&self) -> patina::error::Result<Vec<u8>> {
+            self.0.clone()
+        }
+
+        fn update_comm_buffer(&self, _update: &MmSupervisorCommUpdateBuffer) -> patina::error::Result<()> {
+            Err(EfiError::Unsupported)
+        }
+
+        fn unblock_memory(&self, _params: &MmSupervisorUnblockMemoryParams) -> patina::error::Result<()> {
+            Err(EfiError::Unsupported)
+        }
+    }
+
+    fn expecting(blob: Vec<u8>) -> MmSupervisorPolicyConfiguration {
+        MmSupervisorPolicyConfiguration { expected_policy: Some(blob.leak()) }
+    }
+
+    /// Builds a policy blob with one root per `(type, deny, descriptors)` entry.
+    fn policy_blob(roots: &[(u32, bool, Vec<u8>, u32)]) -> Vec<u8> {
+        let header_size = MmSupervisorPolicyHeader::SIZE;
+        let roots_size = roots.len() * core::mem::size_of::<PolicyRootDescriptor>();
+        let mut descriptor_offset = header_size + roots_size;
+
+        let mut root_bytes = Vec::new();
+        let mut descriptor_bytes = Vec::new();
+        for (descriptor_type, deny, descriptors, count) in roots {
+            for value in [1u32, 24, *descriptor_type, descriptor_offset as u32, *count] {
+                root_bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            root_bytes.extend_from_slice(&[u8::from(*deny), 0, 0, 0]);
+            descriptor_bytes.extend_from_slice(descriptors);
+            descriptor_offset += descriptors.len();
+        }
+
+        let mut blob = Vec::new();
+        blob.extend_from_slice(&0u16.to_le_bytes());
+        blob.extend_from_slice(&1u16.to_le_bytes());
+        blob.extend_from_slice(&(descriptor_offset as u32).to_le_bytes());
+        blob.extend_from_slice(&[0; 16]);
+        blob.extend_from_slice(&0u64.to_le_bytes());
+        blob.extend_from_slice(&(header_size as u32).to_le_bytes());
+        blob.extend_from_slice(&(roots.len() as u32).to_le_bytes());
+        blob.extend_from_slice(&root_bytes);
+        blob.extend_from_slice(&descriptor_bytes);
+        blob
+    }
+
+    fn io(port: u16, width: u16, attributes: u16) -> Vec<u8> {
+        [port, width, attributes, 0].iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    fn msr(address: u32, size: u16, attributes: u16) -> Vec<u8> {
+        let mut bytes = address.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&size.to_le_bytes());
+        bytes.extend_from_slice(&attributes.to_le_bytes());
+        bytes
+    }
+
+    fn sample_policy() -> Vec<u8> {
+        let mut memory = 0x1000u64.to_le_bytes().to_vec();
+        memory.extend_from_slice(&0x2000u64.to_le_bytes());
+        memory.extend_from_slice(&(attribute::READ | attribute::WRITE).to_le_bytes());
+        memory.extend_from_slice(&[0; 4]);
+
+        let save_state = [0u32, attribute::READ, 2, 0].iter().flat_map(|value| value.to_le_bytes()).collect();
+
+        policy_blob(&[
+            (descriptor::MEMORY, true, memory, 1),
+            (descriptor::IO, false, [io(0xB2, 1, 3), io(0xCF8, 4, 1)].concat(), 2),
+            (descriptor::MSR, true, msr(0xC000_0080, 1, 3), 1),
+            (descriptor::INSTRUCTION, false, [2u16, 4, 0, 0].iter().flat_map(|v| v.to_le_bytes()).collect(), 1),
+            (descriptor::SAVE_STATE, false, save_state, 1),
+        ])
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let policy = SupervisorPolicy::parse(&sample_policy()).unwrap();
+
+        assert_eq!((policy.version_major, policy.version_minor), (1, 0));
+        assert_eq!(policy.roots.len(), 5);
+        assert_eq!(policy.roots[0].access, PolicyAccess::Deny);
+        assert_eq!(
+            policy.roots[1].entries,
+            vec![
+                PolicyEntry::Io { port: 0xB2, width: 1, attributes: 3 },
+                PolicyEntry::Io { port: 0xCF8, width: 4, attributes: 1 },
+            ]
+        );
+        assert_eq!(
+            policy.roots[4].entries[0],
+            PolicyEntry::SaveState {
+                field: SaveStateField::Rax,
+                attributes: attribute::READ,
+                condition: SaveStateCondition::IoWrite
+            }
+        );
+        assert_eq!(policy.rules().count(), 6);
+    }
+
+    #[test]
+    fn test_report_lists_every_descriptor() {
+        let report = format!("{}", SupervisorPolicy::parse(&sample_policy()).unwrap());
+
+        assert!(report.starts_with("MM Supervisor Policy v1.0"));
+        assert!(report.contains("Root 0: Deny list, 1 descriptors"));
+        assert!(report.contains("Memory 0x0000000000001000-0x0000000000002FFF RW"));
+        assert!(report.contains("I/O 0x0CF8 width 4 R"));
+        assert!(report.contains("MSR 0xC0000080 count 1 RW"));
+        assert!(report.contains("Instruction Hlt X"));
+        assert!(report.contains("Save State Rax R when IoWrite"));
+        assert_eq!(report.lines().count(), 12);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_policy() {
+        let blob = sample_policy();
+
+        assert_eq!(SupervisorPolicy::parse(&blob[..16]), Err(EfiError::ProtocolError));
+        assert_eq!(SupervisorPolicy::parse(&blob[..blob.len() - 1]), Err(EfiError::ProtocolError));
+
+        let mut bad_version = blob.clone();
+        bad_version[2] = 2;
+        assert_eq!(SupervisorPolicy::parse(&bad_version), Err(EfiError::Unsupported));
+
+        let unknown_type = policy_blob(&[(9, false, io(0xB2, 1, 3), 1)]);
+        assert_eq!(SupervisorPolicy::parse(&unknown_type), Err(EfiError::ProtocolError));
+
+        let overflowing_count = policy_blob(&[(descriptor::IO, false, io(0xB2, 1, 3), u32::MAX)]);
+        assert_eq!(SupervisorPolicy::parse(&overflowing_count), Err(EfiError::ProtocolError));
+    }
+
+    #[test]
+    fn test_drift_ignores_order() {
+        let policy = SupervisorPolicy::parse(&sample_policy()).unwrap();
+        let mut reordered = policy.clone();
+        reordered.roots.reverse();
+        reordered.roots.iter_mut().for_each(|root| root.entries.reverse());
+
+        assert!(policy.drift(&reordered).is_empty());
+    }
+
+    #[test]
+    fn test_drift_reports_changed_rules() {
+        let expected = SupervisorPolicy::parse(&policy_blob(&[(descriptor::IO, false, io(0xB2, 1, 3), 1)])).unwrap();
+        let actual = SupervisorPolicy::parse(&policy_blob(&[(descriptor::IO, false, io(0xB2, 2, 3), 1)])).unwrap();
+
+        assert_eq!(
+            actual.drift(&expected),
+            vec![
+                PolicyDrift::Unexpected(actual.rules().next().unwrap()),
+                PolicyDrift::Missing(expected.rules().next().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drift_reports_access_change() {
+        let expected = SupervisorPolicy::parse(&policy_blob(&[(descriptor::MSR, true, msr(0x1F2, 1, 3), 1)])).unwrap();
+        let actual = SupervisorPolicy::parse(&policy_blob(&[(descriptor::MSR, false, msr(0x1F2, 1, 3), 1)])).unwrap();
+
+        assert_eq!(actual.drift(&expected).len(), 2);
+    }
+
+    #[test]
+    fn test_audit_without_expected_policy() {
+        let client = FixedPolicyClient(Ok(sample_policy()));
+
+        let policy = audit_policy(&client, &MmSupervisorPolicyConfiguration::default()).unwrap();
+        assert_eq!(policy.roots.len(), 5);
+    }
+
+    #[test]
+    fn test_audit_matches_expected_policy() {
+        let client = FixedPolicyClient(Ok(sample_policy()));
+
+        assert!(audit_policy(&client, &expecting(sample_policy())).is_ok());
+    }
+
+    #[test]
+    fn test_audit_fails_on_drift() {
+        let client = FixedPolicyClient(Ok(sample_policy()));
+        let expected = policy_blob(&[(descriptor::IO, false, io(0xB2, 1, 3), 1)]);
+
+        assert_eq!(audit_policy(&client, &expecting(expected)), Err(EfiError::SecurityViolation));
+    }
+
+    #[test]
+    fn test_audit_rejects_invalid_expected_policy() {
+        let client = FixedPolicyClient(Ok(sample_policy()));
+
+        assert_eq!(audit_policy(&client, &expecting(vec![0; 8])), Err(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn test_audit_reports_fetch_failure() {
+        let client = FixedPolicyClient(Err(EfiError::DeviceError));
+
+        assert_eq!(audit_policy(&client, &MmSupervisorPolicyConfiguration::default()), Err(EfiError::DeviceError));
+    }
+}
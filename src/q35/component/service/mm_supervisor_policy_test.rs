@@ -0,0 +1,43 @@
+//! QEMU Q35 MM Supervisor Policy Test
+//!
+//! Verifies that the MM Supervisor reports a security policy that can be decoded and, if an expected policy is
+//! configured, that it has not drifted from it.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use patina::component::{params::Config, service::Service};
+use patina_test::{patina_test, u_assert};
+
+use crate::q35::component::service::{
+    mm_supervisor_client::MmSupervisorClient,
+    mm_supervisor_policy::{MmSupervisorPolicyConfiguration, audit_policy},
+};
+
+/// Fetches and audits the MM Supervisor policy.
+///
+/// The policy must hold at least one policy root, since the MM Supervisor always restricts some resources.
+#[patina_test]
+fn q35_mm_supervisor_policy_test(
+    client: Service<dyn MmSupervisorClient>,
+    config: Config<MmSupervisorPolicyConfiguration>,
+) -> patina_test::error::Result {
+    log::debug!("MM Supervisor Policy Test - Fetching the policy");
+
+    let policy = match audit_policy(*client, &config) {
+        Ok(policy) => policy,
+        Err(e) => {
+            log::error!("MM Supervisor policy audit failed: {e:?}");
+            return Err("MM Supervisor policy audit failed");
+        }
+    };
+
+    u_assert!(!policy.roots.is_empty(), "MM Supervisor policy should have at least one policy root");
+
+    log::debug!("MM Supervisor Policy Test complete");
+    Ok(())
+}
@@ -1,4 +1,4 @@
-//! QEMU Q35 Management Mode (MM) Test Component
+//! QEMU Q35 Management Mode (MM) Test
 //!
 //! Verifies that MM interfaces are working as expected on the QEMU Q35 platform. By exercising a MM communication
 //! transaction to the MM Supervisor.
@@ -10,100 +10,117 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 
-use patina::component::{component, service::Service};
-use patina_mm::service::MmCommunication;
+use patina::{component::service::Service, management_mode::protocol::mm_supervisor_request::RequestType};
+use patina_test::{patina_test, u_assert, u_assert_ne};
 
-/// MM Supervisor Request Header
-///
-/// Used to request information from the MM Supervisor.
-///
-/// ## Notes
-///
-/// - This structure is only defined here for test purposes.
-#[repr(C, packed(1))]
-struct MmSupervisorRequestHeader {
-    signature: u32,
-    revision: u32,
-    request: u32,
-    reserved: u32,
-    result: u64,
-}
+use crate::q35::component::service::mm_supervisor_client::MmSupervisorClient;
 
-/// MM Supervisor Version Info
+/// Requests version information from the MM Supervisor and validates the response.
 ///
-/// Populated by the MM Supervisor in response to a version request.
-///
-/// ## Notes
-///
-/// - This structure is only defined here for test purposes.
-#[repr(C, packed(1))]
-struct MmSupervisorVersionInfo {
-    version: u32,
-    patch_level: u32,
-    max_supervisor_request_level: u64,
+/// Uses the `MmSupervisorClient` service, which validates the response header, to send a version info request to the
+/// MM Supervisor. The MM Supervisor is expected to be the Standalone MM environment used on the QEMU Q35 platform.
+#[patina_test]
+fn q35_mm_supervisor_version_test(client: Service<dyn MmSupervisorClient>) -> patina_test::error::Result {
+    log::debug!("MM Test - Testing MM Communication");
+
+    let version_info = match client.version_info() {
+        Ok(version_info) => version_info,
+        Err(e) => {
+            log::error!("MM Supervisor version info request failed: {e:?}");
+            return Err("MM Supervisor version info request failed");
+        }
+    };
+
+    let version = version_info.version;
+    let patch_level = version_info.patch_level;
+    let max_request_level = version_info.max_supervisor_request_level;
+    log::info!(
+        "MM Supervisor Version: {version:#X}, Patch Level: {patch_level:#X}, Max Request Level: {max_request_level:#X}",
+    );
+
+    u_assert_ne!(version, 0, "MM Supervisor version should be non-zero");
+    u_assert!(
+        max_request_level >= u32::from(RequestType::VersionInfo) as u64,
+        "MM Supervisor should support the version info request level"
+    );
+
+    log::debug!("MM Test complete");
+    Ok(())
 }
 
-/// QEMU Q35 MM Test Component
-///
-/// Responsible for testing the MM communication interface on the QEMU Q35 platform.
-#[derive(Default)]
-pub struct QemuQ35MmTest;
-
-#[component]
-impl QemuQ35MmTest {
-    /// Creates a new instance of the QEMU Q35 MM Test component.
-    pub fn new() -> Self {
-        Self
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate alloc;
+    use alloc::{boxed::Box, vec::Vec};
+
+    use patina::{
+        error::EfiError,
+        management_mode::protocol::mm_supervisor_request::{MmSupervisorUnblockMemoryParams, MmSupervisorVersionInfo},
+    };
+
+    use super::*;
+    use crate::q35::component::service::mm_supervisor_client::MmSupervisorCommUpdateBuffer;
+
+    /// An `MmSupervisorClient` that answers version requests with a fixed result.
+    struct VersionClient(patina::error::Result<MmSupervisorVersionInfo>);
+
+    impl MmSupervisorClient for VersionClient {
+        fn version_info(&self) -> patina::error::Result<MmSupervisorVersionInfo> {
+            self.0
+        }
+
+        fn fetch_policy(&self) -> patina::error::Result<Vec<u8>> {
+            Err(EfiError::Unsupported)
+        }
+
+        fn update_comm_buffer(&self, _update: &MmSupervisorCommUpdateBuffer) -> patina::error::Result<()> {
+            Err(EfiError::Unsupported)
+        }
+
+        fn unblock_memory(&self, _params: &MmSupervisorUnblockMemoryParams) -> patina::error::Result<()> {
+            Err(EfiError::Unsupported)
+        }
+    }
+
+    fn client(result: patina::error::Result<MmSupervisorVersionInfo>) -> Service<dyn MmSupervisorClient> {
+        Service::mock(Box::new(VersionClient(result)))
+    }
+
+    fn version_info(version: u32, max_request_level: RequestType) -> MmSupervisorVersionInfo {
+        MmSupervisorVersionInfo {
+            version,
+            patch_level: 0,
+            max_supervisor_request_level: u32::from(max_request_level) as u64,
+        }
+    }
+
+    #[test]
+    fn test_version_request_passes() {
+        let client = client(Ok(version_info(0x10000, RequestType::CommUpdate)));
+
+        assert_eq!(q35_mm_supervisor_version_test(client), Ok(()));
+    }
+
+    #[test]
+    fn test_version_request_rejects_zero_version() {
+        let client = client(Ok(version_info(0, RequestType::CommUpdate)));
+
+        assert!(q35_mm_supervisor_version_test(client).is_err());
+    }
+
+    #[test]
+    fn test_version_request_rejects_low_request_level() {
+        let client =
+            client(Ok(MmSupervisorVersionInfo { version: 0x10000, patch_level: 0, max_supervisor_request_level: 0 }));
+
+        assert!(q35_mm_supervisor_version_test(client).is_err());
     }
 
-    /// Entry point for the MM Test component.
-    ///
-    /// Uses the `MmCommunication` service to send a request version information from the MM Supervisor. The MM
-    /// Supervisor is expected to be the Standalone MM environment used on the QEMU Q35 platform.
-    pub fn entry_point(self, mm_comm: Service<dyn MmCommunication>) -> patina::error::Result<()> {
-        log::debug!("MM Test Entry Point - Testing MM Communication");
-
-        let mm_supv_req_header = MmSupervisorRequestHeader {
-            signature: u32::from_le_bytes([b'M', b'S', b'U', b'P']),
-            revision: 1,
-            request: 0x0003, // Request Version Info
-            reserved: 0,
-            result: 0,
-        };
-
-        let result = unsafe {
-            mm_comm
-                .communicate(
-                    0,
-                    core::slice::from_raw_parts(
-                        &mm_supv_req_header as *const _ as *const u8,
-                        core::mem::size_of::<MmSupervisorRequestHeader>(),
-                    ),
-                    patina::Guid::from_fields(
-                        0x8c633b23,
-                        0x1260,
-                        0x4ea6,
-                        0x83,
-                        0x0F,
-                        [0x7d, 0xdc, 0x97, 0x38, 0x21, 0x11],
-                    ),
-                )
-                .map_err(|_| {
-                    log::error!("MM Communication failed");
-                    patina::error::EfiError::DeviceError // Todo: Map actual codes
-                })?
-        };
-
-        let mm_supv_ver_info = unsafe {
-            &*(result[core::mem::size_of::<MmSupervisorRequestHeader>()..].as_ptr() as *const MmSupervisorVersionInfo)
-        };
-        let version = mm_supv_ver_info.version;
-        let patch_level = mm_supv_ver_info.patch_level;
-        let max_request_level = mm_supv_ver_info.max_supervisor_request_level;
-        log::info!(
-            "MM Supervisor Version: {version:#X}, Patch Level: {patch_level:#X}, Max Request Level: {max_request_level:#X}",
-        );
-
-        Ok(())
+    #[test]
+    fn test_version_request_reports_client_failure() {
+        for error in [EfiError::ProtocolError, EfiError::Unsupported, EfiError::DeviceError] {
+            assert!(q35_mm_supervisor_version_test(client(Err(error))).is_err());
+        }
     }
 }
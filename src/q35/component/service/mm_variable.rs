@@ -0,0 +1,1083 @@
+//! QEMU Q35 MM Variable Services Component
+//!
+//! Provides the UEFI variable services on the QEMU Q35 platform by forwarding each request to the Standalone MM
+//! variable handler through the `MmCommunication` service, in place of `VariableSmmRuntimeDxe`. The messages are
+//! described in [`crate::mm_variable`].
+//!
+//! The component installs GetVariable, GetNextVariableName, SetVariable and QueryVariableInfo in the runtime services
+//! table, the Variable and Variable Write architectural protocols, and the [`MmVariableServices`] service.
+//!
+//! The DXE core and the MM communication service are boot services code, so requests are only forwarded until
+//! ExitBootServices. When BeforeExitBootServices is signaled, the runtime services table entries are switched to a
+//! stub in runtime services code that returns `EFI_UNSUPPORTED`, and the `EFI_RT_PROPERTIES_TABLE` installed by the
+//! component reports the variable services as unsupported at runtime. The stub does not reference any address, so
+//! SetVirtualAddressMap only has to convert the runtime services table entries, which the Runtime architectural
+//! protocol does for every runtime service.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::{
+        component,
+        params::{Commands, Config},
+        service::{
+            IntoService, Service,
+            memory::{AccessType, AllocationOptions, MemoryManager},
+        },
+    },
+    efi_types::EfiMemoryType,
+    error::EfiError,
+    guids::EVENT_READY_TO_BOOT,
+    runtime_services::{StandardRuntimeServices, variable_services::VariableInfo},
+};
+use patina_mm::{config::MmCommunicationConfiguration, service::MmCommunication};
+use r_efi::efi;
+
+use crate::{
+    mm_variable::{
+        ACCESS_VARIABLE_HEADER_SIZE, COMMUNICATE_HEADER_SIZE, GET_NEXT_VARIABLE_NAME_HEADER_SIZE,
+        GET_PAYLOAD_SIZE_SIZE, MM_VARIABLE_HANDLER_GUID, QUERY_VARIABLE_INFO_SIZE, function,
+    },
+    q35::component::service::{
+        mm_config_provider::{CommunicateBufferTypeExt, MmCommBufferType},
+        mm_supervisor_client::map_comm_status,
+    },
+    variable_services::{VARIABLE_ARCH_PROTOCOL_GUID, VARIABLE_WRITE_ARCH_PROTOCOL_GUID},
+};
+
+/// `ReturnStatus` sent with every request, so a request the handler does not process is not reported as a success
+const UNHANDLED_STATUS: efi::Status = efi::Status::PROTOCOL_ERROR;
+
+/// x86-64 code of the variable services after ExitBootServices: `mov rax, EFI_UNSUPPORTED` followed by `ret`
+const UNSUPPORTED_STUB: [u8; 11] = [0x48, 0xB8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0xC3];
+
+/// Runtime services reported as unsupported after ExitBootServices in the `EFI_RT_PROPERTIES_TABLE`
+const RT_SUPPORTED_VARIABLE_SERVICES: u32 = efi::RT_SUPPORTED_GET_VARIABLE
+    | efi::RT_SUPPORTED_GET_NEXT_VARIABLE_NAME
+    | efi::RT_SUPPORTED_SET_VARIABLE
+    | efi::RT_SUPPORTED_QUERY_VARIABLE_INFO;
+/// All runtime services defined by `EFI_RT_SUPPORTED_*`
+const RT_SUPPORTED_ALL: u32 = 0x3FFF;
+
+/// The variable services used by the runtime services table entries, published once by the component entry point.
+static RUNTIME_VARIABLES: AtomicPtr<QemuQ35MmVariable> = AtomicPtr::new(ptr::null_mut());
+
+/// MM Variable Services
+///
+/// Boot-time access to the UEFI variables owned by the Standalone MM variable handler. Variable names are
+/// NUL-terminated UCS-2 strings.
+///
+/// ## Errors
+///
+/// - `EfiError::InvalidParameter` if a name is not NUL-terminated or a request does not fit in the payload accepted by
+///   the MM variable handler.
+/// - `EfiError::ProtocolError` if the response is malformed or the handler did not process the request.
+/// - The `EfiError` matching the status reported by the MM variable handler.
+/// - The `EfiError` mapped from the MM communication failure if the request could not be delivered.
+pub trait MmVariableServices {
+    /// Returns the data and attributes of a variable.
+    fn get_variable(&self, name: &[u16], vendor_guid: &efi::Guid) -> patina::error::Result<(Vec<u8>, u32)>;
+
+    /// Returns the name and vendor GUID of the variable that follows the given variable. An empty name starts the
+    /// enumeration and `EfiError::NotFound` ends it.
+    fn get_next_variable_name(
+        &self,
+        name: &[u16],
+        vendor_guid: &efi::Guid,
+    ) -> patina::error::Result<(Vec<u16>, efi::Guid)>;
+
+    /// Sets a variable. Empty data deletes the variable.
+    fn set_variable(
+        &self,
+        name: &[u16],
+        vendor_guid: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> patina::error::Result<()>;
+
+    /// Returns the variable storage information for variables with the given attributes.
+    fn query_variable_info(&self, attributes: u32) -> patina::error::Result<VariableInfo>;
+}
+
+/// QEMU Q35 MM Variable Services Component
+///
+/// Installs the UEFI variable services, the Variable and Variable Write architectural protocols and the
+/// [`MmVariableServices`] service. Requests are sent through the user MM communication buffer.
+#[derive(IntoService, Clone, Default)]
+#[service(dyn MmVariableServices)]
+pub struct QemuQ35MmVariable {
+    /// Maximum size of a variable message payload, after the communicate header, accepted by the MM variable handler.
+    payload_size: usize,
+    mm_comm: Option<Service<dyn MmCommunication>>,
+}
+
+/// State used to switch the runtime services table to the runtime stub at BeforeExitBootServices.
+struct RuntimeSwitch {
+    boot_services: StandardBootServices,
+    runtime_services: StandardRuntimeServices,
+    /// Address of [`UNSUPPORTED_STUB`] in runtime services code
+    stub: usize,
+}
+
+#[component]
+impl QemuQ35MmVariable {
+    /// Creates a new instance of the QEMU Q35 MM Variable Services component.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Entry point for the MM Variable Services component.
+    ///
+    /// Queries the payload size accepted by the MM variable handler, installs the variable services in the runtime
+    /// services table and installs the Variable and Variable Write architectural protocols. Nothing is installed if
+    /// another driver already provides the Variable architectural protocol.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::NotFound` if no user MM communication buffer is configured.
+    /// - `EfiError::BufferTooSmall` if the payload cannot hold a variable request.
+    /// - `EfiError::AlreadyStarted` if the variable services were already installed by this component.
+    /// - Any error returned by the MM variable handler for the payload size request.
+    fn entry_point(
+        mut self,
+        config: Config<MmCommunicationConfiguration>,
+        mm_comm: Service<dyn MmCommunication>,
+        memory_manager: Service<dyn MemoryManager>,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+        mut commands: Commands,
+    ) -> patina::error::Result<()> {
+        log::debug!("MM Variable Entry Point");
+
+        if boot_services.locate_protocol_marker(&VARIABLE_ARCH_PROTOCOL_GUID, None).is_ok() {
+            log::warn!("Variable Arch Protocol is already installed. MM variable services are not installed.");
+            return Ok(());
+        }
+
+        let buffer_type = MmCommBufferType::User;
+        let Some(buffer) = config.comm_buffers.iter().find(|buffer| buffer.buffer_type() == Some(buffer_type)) else {
+            log::error!("No MM Communicate Buffer of type {buffer_type:?} for variable requests");
+            return Err(EfiError::NotFound);
+        };
+
+        self.mm_comm = Some(mm_comm);
+        self.init_payload_size(buffer.message_capacity())?;
+
+        let stub = install_unsupported_stub(*memory_manager)?;
+        let variables = Box::into_raw(Box::new(self.clone()));
+        if RUNTIME_VARIABLES.compare_exchange(ptr::null_mut(), variables, Ordering::AcqRel, Ordering::Acquire).is_err()
+        {
+            // SAFETY: `variables` was just created by `Box::into_raw` and was not published.
+            drop(unsafe { Box::from_raw(variables) });
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        // SAFETY: The runtime services table is valid for the lifetime of the firmware. DXE dispatch is single
+        // threaded, so no other code is accessing the table while it is updated.
+        let rt = unsafe { &mut *runtime_services.as_mut_ptr() };
+        rt.get_variable = get_variable;
+        rt.get_next_variable_name = get_next_variable_name;
+        rt.set_variable = set_variable;
+        rt.query_variable_info = query_variable_info;
+        rt.hdr.crc32 = 0;
+        rt.hdr.crc32 = boot_services.calculate_crc_32(rt)?;
+
+        install_rt_properties_table(&boot_services)?;
+
+        boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(ready_to_boot_callback),
+            Box::new(boot_services.clone()),
+            &EVENT_READY_TO_BOOT,
+        )?;
+
+        boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(before_exit_boot_services_callback),
+            Box::new(RuntimeSwitch { boot_services: boot_services.clone(), runtime_services, stub }),
+            &efi::EVENT_GROUP_BEFORE_EXIT_BOOT_SERVICES,
+        )?;
+
+        // SAFETY: The Variable and Variable Write architectural protocols have no interface.
+        unsafe {
+            let handle = boot_services.install_protocol_interface_unchecked(
+                None,
+                &VARIABLE_ARCH_PROTOCOL_GUID,
+                ptr::null_mut(),
+            )?;
+            boot_services.install_protocol_interface_unchecked(
+                Some(handle),
+                &VARIABLE_WRITE_ARCH_PROTOCOL_GUID,
+                ptr::null_mut(),
+            )?;
+        }
+
+        log::info!("MM variable services installed ({:#X} byte payload)", self.payload_size);
+
+        commands.add_service(self);
+
+        Ok(())
+    }
+
+    /// Sets the payload size to the size accepted by the MM variable handler, bounded by the message capacity of the
+    /// communication buffer.
+    ///
+    /// `VariableStandaloneMm` ignores requests with a larger payload, so every request is bounded by this size.
+    fn init_payload_size(&mut self, message_capacity: usize) -> patina::error::Result<()> {
+        self.payload_size = message_capacity.saturating_sub(COMMUNICATE_HEADER_SIZE);
+
+        let (status, response) = self.request(function::GET_PAYLOAD_SIZE, &[0u8; GET_PAYLOAD_SIZE_SIZE])?;
+        EfiError::status_to_result(status).inspect_err(|e| {
+            log::error!("MM variable payload size request returned {status:?} ({e:?})");
+        })?;
+
+        self.payload_size = self.payload_size.min(read_u64(&response, 0) as usize);
+        if self.payload_size <= ACCESS_VARIABLE_HEADER_SIZE {
+            log::error!("MM variable payload is too small for variable requests: {:#X}", self.payload_size);
+            return Err(EfiError::BufferTooSmall);
+        }
+
+        Ok(())
+    }
+
+    /// Sends a message to the MM variable handler and returns the handler status and the response payload.
+    ///
+    /// `payload` is the function-specific message that follows the communicate header.
+    fn request(&self, function: u64, payload: &[u8]) -> patina::error::Result<(efi::Status, Vec<u8>)> {
+        let mm_comm = self.mm_comm.as_ref().ok_or(EfiError::NotReady)?;
+
+        if payload.len() > self.payload_size {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        let mut message = Vec::with_capacity(COMMUNICATE_HEADER_SIZE + payload.len());
+        message.extend_from_slice(&function.to_le_bytes());
+        message.extend_from_slice(&(UNHANDLED_STATUS.as_usize() as u64).to_le_bytes());
+        message.extend_from_slice(payload);
+
+        let mut response = mm_comm
+            .communicate(MmCommBufferType::User.id(), &message, MM_VARIABLE_HANDLER_GUID.as_guid())
+            .map_err(|status| {
+                log::error!("MM variable request {function} failed to communicate: {status:?}");
+                map_comm_status(status)
+            })?;
+
+        if response.len() < COMMUNICATE_HEADER_SIZE + payload.len() {
+            log::error!("MM variable response is too small: {} bytes", response.len());
+            return Err(EfiError::ProtocolError);
+        }
+
+        let status = efi::Status::from_usize(read_u64(&response, 8) as usize);
+        if status != efi::Status::SUCCESS {
+            log::debug!("MM variable request {function} returned {status:?}");
+        }
+
+        response.drain(..COMMUNICATE_HEADER_SIZE);
+        Ok((status, response))
+    }
+
+    /// Sends a request with no payload that notifies the MM variable handler of a boot event.
+    fn notify(&self, function: u64) {
+        match self.request(function, &[]) {
+            Ok((efi::Status::SUCCESS, _)) => {}
+            Ok((status, _)) => log::error!("MM variable notification {function} returned {status:?}"),
+            Err(e) => log::error!("MM variable notification {function} failed: {e:?}"),
+        }
+    }
+
+    /// Returns the data and attributes of a variable, requesting at most `data_size` bytes of data.
+    ///
+    /// ## Errors
+    ///
+    /// Returns the error with the data size reported by the handler, which is the size of the variable for
+    /// `EfiError::BufferTooSmall`.
+    fn read_variable(
+        &self,
+        name: &[u16],
+        vendor_guid: &efi::Guid,
+        data_size: usize,
+    ) -> Result<(Vec<u8>, u32), (EfiError, usize)> {
+        let name = name_bytes(name).map_err(|e| (e, 0))?;
+        if name.len() == size_of::<u16>() {
+            return Err((EfiError::NotFound, 0));
+        }
+
+        let data_size = data_size.min(self.max_data_size(name.len()));
+        let message = access_variable_message(vendor_guid, &name, 0, data_size);
+        let (status, response) = self.request(function::GET_VARIABLE, &message).map_err(|e| (e, 0))?;
+
+        let response_data_size = read_u64(&response, 16) as usize;
+        if let Err(e) = EfiError::status_to_result(status) {
+            return Err((e, response_data_size));
+        }
+        if response_data_size > data_size {
+            return Err((EfiError::ProtocolError, 0));
+        }
+
+        let attributes = u32::from_le_bytes([response[32], response[33], response[34], response[35]]);
+        let data_offset = ACCESS_VARIABLE_HEADER_SIZE + name.len();
+        Ok((response[data_offset..data_offset + response_data_size].to_vec(), attributes))
+    }
+
+    /// Returns the variable that follows the given variable, requesting at most `name_size` bytes of name.
+    ///
+    /// ## Errors
+    ///
+    /// Returns the error with the name size reported by the handler, which is the size of the next name for
+    /// `EfiError::BufferTooSmall`.
+    fn read_next_variable_name(
+        &self,
+        name: &[u16],
+        vendor_guid: &efi::Guid,
+        name_size: usize,
+    ) -> Result<(Vec<u16>, efi::Guid), (EfiError, usize)> {
+        let name = name_bytes(name).map_err(|e| (e, 0))?;
+        let name_size =
+            name_size.max(name.len()).min(self.payload_size.saturating_sub(GET_NEXT_VARIABLE_NAME_HEADER_SIZE));
+        if name.len() > name_size {
+            return Err((EfiError::InvalidParameter, 0));
+        }
+
+        let mut message = vec![0u8; GET_NEXT_VARIABLE_NAME_HEADER_SIZE + name_size];
+        message[..16].copy_from_slice(vendor_guid.as_bytes());
+        message[16..24].copy_from_slice(&(name_size as u64).to_le_bytes());
+        message[GET_NEXT_VARIABLE_NAME_HEADER_SIZE..][..name.len()].copy_from_slice(&name);
+
+        let (status, response) = self.request(function::GET_NEXT_VARIABLE_NAME, &message).map_err(|e| (e, 0))?;
+
+        let response_name_size = read_u64(&response, 16) as usize;
+        if let Err(e) = EfiError::status_to_result(status) {
+            return Err((e, response_name_size));
+        }
+        if response_name_size > name_size || !response_name_size.is_multiple_of(size_of::<u16>()) {
+            return Err((EfiError::ProtocolError, 0));
+        }
+
+        let mut guid = [0u8; 16];
+        guid.copy_from_slice(&response[..16]);
+        let next_name = response[GET_NEXT_VARIABLE_NAME_HEADER_SIZE..][..response_name_size]
+            .chunks_exact(size_of::<u16>())
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+
+        Ok((next_name, efi::Guid::from_bytes(&guid)))
+    }
+
+    /// Returns the maximum variable data size that fits in a request with a name of `name_size` bytes.
+    fn max_data_size(&self, name_size: usize) -> usize {
+        self.payload_size.saturating_sub(ACCESS_VARIABLE_HEADER_SIZE + name_size)
+    }
+}
+
+impl MmVariableServices for QemuQ35MmVariable {
+    fn get_variable(&self, name: &[u16], vendor_guid: &efi::Guid) -> patina::error::Result<(Vec<u8>, u32)> {
+        self.read_variable(name, vendor_guid, usize::MAX).map_err(|(e, _)| e)
+    }
+
+    fn get_next_variable_name(
+        &self,
+        name: &[u16],
+        vendor_guid: &efi::Guid,
+    ) -> patina::error::Result<(Vec<u16>, efi::Guid)> {
+        self.read_next_variable_name(name, vendor_guid, usize::MAX).map_err(|(e, _)| e)
+    }
+
+    fn set_variable(
+        &self,
+        name: &[u16],
+        vendor_guid: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> patina::error::Result<()> {
+        let name = name_bytes(name)?;
+        if name.len() == size_of::<u16>() || data.len() > self.max_data_size(name.len()) {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        let mut message = access_variable_message(vendor_guid, &name, attributes, data.len());
+        message[ACCESS_VARIABLE_HEADER_SIZE + name.len()..].copy_from_slice(data);
+
+        let (status, _) = self.request(function::SET_VARIABLE, &message)?;
+        EfiError::status_to_result(status)
+    }
+
+    fn query_variable_info(&self, attributes: u32) -> patina::error::Result<VariableInfo> {
+        if attributes == 0 {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        let mut message = vec![0u8; QUERY_VARIABLE_INFO_SIZE];
+        message[24..28].copy_from_slice(&attributes.to_le_bytes());
+
+        let (status, response) = self.request(function::QUERY_VARIABLE_INFO, &message)?;
+        EfiError::status_to_result(status)?;
+
+        Ok(VariableInfo {
+            maximum_variable_storage_size: read_u64(&response, 0),
+            remaining_variable_storage_size: read_u64(&response, 8),
+            maximum_variable_size: read_u64(&response, 16),
+        })
+    }
+}
+
+/// Copies [`UNSUPPORTED_STUB`] to a page of runtime services code and makes it executable.
+fn install_unsupported_stub(memory_manager: &dyn MemoryManager) -> patina::error::Result<usize> {
+    let allocation = memory_manager
+        .allocate_zero_pages(1, AllocationOptions::new().with_memory_type(EfiMemoryType::RuntimeServicesCode))?;
+    let stub = allocation.into_raw_ptr::<u8>().ok_or(EfiError::OutOfResources)?;
+
+    // SAFETY: The page was just allocated, is writable and is never freed. It is only made executable once the stub
+    // is written, and is not written again.
+    unsafe {
+        ptr::copy_nonoverlapping(UNSUPPORTED_STUB.as_ptr(), stub, UNSUPPORTED_STUB.len());
+        memory_manager.set_page_attributes(stub as usize, 1, AccessType::ReadExecute, None)?;
+    }
+
+    Ok(stub as usize)
+}
+
+/// Installs an `EFI_RT_PROPERTIES_TABLE` that reports the variable services as unsupported after ExitBootServices.
+///
+/// Any existing table is replaced. The other runtime services are reported as supported, as in the EDK II default.
+fn install_rt_properties_table(boot_services: &StandardBootServices) -> patina::error::Result<()> {
+    let table = boot_services.allocate_pool_for_type::<efi::RtPropertiesTable>(EfiMemoryType::RuntimeServicesData)?;
+
+    // SAFETY: `table` was just allocated with the size and alignment of the table, in memory that is never freed.
+    unsafe {
+        table.write(efi::RtPropertiesTable {
+            version: efi::RT_PROPERTIES_TABLE_VERSION,
+            length: size_of::<efi::RtPropertiesTable>() as u16,
+            runtime_services_supported: RT_SUPPORTED_ALL & !RT_SUPPORTED_VARIABLE_SERVICES,
+        });
+        boot_services.install_configuration_table_unchecked(&efi::RT_PROPERTIES_TABLE_GUID, table as *mut c_void)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the variable services published by the component entry point.
+fn runtime_variables() -> Option<&'static QemuQ35MmVariable> {
+    // SAFETY: `RUNTIME_VARIABLES` is only set once, to a leaked allocation that is never freed.
+    unsafe { RUNTIME_VARIABLES.load(Ordering::Acquire).as_ref() }
+}
+
+/// Event notification that tells the MM variable handler that ReadyToBoot was signaled.
+extern "efiapi" fn ready_to_boot_callback(event: efi::Event, boot_services: Box<StandardBootServices>) {
+    let _ = boot_services.close_event(event);
+
+    if let Some(variables) = runtime_variables() {
+        variables.notify(function::READY_TO_BOOT);
+    }
+}
+
+/// Event notification that switches the variable services to the runtime stub.
+///
+/// MM communication allocates memory, which is not allowed in ExitBootServices notifications, so the handler is
+/// told that boot services are ending and the variable services report `EFI_UNSUPPORTED` from this point on.
+extern "efiapi" fn before_exit_boot_services_callback(event: efi::Event, switch: Box<RuntimeSwitch>) {
+    let _ = switch.boot_services.close_event(event);
+
+    if let Some(variables) = runtime_variables() {
+        variables.notify(function::EXIT_BOOT_SERVICE);
+    }
+
+    // SAFETY: `stub` is the address of `UNSUPPORTED_STUB`, which only sets the return value, so it is a valid
+    // implementation of each variable service. See `entry_point()` for the runtime services table.
+    unsafe {
+        let rt = &mut *switch.runtime_services.as_mut_ptr();
+        rt.get_variable = core::mem::transmute::<usize, efi::RuntimeGetVariable>(switch.stub);
+        rt.get_next_variable_name = core::mem::transmute::<usize, efi::RuntimeGetNextVariableName>(switch.stub);
+        rt.set_variable = core::mem::transmute::<usize, efi::RuntimeSetVariable>(switch.stub);
+        rt.query_variable_info = core::mem::transmute::<usize, efi::RuntimeQueryVariableInfo>(switch.stub);
+        rt.hdr.crc32 = 0;
+        match switch.boot_services.calculate_crc_32(rt) {
+            Ok(crc32) => rt.hdr.crc32 = crc32,
+            Err(status) => log::error!("Failed to update the runtime services table CRC: {status:?}"),
+        }
+    }
+}
+
+/// Reads a little-endian `u64` at `offset`.
+fn read_u64(buffer: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buffer[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Returns the bytes of a NUL-terminated UCS-2 name, up to and including the terminator.
+///
+/// ## Errors
+///
+/// - `EfiError::InvalidParameter` if `name` is not NUL-terminated.
+fn name_bytes(name: &[u16]) -> patina::error::Result<Vec<u8>> {
+    let len = name.iter().position(|&c| c == 0).ok_or(EfiError::InvalidParameter)? + 1;
+    Ok(name[..len].iter().flat_map(|c| c.to_le_bytes()).collect())
+}
+
+/// Reads a NUL-terminated UCS-2 name of at most `max_len` characters, including the terminator.
+///
+/// ## Safety
+///
+/// `name` must be readable up to its terminator or `max_len` characters, whichever comes first.
+unsafe fn read_name(name: *const u16, max_len: usize) -> Option<Vec<u16>> {
+    let mut chars = Vec::new();
+    for i in 0..max_len {
+        // SAFETY: The caller guarantees `name` is readable up to the terminator or `max_len` characters.
+        let c = unsafe { name.add(i).read_unaligned() };
+        chars.push(c);
+        if c == 0 {
+            return Some(chars);
+        }
+    }
+    None
+}
+
+/// Builds an `SMM_VARIABLE_COMMUNICATE_ACCESS_VARIABLE` message with room for `data_size` bytes of data.
+fn access_variable_message(guid: &efi::Guid, name: &[u8], attributes: u32, data_size: usize) -> Vec<u8> {
+    let mut message = vec![0u8; ACCESS_VARIABLE_HEADER_SIZE + name.len() + data_size];
+    message[..16].copy_from_slice(guid.as_bytes());
+    message[16..24].copy_from_slice(&(data_size as u64).to_le_bytes());
+    message[24..32].copy_from_slice(&(name.len() as u64).to_le_bytes());
+    message[32..36].copy_from_slice(&attributes.to_le_bytes());
+    message[ACCESS_VARIABLE_HEADER_SIZE..][..name.len()].copy_from_slice(name);
+    message
+}
+
+/// Returns the maximum number of characters of a name that fits in a request to `variables`.
+fn max_name_len(variables: &QemuQ35MmVariable) -> usize {
+    variables.payload_size / size_of::<u16>()
+}
+
+/// Implements `EFI_RUNTIME_SERVICES.GetVariable()`.
+unsafe extern "efiapi" fn get_variable(
+    variable_name: *mut efi::Char16,
+    vendor_guid: *mut efi::Guid,
+    attributes: *mut u32,
+    data_size: *mut usize,
+    data: *mut c_void,
+) -> efi::Status {
+    let Some(variables) = runtime_variables() else {
+        return efi::Status::NOT_READY;
+    };
+    // SAFETY: The caller guarantees the pointers are valid per the UEFI specification.
+    unsafe { get_variable_with(variables, variable_name, vendor_guid, attributes, data_size, data) }
+}
+
+/// Implements `EFI_RUNTIME_SERVICES.GetNextVariableName()`.
+unsafe extern "efiapi" fn get_next_variable_name(
+    variable_name_size: *mut usize,
+    variable_name: *mut efi::Char16,
+    vendor_guid: *mut efi::Guid,
+) -> efi::Status {
+    let Some(variables) = runtime_variables() else {
+        return efi::Status::NOT_READY;
+    };
+    // SAFETY: The caller guarantees the pointers are valid per the UEFI specification.
+    unsafe { get_next_variable_name_with(variables, variable_name_size, variable_name, vendor_guid) }
+}
+
+/// Implements `EFI_RUNTIME_SERVICES.SetVariable()`.
+unsafe extern "efiapi" fn set_variable(
+    variable_name: *mut efi::Char16,
+    vendor_guid: *mut efi::Guid,
+    attributes: u32,
+    data_size: usize,
+    data: *mut c_void,
+) -> efi::Status {
+    let Some(variables) = runtime_variables() else {
+        return efi::Status::NOT_READY;
+    };
+    // SAFETY: The caller guarantees the pointers are valid per the UEFI specification.
+    unsafe { set_variable_with(variables, variable_name, vendor_guid, attributes, data_size, data) }
+}
+
+/// Implements `EFI_RUNTIME_SERVICES.QueryVariableInfo()`.
+unsafe extern "efiapi" fn query_variable_info(
+    attributes: u32,
+    maximum_variable_storage_size: *mut u64,
+    remaining_variable_storage_size: *mut u64,
+    maximum_variable_size: *mut u64,
+) -> efi::Status {
+    let Some(variables) = runtime_variables() else {
+        return efi::Status::NOT_READY;
+    };
+    // SAFETY: The caller guarantees the pointers are valid per the UEFI specification.
+    unsafe {
+        query_variable_info_with(
+            variables,
+            attributes,
+            maximum_variable_storage_size,
+            remaining_variable_storage_size,
+            maximum_variable_size,
+        )
+    }
+}
+
+/// GetVariable with `variables`.
+///
+/// ## Safety
+///
+/// Non-null pointers must be valid as described by the UEFI specification.
+unsafe fn get_variable_with(
+    variables: &QemuQ35MmVariable,
+    variable_name: *const efi::Char16,
+    vendor_guid: *const efi::Guid,
+    attributes: *mut u32,
+    data_size: *mut usize,
+    data: *mut c_void,
+) -> efi::Status {
+    if variable_name.is_null() || vendor_guid.is_null() || data_size.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // SAFETY: The caller guarantees that non-null pointers are valid.
+    unsafe {
+        if *data_size != 0 && data.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(name) = read_name(variable_name, max_name_len(variables)) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        match variables.read_variable(&name, &*vendor_guid, *data_size) {
+            Ok((value, value_attributes)) => {
+                if !value.is_empty() {
+                    ptr::copy_nonoverlapping(value.as_ptr(), data as *mut u8, value.len());
+                }
+                *data_size = value.len();
+                if !attributes.is_null() {
+                    *attributes = value_attributes;
+                }
+                efi::Status::SUCCESS
+            }
+            Err((EfiError::BufferTooSmall, size)) => {
+                *data_size = size;
+                efi::Status::BUFFER_TOO_SMALL
+            }
+            Err((e, _)) => e.into(),
+        }
+    }
+}
+
+/// GetNextVariableName with `variables`.
+///
+/// ## Safety
+///
+/// Non-null pointers must be valid as described by the UEFI specification.
+unsafe fn get_next_variable_name_with(
+    variables: &QemuQ35MmVariable,
+    variable_name_size: *mut usize,
+    variable_name: *mut efi::Char16,
+    vendor_guid: *mut efi::Guid,
+) -> efi::Status {
+    if variable_name_size.is_null() || variable_name.is_null() || vendor_guid.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // SAFETY: The caller guarantees that non-null pointers are valid.
+    unsafe {
+        let buffer_size = *variable_name_size;
+        let max_len = (buffer_size / size_of::<u16>()).min(max_name_len(variables));
+        let Some(name) = read_name(variable_name, max_len) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        match variables.read_next_variable_name(&name, &*vendor_guid, buffer_size) {
+            Ok((next_name, next_guid)) => {
+                ptr::copy_nonoverlapping(next_name.as_ptr(), variable_name, next_name.len());
+                *variable_name_size = next_name.len() * size_of::<u16>();
+                *vendor_guid = next_guid;
+                efi::Status::SUCCESS
+            }
+            Err((EfiError::BufferTooSmall, size)) => {
+                *variable_name_size = size;
+                efi::Status::BUFFER_TOO_SMALL
+            }
+            Err((e, _)) => e.into(),
+        }
+    }
+}
+
+/// SetVariable with `variables`.
+///
+/// ## Safety
+///
+/// Non-null pointers must be valid as described by the UEFI specification.
+unsafe fn set_variable_with(
+    variables: &QemuQ35MmVariable,
+    variable_name: *const efi::Char16,
+    vendor_guid: *const efi::Guid,
+    attributes: u32,
+    data_size: usize,
+    data: *const c_void,
+) -> efi::Status {
+    if variable_name.is_null() || vendor_guid.is_null() || (data_size != 0 && data.is_null()) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // SAFETY: The caller guarantees that non-null pointers are valid.
+    unsafe {
+        let Some(name) = read_name(variable_name, max_name_len(variables)) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let data = if data_size == 0 { &[][..] } else { core::slice::from_raw_parts(data as *const u8, data_size) };
+
+        match variables.set_variable(&name, &*vendor_guid, attributes, data) {
+            Ok(()) => efi::Status::SUCCESS,
+            Err(e) => e.into(),
+        }
+    }
+}
+
+/// QueryVariableInfo with `variables`.
+///
+/// ## Safety
+///
+/// Non-null pointers must be valid as described by the UEFI specification.
+unsafe fn query_variable_info_with(
+    variables: &QemuQ35MmVariable,
+    attributes: u32,
+    maximum_variable_storage_size: *mut u64,
+    remaining_variable_storage_size: *mut u64,
+    maximum_variable_size: *mut u64,
+) -> efi::Status {
+    if maximum_variable_storage_size.is_null()
+        || remaining_variable_storage_size.is_null()
+        || maximum_variable_size.is_null()
+    {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    match variables.query_variable_info(attributes) {
+        // SAFETY: The caller guarantees that non-null pointers are valid.
+        Ok(info) => unsafe {
+            *maximum_variable_storage_size = info.maximum_variable_storage_size;
+            *remaining_variable_storage_size = info.remaining_variable_storage_size;
+            *maximum_variable_size = info.maximum_variable_size;
+            efi::Status::SUCCESS
+        },
+        Err(e) => e.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use patina_mm::component::communicator::Status as MmCommStatus;
+
+    use super::*;
+    use crate::sim::ScriptedMmCommunication;
+
+    /// Payload size accepted by the test MM variable handler
+    const PAYLOAD_SIZE: usize = 0x80;
+
+    const VENDOR_GUID: efi::Guid =
+        efi::Guid::from_fields(0x12345678, 0x1234, 0x5678, 0x9a, 0xbc, &[0xde, 0xf0, 0x12, 0x34, 0x56, 0x78]);
+
+    fn variables(mm_comm: ScriptedMmCommunication) -> QemuQ35MmVariable {
+        QemuQ35MmVariable { payload_size: PAYLOAD_SIZE, mm_comm: Some(mm_comm.into_service()) }
+    }
+
+    fn ucs2(name: &str) -> Vec<u16> {
+        name.encode_utf16().chain([0]).collect()
+    }
+
+    /// Writes the handler `status` to the request.
+    fn set_status(buffer: &mut [u8], status: efi::Status) {
+        buffer[8..16].copy_from_slice(&(status.as_usize() as u64).to_le_bytes());
+    }
+
+    /// Offset of a field of the function-specific payload in the message.
+    fn payload(offset: usize) -> usize {
+        COMMUNICATE_HEADER_SIZE + offset
+    }
+
+    #[test]
+    fn test_init_payload_size_uses_handler_size() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            buffer[payload(0)..payload(8)].copy_from_slice(&(PAYLOAD_SIZE as u64).to_le_bytes());
+            set_status(buffer, efi::Status::SUCCESS);
+            Ok(())
+        });
+        let requests = mm_comm.requests();
+        let mut variables = QemuQ35MmVariable { payload_size: 0, mm_comm: Some(mm_comm.into_service()) };
+
+        assert_eq!(variables.init_payload_size(0x1000), Ok(()));
+        assert_eq!(variables.payload_size, PAYLOAD_SIZE);
+
+        let requests = requests.borrow();
+        assert_eq!(requests[0].recipient, MM_VARIABLE_HANDLER_GUID);
+        assert_eq!(requests[0].data.len(), COMMUNICATE_HEADER_SIZE + GET_PAYLOAD_SIZE_SIZE);
+        assert_eq!(read_u64(&requests[0].data, 0), function::GET_PAYLOAD_SIZE);
+    }
+
+    #[test]
+    fn test_init_payload_size_is_bounded_by_the_comm_buffer() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            buffer[payload(0)..payload(8)].copy_from_slice(&0x10000u64.to_le_bytes());
+            set_status(buffer, efi::Status::SUCCESS);
+            Ok(())
+        });
+        let mut variables = QemuQ35MmVariable { payload_size: 0, mm_comm: Some(mm_comm.into_service()) };
+
+        assert_eq!(variables.init_payload_size(0x100), Ok(()));
+        assert_eq!(variables.payload_size, 0x100 - COMMUNICATE_HEADER_SIZE);
+    }
+
+    #[test]
+    fn test_init_payload_size_rejects_small_payloads() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            buffer[payload(0)..payload(8)].copy_from_slice(&(ACCESS_VARIABLE_HEADER_SIZE as u64).to_le_bytes());
+            set_status(buffer, efi::Status::SUCCESS);
+            Ok(())
+        });
+        let mut variables = QemuQ35MmVariable { payload_size: 0, mm_comm: Some(mm_comm.into_service()) };
+
+        assert_eq!(variables.init_payload_size(0x1000), Err(EfiError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_request_prefills_a_failure_status() {
+        let mm_comm = ScriptedMmCommunication::new().then(|_| Ok(()));
+        let requests = mm_comm.requests();
+
+        assert_eq!(variables(mm_comm).set_variable(&ucs2("A"), &VENDOR_GUID, 0x7, &[1]), Err(EfiError::ProtocolError));
+        assert_eq!(read_u64(&requests.borrow()[0].data, 8), UNHANDLED_STATUS.as_usize() as u64);
+    }
+
+    #[test]
+    fn test_request_maps_communication_failures() {
+        let mm_comm = ScriptedMmCommunication::new().then(|_| Err(MmCommStatus::CommBufferNotFound));
+
+        assert_eq!(variables(mm_comm).query_variable_info(0x7).err(), Some(EfiError::NotFound));
+    }
+
+    #[test]
+    fn test_get_variable_request_is_bounded_by_the_payload_size() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            let name_offset = payload(ACCESS_VARIABLE_HEADER_SIZE);
+            buffer[payload(16)..payload(24)].copy_from_slice(&2u64.to_le_bytes());
+            buffer[payload(32)..payload(36)].copy_from_slice(&0x7u32.to_le_bytes());
+            buffer[name_offset + 4..name_offset + 6].copy_from_slice(&[0xAA, 0xBB]);
+            set_status(buffer, efi::Status::SUCCESS);
+            Ok(())
+        });
+        let requests = mm_comm.requests();
+
+        assert_eq!(variables(mm_comm).get_variable(&ucs2("A"), &VENDOR_GUID), Ok((vec![0xAA, 0xBB], 0x7)));
+
+        let requests = requests.borrow();
+        let data = &requests[0].data;
+        assert_eq!(data.len(), COMMUNICATE_HEADER_SIZE + PAYLOAD_SIZE);
+        assert_eq!(read_u64(data, 0), function::GET_VARIABLE);
+        assert_eq!(&data[payload(0)..payload(16)], VENDOR_GUID.as_bytes());
+        assert_eq!(read_u64(data, payload(16)) as usize, PAYLOAD_SIZE - ACCESS_VARIABLE_HEADER_SIZE - 4);
+        assert_eq!(read_u64(data, payload(24)), 4);
+        assert_eq!(&data[payload(ACCESS_VARIABLE_HEADER_SIZE)..][..4], &[b'A', 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_get_variable_rejects_oversized_responses() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            buffer[payload(16)..payload(24)].copy_from_slice(&(PAYLOAD_SIZE as u64).to_le_bytes());
+            set_status(buffer, efi::Status::SUCCESS);
+            Ok(())
+        });
+
+        assert_eq!(variables(mm_comm).get_variable(&ucs2("A"), &VENDOR_GUID), Err(EfiError::ProtocolError));
+    }
+
+    #[test]
+    fn test_set_variable_rejects_data_larger_than_the_payload() {
+        let variables = variables(ScriptedMmCommunication::new());
+        let data = vec![0u8; PAYLOAD_SIZE - ACCESS_VARIABLE_HEADER_SIZE - 3];
+
+        assert_eq!(variables.set_variable(&ucs2("A"), &VENDOR_GUID, 0x7, &data), Err(EfiError::InvalidParameter));
+        assert_eq!(
+            variables.set_variable(&[u16::from(b'A')], &VENDOR_GUID, 0x7, &[1]),
+            Err(EfiError::InvalidParameter)
+        );
+    }
+
+    #[test]
+    fn test_set_variable_request() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            set_status(buffer, efi::Status::SUCCESS);
+            Ok(())
+        });
+        let requests = mm_comm.requests();
+
+        assert_eq!(variables(mm_comm).set_variable(&ucs2("A"), &VENDOR_GUID, 0x7, &[1, 2, 3]), Ok(()));
+
+        let requests = requests.borrow();
+        let data = &requests[0].data;
+        assert_eq!(data.len(), payload(ACCESS_VARIABLE_HEADER_SIZE) + 4 + 3);
+        assert_eq!(read_u64(data, 0), function::SET_VARIABLE);
+        assert_eq!(read_u64(data, payload(16)), 3);
+        assert_eq!(u32::from_le_bytes(data[payload(32)..payload(36)].try_into().unwrap()), 0x7);
+        assert_eq!(&data[data.len() - 3..], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_query_variable_info() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            assert_eq!(u32::from_le_bytes(buffer[payload(24)..payload(28)].try_into().unwrap()), 0x3);
+            buffer[payload(0)..payload(8)].copy_from_slice(&0x10000u64.to_le_bytes());
+            buffer[payload(8)..payload(16)].copy_from_slice(&0x8000u64.to_le_bytes());
+            buffer[payload(16)..payload(24)].copy_from_slice(&0x400u64.to_le_bytes());
+            set_status(buffer, efi::Status::SUCCESS);
+            Ok(())
+        });
+
+        let info = variables(mm_comm).query_variable_info(0x3).unwrap();
+        assert_eq!(info.maximum_variable_storage_size, 0x10000);
+        assert_eq!(info.remaining_variable_storage_size, 0x8000);
+        assert_eq!(info.maximum_variable_size, 0x400);
+    }
+
+    #[test]
+    fn test_get_variable_with_reports_the_required_size() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            assert_eq!(read_u64(buffer, payload(16)), 1);
+            buffer[payload(16)..payload(24)].copy_from_slice(&0x20u64.to_le_bytes());
+            set_status(buffer, efi::Status::BUFFER_TOO_SMALL);
+            Ok(())
+        });
+        let variables = variables(mm_comm);
+        let name = ucs2("A");
+        let mut data_size = 1usize;
+        let mut data = [0u8; 1];
+
+        // SAFETY: All pointers reference valid locals.
+        let status = unsafe {
+            get_variable_with(
+                &variables,
+                name.as_ptr(),
+                &VENDOR_GUID,
+                ptr::null_mut(),
+                &mut data_size,
+                data.as_mut_ptr() as *mut c_void,
+            )
+        };
+
+        assert_eq!(status, efi::Status::BUFFER_TOO_SMALL);
+        assert_eq!(data_size, 0x20);
+    }
+
+    #[test]
+    fn test_get_variable_with_copies_the_data() {
+        let mm_comm = ScriptedMmCommunication::new().then(|buffer| {
+            let data_offset = payload(ACCESS_VARIABLE_HEADER_SIZE) + 4;
+            buffer[payload(16)..payload(24)].copy_from_slice(&2u64.to_le_bytes());
+            buffer[payload(32)..payload(36)].copy_from_slice(&0x3u32.to_le_bytes());
+            buffer[data_offset..data_offset + 2].copy_from_slice(&[0x55, 0x66]);
+            set_status(buffer, efi::Status::SUCCESS);
+            Ok(())
+        });
+        let variables = variables(mm_comm);
+        let name = ucs2("A");
+        let mut attributes = 0u32;
+        let mut data_size = 4usize;
+        let mut data = [0u8; 4];
+
+        // SAFETY: All pointers reference valid locals.
+        let status = unsafe {
+            get_variable_with(
+                &variables,
+                name.as_ptr(),
+                &VENDOR_GUID,
+                &mut attributes,
+                &mut data_size,
+                data.as_mut_ptr() as *mut c_void,
+            )
+        };
+
+        assert_eq!(status, efi::Status::SUCCESS);
+        assert_eq!(data_size, 2);
+        assert_eq!(attributes, 0x3);
+        assert_eq!(&data[..2], &[0x55, 0x66]);
+    }
+
+    #[test]
+    fn test_get_variable_with_rejects_unterminated_names() {
+        let variables = variables(ScriptedMmCommunication::new());
+        let name = vec![u16::from(b'A'); PAYLOAD_SIZE];
+        let mut data_size = 0usize;
+
+        // SAFETY: `name` is readable for more characters than the payload holds, and the other pointers reference
+        // valid locals.
+        let status = unsafe {
+            get_variable_with(&variables, name.as_ptr(), &VENDOR_GUID, ptr::null_mut(), &mut data_size, ptr::null_mut())
+        };
+
+        assert_eq!(status, efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_get_next_variable_name_with() {
+        let mm_comm = ScriptedMmCommunication::new()
+            .then(|buffer| {
+                assert_eq!(read_u64(buffer, payload(16)), 4);
+                buffer[payload(16)..payload(24)].copy_from_slice(&6u64.to_le_bytes());
+                set_status(buffer, efi::Status::BUFFER_TOO_SMALL);
+                Ok(())
+            })
+            .then(|buffer| {
+                let name_offset = payload(GET_NEXT_VARIABLE_NAME_HEADER_SIZE);
+                buffer[payload(0)..payload(16)].copy_from_slice(VENDOR_GUID.as_bytes());
+                buffer[payload(16)..payload(24)].copy_from_slice(&6u64.to_le_bytes());
+                buffer[name_offset..name_offset + 6].copy_from_slice(&[b'B', 0, b'C', 0, 0, 0]);
+                set_status(buffer, efi::Status::SUCCESS);
+                Ok(())
+            });
+        let variables = variables(mm_comm);
+        let mut name = [0u16; 8];
+        let mut name_size = 4usize;
+        let mut guid = efi::Guid::from_bytes(&[0; 16]);
+
+        // SAFETY: All pointers reference valid locals.
+        let status = unsafe { get_next_variable_name_with(&variables, &mut name_size, name.as_mut_ptr(), &mut guid) };
+        assert_eq!(status, efi::Status::BUFFER_TOO_SMALL);
+        assert_eq!(name_size, 6);
+
+        // SAFETY: All pointers reference valid locals.
+        let status = unsafe { get_next_variable_name_with(&variables, &mut name_size, name.as_mut_ptr(), &mut guid) };
+        assert_eq!(status, efi::Status::SUCCESS);
+        assert_eq!(name_size, 6);
+        assert_eq!(&name[..3], &ucs2("BC")[..]);
+        assert_eq!(guid, VENDOR_GUID);
+    }
+
+    #[test]
+    fn test_set_variable_with_rejects_missing_data() {
+        let variables = variables(ScriptedMmCommunication::new());
+        let name = ucs2("A");
+
+        // SAFETY: `name` and `VENDOR_GUID` are valid, and `data` is null.
+        let status = unsafe { set_variable_with(&variables, name.as_ptr(), &VENDOR_GUID, 0x7, 4, ptr::null()) };
+
+        assert_eq!(status, efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_unsupported_stub_returns_unsupported() {
+        assert_eq!(&UNSUPPORTED_STUB[..2], &[0x48, 0xB8]);
+        assert_eq!(read_u64(&UNSUPPORTED_STUB, 2) as usize, efi::Status::UNSUPPORTED.as_usize());
+        assert_eq!(UNSUPPORTED_STUB[10], 0xC3);
+    }
+}
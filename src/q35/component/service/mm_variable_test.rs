@@ -0,0 +1,141 @@
+//! QEMU Q35 MM Variable Services Test
+//!
+//! Verifies that the MM variable services backed by the Standalone MM variable handler round-trip volatile and
+//! non-volatile variables on the QEMU Q35 platform.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use patina::{component::service::Service, error::EfiError};
+use patina_test::{patina_test, u_assert, u_assert_eq};
+use r_efi::efi;
+
+use crate::q35::component::service::mm_variable::MmVariableServices;
+
+/// Vendor GUID used for the test variables
+const TEST_VENDOR_GUID: efi::Guid =
+    efi::Guid::from_fields(0x5d2b8a3e, 0x6f41, 0x4c0a, 0x9b, 0x7e, &[0x21, 0x3c, 0x55, 0x0d, 0xa4, 0x19]);
+
+/// Upper bound on the number of variables enumerated before the test gives up
+const MAX_ENUMERATED_VARIABLES: usize = 1024;
+
+/// Round-trips a volatile variable through SetVariable, GetVariable, GetNextVariableName and delete.
+#[patina_test]
+fn q35_mm_variable_volatile_test(variables: Service<dyn MmVariableServices>) -> patina_test::error::Result {
+    variable_round_trip(
+        *variables,
+        "PatinaMmVolatileTest",
+        efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS,
+    )
+}
+
+/// Round-trips a non-volatile variable through SetVariable, GetVariable, GetNextVariableName and delete.
+#[patina_test]
+fn q35_mm_variable_non_volatile_test(variables: Service<dyn MmVariableServices>) -> patina_test::error::Result {
+    variable_round_trip(
+        *variables,
+        "PatinaMmNonVolatileTest",
+        efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS,
+    )
+}
+
+/// Checks that QueryVariableInfo reports consistent storage sizes for non-volatile variables.
+#[patina_test]
+fn q35_mm_variable_query_info_test(variables: Service<dyn MmVariableServices>) -> patina_test::error::Result {
+    let Ok(info) = variables.query_variable_info(
+        efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS,
+    ) else {
+        return Err("QueryVariableInfo failed for non-volatile variables");
+    };
+    log::debug!(
+        "  Max storage: {:#X}, Remaining: {:#X}, Max variable: {:#X}",
+        info.maximum_variable_storage_size,
+        info.remaining_variable_storage_size,
+        info.maximum_variable_size
+    );
+
+    u_assert!(info.maximum_variable_storage_size != 0, "Maximum variable storage size should be non-zero");
+    u_assert!(
+        info.remaining_variable_storage_size <= info.maximum_variable_storage_size,
+        "Remaining variable storage should not exceed the maximum"
+    );
+    u_assert!(info.maximum_variable_size != 0, "Maximum variable size should be non-zero");
+
+    Ok(())
+}
+
+/// Sets, reads back, enumerates and deletes a variable with the given attributes.
+fn variable_round_trip(variables: &dyn MmVariableServices, name: &str, attributes: u32) -> patina_test::error::Result {
+    log::debug!("MM Variable Test - {name} ({attributes:#X})");
+
+    let name = to_ucs2(name);
+    let data: Vec<u8> = (0..64u8).collect();
+
+    u_assert!(variables.set_variable(&name, &TEST_VENDOR_GUID, attributes, &data).is_ok(), "SetVariable failed");
+
+    let Ok((read_data, read_attributes)) = variables.get_variable(&name, &TEST_VENDOR_GUID) else {
+        return Err("GetVariable failed for a variable that was just set");
+    };
+    u_assert_eq!(read_data, data, "GetVariable returned different data");
+    u_assert_eq!(read_attributes, attributes, "GetVariable returned different attributes");
+
+    u_assert!(find_variable(variables, &name)?, "GetNextVariableName did not return the variable");
+
+    let updated: Vec<u8> = (0..16u8).rev().collect();
+    u_assert!(
+        variables.set_variable(&name, &TEST_VENDOR_GUID, attributes, &updated).is_ok(),
+        "SetVariable failed to update the variable"
+    );
+    let Ok((read_data, _)) = variables.get_variable(&name, &TEST_VENDOR_GUID) else {
+        return Err("GetVariable failed for an updated variable");
+    };
+    u_assert_eq!(read_data, updated, "GetVariable returned stale data after an update");
+
+    u_assert!(
+        variables.set_variable(&name, &TEST_VENDOR_GUID, attributes, &[]).is_ok(),
+        "SetVariable failed to delete the variable"
+    );
+    u_assert_eq!(
+        variables.get_variable(&name, &TEST_VENDOR_GUID).err(),
+        Some(EfiError::NotFound),
+        "GetVariable should return NOT_FOUND for a deleted variable"
+    );
+
+    log::debug!("MM Variable Test complete");
+    Ok(())
+}
+
+/// Enumerates all variables and returns whether `name` exists under the test vendor GUID.
+fn find_variable(variables: &dyn MmVariableServices, name: &[u16]) -> Result<bool, &'static str> {
+    let mut current_name = alloc::vec![0u16];
+    let mut current_guid = efi::Guid::from_bytes(&[0; 16]);
+
+    for _ in 0..MAX_ENUMERATED_VARIABLES {
+        match variables.get_next_variable_name(&current_name, &current_guid) {
+            Ok((next_name, next_guid)) => {
+                let len = next_name.iter().position(|&c| c == 0).map_or(next_name.len(), |p| p + 1);
+                if next_guid == TEST_VENDOR_GUID && next_name[..len] == *name {
+                    return Ok(true);
+                }
+                current_name = next_name;
+                current_guid = next_guid;
+            }
+            Err(EfiError::NotFound) => return Ok(false),
+            Err(_) => return Err("GetNextVariableName failed"),
+        }
+    }
+
+    Err("GetNextVariableName did not terminate")
+}
+
+/// Converts a string to a NUL-terminated UCS-2 string.
+fn to_ucs2(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(core::iter::once(0)).collect()
+}
@@ -0,0 +1,297 @@
+//! OVMF Management Mode (MM) Configuration Provider
+//!
+//! Produces MM configuration for OVMF builds with `SMM_REQUIRE` enabled. OVMF runs on the QEMU Q35 machine, so the
+//! ICH9 PMBASE and APM ports are the same as on Q35. SMRAM is described by the SMRAM Memory HOB that OVMF's PEI phase
+//! produces whenever `SMM_REQUIRE` is enabled.
+//!
+//! A fixed communication buffer is only described by the MM Communication Buffer HOB, which is produced by the
+//! Standalone MM IPL. Traditional SMM (`PiSmmIpl`) does not publish one: its SMM core only processes buffers passed
+//! through `EFI_MM_COMMUNICATION2_PROTOCOL`, so the configuration has no communicate buffers in that case.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use patina::{
+    BinaryGuid,
+    component::{
+        component,
+        hob::{FromHob, Hob},
+        params::ConfigMut,
+    },
+    error::EfiError,
+};
+use patina_mm::config::{CommunicateBuffer, MmCommunicationConfiguration};
+
+use crate::q35::component::service::mm_config_provider::{MmCommBufferType, read_pm_base};
+
+/// MM Communication Buffer HOB (`gMmCommBufferHobGuid`).
+///
+/// Describes the fixed communication buffer outside of SMRAM and the status mailbox shared with the MM core. Only
+/// produced by the Standalone MM IPL.
+#[derive(FromHob, Debug, Default, Clone, Copy, zerocopy::FromBytes)]
+#[hob = "6c2a2520-0131-4aee-a750-cc384aace8c6"]
+#[repr(C)]
+pub struct OvmfMmCommBufferHob {
+    physical_start: u64,
+    number_of_pages: u64,
+    status_buffer: u64,
+}
+
+/// A region of SMRAM from an `EFI_SMRAM_DESCRIPTOR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmramRegion {
+    /// Physical start address of the region.
+    pub physical_start: u64,
+    /// Size of the region in bytes.
+    pub physical_size: u64,
+}
+
+impl SmramRegion {
+    /// Returns `true` if the region overlaps `size` bytes starting at `address`.
+    pub fn overlaps(&self, address: u64, size: u64) -> bool {
+        address < self.physical_start.saturating_add(self.physical_size)
+            && self.physical_start < address.saturating_add(size)
+    }
+}
+
+/// SMRAM Memory HOB (`gEfiSmmSmramMemoryGuid`).
+///
+/// Holds an `EFI_SMRAM_HOB_DESCRIPTOR_BLOCK`: a 32-bit region count followed by 8-byte aligned
+/// `EFI_SMRAM_DESCRIPTOR` entries of `PhysicalStart`, `CpuStart`, `PhysicalSize` and `RegionState`.
+#[derive(Debug, Default, Clone)]
+pub struct OvmfSmramHob {
+    regions: Vec<SmramRegion>,
+}
+
+impl OvmfSmramHob {
+    /// Offset of the first descriptor in the descriptor block
+    const DESCRIPTORS_OFFSET: usize = 8;
+    /// Size of an `EFI_SMRAM_DESCRIPTOR`
+    const DESCRIPTOR_SIZE: usize = 32;
+
+    /// Returns the SMRAM regions described by the HOB.
+    pub fn regions(&self) -> &[SmramRegion] {
+        &self.regions
+    }
+}
+
+impl FromHob for OvmfSmramHob {
+    const HOB_GUID: BinaryGuid = BinaryGuid::from_string("6dadf1d1-d4cc-4910-bb6e-82b1fd80ff3d");
+
+    fn parse(bytes: &[u8]) -> Self {
+        let read_u64 = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        let count = bytes.get(..4).map_or(0, |count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+        let available = bytes.len().saturating_sub(Self::DESCRIPTORS_OFFSET) / Self::DESCRIPTOR_SIZE;
+        if count > available {
+            log::error!("SMRAM HOB describes {count} regions but only holds {available}");
+        }
+
+        let regions = (0..count.min(available))
+            .map(|index| {
+                let offset = Self::DESCRIPTORS_OFFSET + index * Self::DESCRIPTOR_SIZE;
+                SmramRegion { physical_start: read_u64(offset), physical_size: read_u64(offset + 16) }
+            })
+            .collect();
+
+        Self { regions }
+    }
+}
+
+/// Responsible for providing MM configuration information on OVMF.
+///
+/// Creates a single user communicate buffer from the MM Communication Buffer HOB, if present. The buffer uses the
+/// status mailbox from the HOB so the MM core can find the request in the fixed buffer and report its result.
+pub struct OvmfMmConfigurationProvider;
+
+#[component]
+impl OvmfMmConfigurationProvider {
+    /// Entry point for the OVMF MM Configuration Provider.
+    ///
+    /// Depends on the SMRAM Memory HOB. This component will not be dispatched on OVMF builds without
+    /// `SMM_REQUIRE`, which do not produce the HOB. If the MM Communication Buffer HOB is present, the communication
+    /// buffer is checked to lie entirely outside of SMRAM.
+    ///
+    /// Populates the `MmCommunicationConfiguration` with the PMBASE I/O port and the communicate buffer, if any, then
+    /// locks it so components that depend on the immutable configuration can be dispatched.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::SecurityViolation` if the communication buffer overlaps SMRAM.
+    /// - `EfiError::InvalidParameter` if the HOB does not describe a usable communicate buffer.
+    pub fn entry_point(
+        self,
+        smram_hob: Hob<OvmfSmramHob>,
+        comm_buffer_hob: Option<Hob<OvmfMmCommBufferHob>>,
+        mut config_mut: ConfigMut<MmCommunicationConfiguration>,
+    ) -> patina::error::Result<()> {
+        log::debug!("OVMF MM Configuration Provider Entry Point");
+
+        config_mut.acpi_base = read_pm_base().into();
+
+        let Some(comm_buffer_hob) = comm_buffer_hob else {
+            log::warn!(
+                "No MM Comm Buffer HOB. Traditional SMM does not support MM communication through a fixed buffer."
+            );
+            config_mut.lock();
+            return Ok(());
+        };
+
+        let address = comm_buffer_hob.physical_start;
+        let size = comm_buffer_hob.number_of_pages.saturating_mul(patina::base::UEFI_PAGE_SIZE as u64);
+        log::debug!(
+            "MM Comm Buffer: {address:#X}, {:#X} pages, status at {:#X}",
+            comm_buffer_hob.number_of_pages,
+            comm_buffer_hob.status_buffer
+        );
+
+        for region in smram_hob.iter().flat_map(|hob| hob.regions()) {
+            log::debug!("SMRAM Region: {:#X}, {:#X} bytes", region.physical_start, region.physical_size);
+            if region.overlaps(address, size) {
+                log::error!("MM Comm Buffer {address:#X} ({size:#X} bytes) overlaps SMRAM region {region:X?}");
+                return Err(EfiError::SecurityViolation);
+            }
+        }
+
+        let status_mailbox = (comm_buffer_hob.status_buffer != 0).then_some(comm_buffer_hob.status_buffer);
+        if status_mailbox.is_none() {
+            log::warn!("MM Comm Buffer HOB has no status mailbox");
+        }
+
+        let buffer = usize::try_from(size).map_err(|_| EfiError::InvalidParameter).and_then(|size| {
+            // SAFETY: The HOB describes a buffer outside of SMRAM that PEI reserved for MM communication for the rest
+            // of boot. The status mailbox is part of the same reservation.
+            unsafe {
+                CommunicateBuffer::from_firmware_region(address, size, MmCommBufferType::User.id(), status_mailbox)
+            }
+            .map_err(|e| {
+                log::error!("Failed to create MM Communicate Buffer from HOB: {e:?}");
+                EfiError::InvalidParameter
+            })
+        })?;
+
+        log::info!("MM Communicate Buffer {:?} (ID {}) configured", MmCommBufferType::User, buffer.id());
+        config_mut.comm_buffers.push(buffer);
+        config_mut.lock();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use patina::component::Storage;
+
+    use super::*;
+    use crate::{
+        q35::{
+            component::service::mm_config_provider::CommunicateBufferTypeExt,
+            sim::{self, FakeHobList},
+        },
+        sim::run_component,
+    };
+
+    const TEST_PM_BASE: u16 = 0x600;
+
+    fn storage_with_hobs(hobs: FakeHobList) -> Storage {
+        sim::reset();
+        sim::with_chipset(|chipset| chipset.set_pm_base(TEST_PM_BASE));
+
+        let mut storage = Storage::new();
+        hobs.install(&mut storage);
+        storage
+    }
+
+    #[test]
+    fn test_smram_hob_parse() {
+        let storage =
+            storage_with_hobs(FakeHobList::new().with_smram(&[(0x7F00_0000, 0x100_0000), (0xA0000, 0x20000)]));
+        let hob = storage.get_hob::<OvmfSmramHob>();
+
+        assert_eq!(
+            hob.map(|hob| hob.regions().to_vec()),
+            Some(alloc::vec![
+                SmramRegion { physical_start: 0x7F00_0000, physical_size: 0x100_0000 },
+                SmramRegion { physical_start: 0xA0000, physical_size: 0x20000 },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_smram_hob_parse_truncated() {
+        let mut bytes = 3u64.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 32]);
+
+        assert_eq!(OvmfSmramHob::parse(&bytes).regions().len(), 1);
+        assert!(OvmfSmramHob::parse(&[]).regions().is_empty());
+    }
+
+    #[test]
+    fn test_smram_region_overlaps() {
+        let region = SmramRegion { physical_start: 0x1000, physical_size: 0x1000 };
+
+        assert!(region.overlaps(0x1800, 0x1000));
+        assert!(region.overlaps(0x0, 0x1001));
+        assert!(!region.overlaps(0x0, 0x1000));
+        assert!(!region.overlaps(0x2000, 0x1000));
+    }
+
+    #[test]
+    fn test_provider_builds_config() {
+        let mut storage =
+            storage_with_hobs(FakeHobList::new().with_mm_comm_buffer(2).with_smram(&[(0x7F00_0000, 0x100_0000)]));
+
+        assert_eq!(run_component(OvmfMmConfigurationProvider, &mut storage), Ok(true));
+
+        let config = storage.get_config::<MmCommunicationConfiguration>().unwrap();
+        assert_eq!(config.acpi_base.get_io_value(), TEST_PM_BASE);
+        assert_eq!(config.comm_buffers.len(), 1);
+        assert_eq!(config.comm_buffers[0].buffer_type(), Some(MmCommBufferType::User));
+        assert_eq!(config.comm_buffers[0].len(), 2 * patina::base::UEFI_PAGE_SIZE);
+        assert!(config.comm_buffers[0].has_status_mailbox());
+    }
+
+    #[test]
+    fn test_provider_not_dispatched_without_smram_hob() {
+        let mut storage = storage_with_hobs(FakeHobList::new().with_mm_comm_buffer(1));
+
+        assert_eq!(run_component(OvmfMmConfigurationProvider, &mut storage), Ok(false));
+    }
+
+    #[test]
+    fn test_provider_without_comm_buffer_hob() {
+        let mut storage = storage_with_hobs(FakeHobList::new().with_smram(&[(0x7F00_0000, 0x100_0000)]));
+
+        assert_eq!(run_component(OvmfMmConfigurationProvider, &mut storage), Ok(true));
+
+        let config = storage.get_config::<MmCommunicationConfiguration>().unwrap();
+        assert_eq!(config.acpi_base.get_io_value(), TEST_PM_BASE);
+        assert!(config.comm_buffers.is_empty());
+    }
+
+    #[test]
+    fn test_provider_rejects_buffer_in_smram() {
+        let mut storage = storage_with_hobs(
+            FakeHobList::new().with_raw_mm_comm_buffer(0x7F00_0000, 1, 0).with_smram(&[(0x7F00_0000, 0x100_0000)]),
+        );
+
+        assert_eq!(run_component(OvmfMmConfigurationProvider, &mut storage), Err(EfiError::SecurityViolation));
+    }
+
+    #[test]
+    fn test_provider_rejects_unaligned_buffer() {
+        let mut storage = storage_with_hobs(
+            FakeHobList::new().with_raw_mm_comm_buffer(0x1234, 1, 0).with_smram(&[(0x7F00_0000, 0x100_0000)]),
+        );
+
+        assert_eq!(run_component(OvmfMmConfigurationProvider, &mut storage), Err(EfiError::InvalidParameter));
+    }
+}
@@ -1,9 +1,14 @@
 //! Q35 SMBIOS Platform Component
 //!
-//! Platform component that populates and publishes SMBIOS tables:
-//! 1. Uses the type-safe `add_record<T>()` API for adding SMBIOS records
-//! 2. Publishes the table after all records are added
-//! 3. Uses structured record types (Type0, Type1)
+//! Platform component that populates and publishes SMBIOS tables. The system identity comes from the
+//! `SmbiosPlatformConfig` set by the binary, and the common records are built by the shared `SmbiosPlatformBuilder`.
+//! Processor and cache records are derived from CPUID and the fw_cfg CPU count, and memory records from the HOB list
+//! the DXE core installs in the UEFI Configuration Table. The SMBIOS tables QEMU provides through fw_cfg are merged
+//! into the records, and the system UUID comes from the fw_cfg UUID item or from a persisted fallback. Slot and onboard
+//! device records are built from the PCI root bus, read through the PCI Express configuration space.
+//!
+//! The persisted UUID fallback and the boot status are kept in UEFI variables, so the records are added and the table
+//! is published once the Variable Write architectural protocol is installed.
 //!
 //! ## License
 //!
@@ -11,30 +16,46 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+#![cfg(any(test, all(target_os = "uefi", target_arch = "x86_64", feature = "x64")))]
 
 extern crate alloc;
-use alloc::{string::String, vec};
+use alloc::{format, vec::Vec};
 
 use patina::{
-    component::{component, service::Service},
-    error::Result,
+    boot_services::StandardBootServices,
+    component::{component, params::Config, service::Service},
+    error::{EfiError, Result},
+    runtime_services::StandardRuntimeServices,
 };
 use patina_smbios::{
-    service::{SMBIOS_HANDLE_PI_RESERVED, Smbios, SmbiosExt, SmbiosTableHeader},
-    smbios_record::{
-        Type0PlatformFirmwareInformation, Type1SystemInformation, Type2BaseboardInformation, Type3SystemEnclosure,
-    },
+    service::{SMBIOS_HANDLE_PI_RESERVED, Smbios, SmbiosHandle, SmbiosTableHeader},
     smbios_types::{
-        BiosCharacteristics, BiosCharacteristicsExt1, BiosCharacteristicsExt2, BoardType, BootUpState,
-        ExtendedBiosRomSize, FeatureFlags, PowerSupplyState, SecurityStatus, ThermalState, WakeUpType,
+        AssociativityField, ProcessorCharacteristics, ProcessorFamilyData, ProcessorInformationStatus,
+        ProcessorTypeData, ProcessorUpgrade, ProcessorVoltage, SystemCacheType,
+    },
+};
+
+use crate::{
+    q35::{
+        cpuid::{CacheInfo, CacheType, ProcessorInfo, Vendor},
+        fw_cfg::{self, FwCfg},
+        hw, registers as register,
+    },
+    smbios::{
+        PciRootBus, QemuSmbiosTables, SmbiosCache, SmbiosMemoryMap, SmbiosPlatformBuilder, SmbiosPlatformConfig,
+        StringPool, boot_status, pci, qemu, record::Type4ProcessorInformation, uuid,
     },
+    variable_services,
 };
 
 /// Q35 platform SMBIOS component that populates and publishes SMBIOS tables.
 ///
-/// This component adds platform-specific SMBIOS records (Type 0 BIOS Information,
-/// Type 1 System Information) and publishes the complete SMBIOS table to the
-/// UEFI Configuration Table for OS consumption.
+/// This component adds the system SMBIOS records (Type 0 BIOS Information, Type 1 System Information, Type 2
+/// Baseboard Information and Type 3 System Enclosure), one Type 4 Processor Information record per socket with its
+/// Type 7 Cache Information records, the Type 16, 17 and 19 memory records, the Type 11 OEM Strings and Type 12
+/// System Configuration Options records, the Type 32 System Boot Information and Type 45 Firmware Inventory records,
+/// the Type 9 System Slots and Type 41 Onboard Devices Extended records, and publishes the complete SMBIOS table to
+/// the UEFI Configuration Table for OS consumption.
 #[derive(Default)]
 pub struct Q35SmbiosPlatform;
 
@@ -45,156 +66,465 @@ impl Q35SmbiosPlatform {
         Self
     }
 
-    fn entry_point(self, smbios: Service<dyn Smbios>) -> Result<()> {
+    /// Registers the records to be added and published once the variable services are installed.
+    fn entry_point(
+        self,
+        smbios: Service<dyn Smbios>,
+        config: Config<SmbiosPlatformConfig>,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+    ) -> Result<()> {
         log::debug!("=== Q35 SMBIOS Platform Component ===");
 
-        // Verify SMBIOS version
-        let (major, minor) = smbios.version();
-        log::trace!("SMBIOS Version: {}.{}", major, minor);
-
-        // Add platform SMBIOS records using the type-safe API
-        log::trace!("Creating platform SMBIOS records...");
-
-        // Type 0: BIOS/Firmware Information
-        // Uses add_record<T>() - the recommended type-safe API
-        let bios_info = Type0PlatformFirmwareInformation {
-            header: SmbiosTableHeader::new(0, 0, SMBIOS_HANDLE_PI_RESERVED),
-            vendor: 1,
-            firmware_version: 2,
-            bios_starting_address_segment: 0xE800,
-            firmware_release_date: 3,
-            firmware_rom_size: 0xFF, // 16MB
-            characteristics: BiosCharacteristics::new().with_pci_supported(true),
-            characteristics_ext1: BiosCharacteristicsExt1::new()
-                .with_acpi_supported(true)
-                .with_usb_legacy_supported(true)
-                .with_smart_battery_supported(true),
-            characteristics_ext2: BiosCharacteristicsExt2::new()
-                .with_bios_boot_specification_supported(true)
-                .with_uefi_spec_supported(true),
-            system_bios_major_release: 1,
-            system_bios_minor_release: 0,
-            embedded_controller_major_release: 0xFF,
-            embedded_controller_minor_release: 0xFF,
-            extended_bios_rom_size: ExtendedBiosRomSize::new(),
-            string_pool: vec![
-                String::from("Patina Firmware"),
-                String::from(env!("CARGO_PKG_VERSION")),
-                String::from(option_env!("BUILD_DATE").unwrap_or("01/01/1970")),
-            ],
-        };
+        let platform =
+            PlatformRecords { smbios, config: *config, boot_services: boot_services.clone(), runtime_services };
+        variable_services::on_variable_write(&boot_services, platform, PlatformRecords::publish_callback).map_err(
+            |status| {
+                log::error!("Failed to wait for the variable services: {status:?}");
+                EfiError::from(status)
+            },
+        )
+    }
+}
+
+/// What the component needs to add and publish the records once the variable services are installed.
+struct PlatformRecords {
+    smbios: Service<dyn Smbios>,
+    config: SmbiosPlatformConfig,
+    boot_services: StandardBootServices,
+    runtime_services: StandardRuntimeServices,
+}
 
-        match smbios.add_record(None, &bios_info) {
-            Ok(handle) => log::trace!("  Type 0 (BIOS Info) - Handle 0x{:04X}", handle),
-            Err(e) => log::warn!("  Failed to add Type 0: {:?}", e),
+impl PlatformRecords {
+    /// Adds the platform records and publishes the SMBIOS table, logging any failure.
+    fn publish_callback(&'static self) {
+        if let Err(e) = self.publish() {
+            log::error!("Failed to publish the SMBIOS table: {e:?}");
         }
+    }
 
-        // Type 1: System Information
-        let system_info = Type1SystemInformation {
-            header: SmbiosTableHeader::new(1, 0, SMBIOS_HANDLE_PI_RESERVED),
-            manufacturer: 1,
-            product_name: 2,
-            version: 3,
-            serial_number: 4,
-            uuid: [0; 16],
-            wake_up_type: WakeUpType::PowerSwitch,
-            sku_number: 5,
-            family: 6,
-            string_pool: vec![
-                String::from("QEMU"),
-                String::from("Q35 Virtual Machine"),
-                String::from("1.0"),
-                String::from("VM-001"),
-                String::from("Q35-STANDARD"),
-                String::from("Virtual Machine Family"),
-            ],
-        };
+    /// Adds the platform records and publishes the SMBIOS table.
+    ///
+    /// ## Errors
+    ///
+    /// - The error returned by the SMBIOS service if a required record could not be added or the table could not be
+    ///   published.
+    fn publish(&self) -> Result<()> {
+        let config = &self.config;
+        let (major, minor) = config.smbios_version;
+        log::trace!("SMBIOS Version: {}.{} ({:?} entry point)", major, minor, config.entry_point);
 
-        match smbios.add_record(None, &system_info) {
-            Ok(handle) => log::trace!("  Type 1 (System Info) - Handle 0x{:04X}", handle),
-            Err(e) => log::warn!("  Failed to add Type 1: {:?}", e),
+        let qemu_tables = qemu_tables();
+        let system_uuid =
+            uuid::resolve(fw_cfg_uuid(), &qemu_tables, || uuid::persisted_fallback(&self.runtime_services, hw::rdtsc));
+        if let Some(uuid) = system_uuid {
+            log::info!("System UUID: {uuid}");
         }
 
-        let enclosure_info = Type3SystemEnclosure {
-            header: SmbiosTableHeader::new(3, 0, SMBIOS_HANDLE_PI_RESERVED),
-            manufacturer: 1,
-            enclosure_type: 0x03, // Desktop
-            version: 2,
-            serial_number: 3,
-            asset_tag_number: 4,
-            bootup_state: BootUpState::Safe,
-            power_supply_state: PowerSupplyState::Safe,
-            thermal_state: ThermalState::Safe,
-            security_status: SecurityStatus::Unknown,
-            oem_defined: 0x00000000,
-            height: 0x00,
-            number_of_power_cords: 0x01,
-            contained_element_count: 0x00,
-            contained_element_record_length: 0x00,
-            string_pool: vec![
-                String::from("Example Corporation"),
-                String::from("Example Chassis v1.0"),
-                String::from("CHASSIS-99999"),
-                String::from("ASSET-CHASSIS-001"),
+        let previous_boot = boot_status::track_boot(&self.boot_services, &self.runtime_services);
+        log::info!("Previous boot status: {previous_boot:#04X}");
+
+        let processor = ProcessorInfo::read();
+        let sockets = socket_count(&processor);
+        log::info!(
+            "Processor: {} socket(s), {} core(s) and {} thread(s) per socket",
+            sockets,
+            processor.topology.cores_per_package(),
+            processor.topology.logical_per_package
+        );
+
+        let memory = SmbiosMemoryMap::from_configuration_table(&self.boot_services);
+
+        let builder =
+            SmbiosPlatformBuilder::new(&self.smbios, config).with_qemu_tables(&qemu_tables).with_uuid(system_uuid);
+        add_records(&builder, &processor, sockets, &memory, &root_bus(), previous_boot)?;
+
+        // Type 127 End-of-Table marker is automatically added by the manager during initialization
+        builder.publish(&self.boot_services)
+    }
+}
+
+/// Adds every platform record, in table order, from the processor, memory and PCI root bus of the machine.
+///
+/// ## Errors
+///
+/// - The error returned by the SMBIOS service if the required Type 0 or Type 1 record could not be added.
+fn add_records(
+    builder: &SmbiosPlatformBuilder,
+    processor: &ProcessorInfo,
+    sockets: u8,
+    memory: &SmbiosMemoryMap,
+    bus: &PciRootBus,
+    previous_boot: u8,
+) -> Result<()> {
+    let handles = builder.add_system_records()?;
+    for socket in 0..sockets {
+        add_processor_records(builder, processor, socket);
+    }
+
+    builder.add_memory_records(memory);
+    builder.add_oem_records();
+    builder.add_boot_information(previous_boot);
+    builder.add_firmware_inventory(handles.firmware);
+    builder.add_pci_records(bus);
+    builder.add_qemu_records();
+    Ok(())
+}
+
+/// Reads the SMBIOS tables QEMU provides through fw_cfg. Returns empty tables if fw_cfg is not available.
+fn qemu_tables() -> QemuSmbiosTables {
+    let Some(fw_cfg) = FwCfg::new() else {
+        return QemuSmbiosTables::default();
+    };
+    let read = |name| fw_cfg.find_file(name).map(|file| fw_cfg.read_file(&file)).unwrap_or_default();
+    QemuSmbiosTables::parse(&read(qemu::ANCHOR_FILE), &read(qemu::TABLES_FILE))
+}
+
+/// Reads the UUID QEMU was started with from fw_cfg. Returns all zeros if fw_cfg is not available or QEMU has none.
+fn fw_cfg_uuid() -> [u8; 16] {
+    let mut uuid = [0u8; 16];
+    if let Some(fw_cfg) = FwCfg::new() {
+        fw_cfg.read_item(fw_cfg::KEY_UUID, &mut uuid);
+    }
+    uuid
+}
+
+/// Enumerates the PCI root bus through the PCI Express configuration space.
+fn root_bus() -> PciRootBus {
+    PciRootBus::scan(|device, function, offset| {
+        let address = register::PCI_EXPRESS_BASE_ADDRESS as usize
+            + patina::pci_address!(pci::ROOT_BUS as u32, device as u32, function as u32, offset as u32) as usize;
+        // SAFETY: The PCI Express configuration space of the root bus is always mapped through MMIO on Q35.
+        unsafe { hw::read_volatile(address as *const u32) }
+    })
+}
+
+/// Returns the number of populated sockets, from the boot CPU count in fw_cfg and the logical processors per package.
+fn socket_count(processor: &ProcessorInfo) -> u8 {
+    let Some(fw_cfg) = FwCfg::new() else {
+        log::warn!("fw_cfg is not available, assuming a single socket");
+        return 1;
+    };
+
+    let mut cpus = [0u8; 2];
+    fw_cfg.read_item(fw_cfg::KEY_NB_CPUS, &mut cpus);
+    let cpus = u16::from_le_bytes(cpus) as u32;
+    cpus.div_ceil(processor.topology.logical_per_package).clamp(1, u8::MAX as u32) as u8
+}
+
+/// Adds the Type 7 records of a socket followed by its Type 4 record.
+fn add_processor_records(builder: &SmbiosPlatformBuilder, processor: &ProcessorInfo, socket: u8) {
+    let caches: Vec<_> =
+        processor.caches.iter().map(|cache| smbios_cache(cache, processor.topology.logical_per_package)).collect();
+    let cache_handles = builder.add_cache_records(socket, &caches);
+
+    let record = processor_information(builder, processor, socket, cache_handles);
+    builder.add_record(&format!("Type 4 (CPU {socket})"), &record);
+}
+
+/// Describes all instances of a cache in one socket.
+fn smbios_cache(cache: &CacheInfo, logical_per_package: u32) -> SmbiosCache {
+    SmbiosCache {
+        level: cache.level,
+        cache_type: match cache.cache_type {
+            CacheType::Data => SystemCacheType::Data,
+            CacheType::Instruction => SystemCacheType::Instruction,
+            CacheType::Unified => SystemCacheType::Unified,
+        },
+        size: cache.size * cache.instances_per_package(logical_per_package) as u64,
+        associativity: match cache.fully_associative {
+            true => AssociativityField::FullyAssociative,
+            false => SmbiosCache::set_associativity(cache.ways),
+        },
+    }
+}
+
+/// Returns the Type 4 Processor Information record of a socket, linked to its L1, L2 and L3 cache records.
+///
+/// The strings QEMU sets for the socket, with `-smbios type=4`, override the ones read from CPUID.
+fn processor_information(
+    builder: &SmbiosPlatformBuilder,
+    processor: &ProcessorInfo,
+    socket: u8,
+    [l1_cache_handle, l2_cache_handle, l3_cache_handle]: [SmbiosHandle; 3],
+) -> Type4ProcessorInformation {
+    let mut strings = StringPool::new();
+    let manufacturer = match processor.vendor() {
+        Vendor::Intel => "Intel(R) Corporation",
+        Vendor::Amd => "Advanced Micro Devices, Inc.",
+        Vendor::Other => processor.vendor_id.trim(),
+    };
+    let socket_designation = format!("CPU {socket}");
+    let qemu_string = |offset, default| builder.qemu_instance_string(4, socket as usize, offset, default);
+    let topology = processor.topology;
+    let cores = topology.cores_per_package();
+    let threads = topology.logical_per_package;
+
+    Type4ProcessorInformation {
+        header: SmbiosTableHeader::new(4, 0, SMBIOS_HANDLE_PI_RESERVED),
+        socket_designation: strings.add(qemu_string(0x04, &socket_designation)),
+        processor_type: ProcessorTypeData::CentralProcessor,
+        // QEMU does not model a specific processor family
+        processor_family: 0x01,
+        processor_manufacturer: strings.add(qemu_string(0x07, manufacturer)),
+        processor_id: processor.processor_id(),
+        processor_version: strings.add(qemu_string(0x10, processor.brand.as_deref().unwrap_or_default())),
+        voltage: ProcessorVoltage::new(),
+        external_clock: 0, // Unknown
+        max_speed: processor.max_frequency,
+        current_speed: processor.base_frequency,
+        status: ProcessorInformationStatus::new().with_cpu_status(1).with_cpu_socket_populated(true),
+        processor_upgrade: ProcessorUpgrade::Other,
+        l1_cache_handle,
+        l2_cache_handle,
+        l3_cache_handle,
+        serial_number: strings.add(qemu_string(0x20, "")),
+        asset_tag: strings.add(qemu_string(0x21, "")),
+        part_number: strings.add(qemu_string(0x22, "")),
+        core_count: cores.min(0xFF) as u8,
+        core_enabled: cores.min(0xFF) as u8,
+        thread_count: threads.min(0xFF) as u8,
+        processor_characteristics: ProcessorCharacteristics::new()
+            .with_capable_64bit(processor.long_mode())
+            .with_multi_core(cores > 1)
+            .with_hardware_thread(topology.threads_per_core > 1)
+            .with_execute_protection(processor.execute_protection())
+            .with_enhanced_virtualization(processor.virtualization()),
+        processor_family2: ProcessorFamilyData::Other,
+        core_count2: cores.min(0xFFFF) as u16,
+        core_enabled2: cores.min(0xFFFF) as u16,
+        thread_count2: threads.min(0xFFFF) as u16,
+        thread_enabled: threads.min(0xFFFF) as u16,
+        socket_type: 0,
+        string_pool: strings.into_strings(),
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use std::{string::String, vec};
+
+    use super::*;
+    use crate::{
+        q35::{cpuid::Topology, sim},
+        smbios::{
+            SMBIOS_HANDLE_NONE, SystemUuid,
+            golden::{self, RecordingSmbios},
+            qemu::tests::{anchor_64, structure},
+        },
+    };
+
+    fn cache(level: u8, cache_type: CacheType, size: u64, ways: u32, sharing: u32) -> CacheInfo {
+        CacheInfo { level, cache_type, size, ways, fully_associative: false, sharing }
+    }
+
+    fn processor(threads_per_core: u32, logical_per_package: u32) -> ProcessorInfo {
+        ProcessorInfo {
+            vendor_id: String::from("GenuineIntel"),
+            brand: Some(String::from("Test CPU")),
+            signature: 0x000A_06A4,
+            features_edx: 0x1FAB_FBFF,
+            features_ecx: 0,
+            extended_features_edx: 1 << 29,
+            extended_features_ecx: 0,
+            base_frequency: 2000,
+            max_frequency: 3000,
+            topology: Topology { threads_per_core, logical_per_package },
+            caches: vec![
+                cache(1, CacheType::Data, 48 * 1024, 12, 2),
+                cache(1, CacheType::Instruction, 32 * 1024, 8, 2),
+                cache(2, CacheType::Unified, 2048 * 1024, 16, 2),
+                cache(3, CacheType::Unified, 64 * 1024 * 1024, 15, logical_per_package),
             ],
+        }
+    }
+
+    #[test]
+    fn test_smbios_cache_covers_the_socket() {
+        let processor = processor(2, 8);
+
+        let l1d = smbios_cache(&processor.caches[0], 8);
+        assert_eq!((l1d.level, l1d.size), (1, 4 * 48 * 1024));
+        assert!(matches!(l1d.cache_type, SystemCacheType::Data));
+        assert!(matches!(l1d.associativity, AssociativityField::SetAssociative12Way));
+
+        let l3 = smbios_cache(&processor.caches[3], 8);
+        assert_eq!((l3.level, l3.size), (3, 64 * 1024 * 1024));
+        assert!(matches!(l3.cache_type, SystemCacheType::Unified));
+
+        let fully_associative = CacheInfo { fully_associative: true, ..processor.caches[1] };
+        assert!(matches!(smbios_cache(&fully_associative, 8).associativity, AssociativityField::FullyAssociative));
+    }
+
+    #[test]
+    fn test_processor_information_links_caches() {
+        let processor = processor(2, 8);
+        let smbios = RecordingSmbios::new().into_service();
+        let config = SmbiosPlatformConfig::default();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+
+        let record = processor_information(&builder, &processor, 1, [0x10, 0x11, SMBIOS_HANDLE_NONE]);
+        assert_eq!((record.l1_cache_handle, record.l2_cache_handle, record.l3_cache_handle), (0x10, 0x11, 0xFFFF));
+        assert_eq!(record.processor_id, [0xA4, 0x06, 0x0A, 0x00, 0xFF, 0xFB, 0xAB, 0x1F]);
+        assert_eq!((record.core_count, record.thread_count), (4, 8));
+        assert_eq!((record.core_count2, record.thread_count2), (4, 8));
+        assert_eq!((record.current_speed, record.max_speed), (2000, 3000));
+        assert!(record.processor_characteristics.capable_64bit());
+        assert!(record.processor_characteristics.multi_core());
+        assert!(record.processor_characteristics.hardware_thread());
+        assert!(!record.processor_characteristics.execute_protection());
+        assert_eq!(
+            record.string_pool,
+            vec![String::from("CPU 1"), String::from("Intel(R) Corporation"), String::from("Test CPU")]
+        );
+    }
+
+    #[test]
+    fn test_processor_information_without_brand_string() {
+        let mut processor = processor(1, 1);
+        processor.vendor_id = String::from("QEMUVirtCPU ");
+        processor.brand = None;
+        let smbios = RecordingSmbios::new().into_service();
+        let config = SmbiosPlatformConfig::default();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+
+        let record = processor_information(&builder, &processor, 0, [SMBIOS_HANDLE_NONE; 3]);
+        assert_eq!(record.processor_version, 0);
+        assert!(!record.processor_characteristics.multi_core());
+        let strings: Vec<_> = record.string_pool.iter().map(String::as_str).collect();
+        assert_eq!(strings, ["CPU 0", "QEMUVirtCPU"]);
+    }
+
+    #[test]
+    fn test_processor_information_merges_qemu_strings() {
+        // QEMU builds one Type 4 structure per socket from `-smbios type=4`
+        let type4 = |designation| {
+            let mut formatted = [0u8; 0x26];
+            formatted[0x00] = 1;
+            formatted[0x03] = 2;
+            formatted[0x0C] = 3;
+            formatted[0x1C..0x1F].copy_from_slice(&[4, 5, 6]);
+            structure(4, 0x400, &formatted, &[designation, "QEMU", "pc-q35", "CPU-SN", "CPU-ASSET", "CPU-PN"])
         };
+        let tables = [type4("Socket A"), type4("Socket B")].concat();
+        let tables = QemuSmbiosTables::parse(&anchor_64(tables.len() as u32), &tables);
+        let smbios = RecordingSmbios::new().into_service();
+        let config = SmbiosPlatformConfig::default();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config).with_qemu_tables(&tables);
 
-        let mut type3_handle = 0xFFFF;
-        match smbios.add_record(None, &enclosure_info) {
-            Ok(handle) => {
-                log::trace!("  Type 3 (System Enclosure) - Handle 0x{:04X}", handle);
-                type3_handle = handle;
-            }
-            Err(e) => log::warn!("  Failed to add Type 3: {:?}", e),
-        }
+        let record = processor_information(&builder, &processor(2, 8), 1, [SMBIOS_HANDLE_NONE; 3]);
+        assert_eq!(record.string_pool, ["Socket B", "QEMU", "pc-q35", "CPU-SN", "CPU-ASSET", "CPU-PN"]);
+        assert_eq!((record.serial_number, record.asset_tag, record.part_number), (4, 5, 6));
 
-        let baseboard_info = Type2BaseboardInformation {
-            header: SmbiosTableHeader::new(2, 0, SMBIOS_HANDLE_PI_RESERVED),
-            manufacturer: 1,
-            product: 2,
-            version: 3,
-            serial_number: 4,
-            asset_tag: 5,
-            feature_flags: FeatureFlags::new().with_hosting_board(true),
-            location_in_chassis: 6,
-            chassis_handle: type3_handle,
-            board_type: BoardType::Motherboard,
-            contained_object_handles: 0,
-            string_pool: vec![
-                String::from("Example Corporation"),
-                String::from("Example Baseboard"),
-                String::from("1.0"),
-                String::from("MB-67890"),
-                String::from("ASSET-MB-001"),
-                String::from("Main Board Slot"),
-            ],
+        // A socket QEMU has no structure for keeps the processor strings
+        let record = processor_information(&builder, &processor(2, 8), 2, [SMBIOS_HANDLE_NONE; 3]);
+        assert_eq!(record.string_pool, ["CPU 2", "Intel(R) Corporation", "Test CPU"]);
+    }
+
+    #[test]
+    fn test_socket_count_from_fw_cfg() {
+        let processor = processor(2, 8);
+
+        sim::reset();
+        assert_eq!(socket_count(&processor), 1);
+
+        sim::with_chipset(|chipset| {
+            chipset.enable_fw_cfg(false);
+            chipset.set_fw_cfg_item(fw_cfg::KEY_NB_CPUS, &16u16.to_le_bytes());
+        });
+        assert_eq!(socket_count(&processor), 2);
+
+        sim::with_chipset(|chipset| chipset.set_fw_cfg_item(fw_cfg::KEY_NB_CPUS, &12u16.to_le_bytes()));
+        assert_eq!(socket_count(&processor), 2);
+    }
+
+    #[test]
+    fn test_fw_cfg_uuid() {
+        let uuid = [0x5A; 16];
+
+        sim::reset();
+        assert_eq!(fw_cfg_uuid(), [0; 16]);
+
+        sim::with_chipset(|chipset| {
+            chipset.enable_fw_cfg(false);
+            chipset.set_fw_cfg_item(fw_cfg::KEY_UUID, &uuid);
+        });
+        assert_eq!(fw_cfg_uuid(), uuid);
+    }
+
+    #[test]
+    fn test_root_bus_from_pci_express_configuration_space() {
+        let write = |device: u32, offset: u32, value: u32| {
+            let address =
+                register::PCI_EXPRESS_BASE_ADDRESS as usize + patina::pci_address!(0, device, 0, offset) as usize;
+            // SAFETY: The simulated chipset backs every address.
+            unsafe { hw::write_volatile(address as *mut u32, value) };
         };
 
-        match smbios.add_record(None, &baseboard_info) {
-            Ok(handle) => log::trace!("  Type 2 (Base Board Info) - Handle 0x{:04X}", handle),
-            Err(e) => log::warn!("  Failed to add Type 2: {:?}", e),
-        }
+        sim::reset();
+        write(0, 0x00, 0x29C0_8086);
+        write(0, 0x08, 0x0600_0000);
+        write(2, 0x00, 0x10D3_8086);
+        write(2, 0x08, 0x0200_0000);
+        write(0x1F, 0x00, 0x2918_8086);
+        write(0x1F, 0x08, 0x0601_0002);
 
-        // Type 127 End-of-Table marker is automatically added by the manager during initialization
-        log::trace!("Platform SMBIOS records created successfully");
-
-        // Publish the SMBIOS table
-        // This makes the table available to the OS via UEFI Configuration Table
-        log::debug!("Publishing SMBIOS table to Configuration Table...");
-        match smbios.publish_table() {
-            Ok((table_addr, entry_point_addr)) => {
-                log::debug!("SMBIOS table published successfully");
-                log::debug!("  Entry Point: 0x{:X}", entry_point_addr);
-                log::debug!("  Table Data: 0x{:X}", table_addr);
-                log::debug!("Use 'smbiosview' in UEFI Shell to view records");
-            }
-            Err(e) => {
-                log::error!("Failed to publish SMBIOS table: {:?}", e);
-                // Continue even if publication fails - this is not critical
-            }
-        }
+        let bus = root_bus();
+        let functions: Vec<_> = bus.functions().iter().map(|f| (f.device, f.device_id, f.base_class)).collect();
+        assert_eq!(functions, [(0, 0x29C0, 0x06), (2, 0x10D3, 0x02), (0x1F, 0x2918, 0x06)]);
+    }
+
+    #[test]
+    fn test_records_match_golden_dump() {
+        // QEMU provides the firmware strings, and an OEM string it adds as its own Type 11 record
+        let type0 = structure(
+            0,
+            0,
+            &[1, 2, 0, 0xE8, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            &["EFI Development Kit II", "edk2-stable", "02/01/2020"],
+        );
+        let type11 = structure(11, 0x1100, &[1], &["QEMU OEM"]);
+        // The DIMM serial and part numbers QEMU sets are merged into the memory device record
+        let mut type17 = [0u8; 0x24];
+        type17[0x0C] = 1;
+        type17[0x14] = 2;
+        type17[0x16] = 3;
+        let type17 = structure(17, 0x1700, &type17, &["DIMM A", "DIMM-SN", "DIMM-PN"]);
+        // QEMU Type 20 and Type 32 structures are replaced by the platform records
+        let type20 = structure(20, 0x1400, &[0; 0x0F], &[]);
+        let type32 = structure(32, 0x2000, &[0, 0, 0, 0, 0, 0, 8], &[]);
+        let tables = [type0, type11, type17, type20, type32].concat();
+        let tables = QemuSmbiosTables::parse(&anchor_64(tables.len() as u32), &tables);
+
+        let mut pci = crate::smbios::pci::tests::FakeConfigSpace::default();
+        pci.add_function(0, 0, 0x06_00_00);
+        pci.add_function(1, 0, 0x03_00_00);
+        pci.add_function(2, 0, 0x02_00_00);
+        pci.add_root_port(3, 0, (3, 4), 1, true);
+        pci.add_function(4, 0, 0x01_06_01);
+
+        let config = SmbiosPlatformConfig {
+            product_name: "Q35 Golden",
+            serial_number: "SN-Q35",
+            oem_strings: &["OEM-1"],
+            configuration_options: &["CONFIG-1"],
+            ..Default::default()
+        };
+        let memory = SmbiosMemoryMap::new([0..0x8000_0000, 0x1_0000_0000..0x1_8000_0000]);
+        let smbios = RecordingSmbios::new();
+        let records = smbios.records();
+        let smbios = smbios.into_service();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config)
+            .with_qemu_tables(&tables)
+            .with_uuid(SystemUuid::from_bytes([0x5A; 16]));
+
+        add_records(&builder, &processor(2, 8), 2, &memory, &pci.scan(), boot_status::NO_ERRORS).unwrap();
 
-        log::debug!("SMBIOS platform component initialized successfully");
-        Ok(())
+        golden::assert_golden(
+            "src/q35/component/service/testdata/smbios_platform.golden",
+            &golden::dump(&records.borrow()),
+        );
     }
 }
@@ -0,0 +1,248 @@
+//! QEMU Q35 SMI Feature Negotiation
+//!
+//! Negotiates the SMI features requested by the platform with QEMU through fw_cfg and records the result in the
+//! `SmiFeatureConfiguration`.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg(any(test, all(target_os = "uefi", target_arch = "x86_64", feature = "x64")))]
+
+use patina::{
+    component::{
+        component,
+        params::{Config, ConfigMut},
+    },
+    error::EfiError,
+};
+use patina_mm::config::MmCommunicationConfiguration;
+
+use crate::q35::{
+    fw_cfg::FwCfg,
+    smi_features::{
+        FEATURES_OK_FILE, REQUESTED_FEATURES_FILE, SUPPORTED_FEATURES_FILE, SmiFeatureConfiguration, SmiFeatures,
+    },
+};
+
+/// The QEMU Q35 SMI feature negotiation component.
+///
+/// Negotiates SMI features with QEMU once the MM configuration provider has locked the `MmCommunicationConfiguration`,
+/// so the negotiated features are in effect before `PlatformMmControl::init` enables MMIs.
+#[derive(Default)]
+pub struct QemuQ35SmiFeatureNegotiation;
+
+#[component]
+impl QemuQ35SmiFeatureNegotiation {
+    /// Creates a new instance of the QEMU Q35 SMI feature negotiation component.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Entry point for the QEMU Q35 SMI feature negotiation component.
+    ///
+    /// Depends on the locked `MmCommunicationConfiguration` and a mutable `SmiFeatureConfiguration`. The result of the
+    /// negotiation is recorded in the `SmiFeatureConfiguration`, which is locked once negotiation completes to allow
+    /// components that depend on the outcome to be dispatched.
+    ///
+    /// Negotiation is skipped if the configuration has no communicate buffers, which are only added by the MM
+    /// configuration provider.
+    pub fn entry_point(
+        self,
+        mm_config: Config<MmCommunicationConfiguration>,
+        mut smi_features: ConfigMut<SmiFeatureConfiguration>,
+    ) -> patina::error::Result<()> {
+        log::debug!("SMI Feature Negotiation Entry Point");
+
+        if mm_config.comm_buffers.is_empty() {
+            log::info!("MM is not configured. SMI features not negotiated.");
+            smi_features.lock();
+            return Ok(());
+        }
+
+        Self::negotiate(&mut smi_features)?;
+        smi_features.lock();
+
+        Ok(())
+    }
+
+    /// Negotiates the requested SMI features with QEMU through fw_cfg.
+    ///
+    /// QEMU versions without SMI feature negotiation leave the configuration with no supported or negotiated
+    /// features. In that case SMIs are only delivered to the vCPU that raised them.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::Unsupported` if QEMU rejects the requested features.
+    /// - Any error returned while writing the requested features to fw_cfg.
+    fn negotiate(config: &mut SmiFeatureConfiguration) -> patina::error::Result<()> {
+        let Some(fw_cfg) = FwCfg::new() else {
+            log::warn!("fw_cfg is not present. SMI features not negotiated.");
+            return Ok(());
+        };
+
+        let (Some(supported_file), Some(requested_file), Some(features_ok_file)) = (
+            fw_cfg.find_file(SUPPORTED_FEATURES_FILE),
+            fw_cfg.find_file(REQUESTED_FEATURES_FILE),
+            fw_cfg.find_file(FEATURES_OK_FILE),
+        ) else {
+            log::info!("QEMU does not support SMI feature negotiation. SMIs are not broadcast.");
+            return Ok(());
+        };
+
+        let mut supported = [0u8; 8];
+        fw_cfg.read_item(supported_file.select, &mut supported);
+        config.supported = SmiFeatures::from_bits(u64::from_le_bytes(supported));
+
+        let requested = config.requested.intersection(config.supported);
+        if requested != config.requested {
+            log::warn!("Requested SMI features [{}] not supported by QEMU [{}]", config.requested, config.supported);
+        }
+
+        fw_cfg.write_file(&requested_file, &requested.bits().to_le_bytes())?;
+
+        // Selecting the features-ok file makes QEMU validate and lock the requested features.
+        let mut features_ok = [0u8; 1];
+        fw_cfg.read_item(features_ok_file.select, &mut features_ok);
+        if features_ok[0] != 1 {
+            log::error!("QEMU rejected the requested SMI features [{requested}]");
+            return Err(EfiError::Unsupported);
+        }
+
+        config.negotiated = requested;
+        log::info!("Negotiated SMI features: [{}] (supported: [{}])", config.negotiated, config.supported);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+
+    use patina::component::Storage;
+
+    use super::*;
+    use crate::{
+        q35::{
+            component::service::mm_config_provider::MmConfigurationProvider,
+            sim::{self, FakeHobList},
+        },
+        sim::run_component,
+    };
+
+    fn storage(smi_features: SmiFeatureConfiguration) -> Storage {
+        sim::reset();
+        let mut storage = Storage::new();
+        FakeHobList::new().with_comm_region(0, 1).install(&mut storage);
+        storage.add_config(smi_features);
+        assert_eq!(run_component(MmConfigurationProvider, &mut storage), Ok(true));
+        storage
+    }
+
+    fn smi_features(storage: &Storage) -> SmiFeatureConfiguration {
+        *storage.get_config::<SmiFeatureConfiguration>().unwrap()
+    }
+
+    #[test]
+    fn test_entry_point_without_fw_cfg() {
+        let mut storage = storage(SmiFeatureConfiguration::default());
+
+        assert_eq!(run_component(QemuQ35SmiFeatureNegotiation::new(), &mut storage), Ok(true));
+
+        let features = smi_features(&storage);
+        assert_eq!(features.supported, SmiFeatures::NONE);
+        assert_eq!(features.negotiated, SmiFeatures::NONE);
+    }
+
+    #[test]
+    fn test_entry_point_without_feature_negotiation() {
+        let mut storage = storage(SmiFeatureConfiguration::default());
+        sim::with_chipset(|chipset| chipset.enable_fw_cfg(true));
+
+        assert_eq!(run_component(QemuQ35SmiFeatureNegotiation::new(), &mut storage), Ok(true));
+
+        assert_eq!(smi_features(&storage).negotiated, SmiFeatures::NONE);
+    }
+
+    #[test]
+    fn test_entry_point_negotiates_features() {
+        let mut storage = storage(SmiFeatureConfiguration::default());
+        sim::with_chipset(|chipset| {
+            chipset.enable_fw_cfg(true);
+            chipset.add_smi_feature_files(SmiFeatures::BROADCAST.bits() | SmiFeatures::CPU_HOTPLUG.bits());
+        });
+
+        assert_eq!(run_component(QemuQ35SmiFeatureNegotiation::new(), &mut storage), Ok(true));
+
+        let features = smi_features(&storage);
+        assert!(features.supported.contains(SmiFeatures::CPU_HOTPLUG));
+        assert_eq!(features.negotiated, SmiFeatures::BROADCAST);
+        sim::with_chipset(|chipset| {
+            assert_eq!(
+                chipset.fw_cfg_file(REQUESTED_FEATURES_FILE),
+                Some(SmiFeatures::BROADCAST.bits().to_le_bytes().to_vec())
+            );
+            assert_eq!(chipset.fw_cfg_file(FEATURES_OK_FILE), Some(std::vec![1]));
+        });
+    }
+
+    #[test]
+    fn test_entry_point_drops_unsupported_features() {
+        let mut storage = storage(SmiFeatureConfiguration {
+            requested: SmiFeatures::from_bits(SmiFeatures::BROADCAST.bits() | SmiFeatures::CPU_HOT_UNPLUG.bits()),
+            ..Default::default()
+        });
+        sim::with_chipset(|chipset| {
+            chipset.enable_fw_cfg(true);
+            chipset.add_smi_feature_files(SmiFeatures::BROADCAST.bits());
+        });
+
+        assert_eq!(run_component(QemuQ35SmiFeatureNegotiation::new(), &mut storage), Ok(true));
+
+        assert_eq!(smi_features(&storage).negotiated, SmiFeatures::BROADCAST);
+    }
+
+    #[test]
+    fn test_entry_point_fails_when_features_rejected() {
+        let mut storage = storage(SmiFeatureConfiguration::default());
+        sim::with_chipset(|chipset| {
+            chipset.enable_fw_cfg(true);
+            chipset.add_smi_feature_files(SmiFeatures::BROADCAST.bits());
+            chipset.reject_smi_features();
+        });
+
+        assert_eq!(run_component(QemuQ35SmiFeatureNegotiation::new(), &mut storage), Err(EfiError::Unsupported));
+    }
+
+    #[test]
+    fn test_entry_point_fails_without_fw_cfg_dma() {
+        let mut storage = storage(SmiFeatureConfiguration::default());
+        sim::with_chipset(|chipset| {
+            chipset.enable_fw_cfg(false);
+            chipset.add_smi_feature_files(SmiFeatures::BROADCAST.bits());
+        });
+
+        assert_eq!(run_component(QemuQ35SmiFeatureNegotiation::new(), &mut storage), Err(EfiError::Unsupported));
+    }
+
+    #[test]
+    fn test_entry_point_skips_unconfigured_mm() {
+        sim::reset();
+        sim::with_chipset(|chipset| {
+            chipset.enable_fw_cfg(true);
+            chipset.add_smi_feature_files(SmiFeatures::BROADCAST.bits());
+        });
+        let mut storage = Storage::new();
+        storage.add_config(MmCommunicationConfiguration::default());
+        storage.add_config(SmiFeatureConfiguration::default());
+
+        assert_eq!(run_component(QemuQ35SmiFeatureNegotiation::new(), &mut storage), Ok(true));
+
+        assert_eq!(smi_features(&storage).supported, SmiFeatures::NONE);
+        sim::with_chipset(|chipset| assert_ne!(chipset.fw_cfg_file(FEATURES_OK_FILE), Some(std::vec![1])));
+    }
+}
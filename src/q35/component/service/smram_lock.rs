@@ -0,0 +1,207 @@
+//! QEMU Q35 SMRAM Lock Component
+//!
+//! Closes and locks SMRAM and TSEG on the QEMU Q35 platform before third-party code runs.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::boxed::Box;
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::component,
+    error::EfiError,
+    guids::{EVENT_GROUP_END_OF_DXE, EVENT_READY_TO_BOOT},
+};
+
+use crate::q35::{hw, registers as register};
+
+/// QEMU Q35 SMRAM Lock Component
+///
+/// Registers for the EndOfDxe event group to close the SMRAM window and set the SMRAMC D_LCK bit. Once D_LCK is set,
+/// the SMRAMC and ESMRAMC registers are read-only until the next platform reset. ReadyToBoot is also registered as a
+/// backstop in case EndOfDxe is never signaled.
+#[derive(Default)]
+pub struct QemuQ35SmramLock;
+
+#[component]
+impl QemuQ35SmramLock {
+    /// Creates a new instance of the QEMU Q35 SMRAM Lock component.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Entry point for the SMRAM Lock component.
+    ///
+    /// Registers the EndOfDxe and ReadyToBoot event notifications that perform the SMRAM lock.
+    pub fn entry_point(self, boot_services: StandardBootServices) -> patina::error::Result<()> {
+        log::debug!("SMRAM Lock Entry Point");
+
+        boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(lock_smram_callback),
+            Box::new(boot_services.clone()),
+            &EVENT_GROUP_END_OF_DXE,
+        )?;
+
+        boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(lock_smram_callback),
+            Box::new(boot_services.clone()),
+            &EVENT_READY_TO_BOOT,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Event notification that locks SMRAM and closes its event.
+extern "efiapi" fn lock_smram_callback(event: r_efi::efi::Event, boot_services: Box<StandardBootServices>) {
+    let _ = boot_services.close_event(event);
+
+    if let Err(e) = lock_smram() {
+        log::error!("Failed to lock SMRAM: {e:?}");
+    }
+}
+
+/// Returns a pointer to an 8-bit Q35 MCH register in PCI Express configuration space.
+pub(crate) fn mch_register(offset: u32) -> *mut u8 {
+    (register::PCI_EXPRESS_BASE_ADDRESS as usize + patina::pci_address!(0, 0, 0, offset) as usize) as *mut u8
+}
+
+/// Reads the current `(SMRAMC, ESMRAMC)` register values.
+pub(crate) fn read_smram_registers() -> (u8, u8) {
+    // SAFETY: SMRAMC and ESMRAMC are 8-bit registers in the MCH configuration space which is always mapped through
+    // PCI Express MMIO on Q35.
+    unsafe {
+        (
+            hw::read_volatile(mch_register(register::mch::SMRAMC)),
+            hw::read_volatile(mch_register(register::mch::ESMRAMC)),
+        )
+    }
+}
+
+/// Returns `true` if the given register values describe SMRAM and TSEG as closed and locked.
+pub(crate) fn is_smram_locked(smramc: u8, esmramc: u8) -> bool {
+    smramc & register::mch::SMRAMC_D_LCK != 0
+        && smramc & register::mch::SMRAMC_D_OPEN == 0
+        && esmramc & register::mch::ESMRAMC_T_EN != 0
+}
+
+/// Closes SMRAM and sets D_LCK.
+///
+/// TSEG must already have been enabled by PEI. It is not enabled here, as that would remove the top of low memory from
+/// under allocations DXE has already made.
+///
+/// Safe to call more than once. If D_LCK is already set, no registers are written and the current state is verified.
+///
+/// ## Errors
+///
+/// - `EfiError::AccessDenied` if D_LCK is already set but SMRAM or TSEG is left open.
+/// - `EfiError::NotStarted` if PEI did not enable TSEG. Nothing is locked in this case.
+/// - `EfiError::DeviceError` if the lock did not take effect after the registers were written.
+pub fn lock_smram() -> patina::error::Result<()> {
+    let (smramc, esmramc) = read_smram_registers();
+    log::debug!("SMRAMC: {smramc:#04X}, ESMRAMC: {esmramc:#04X}");
+
+    if smramc & register::mch::SMRAMC_D_LCK != 0 {
+        if !is_smram_locked(smramc, esmramc) {
+            log::error!("SMRAM is locked open. SMRAMC: {smramc:#04X}, ESMRAMC: {esmramc:#04X}");
+            return Err(EfiError::AccessDenied);
+        }
+        log::debug!("SMRAM is already locked.");
+        return Ok(());
+    }
+
+    if esmramc & register::mch::ESMRAMC_T_EN == 0 {
+        log::error!("TSEG was not enabled before DXE. SMRAM not locked. ESMRAMC: {esmramc:#04X}");
+        return Err(EfiError::NotStarted);
+    }
+
+    // SAFETY: See `read_smram_registers()`.
+    unsafe {
+        // Close the legacy SMRAM window.
+        hw::write_volatile(mch_register(register::mch::SMRAMC), smramc & !register::mch::SMRAMC_D_OPEN);
+        // Lock SMRAMC and ESMRAMC until the next reset.
+        hw::write_volatile(
+            mch_register(register::mch::SMRAMC),
+            (smramc & !register::mch::SMRAMC_D_OPEN) | register::mch::SMRAMC_D_LCK,
+        );
+    }
+
+    let (smramc, esmramc) = read_smram_registers();
+    if !is_smram_locked(smramc, esmramc) {
+        log::error!("SMRAM lock did not take effect. SMRAMC: {smramc:#04X}, ESMRAMC: {esmramc:#04X}");
+        return Err(EfiError::DeviceError);
+    }
+
+    log::info!("SMRAM closed and locked. SMRAMC: {smramc:#04X}, ESMRAMC: {esmramc:#04X}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::q35::sim;
+
+    #[test]
+    fn test_lock_smram_closes_and_locks() {
+        sim::reset();
+        sim::with_chipset(|chipset| {
+            chipset.set_mch_register(register::mch::SMRAMC, register::mch::SMRAMC_D_OPEN | 0x02);
+            chipset.set_mch_register(register::mch::ESMRAMC, register::mch::ESMRAMC_T_EN | 0x04);
+        });
+
+        assert!(lock_smram().is_ok());
+        let (smramc, esmramc) = read_smram_registers();
+        assert_eq!(smramc, register::mch::SMRAMC_D_LCK | 0x02);
+        assert_eq!(esmramc, register::mch::ESMRAMC_T_EN | 0x04);
+        assert!(is_smram_locked(smramc, esmramc));
+
+        // A second signal finds the registers locked and leaves them unchanged
+        assert!(lock_smram().is_ok());
+        assert_eq!(read_smram_registers(), (smramc, esmramc));
+    }
+
+    #[test]
+    fn test_lock_smram_rejects_smram_locked_open() {
+        sim::reset();
+        sim::with_chipset(|chipset| {
+            chipset.set_mch_register(register::mch::SMRAMC, register::mch::SMRAMC_D_LCK | register::mch::SMRAMC_D_OPEN)
+        });
+
+        assert_eq!(lock_smram(), Err(EfiError::AccessDenied));
+    }
+
+    #[test]
+    fn test_lock_smram_skips_disabled_tseg() {
+        sim::reset();
+        sim::with_chipset(|chipset| {
+            chipset.set_mch_register(register::mch::SMRAMC, register::mch::SMRAMC_D_OPEN | 0x02);
+            chipset.set_mch_register(register::mch::ESMRAMC, 0x04);
+        });
+
+        assert_eq!(lock_smram(), Err(EfiError::NotStarted));
+        assert_eq!(read_smram_registers(), (register::mch::SMRAMC_D_OPEN | 0x02, 0x04));
+    }
+
+    #[test]
+    fn test_lock_smram_detects_ignored_writes() {
+        sim::reset();
+        sim::with_chipset(|chipset| {
+            chipset.set_mch_register(register::mch::ESMRAMC, register::mch::ESMRAMC_T_EN);
+            chipset.make_smram_registers_read_only();
+        });
+
+        assert_eq!(lock_smram(), Err(EfiError::DeviceError));
+    }
+}
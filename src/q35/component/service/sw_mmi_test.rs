@@ -0,0 +1,82 @@
+//! QEMU Q35 Software MMI Test
+//!
+//! Verifies that the `SwMmiTrigger` service raises a software MMI through the APM ports on the QEMU Q35 platform and
+//! that the MM environment handles it and returns.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg(all(target_os = "uefi", target_arch = "x86_64", feature = "x64"))]
+
+use patina::component::{params::Config, service::Service};
+use patina_mm::{
+    config::{MmCommunicationConfiguration, MmiPort},
+    service::SwMmiTrigger,
+};
+use patina_test::{patina_test, u_assert, u_assert_eq};
+
+use crate::q35::{hw::Port, registers as register};
+
+/// Software MMI command value used by the test. No MM handler is registered for it, so the MM environment only
+/// acknowledges the MMI and returns.
+const TEST_SW_MMI_COMMAND: u8 = 0x5A;
+
+/// Data values written with each software MMI. Each call uses a different value so stale port values are detected.
+const TEST_SW_MMI_DATA: [u8; 3] = [0xA5, 0x3C, 0x00];
+
+/// Triggers software MMIs through the `SwMmiTrigger` service and checks the APM ports and SMI status afterwards.
+#[patina_test]
+fn q35_sw_mmi_round_trip_test(
+    sw_mmi: Service<dyn SwMmiTrigger>,
+    config: Config<MmCommunicationConfiguration>,
+) -> patina_test::error::Result {
+    log::debug!("SW MMI Test - Triggering software MMIs");
+
+    u_assert!(matches!(config.cmd_port, MmiPort::Smi(register::ich9::APM_CNT)), "MMI command port should be APM_CNT");
+    u_assert!(matches!(config.data_port, MmiPort::Smi(register::ich9::APM_STS)), "MMI data port should be APM_STS");
+
+    let pm_base = config.acpi_base.get_io_value();
+    u_assert!(pm_base != 0, "PMBASE I/O port should be configured");
+
+    let mut smi_en: Port<u32> = Port::new(pm_base + register::ich9::PMBASE_OFS_SMI_EN as u16);
+    let mut smi_sts: Port<u32> = Port::new(pm_base + register::ich9::PMBASE_OFS_SMI_STS as u16);
+    let mut apm_cnt: Port<u8> = Port::new(register::ich9::APM_CNT);
+    let mut apm_sts: Port<u8> = Port::new(register::ich9::APM_STS);
+
+    // SAFETY: PMBASE was discovered from the ICH9 LPC bridge and SMI_EN is a 32-bit register at a fixed offset.
+    let smi_enable = unsafe { smi_en.read() };
+    log::trace!("  SMI_EN: {smi_enable:#010X}");
+    u_assert_eq!(
+        smi_enable & (register::ich9::SMI_EN_APMC_EN | register::ich9::SMI_EN_GBL_SMI_EN),
+        register::ich9::SMI_EN_APMC_EN | register::ich9::SMI_EN_GBL_SMI_EN,
+        "APMC_EN and GBL_SMI_EN should be set"
+    );
+
+    for (call, data) in TEST_SW_MMI_DATA.into_iter().enumerate() {
+        log::trace!("  Call {call}: command {TEST_SW_MMI_COMMAND:#04X}, data {data:#04X}");
+
+        u_assert!(sw_mmi.trigger_sw_mmi(TEST_SW_MMI_COMMAND, data).is_ok(), "trigger_sw_mmi should succeed");
+
+        // SAFETY: The APM ports are always decoded on Q35, and SMI_STS is a 32-bit register at a fixed offset from
+        // PMBASE. Reading them has no side effects.
+        let (command, status, smi_status) = unsafe { (apm_cnt.read(), apm_sts.read(), smi_sts.read()) };
+        log::trace!("  APM_CNT: {command:#04X}, APM_STS: {status:#04X}, SMI_STS: {smi_status:#010X}");
+
+        u_assert_eq!(command, TEST_SW_MMI_COMMAND, "APM_CNT should hold the software MMI command");
+        u_assert_eq!(status, data, "APM_STS should hold the software MMI data");
+        u_assert_eq!(
+            smi_status & register::ich9::SMI_STS_APM_STS,
+            0,
+            "APM_STS in SMI_STS should be cleared by the MM environment"
+        );
+    }
+
+    // SAFETY: See above.
+    u_assert_eq!(unsafe { smi_en.read() }, smi_enable, "SMI_EN should not change across software MMIs");
+
+    log::debug!("SW MMI Test complete");
+    Ok(())
+}
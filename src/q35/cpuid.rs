@@ -0,0 +1,451 @@
+//! QEMU Q35 Processor Identification
+//!
+//! Decodes the CPUID leaves that describe the boot processor: the vendor and brand strings, the processor signature
+//! and feature flags, the deterministic cache parameters and the package topology. QEMU reports the same values on
+//! every vCPU, so the boot processor describes every socket.
+//!
+//! ## References
+//!
+//! - Intel 64 and IA-32 Architectures Software Developer's Manual, Volume 2A, CPUID
+//! - AMD64 Architecture Programmer's Manual, Volume 3, Appendix E
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg(any(test, all(target_os = "uefi", target_arch = "x86_64", feature = "x64")))]
+
+extern crate alloc;
+use alloc::{string::String, vec::Vec};
+
+use crate::q35::hw::cpuid;
+
+/// CPUID leaves used by this module.
+mod leaf {
+    pub const VENDOR: u32 = 0x0000_0000;
+    pub const FEATURES: u32 = 0x0000_0001;
+    pub const CACHE_PARAMETERS: u32 = 0x0000_0004;
+    pub const EXTENDED_TOPOLOGY: u32 = 0x0000_000B;
+    pub const FREQUENCY: u32 = 0x0000_0016;
+    pub const V2_EXTENDED_TOPOLOGY: u32 = 0x0000_001F;
+    pub const EXTENDED_MAX: u32 = 0x8000_0000;
+    pub const EXTENDED_FEATURES: u32 = 0x8000_0001;
+    pub const BRAND_STRING: u32 = 0x8000_0002;
+    pub const AMD_CACHE_PROPERTIES: u32 = 0x8000_001D;
+}
+
+/// Leaf 0x1 ECX: virtual machine extensions
+const FEATURES_ECX_VMX: u32 = 1 << 5;
+/// Leaf 0x1 EDX: multi-threading, EBX[23:16] holds the logical processor count
+const FEATURES_EDX_HTT: u32 = 1 << 28;
+/// Leaf 0x80000001 ECX: secure virtual machine
+const EXTENDED_FEATURES_ECX_SVM: u32 = 1 << 2;
+/// Leaf 0x80000001 ECX: topology extensions, leaf 0x8000001D is available
+const EXTENDED_FEATURES_ECX_TOPOEXT: u32 = 1 << 22;
+/// Leaf 0x80000001 EDX: no-execute page protection
+const EXTENDED_FEATURES_EDX_NX: u32 = 1 << 20;
+/// Leaf 0x80000001 EDX: long mode
+const EXTENDED_FEATURES_EDX_LM: u32 = 1 << 29;
+
+/// Topology level type reported in ECX[15:8] of leaves 0xB and 0x1F: the end of the list
+const TOPOLOGY_LEVEL_INVALID: u32 = 0;
+/// Topology level type reported in ECX[15:8] of leaves 0xB and 0x1F: SMT threads
+const TOPOLOGY_LEVEL_SMT: u32 = 1;
+
+/// Upper bound on the subleaves walked in the cache and topology leaves
+const MAX_SUBLEAVES: u32 = 16;
+
+/// Processor vendor, from the vendor identification string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vendor {
+    /// `GenuineIntel`
+    Intel,
+    /// `AuthenticAMD`
+    Amd,
+    /// Any other vendor
+    Other,
+}
+
+/// The kind of data a cache holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheType {
+    /// Data cache
+    Data,
+    /// Instruction cache
+    Instruction,
+    /// Unified cache
+    Unified,
+}
+
+/// A cache described by leaf 0x4 or 0x8000001D.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheInfo {
+    /// Cache level, starting at 1
+    pub level: u8,
+    /// Kind of data held by the cache
+    pub cache_type: CacheType,
+    /// Size of one instance of the cache in bytes
+    pub size: u64,
+    /// Number of ways of associativity
+    pub ways: u32,
+    /// Whether the cache is fully associative
+    pub fully_associative: bool,
+    /// Maximum number of logical processors sharing one instance of the cache
+    pub sharing: u32,
+}
+
+impl CacheInfo {
+    /// Decodes a cache parameter subleaf. Returns `None` for the null entry that ends the list.
+    fn decode([eax, ebx, ecx, _]: [u32; 4]) -> Option<Self> {
+        let cache_type = match eax & 0x1F {
+            1 => CacheType::Data,
+            2 => CacheType::Instruction,
+            3 => CacheType::Unified,
+            _ => return None,
+        };
+        let line_size = (ebx & 0xFFF) as u64 + 1;
+        let partitions = ((ebx >> 12) & 0x3FF) as u64 + 1;
+        let ways = (ebx >> 22) + 1;
+        let sets = ecx as u64 + 1;
+
+        Some(Self {
+            level: ((eax >> 5) & 0x7) as u8,
+            cache_type,
+            size: ways as u64 * partitions * line_size * sets,
+            ways,
+            fully_associative: eax & (1 << 9) != 0,
+            sharing: ((eax >> 14) & 0xFFF) + 1,
+        })
+    }
+
+    /// Returns the number of instances of the cache in a package with `logical_per_package` logical processors.
+    pub fn instances_per_package(&self, logical_per_package: u32) -> u32 {
+        logical_per_package.div_ceil(self.sharing).max(1)
+    }
+}
+
+/// Number of logical processors at each level of a processor package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Topology {
+    /// Logical processors per core
+    pub threads_per_core: u32,
+    /// Logical processors per package
+    pub logical_per_package: u32,
+}
+
+impl Topology {
+    /// Returns the number of cores per package.
+    pub fn cores_per_package(&self) -> u32 {
+        (self.logical_per_package / self.threads_per_core).max(1)
+    }
+}
+
+/// Identification of the processor executing this code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessorInfo {
+    /// Vendor identification string, such as `GenuineIntel`
+    pub vendor_id: String,
+    /// Processor brand string, if the processor reports one
+    pub brand: Option<String>,
+    /// Processor signature (family, model and stepping) from leaf 0x1 EAX
+    pub signature: u32,
+    /// Feature flags from leaf 0x1 EDX
+    pub features_edx: u32,
+    /// Feature flags from leaf 0x1 ECX
+    pub features_ecx: u32,
+    /// Extended feature flags from leaf 0x80000001 EDX
+    pub extended_features_edx: u32,
+    /// Extended feature flags from leaf 0x80000001 ECX
+    pub extended_features_ecx: u32,
+    /// Base frequency in MHz, or 0 if unknown
+    pub base_frequency: u16,
+    /// Maximum frequency in MHz, or 0 if unknown
+    pub max_frequency: u16,
+    /// Package topology
+    pub topology: Topology,
+    /// Caches of one core or package, from the lowest level up
+    pub caches: Vec<CacheInfo>,
+}
+
+impl ProcessorInfo {
+    /// Reads the processor identification from CPUID.
+    pub fn read() -> Self {
+        let [max_leaf, ebx, ecx, edx] = cpuid(leaf::VENDOR, 0);
+        let vendor_id = [ebx, edx, ecx].iter().flat_map(|r| r.to_le_bytes()).map(char::from).collect();
+        let [signature, features_ebx, features_ecx, features_edx] =
+            if max_leaf >= leaf::FEATURES { cpuid(leaf::FEATURES, 0) } else { [0; 4] };
+
+        let max_extended_leaf = cpuid(leaf::EXTENDED_MAX, 0)[0];
+        let [_, _, extended_features_ecx, extended_features_edx] =
+            if max_extended_leaf >= leaf::EXTENDED_FEATURES { cpuid(leaf::EXTENDED_FEATURES, 0) } else { [0; 4] };
+
+        let brand = (max_extended_leaf >= leaf::BRAND_STRING + 2).then(read_brand_string).flatten();
+
+        let (base_frequency, max_frequency) = if max_leaf >= leaf::FREQUENCY {
+            let [base, max, ..] = cpuid(leaf::FREQUENCY, 0);
+            (base as u16, max as u16)
+        } else {
+            (0, 0)
+        };
+
+        let mut info = Self {
+            vendor_id,
+            brand,
+            signature,
+            features_edx,
+            features_ecx,
+            extended_features_edx,
+            extended_features_ecx,
+            base_frequency,
+            max_frequency,
+            topology: Topology { threads_per_core: 1, logical_per_package: 1 },
+            caches: Vec::new(),
+        };
+
+        let cache_leaf = match info.vendor() {
+            Vendor::Amd if max_extended_leaf >= leaf::AMD_CACHE_PROPERTIES && info.topology_extensions() => {
+                Some(leaf::AMD_CACHE_PROPERTIES)
+            }
+            Vendor::Amd => None,
+            _ if max_leaf >= leaf::CACHE_PARAMETERS => Some(leaf::CACHE_PARAMETERS),
+            _ => None,
+        };
+        if let Some(cache_leaf) = cache_leaf {
+            info.caches =
+                (0..MAX_SUBLEAVES).map_while(|subleaf| CacheInfo::decode(cpuid(cache_leaf, subleaf))).collect();
+            info.caches.sort_by_key(|cache| (cache.level, cache.cache_type != CacheType::Data));
+        } else {
+            log::debug!("CPUID does not report deterministic cache parameters");
+        }
+
+        info.topology =
+            read_topology(max_leaf).unwrap_or_else(|| legacy_topology(features_ebx, features_edx, info.caches.first()));
+
+        info
+    }
+
+    /// Returns the processor vendor.
+    pub fn vendor(&self) -> Vendor {
+        match self.vendor_id.as_str() {
+            "GenuineIntel" => Vendor::Intel,
+            "AuthenticAMD" => Vendor::Amd,
+            _ => Vendor::Other,
+        }
+    }
+
+    /// Returns the processor ID as SMBIOS Type 4 reports it for x86: leaf 0x1 EAX followed by EDX.
+    pub fn processor_id(&self) -> [u8; 8] {
+        let mut id = [0u8; 8];
+        id[..4].copy_from_slice(&self.signature.to_le_bytes());
+        id[4..].copy_from_slice(&self.features_edx.to_le_bytes());
+        id
+    }
+
+    /// Returns whether the processor supports 64-bit long mode.
+    pub fn long_mode(&self) -> bool {
+        self.extended_features_edx & EXTENDED_FEATURES_EDX_LM != 0
+    }
+
+    /// Returns whether the processor supports no-execute page protection.
+    pub fn execute_protection(&self) -> bool {
+        self.extended_features_edx & EXTENDED_FEATURES_EDX_NX != 0
+    }
+
+    /// Returns whether the processor supports hardware virtualization (VMX or SVM).
+    pub fn virtualization(&self) -> bool {
+        self.features_ecx & FEATURES_ECX_VMX != 0 || self.extended_features_ecx & EXTENDED_FEATURES_ECX_SVM != 0
+    }
+
+    fn topology_extensions(&self) -> bool {
+        self.extended_features_ecx & EXTENDED_FEATURES_ECX_TOPOEXT != 0
+    }
+}
+
+/// Reads the 48-byte brand string from leaves 0x80000002 to 0x80000004. Returns `None` if it is blank.
+fn read_brand_string() -> Option<String> {
+    let bytes: Vec<u8> = (0..3).flat_map(|i| cpuid(leaf::BRAND_STRING + i, 0)).flat_map(u32::to_le_bytes).collect();
+    let bytes = &bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())];
+    let brand: String = bytes.iter().map(|&b| if b.is_ascii_graphic() { b as char } else { ' ' }).collect();
+    let brand = brand.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!brand.is_empty()).then_some(brand)
+}
+
+/// Reads the topology from leaf 0x1F, or from leaf 0xB if 0x1F is not implemented.
+fn read_topology(max_leaf: u32) -> Option<Topology> {
+    let topology_leaf = [leaf::V2_EXTENDED_TOPOLOGY, leaf::EXTENDED_TOPOLOGY]
+        .into_iter()
+        .find(|&topology_leaf| max_leaf >= topology_leaf && cpuid(topology_leaf, 0)[1] & 0xFFFF != 0)?;
+
+    let mut topology = Topology { threads_per_core: 1, logical_per_package: 1 };
+    for subleaf in 0..MAX_SUBLEAVES {
+        let [_, ebx, ecx, _] = cpuid(topology_leaf, subleaf);
+        let level_type = (ecx >> 8) & 0xFF;
+        if level_type == TOPOLOGY_LEVEL_INVALID {
+            break;
+        }
+        // Each level reports the logical processors it contains, so the outermost level covers the package
+        let logical = (ebx & 0xFFFF).max(1);
+        if level_type == TOPOLOGY_LEVEL_SMT {
+            topology.threads_per_core = logical;
+        }
+        topology.logical_per_package = logical;
+    }
+    Some(topology)
+}
+
+/// Derives the topology from leaf 0x1 and the first cache for processors without the extended topology leaves.
+fn legacy_topology(features_ebx: u32, features_edx: u32, first_cache: Option<&CacheInfo>) -> Topology {
+    if features_edx & FEATURES_EDX_HTT == 0 {
+        return Topology { threads_per_core: 1, logical_per_package: 1 };
+    }
+    let logical_per_package = ((features_ebx >> 16) & 0xFF).max(1);
+    // L1 caches are private to a core, so their sharing count is the number of threads in a core
+    let threads_per_core = first_cache.map_or(1, |cache| cache.sharing.min(logical_per_package));
+    Topology { threads_per_core, logical_per_package }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::q35::sim;
+
+    /// Builds a leaf 0x4 subleaf.
+    fn cache_leaf(cache_type: u32, level: u32, sharing: u32, ways: u32, sets: u32, line_size: u32) -> [u32; 4] {
+        [
+            cache_type | (level << 5) | (1 << 8) | ((sharing - 1) << 14),
+            ((ways - 1) << 22) | (line_size - 1),
+            sets - 1,
+            0,
+        ]
+    }
+
+    /// Simulates a two-thread, four-core Intel package with 32 KiB L1 caches, a 1 MiB L2 cache per core and a shared
+    /// 16 MiB L3 cache.
+    fn simulate_intel_processor() {
+        sim::with_chipset(|chipset| {
+            let [b, d, c] = [*b"Genu", *b"ineI", *b"ntel"].map(u32::from_le_bytes);
+            chipset.set_cpuid(leaf::VENDOR, 0, [0x1F, b, c, d]);
+            chipset.set_cpuid(leaf::FEATURES, 0, [0x000A_06A4, 0x0010_0800, FEATURES_ECX_VMX, 0x1FAB_FBFF]);
+            chipset.set_cpuid(leaf::CACHE_PARAMETERS, 0, cache_leaf(1, 1, 2, 8, 64, 64));
+            chipset.set_cpuid(leaf::CACHE_PARAMETERS, 1, cache_leaf(2, 1, 2, 8, 64, 64));
+            chipset.set_cpuid(leaf::CACHE_PARAMETERS, 2, cache_leaf(3, 2, 2, 16, 1024, 64));
+            chipset.set_cpuid(leaf::CACHE_PARAMETERS, 3, cache_leaf(3, 3, 8, 16, 16384, 64));
+            chipset.set_cpuid(leaf::EXTENDED_TOPOLOGY, 0, [1, 2, 0x100, 0]);
+            chipset.set_cpuid(leaf::EXTENDED_TOPOLOGY, 1, [4, 8, 0x201, 0]);
+            chipset.set_cpuid(leaf::EXTENDED_MAX, 0, [0x8000_0008, 0, 0, 0]);
+            chipset.set_cpuid(
+                leaf::EXTENDED_FEATURES,
+                0,
+                [0, 0, 0, EXTENDED_FEATURES_EDX_LM | EXTENDED_FEATURES_EDX_NX],
+            );
+            for (i, chunk) in b"Intel(R) Xeon(R) Test CPU @ 2.00GHz\0\0\0\0\0\0\0\0\0\0\0\0\0".chunks(16).enumerate() {
+                let registers = [0, 1, 2, 3].map(|r| u32::from_le_bytes(chunk[r * 4..r * 4 + 4].try_into().unwrap()));
+                chipset.set_cpuid(leaf::BRAND_STRING + i as u32, 0, registers);
+            }
+        });
+    }
+
+    #[test]
+    fn test_read_intel_processor() {
+        sim::reset();
+        simulate_intel_processor();
+
+        let info = ProcessorInfo::read();
+        assert_eq!(info.vendor(), Vendor::Intel);
+        assert_eq!(info.brand.as_deref(), Some("Intel(R) Xeon(R) Test CPU @ 2.00GHz"));
+        assert_eq!(info.processor_id(), [0xA4, 0x06, 0x0A, 0x00, 0xFF, 0xFB, 0xAB, 0x1F]);
+        assert!(info.long_mode());
+        assert!(info.execute_protection());
+        assert!(info.virtualization());
+        assert_eq!(info.topology, Topology { threads_per_core: 2, logical_per_package: 8 });
+        assert_eq!(info.topology.cores_per_package(), 4);
+        assert_eq!((info.base_frequency, info.max_frequency), (0, 0));
+
+        let summary: Vec<_> = info.caches.iter().map(|c| (c.level, c.cache_type, c.size, c.ways)).collect();
+        assert_eq!(
+            summary,
+            [
+                (1, CacheType::Data, 32 * 1024, 8),
+                (1, CacheType::Instruction, 32 * 1024, 8),
+                (2, CacheType::Unified, 1024 * 1024, 16),
+                (3, CacheType::Unified, 16 * 1024 * 1024, 16),
+            ]
+        );
+        assert_eq!(info.caches[0].instances_per_package(8), 4);
+        assert_eq!(info.caches[3].instances_per_package(8), 1);
+    }
+
+    #[test]
+    fn test_read_prefers_v2_extended_topology() {
+        sim::reset();
+        simulate_intel_processor();
+        sim::with_chipset(|chipset| {
+            chipset.set_cpuid(leaf::V2_EXTENDED_TOPOLOGY, 0, [1, 1, 0x100, 0]);
+            chipset.set_cpuid(leaf::V2_EXTENDED_TOPOLOGY, 1, [3, 6, 0x201, 0]);
+            chipset.set_cpuid(leaf::V2_EXTENDED_TOPOLOGY, 2, [5, 12, 0x502, 0]);
+        });
+
+        assert_eq!(ProcessorInfo::read().topology, Topology { threads_per_core: 1, logical_per_package: 12 });
+    }
+
+    #[test]
+    fn test_read_amd_cache_properties() {
+        sim::reset();
+        sim::with_chipset(|chipset| {
+            let [b, d, c] = [*b"Auth", *b"enti", *b"cAMD"].map(u32::from_le_bytes);
+            chipset.set_cpuid(leaf::VENDOR, 0, [0xD, b, c, d]);
+            chipset.set_cpuid(leaf::FEATURES, 0, [0x00A0_0F11, 0x0002_0800, 0, FEATURES_EDX_HTT]);
+            // Leaf 4 is reserved on AMD and must be ignored
+            chipset.set_cpuid(leaf::CACHE_PARAMETERS, 0, cache_leaf(3, 1, 1, 4, 4, 64));
+            chipset.set_cpuid(leaf::EXTENDED_MAX, 0, [leaf::AMD_CACHE_PROPERTIES, 0, 0, 0]);
+            chipset.set_cpuid(
+                leaf::EXTENDED_FEATURES,
+                0,
+                [0, 0, EXTENDED_FEATURES_ECX_SVM | EXTENDED_FEATURES_ECX_TOPOEXT, EXTENDED_FEATURES_EDX_LM],
+            );
+            chipset.set_cpuid(leaf::AMD_CACHE_PROPERTIES, 0, cache_leaf(2, 1, 1, 8, 64, 64));
+            chipset.set_cpuid(leaf::AMD_CACHE_PROPERTIES, 1, cache_leaf(1, 1, 1, 8, 64, 64));
+            chipset.set_cpuid(leaf::AMD_CACHE_PROPERTIES, 2, cache_leaf(3, 2, 1, 8, 1024, 64));
+        });
+
+        let info = ProcessorInfo::read();
+        assert_eq!(info.vendor(), Vendor::Amd);
+        assert_eq!(info.brand, None);
+        assert!(info.virtualization());
+        assert!(!info.execute_protection());
+        assert_eq!(info.topology, Topology { threads_per_core: 1, logical_per_package: 2 });
+
+        // Data caches are listed before instruction caches of the same level
+        let summary: Vec<_> = info.caches.iter().map(|c| (c.level, c.cache_type)).collect();
+        assert_eq!(summary, [(1, CacheType::Data), (1, CacheType::Instruction), (2, CacheType::Unified)]);
+    }
+
+    #[test]
+    fn test_read_minimal_processor() {
+        sim::reset();
+
+        let info = ProcessorInfo::read();
+        assert_eq!(info.vendor(), Vendor::Other);
+        assert!(info.caches.is_empty());
+        assert_eq!(info.topology, Topology { threads_per_core: 1, logical_per_package: 1 });
+        assert!(!info.long_mode());
+    }
+
+    #[test]
+    fn test_brand_string_is_trimmed() {
+        sim::reset();
+        sim::with_chipset(|chipset| {
+            chipset.set_cpuid(leaf::EXTENDED_MAX, 0, [leaf::BRAND_STRING + 2, 0, 0, 0]);
+            chipset.set_cpuid(
+                leaf::BRAND_STRING,
+                0,
+                [u32::from_le_bytes(*b"  QE"), u32::from_le_bytes(*b"MU  "), 0, 0],
+            );
+        });
+
+        assert_eq!(ProcessorInfo::read().brand.as_deref(), Some("QEMU"));
+    }
+}
@@ -0,0 +1,201 @@
+//! QEMU Q35 Firmware Configuration (fw_cfg) Access
+//!
+//! This module provides access to the QEMU Firmware Configuration device through its x86 I/O port interface. Items
+//! are read through the selector and data ports, and writes use the DMA interface because QEMU ignores writes to
+//! the data port.
+//!
+//! ## References
+//!
+//! - [QEMU Firmware Configuration (fw_cfg) Device](https://www.qemu.org/docs/master/specs/fw_cfg.html)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg(any(test, all(target_os = "uefi", target_arch = "x86_64", feature = "x64")))]
+
+extern crate alloc;
+use alloc::{vec, vec::Vec};
+use core::sync::atomic::{Ordering, fence};
+
+use crate::q35::hw::Port;
+use patina::error::EfiError;
+
+/// fw_cfg selector I/O port
+const SELECTOR_PORT: u16 = 0x510;
+/// fw_cfg data I/O port
+const DATA_PORT: u16 = 0x511;
+/// fw_cfg DMA address I/O port (high 32 bits, low 32 bits at +4)
+const DMA_PORT: u16 = 0x514;
+
+/// Signature item key
+pub const KEY_SIGNATURE: u16 = 0x0000;
+/// Feature bitmap item key
+pub const KEY_ID: u16 = 0x0001;
+/// System UUID item key
+pub const KEY_UUID: u16 = 0x0002;
+/// Number of boot CPUs item key
+pub const KEY_NB_CPUS: u16 = 0x0005;
+/// File directory item key
+pub const KEY_FILE_DIR: u16 = 0x0019;
+
+/// Expected value of the signature item
+const SIGNATURE: [u8; 4] = *b"QEMU";
+/// DMA interface bit in the feature bitmap item
+const ID_DMA: u32 = 0x02;
+
+/// DMA control: error
+const DMA_CTL_ERROR: u32 = 0x01;
+/// DMA control: select the item in bits 31:16 before the transfer
+const DMA_CTL_SELECT: u32 = 0x08;
+/// DMA control: write
+const DMA_CTL_WRITE: u32 = 0x10;
+
+/// Maximum length of a file name in the file directory, including the NUL terminator
+const FILE_NAME_SIZE: usize = 56;
+/// Size of a file directory entry
+const FILE_ENTRY_SIZE: usize = 4 + 2 + 2 + FILE_NAME_SIZE;
+
+/// fw_cfg DMA access descriptor. All fields are big-endian.
+#[repr(C, align(8))]
+struct DmaAccess {
+    control: u32,
+    length: u32,
+    address: u64,
+}
+
+/// A file in the fw_cfg file directory.
+#[derive(Debug, Clone, Copy)]
+pub struct FwCfgFile {
+    /// Size of the file in bytes
+    pub size: u32,
+    /// Item key used to select the file
+    pub select: u16,
+}
+
+/// Access to the QEMU fw_cfg device.
+pub struct FwCfg {
+    dma: bool,
+}
+
+impl FwCfg {
+    /// Returns fw_cfg access if the device is present.
+    pub fn new() -> Option<Self> {
+        let mut fw_cfg = Self { dma: false };
+
+        let mut signature = [0u8; 4];
+        fw_cfg.read_item(KEY_SIGNATURE, &mut signature);
+        if signature != SIGNATURE {
+            log::debug!("fw_cfg signature not found: {signature:02X?}");
+            return None;
+        }
+
+        let mut id = [0u8; 4];
+        fw_cfg.read_item(KEY_ID, &mut id);
+        fw_cfg.dma = u32::from_le_bytes(id) & ID_DMA != 0;
+
+        Some(fw_cfg)
+    }
+
+    /// Selects the item with the given key and reads `buffer.len()` bytes from the start of the item.
+    pub fn read_item(&self, key: u16, buffer: &mut [u8]) {
+        let mut selector: Port<u16> = Port::new(SELECTOR_PORT);
+        let mut data: Port<u8> = Port::new(DATA_PORT);
+
+        // SAFETY: QEMU Q35 always decodes the fw_cfg selector and data ports. Reading past the end of an item or
+        // reading a missing item returns zeros.
+        unsafe {
+            selector.write(key);
+            for byte in buffer.iter_mut() {
+                *byte = data.read();
+            }
+        }
+    }
+
+    /// Searches the file directory for a file with the given name.
+    pub fn find_file(&self, name: &str) -> Option<FwCfgFile> {
+        let mut count = [0u8; 4];
+        self.read_item(KEY_FILE_DIR, &mut count);
+        let count = u32::from_be_bytes(count) as usize;
+
+        let mut directory = vec![0u8; 4 + count * FILE_ENTRY_SIZE];
+        self.read_item(KEY_FILE_DIR, &mut directory);
+
+        directory[4..].chunks_exact(FILE_ENTRY_SIZE).find_map(|entry| {
+            let file_name = &entry[8..];
+            let file_name = &file_name[..file_name.iter().position(|&b| b == 0).unwrap_or(FILE_NAME_SIZE)];
+            (file_name == name.as_bytes()).then(|| FwCfgFile {
+                size: u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]),
+                select: u16::from_be_bytes([entry[4], entry[5]]),
+            })
+        })
+    }
+
+    /// Reads the full contents of a file.
+    pub fn read_file(&self, file: &FwCfgFile) -> Vec<u8> {
+        let mut buffer = vec![0u8; file.size as usize];
+        self.read_item(file.select, &mut buffer);
+        buffer
+    }
+
+    /// Writes `data` to the start of a file.
+    ///
+    /// ## Errors
+    ///
+    /// - `EfiError::Unsupported` if the fw_cfg device does not support DMA.
+    /// - `EfiError::BadBufferSize` if `data` is larger than the file.
+    /// - `EfiError::DeviceError` if QEMU reports an error for the transfer.
+    pub fn write_file(&self, file: &FwCfgFile, data: &[u8]) -> patina::error::Result<()> {
+        if !self.dma {
+            log::error!("fw_cfg DMA is not available to write item {:#06X}", file.select);
+            return Err(EfiError::Unsupported);
+        }
+        if data.len() > file.size as usize {
+            log::error!("fw_cfg write of {} bytes exceeds item {:#06X} size {}", data.len(), file.select, file.size);
+            return Err(EfiError::BadBufferSize);
+        }
+
+        let mut access = DmaAccess { control: 0, length: 0, address: 0 };
+        // SAFETY: `access` is a live local. A volatile write keeps the descriptor stores that only QEMU reads.
+        unsafe {
+            core::ptr::write_volatile(
+                &mut access,
+                DmaAccess {
+                    control: (((file.select as u32) << 16) | DMA_CTL_SELECT | DMA_CTL_WRITE).to_be(),
+                    length: (data.len() as u32).to_be(),
+                    address: (data.as_ptr() as u64).to_be(),
+                },
+            )
+        };
+        let access_address = &access as *const DmaAccess as u64;
+
+        // Make the descriptor and data visible to the device before starting the transfer.
+        fence(Ordering::SeqCst);
+
+        let mut dma_high: Port<u32> = Port::new(DMA_PORT);
+        let mut dma_low: Port<u32> = Port::new(DMA_PORT + 4);
+
+        // SAFETY: The DMA descriptor and data buffer are identity mapped and remain valid until QEMU clears the
+        // control field. Writing the low half of the descriptor address starts the transfer.
+        unsafe {
+            dma_high.write(((access_address >> 32) as u32).to_be());
+            dma_low.write((access_address as u32).to_be());
+        }
+
+        loop {
+            // SAFETY: `access` is a live local. QEMU updates the control field when the transfer completes.
+            let control = u32::from_be(unsafe { core::ptr::read_volatile(&access.control) });
+            if control & DMA_CTL_ERROR != 0 {
+                log::error!("fw_cfg DMA write to item {:#06X} failed", file.select);
+                return Err(EfiError::DeviceError);
+            }
+            if control == 0 {
+                fence(Ordering::SeqCst);
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
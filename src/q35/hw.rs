@@ -0,0 +1,37 @@
+//! QEMU Q35 Hardware Access
+//!
+//! Port I/O, MMIO register, CPUID and time-stamp counter accessors used by the Q35 components. On the target these are
+//! the `x86_64` port type, the `core::ptr` volatile accessors and the CPUID and RDTSC instructions. Host unit tests replace them with accessors
+//! backed by the simulated chipset in `q35::sim`, so component logic can be exercised without hardware.
+//!
+//! Only chipset registers should be accessed through this module. Ordinary memory, such as a DMA descriptor in a
+//! local variable, must keep using `core::ptr`.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+#[cfg(not(test))]
+pub use core::ptr::{read_volatile, write_volatile};
+#[cfg(all(not(test), target_arch = "x86_64", feature = "x64"))]
+pub use x86_64::instructions::port::Port;
+
+#[cfg(test)]
+pub use crate::q35::sim::{Port, cpuid, rdtsc, read_volatile, write_volatile};
+
+/// Executes CPUID with the given leaf and subleaf. Returns EAX, EBX, ECX and EDX.
+#[cfg(all(not(test), target_arch = "x86_64", feature = "x64"))]
+pub fn cpuid(leaf: u32, subleaf: u32) -> [u32; 4] {
+    let result = core::arch::x86_64::__cpuid_count(leaf, subleaf);
+    [result.eax, result.ebx, result.ecx, result.edx]
+}
+
+/// Reads the time-stamp counter.
+#[cfg(all(not(test), target_arch = "x86_64", feature = "x64"))]
+pub fn rdtsc() -> u64 {
+    // SAFETY: RDTSC has no side effects and is available on every x86_64 processor.
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
@@ -1,12 +1,13 @@
 //! QEMU Q35 Registers
 //!
 //! This module defines constants for QEMU Q35 register offsets and masks,
-//! including PCI Express base address and Intel I/O Controller Hub 9 (ICH9)
-//! specific registers.
+//! including PCI Express base address, Intel I/O Controller Hub 9 (ICH9)
+//! specific registers and Q35 Memory Controller Hub (MCH) registers.
 //!
 //! ## References
 //!
 //! - [Intel I/O Controller Hub 9 (ICH9) Datasheet](https://www.intel.com/content/dam/doc/datasheet/io-controller-hub-9-datasheet.pdf)
+//! - [Intel 3 Series Express Chipset Family Datasheet](https://www.intel.com/Assets/PDF/datasheet/316966.pdf)
 //!
 //! ## License
 //!
@@ -30,8 +31,38 @@ pub mod ich9 {
     pub const SMI_EN_GBL_SMI_EN: u32 = 0x01;
     /// APMC Enable bit
     pub const SMI_EN_APMC_EN: u32 = 0x20;
+    /// SMI Status offset (from PMBASE)
+    pub const PMBASE_OFS_SMI_STS: u32 = 0x34;
+    /// APM Status bit (write 1 to clear)
+    pub const SMI_STS_APM_STS: u32 = 0x20;
+    /// Advanced Power Management Control I/O port
+    pub const APM_CNT: u16 = 0xB2;
+    /// Advanced Power Management Status I/O port
+    pub const APM_STS: u16 = 0xB3;
     /// ICH9 General PM Control 1 register offset
     pub const GEN_PMCON_1: u32 = 0xA0;
     /// SMI Lock bit
     pub const GEN_PMCON_1_SMI_LOCK: u16 = 0x10;
 }
+
+/// Q35 Memory Controller Hub (MCH) registers (Bus 0, Device 0, Function 0)
+pub mod mch {
+    /// System Management RAM Control register offset
+    pub const SMRAMC: u32 = 0x9D;
+    /// SMRAM Open bit
+    pub const SMRAMC_D_OPEN: u8 = 0x40;
+    /// SMRAM Closed bit
+    pub const SMRAMC_D_CLS: u8 = 0x20;
+    /// SMRAM Lock bit
+    pub const SMRAMC_D_LCK: u8 = 0x10;
+    /// Global SMRAM Enable bit
+    pub const SMRAMC_G_SMRAME: u8 = 0x08;
+    /// Extended System Management RAM Control register offset
+    pub const ESMRAMC: u32 = 0x9E;
+    /// High SMRAM Enable bit
+    pub const ESMRAMC_H_SMRAME: u8 = 0x80;
+    /// TSEG Size mask
+    pub const ESMRAMC_TSEG_SZ_MASK: u8 = 0x06;
+    /// TSEG Enable bit
+    pub const ESMRAMC_T_EN: u8 = 0x01;
+}
@@ -0,0 +1,579 @@
+//! QEMU Q35 Simulated MM Environment
+//!
+//! A host-only simulation of the parts of the QEMU Q35 platform that the MM components touch, so their entry points
+//! can be exercised by unit tests:
+//!
+//! - [`SimulatedChipset`]: ICH9 LPC configuration space, PMBASE I/O registers, the fw_cfg device and the CPUID
+//!   leaves and time-stamp counter of the processor, reached through the [`Port`], [`read_volatile`],
+//!   [`write_volatile`], [`cpuid`] and [`rdtsc`] accessors that `q35::hw` exports under `cfg(test)`.
+//! - [`FakeHobList`]: MM GUID HOBs, with communication buffers backed by page-aligned host memory.
+//! - [`MockPlatformMmControl`]: a `PlatformMmControl` service with a fixed result that counts its calls.
+//!
+//! The platform-neutral helpers, such as `run_component` and `ScriptedMmCommunication`, are in [`crate::sim`].
+//!
+//! The chipset state is thread-local, so tests running in parallel do not observe each other.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg(test)]
+
+extern crate std;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use patina::component::{Storage, hob::FromHob, service::IntoService};
+use patina_mm::service::platform_mm_control::PlatformMmControl;
+
+use crate::{
+    q35::{
+        component::service::{
+            mm_config_provider::MmCommRegionHob,
+            ovmf_mm_config_provider::{OvmfMmCommBufferHob, OvmfSmramHob},
+        },
+        registers as register, smi_features,
+    },
+    sim::allocate_pages,
+};
+
+/// fw_cfg selector I/O port
+const FW_CFG_SELECTOR_PORT: u16 = 0x510;
+/// fw_cfg data I/O port
+const FW_CFG_DATA_PORT: u16 = 0x511;
+/// fw_cfg DMA address I/O port, high half
+const FW_CFG_DMA_HIGH_PORT: u16 = 0x514;
+/// fw_cfg DMA address I/O port, low half
+const FW_CFG_DMA_LOW_PORT: u16 = 0x518;
+/// Item key of the first fw_cfg file
+const FW_CFG_FIRST_FILE_KEY: u16 = 0x20;
+/// fw_cfg DMA control: error
+const FW_CFG_DMA_CTL_ERROR: u32 = 0x01;
+/// fw_cfg DMA control: select
+const FW_CFG_DMA_CTL_SELECT: u32 = 0x08;
+/// fw_cfg DMA control: write
+const FW_CFG_DMA_CTL_WRITE: u32 = 0x10;
+/// Time-stamp counter ticks between two reads
+const TSC_TICKS_PER_READ: u64 = 0x1000;
+
+std::thread_local! {
+    static CHIPSET: RefCell<SimulatedChipset> = RefCell::new(SimulatedChipset::default());
+}
+
+/// Resets the simulated chipset of the current thread to its power-on state.
+pub fn reset() {
+    CHIPSET.with(|chipset| *chipset.borrow_mut() = SimulatedChipset::default());
+}
+
+/// Runs `f` with the simulated chipset of the current thread.
+pub fn with_chipset<R>(f: impl FnOnce(&mut SimulatedChipset) -> R) -> R {
+    CHIPSET.with(|chipset| f(&mut chipset.borrow_mut()))
+}
+
+/// Returns the MMIO address of an ICH9 LPC bridge configuration register.
+pub fn lpc_register(offset: u32) -> usize {
+    register::PCI_EXPRESS_BASE_ADDRESS as usize + patina::pci_address!(0, 0x1F, 0, offset) as usize
+}
+
+/// Returns the MMIO address of a Q35 MCH (host bridge) configuration register.
+pub fn mch_register(offset: u32) -> usize {
+    register::PCI_EXPRESS_BASE_ADDRESS as usize + patina::pci_address!(0, 0, 0, offset) as usize
+}
+
+/// Simulated QEMU Q35 chipset state.
+///
+/// Unwritten registers read as zero. The simulation models the register behavior the MM components rely on:
+///
+/// - SMI_LOCK in GEN_PMCON_1 cannot be cleared once set.
+/// - GBL_SMI_EN in SMI_EN cannot be changed while SMI_LOCK is set.
+/// - SMRAMC and ESMRAMC cannot be changed once D_LCK is set in SMRAMC.
+/// - fw_cfg items are read through the selector and data ports, and files are written through DMA.
+/// - Writing `etc/smi/requested-features` updates `etc/smi/features-ok` the way QEMU's ICH9 LPC bridge does.
+/// - Unset CPUID leaves return zeros in every register.
+/// - The time-stamp counter advances by a fixed amount on every read.
+#[derive(Default)]
+pub struct SimulatedChipset {
+    mmio: BTreeMap<usize, u8>,
+    io: BTreeMap<u16, u8>,
+    read_only_mmio: BTreeSet<usize>,
+    read_only_io: BTreeSet<u16>,
+    fw_cfg: Option<SimulatedFwCfg>,
+    cpuid: BTreeMap<(u32, u32), [u32; 4]>,
+    tsc: u64,
+}
+
+impl SimulatedChipset {
+    /// Programs the ICH9 PMBASE register with an I/O base address.
+    pub fn set_pm_base(&mut self, pm_base: u16) {
+        self.mmio_write(lpc_register(register::ich9::PMBASE), 2, (pm_base | 0x01) as u32);
+    }
+
+    /// Returns the I/O base address programmed in PMBASE.
+    pub fn pm_base(&self) -> u16 {
+        self.mmio_read(lpc_register(register::ich9::PMBASE), 2) as u16 & register::ich9::PMBASE_MASK
+    }
+
+    /// Returns the SMI_EN register.
+    pub fn smi_en(&self) -> u32 {
+        self.io_read_raw(self.pm_base() + register::ich9::PMBASE_OFS_SMI_EN as u16, 4)
+    }
+
+    /// Sets the SMI_EN register, bypassing register locks.
+    pub fn set_smi_en(&mut self, value: u32) {
+        self.io_write_raw(self.pm_base() + register::ich9::PMBASE_OFS_SMI_EN as u16, 4, value);
+    }
+
+    /// Returns the GEN_PMCON_1 register.
+    pub fn gen_pmcon_1(&self) -> u16 {
+        self.mmio_read(lpc_register(register::ich9::GEN_PMCON_1), 2) as u16
+    }
+
+    /// Sets the GEN_PMCON_1 register, bypassing register locks.
+    pub fn set_gen_pmcon_1(&mut self, value: u16) {
+        self.mmio_write_raw(lpc_register(register::ich9::GEN_PMCON_1), 2, value as u32);
+    }
+
+    /// Makes the SMI_EN register ignore writes.
+    pub fn make_smi_en_read_only(&mut self) {
+        let port = self.pm_base() + register::ich9::PMBASE_OFS_SMI_EN as u16;
+        self.read_only_io.extend(port..port + 4);
+    }
+
+    /// Makes the GEN_PMCON_1 register ignore writes.
+    pub fn make_gen_pmcon_1_read_only(&mut self) {
+        let address = lpc_register(register::ich9::GEN_PMCON_1);
+        self.read_only_mmio.extend(address..address + 2);
+    }
+
+    /// Returns an 8-bit MCH configuration register.
+    pub fn mch_register(&self, offset: u32) -> u8 {
+        self.mmio_read(mch_register(offset), 1) as u8
+    }
+
+    /// Sets an 8-bit MCH configuration register, bypassing register locks.
+    pub fn set_mch_register(&mut self, offset: u32, value: u8) {
+        self.mmio_write_raw(mch_register(offset), 1, value as u32);
+    }
+
+    /// Makes the SMRAMC and ESMRAMC registers ignore writes.
+    pub fn make_smram_registers_read_only(&mut self) {
+        self.read_only_mmio.insert(mch_register(register::mch::SMRAMC));
+        self.read_only_mmio.insert(mch_register(register::mch::ESMRAMC));
+    }
+
+    /// Adds a fw_cfg device, with or without the DMA interface.
+    pub fn enable_fw_cfg(&mut self, dma: bool) {
+        self.fw_cfg = Some(SimulatedFwCfg::new(dma));
+    }
+
+    /// Adds a file to the fw_cfg device. The device must be enabled first.
+    pub fn add_fw_cfg_file(&mut self, name: &str, data: &[u8]) {
+        self.fw_cfg.as_mut().expect("fw_cfg is not enabled").add_file(name, data);
+    }
+
+    /// Sets the contents of a fw_cfg item that is not a file. The device must be enabled first.
+    pub fn set_fw_cfg_item(&mut self, key: u16, data: &[u8]) {
+        self.fw_cfg.as_mut().expect("fw_cfg is not enabled").items.insert(key, data.to_vec());
+    }
+
+    /// Makes the fw_cfg device reject every SMI feature request, as if a requested feature were not supported.
+    pub fn reject_smi_features(&mut self) {
+        self.fw_cfg.as_mut().expect("fw_cfg is not enabled").reject_smi_features = true;
+    }
+
+    /// Returns the contents of a fw_cfg file.
+    pub fn fw_cfg_file(&self, name: &str) -> Option<Vec<u8>> {
+        let fw_cfg = self.fw_cfg.as_ref()?;
+        fw_cfg.file_key(name).map(|key| fw_cfg.items[&key].clone())
+    }
+
+    /// Adds the QEMU SMI feature negotiation files with the given supported features.
+    pub fn add_smi_feature_files(&mut self, supported: u64) {
+        self.add_fw_cfg_file(smi_features::SUPPORTED_FEATURES_FILE, &supported.to_le_bytes());
+        self.add_fw_cfg_file(smi_features::REQUESTED_FEATURES_FILE, &0u64.to_le_bytes());
+        self.add_fw_cfg_file(smi_features::FEATURES_OK_FILE, &[0]);
+    }
+
+    /// Sets the EAX, EBX, ECX and EDX values returned by a CPUID leaf and subleaf.
+    pub fn set_cpuid(&mut self, leaf: u32, subleaf: u32, registers: [u32; 4]) {
+        self.cpuid.insert((leaf, subleaf), registers);
+    }
+
+    fn mmio_read(&self, address: usize, width: usize) -> u32 {
+        (0..width).fold(0, |value, i| value | (*self.mmio.get(&(address + i)).unwrap_or(&0) as u32) << (i * 8))
+    }
+
+    fn mmio_write(&mut self, address: usize, width: usize, mut value: u32) {
+        if address == lpc_register(register::ich9::GEN_PMCON_1) {
+            value |= (self.gen_pmcon_1() & register::ich9::GEN_PMCON_1_SMI_LOCK) as u32;
+        }
+        let smram_registers = mch_register(register::mch::SMRAMC)..=mch_register(register::mch::ESMRAMC);
+        if smram_registers.contains(&address)
+            && self.mch_register(register::mch::SMRAMC) & register::mch::SMRAMC_D_LCK != 0
+        {
+            return;
+        }
+        for i in 0..width {
+            if !self.read_only_mmio.contains(&(address + i)) {
+                self.mmio.insert(address + i, (value >> (i * 8)) as u8);
+            }
+        }
+    }
+
+    fn mmio_write_raw(&mut self, address: usize, width: usize, value: u32) {
+        for i in 0..width {
+            self.mmio.insert(address + i, (value >> (i * 8)) as u8);
+        }
+    }
+
+    fn io_read(&mut self, port: u16, width: usize) -> u32 {
+        if let Some(fw_cfg) = self.fw_cfg.as_mut()
+            && port == FW_CFG_DATA_PORT
+        {
+            return fw_cfg.read_data() as u32;
+        }
+        self.io_read_raw(port, width)
+    }
+
+    fn io_read_raw(&self, port: u16, width: usize) -> u32 {
+        (0..width as u16).fold(0, |value, i| value | (*self.io.get(&(port + i)).unwrap_or(&0) as u32) << (i * 8))
+    }
+
+    fn io_write(&mut self, port: u16, width: usize, mut value: u32) {
+        if let Some(fw_cfg) = self.fw_cfg.as_mut() {
+            match port {
+                FW_CFG_SELECTOR_PORT => return fw_cfg.select(value as u16),
+                FW_CFG_DMA_HIGH_PORT => return fw_cfg.dma_high = u32::from_be(value),
+                // SAFETY: The fw_cfg code under test passes the address of a live DMA descriptor.
+                FW_CFG_DMA_LOW_PORT => return unsafe { fw_cfg.dma(u32::from_be(value)) },
+                _ => {}
+            }
+        }
+
+        let pm_base = self.pm_base();
+        if pm_base != 0
+            && port == pm_base + register::ich9::PMBASE_OFS_SMI_EN as u16
+            && self.gen_pmcon_1() & register::ich9::GEN_PMCON_1_SMI_LOCK != 0
+        {
+            value = (value & !register::ich9::SMI_EN_GBL_SMI_EN) | (self.smi_en() & register::ich9::SMI_EN_GBL_SMI_EN);
+        }
+
+        for i in 0..width as u16 {
+            if !self.read_only_io.contains(&(port + i)) {
+                self.io.insert(port + i, (value >> (i * 8)) as u8);
+            }
+        }
+    }
+
+    fn io_write_raw(&mut self, port: u16, width: usize, value: u32) {
+        for i in 0..width as u16 {
+            self.io.insert(port + i, (value >> (i * 8)) as u8);
+        }
+    }
+}
+
+/// Simulated QEMU fw_cfg device.
+struct SimulatedFwCfg {
+    items: BTreeMap<u16, Vec<u8>>,
+    files: Vec<(String, u16)>,
+    selector: u16,
+    offset: usize,
+    dma_high: u32,
+    reject_smi_features: bool,
+}
+
+impl SimulatedFwCfg {
+    fn new(dma: bool) -> Self {
+        let mut items = BTreeMap::new();
+        items.insert(crate::q35::fw_cfg::KEY_SIGNATURE, b"QEMU".to_vec());
+        items.insert(crate::q35::fw_cfg::KEY_ID, (if dma { 0x03u32 } else { 0x01u32 }).to_le_bytes().to_vec());
+        let mut fw_cfg =
+            Self { items, files: Vec::new(), selector: 0, offset: 0, dma_high: 0, reject_smi_features: false };
+        fw_cfg.update_directory();
+        fw_cfg
+    }
+
+    fn dma_enabled(&self) -> bool {
+        self.items[&crate::q35::fw_cfg::KEY_ID][0] & 0x02 != 0
+    }
+
+    fn add_file(&mut self, name: &str, data: &[u8]) {
+        let key = FW_CFG_FIRST_FILE_KEY + self.files.len() as u16;
+        self.files.push((name.to_string(), key));
+        self.items.insert(key, data.to_vec());
+        self.update_directory();
+    }
+
+    fn file_key(&self, name: &str) -> Option<u16> {
+        self.files.iter().find(|(file, _)| file == name).map(|(_, key)| *key)
+    }
+
+    fn update_directory(&mut self) {
+        let mut directory = (self.files.len() as u32).to_be_bytes().to_vec();
+        for (name, key) in &self.files {
+            directory.extend_from_slice(&(self.items[key].len() as u32).to_be_bytes());
+            directory.extend_from_slice(&key.to_be_bytes());
+            directory.extend_from_slice(&[0; 2]);
+            let mut file_name = [0u8; 56];
+            file_name[..name.len()].copy_from_slice(name.as_bytes());
+            directory.extend_from_slice(&file_name);
+        }
+        self.items.insert(crate::q35::fw_cfg::KEY_FILE_DIR, directory);
+    }
+
+    fn select(&mut self, key: u16) {
+        self.selector = key;
+        self.offset = 0;
+    }
+
+    fn read_data(&mut self) -> u8 {
+        let value = self.items.get(&self.selector).and_then(|item| item.get(self.offset)).copied().unwrap_or(0);
+        self.offset += 1;
+        value
+    }
+
+    /// Processes the DMA descriptor at the address formed with the previously written high half.
+    ///
+    /// ## Safety
+    ///
+    /// The address must point to a valid, writable fw_cfg DMA descriptor whose data buffer is readable.
+    unsafe fn dma(&mut self, low: u32) {
+        let descriptor = (((self.dma_high as u64) << 32) | low as u64) as *mut u32;
+        // SAFETY: The caller guarantees the descriptor is valid. The descriptor is 16 bytes: control, length, address.
+        let (control, length, address) = unsafe {
+            (
+                u32::from_be(descriptor.read_volatile()),
+                u32::from_be(descriptor.add(1).read_volatile()) as usize,
+                u64::from_be((descriptor.add(2) as *const u64).read_volatile()) as usize as *const u8,
+            )
+        };
+
+        let mut result = 0;
+        if !self.dma_enabled() {
+            return;
+        }
+        if control & FW_CFG_DMA_CTL_SELECT != 0 {
+            self.select((control >> 16) as u16);
+        }
+        if control & FW_CFG_DMA_CTL_WRITE != 0 {
+            match self.items.get_mut(&self.selector) {
+                Some(item) if self.offset + length <= item.len() => {
+                    // SAFETY: The caller guarantees the data buffer is readable for `length` bytes.
+                    let data = unsafe { core::slice::from_raw_parts(address, length) };
+                    item[self.offset..self.offset + length].copy_from_slice(data);
+                    self.offset += length;
+                    self.on_file_written();
+                }
+                _ => result = FW_CFG_DMA_CTL_ERROR,
+            }
+        }
+
+        // SAFETY: The caller guarantees the descriptor is writable.
+        unsafe { descriptor.write_volatile(result.to_be()) };
+    }
+
+    /// Models QEMU accepting requested SMI features only if they are all supported.
+    fn on_file_written(&mut self) {
+        let (Some(supported), Some(requested), Some(features_ok)) = (
+            self.file_key(smi_features::SUPPORTED_FEATURES_FILE),
+            self.file_key(smi_features::REQUESTED_FEATURES_FILE),
+            self.file_key(smi_features::FEATURES_OK_FILE),
+        ) else {
+            return;
+        };
+        if self.selector != requested {
+            return;
+        }
+
+        let read = |item: &Vec<u8>| u64::from_le_bytes(item[..8].try_into().unwrap());
+        let ok = !self.reject_smi_features && read(&self.items[&requested]) & !read(&self.items[&supported]) == 0;
+        self.items.insert(features_ok, std::vec![ok as u8]);
+    }
+}
+
+/// A value that can be transferred through a simulated port or MMIO register.
+pub trait SimulatedValue: Copy {
+    /// Width of the value in bytes.
+    const WIDTH: usize;
+    /// Converts the value from the low bytes of a `u32`.
+    fn from_u32(value: u32) -> Self;
+    /// Converts the value to a `u32`.
+    fn to_u32(self) -> u32;
+}
+
+macro_rules! impl_simulated_value {
+    ($($ty:ty),*) => {
+        $(
+            impl SimulatedValue for $ty {
+                const WIDTH: usize = size_of::<$ty>();
+                fn from_u32(value: u32) -> Self {
+                    value as $ty
+                }
+                fn to_u32(self) -> u32 {
+                    self as u32
+                }
+            }
+        )*
+    };
+}
+impl_simulated_value!(u8, u16, u32);
+
+/// Simulated I/O port with the same interface as `x86_64::instructions::port::Port`.
+pub struct Port<T> {
+    port: u16,
+    _marker: core::marker::PhantomData<T>,
+}
+
+impl<T: SimulatedValue> Port<T> {
+    /// Creates a port for the given I/O address.
+    pub const fn new(port: u16) -> Self {
+        Self { port, _marker: core::marker::PhantomData }
+    }
+
+    /// Reads from the simulated port.
+    ///
+    /// ## Safety
+    ///
+    /// Always safe. Unsafe to match the hardware port interface.
+    pub unsafe fn read(&mut self) -> T {
+        T::from_u32(with_chipset(|chipset| chipset.io_read(self.port, T::WIDTH)))
+    }
+
+    /// Writes to the simulated port.
+    ///
+    /// ## Safety
+    ///
+    /// Always safe, except for the fw_cfg DMA port which dereferences the descriptor address written to it.
+    pub unsafe fn write(&mut self, value: T) {
+        with_chipset(|chipset| chipset.io_write(self.port, T::WIDTH, value.to_u32()));
+    }
+}
+
+/// Reads a simulated MMIO register. Has the same signature as `core::ptr::read_volatile`.
+///
+/// ## Safety
+///
+/// Always safe. Unsafe to match `core::ptr::read_volatile`.
+pub unsafe fn read_volatile<T: SimulatedValue>(src: *const T) -> T {
+    T::from_u32(with_chipset(|chipset| chipset.mmio_read(src as usize, T::WIDTH)))
+}
+
+/// Writes a simulated MMIO register. Has the same signature as `core::ptr::write_volatile`.
+///
+/// ## Safety
+///
+/// Always safe. Unsafe to match `core::ptr::write_volatile`.
+pub unsafe fn write_volatile<T: SimulatedValue>(dst: *mut T, value: T) {
+    with_chipset(|chipset| chipset.mmio_write(dst as usize, T::WIDTH, value.to_u32()));
+}
+
+/// Executes CPUID on the simulated processor. Returns EAX, EBX, ECX and EDX.
+pub fn cpuid(leaf: u32, subleaf: u32) -> [u32; 4] {
+    with_chipset(|chipset| chipset.cpuid.get(&(leaf, subleaf)).copied().unwrap_or_default())
+}
+
+/// Reads the time-stamp counter of the simulated processor.
+pub fn rdtsc() -> u64 {
+    with_chipset(|chipset| {
+        chipset.tsc += TSC_TICKS_PER_READ;
+        chipset.tsc
+    })
+}
+
+/// Registers the data of a GUID HOB with storage.
+type HobRegistration = fn(&[u8], &mut Storage);
+
+/// A fake HOB list of the MM GUID HOBs produced by PEI.
+#[derive(Default)]
+pub struct FakeHobList {
+    hobs: Vec<(HobRegistration, Vec<u8>)>,
+}
+
+impl FakeHobList {
+    /// Creates an empty HOB list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an MM Communicate Region HOB backed by `pages` zeroed, page-aligned pages of leaked host memory.
+    pub fn with_comm_region(self, buffer_type: u64, pages: usize) -> Self {
+        let address = allocate_pages(pages);
+        self.with_raw_comm_region(buffer_type, address, pages as u64)
+    }
+
+    /// Adds an MM Communicate Region HOB with the given raw contents.
+    pub fn with_raw_comm_region(mut self, buffer_type: u64, address: u64, pages: u64) -> Self {
+        let hob = [buffer_type, address, pages].iter().flat_map(|field| field.to_le_bytes()).collect();
+        self.hobs.push((MmCommRegionHob::register, hob));
+        self
+    }
+
+    /// Adds an MM Communication Buffer HOB backed by `pages` zeroed, page-aligned pages of leaked host memory, with a
+    /// status mailbox in a separate leaked page.
+    pub fn with_mm_comm_buffer(self, pages: usize) -> Self {
+        let address = allocate_pages(pages);
+        let status = allocate_pages(1);
+        self.with_raw_mm_comm_buffer(address, pages as u64, status)
+    }
+
+    /// Adds an MM Communication Buffer HOB with the given raw contents.
+    pub fn with_raw_mm_comm_buffer(mut self, address: u64, pages: u64, status: u64) -> Self {
+        let hob = [address, pages, status].iter().flat_map(|field| field.to_le_bytes()).collect();
+        self.hobs.push((OvmfMmCommBufferHob::register, hob));
+        self
+    }
+
+    /// Adds an SMRAM Memory HOB describing the given `(physical_start, physical_size)` regions.
+    pub fn with_smram(mut self, regions: &[(u64, u64)]) -> Self {
+        let mut hob = (regions.len() as u64).to_le_bytes().to_vec();
+        for &(start, size) in regions {
+            // PhysicalStart, CpuStart, PhysicalSize and RegionState (EFI_ALLOCATED).
+            for field in [start, start, size, 0x10] {
+                hob.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        self.hobs.push((OvmfSmramHob::register, hob));
+        self
+    }
+
+    /// Parses the HOBs into `storage`, as the DXE core does when it processes the HOB list.
+    pub fn install(&self, storage: &mut Storage) {
+        for (register, hob) in &self.hobs {
+            register(hob, storage);
+        }
+    }
+}
+
+/// A `PlatformMmControl` service that returns a fixed result and counts calls to `init`.
+#[derive(IntoService)]
+#[service(dyn PlatformMmControl)]
+pub struct MockPlatformMmControl {
+    result: patina::error::Result<()>,
+    calls: Rc<Cell<usize>>,
+}
+
+impl MockPlatformMmControl {
+    /// Creates a mock whose `init` returns `result`.
+    pub fn new(result: patina::error::Result<()>) -> Self {
+        Self { result, calls: Rc::new(Cell::new(0)) }
+    }
+
+    /// Returns a handle to the number of `init` calls that remains valid after the mock is moved into storage.
+    pub fn calls(&self) -> Rc<Cell<usize>> {
+        self.calls.clone()
+    }
+}
+
+impl PlatformMmControl for MockPlatformMmControl {
+    fn init(&self) -> patina::error::Result<()> {
+        self.calls.set(self.calls.get() + 1);
+        self.result
+    }
+}
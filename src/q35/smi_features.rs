@@ -0,0 +1,114 @@
+//! QEMU Q35 SMI Features
+//!
+//! Defines the SMI features that firmware negotiates with QEMU through fw_cfg and the configuration that records the
+//! result of the negotiation. QEMU only broadcasts SMIs to all vCPUs, and only raises SMIs on CPU hotplug, when the
+//! corresponding feature has been negotiated.
+//!
+//! ## References
+//!
+//! - [QEMU ICH9 LPC SMI feature negotiation](https://gitlab.com/qemu-project/qemu/-/blob/master/hw/isa/lpc_ich9.c)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use core::fmt;
+
+/// fw_cfg file containing the SMI features supported by QEMU (little-endian `u64`)
+pub const SUPPORTED_FEATURES_FILE: &str = "etc/smi/supported-features";
+/// fw_cfg file that firmware writes the requested SMI features to (little-endian `u64`)
+pub const REQUESTED_FEATURES_FILE: &str = "etc/smi/requested-features";
+/// fw_cfg file that reports whether the requested SMI features were accepted (`u8`)
+pub const FEATURES_OK_FILE: &str = "etc/smi/features-ok";
+
+/// A set of QEMU ICH9 SMI features.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct SmiFeatures(u64);
+
+impl SmiFeatures {
+    /// No features
+    pub const NONE: Self = Self(0);
+    /// SMIs raised through the APM control port are broadcast to all vCPUs
+    pub const BROADCAST: Self = Self(1 << 0);
+    /// An SMI is raised when a CPU is hotplugged
+    pub const CPU_HOTPLUG: Self = Self(1 << 1);
+    /// An SMI is raised when a CPU is hot-unplugged
+    pub const CPU_HOT_UNPLUG: Self = Self(1 << 2);
+
+    /// Creates a feature set from its raw bit representation.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw bit representation of the feature set.
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Returns `true` if all features in `other` are in this set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the features present in both sets.
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl fmt::Debug for SmiFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SmiFeatures({:#X}: {})", self.0, self)
+    }
+}
+
+impl fmt::Display for SmiFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (Self::BROADCAST, "BROADCAST"),
+            (Self::CPU_HOTPLUG, "CPU_HOTPLUG"),
+            (Self::CPU_HOT_UNPLUG, "CPU_HOT_UNPLUG"),
+        ];
+
+        let mut first = true;
+        for (feature, name) in names {
+            if self.contains(feature) {
+                write!(f, "{}{name}", if first { "" } else { " | " })?;
+                first = false;
+            }
+        }
+
+        let unknown = self.0 & !(Self::BROADCAST.0 | Self::CPU_HOTPLUG.0 | Self::CPU_HOT_UNPLUG.0);
+        if unknown != 0 {
+            write!(f, "{}{unknown:#X}", if first { "" } else { " | " })?;
+        } else if first {
+            write!(f, "NONE")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// SMI feature negotiation configuration.
+///
+/// The platform sets `requested` to the features it wants. The QEMU Q35 SMI feature negotiation component negotiates
+/// with QEMU before MMIs are enabled, fills in `supported` and `negotiated`, and locks the configuration so
+/// components that depend on the outcome can consume it alongside `MmCommunicationConfiguration`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmiFeatureConfiguration {
+    /// Features the platform requests. Features that QEMU does not support are dropped before negotiation.
+    pub requested: SmiFeatures,
+    /// Features supported by QEMU. Populated during boot.
+    pub supported: SmiFeatures,
+    /// Features accepted by QEMU. Populated during boot.
+    pub negotiated: SmiFeatures,
+}
+
+impl Default for SmiFeatureConfiguration {
+    fn default() -> Self {
+        Self { requested: SmiFeatures::BROADCAST, supported: SmiFeatures::NONE, negotiated: SmiFeatures::NONE }
+    }
+}
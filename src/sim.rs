@@ -0,0 +1,138 @@
+//! Simulated Firmware Environment
+//!
+//! Host-only helpers shared by the unit tests of both QEMU platforms:
+//!
+//! - [`run_component`]: initializes and runs a component against a `Storage`, as the dispatcher does.
+//! - [`allocate_pages`]: page-aligned host memory for buffers that firmware would reserve.
+//! - [`ScriptedMmCommunication`]: an `MmCommunication` service that hands each request to the next scripted MM
+//!   handler and records it.
+//!
+//! The platform simulations, `q35::sim` and `armvirt::sim`, build on these.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg(test)]
+
+extern crate std;
+use std::{
+    alloc::{Layout, alloc_zeroed},
+    boxed::Box,
+    cell::RefCell,
+    collections::VecDeque,
+    rc::Rc,
+    vec::Vec,
+};
+
+use patina::{
+    BinaryGuid, Guid,
+    component::{
+        IntoComponent, Storage, component,
+        service::{IntoService, Service},
+    },
+};
+use patina_mm::{component::communicator::Status, service::MmCommunication};
+
+/// Allocates zeroed, page-aligned pages of host memory that are never freed, so they outlive any `'static` reference
+/// created from them.
+pub fn allocate_pages(pages: usize) -> u64 {
+    let layout = Layout::from_size_align(pages * patina::base::UEFI_PAGE_SIZE, patina::base::UEFI_PAGE_SIZE)
+        .expect("Valid page layout");
+    // SAFETY: The layout has a non-zero size.
+    let address = unsafe { alloc_zeroed(layout) };
+    assert!(!address.is_null(), "Failed to allocate pages");
+    address as u64
+}
+
+/// A request received by [`ScriptedMmCommunication`].
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// Communicate buffer ID
+    pub id: u8,
+    /// Request data
+    pub data: Vec<u8>,
+    /// Recipient handler GUID
+    pub recipient: BinaryGuid,
+}
+
+/// A scripted MM handler. It receives the request in place, as a real handler sees the comm buffer, and may update
+/// it or fail the transaction.
+pub type ScriptedHandler = Box<dyn FnOnce(&mut [u8]) -> Result<(), Status>>;
+
+/// An `MmCommunication` service that answers each request with the next scripted MM handler.
+///
+/// The response has the same length as the request, matching the MM communicator. A request with no scripted handler
+/// left panics.
+#[derive(Default, IntoService)]
+#[service(dyn MmCommunication)]
+pub struct ScriptedMmCommunication {
+    handlers: RefCell<VecDeque<ScriptedHandler>>,
+    requests: Rc<RefCell<Vec<RecordedRequest>>>,
+}
+
+impl ScriptedMmCommunication {
+    /// Creates a service with no scripted handlers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a handler for the next request.
+    pub fn then(self, handler: impl FnOnce(&mut [u8]) -> Result<(), Status> + 'static) -> Self {
+        self.handlers.borrow_mut().push_back(Box::new(handler));
+        self
+    }
+
+    /// Returns a handle to the recorded requests that remains valid after the service is created.
+    pub fn requests(&self) -> Rc<RefCell<Vec<RecordedRequest>>> {
+        self.requests.clone()
+    }
+
+    /// Converts the script into a service.
+    pub fn into_service(self) -> Service<dyn MmCommunication> {
+        Service::mock(Box::new(self))
+    }
+}
+
+impl MmCommunication for ScriptedMmCommunication {
+    fn communicate<'a>(&self, id: u8, data_buffer: &[u8], recipient: Guid<'a>) -> Result<Vec<u8>, Status> {
+        self.requests.borrow_mut().push(RecordedRequest {
+            id,
+            data: data_buffer.to_vec(),
+            recipient: BinaryGuid::from_bytes(&recipient.as_bytes()),
+        });
+
+        let handler = self.handlers.borrow_mut().pop_front().expect("Unexpected MM communication request");
+        let mut buffer = data_buffer.to_vec();
+        handler(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// Initializes and runs a component against `storage`, as the dispatcher does.
+///
+/// Commands issued by the component, such as adding a service, are applied to `storage` before returning. Returns
+/// `Ok(false)` if the component's parameters are not available.
+pub fn run_component<I>(component: impl IntoComponent<I>, storage: &mut Storage) -> patina::error::Result<bool> {
+    let mut component = component.into_component();
+    component.initialize(storage);
+    let result = component.run(storage);
+
+    let mut apply_commands = ApplyCommands.into_component();
+    apply_commands.initialize(storage);
+    apply_commands.run(storage)?;
+
+    result
+}
+
+/// A component with no parameters. Running it applies the commands queued by previously run components.
+struct ApplyCommands;
+
+#[component]
+impl ApplyCommands {
+    fn entry_point(self) -> patina::error::Result<()> {
+        Ok(())
+    }
+}
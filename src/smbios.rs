@@ -0,0 +1,1504 @@
+//! QEMU SMBIOS Platform Builder
+//!
+//! Builds the SMBIOS records shared by the QEMU platforms from an [`SmbiosPlatformConfig`], so each binary sets its
+//! system identity in `ComponentInfo::configs` instead of in component code. Platform components use
+//! [`SmbiosPlatformBuilder`] to add the common records, add their own platform-specific records and publish the table.
+//!
+//! The memory records are built from an [`SmbiosMemoryMap`], which the platform components create from the system
+//! memory resource descriptor HOBs in the HOB list Configuration Table, so they match the memory QEMU was started with.
+//!
+//! When the platform provides the [`QemuSmbiosTables`] read from fw_cfg, the strings QEMU sets for Types 0 to 4 and
+//! Type 17 override the configured ones, and QEMU structures of types the platform does not build, such as Type 11 OEM
+//! Strings, are added as they are. This lets the QEMU `-smbios` options stamp serial numbers and OEM strings.
+//!
+//! The Type 1 UUID is the [`SystemUuid`] the platform resolves from QEMU, or its persisted fallback.
+//!
+//! The Type 11 OEM Strings and Type 12 System Configuration Options records carry the strings in the configuration.
+//! QEMU Type 11 structures, from `-smbios type=11`, are added alongside them, so a test harness can pass both build
+//! and run metadata to the guest.
+//!
+//! The Type 32 System Boot Information record reports whether the previous boot completed, as tracked by
+//! [`boot_status`]. The Type 45 Firmware Inventory records list the DXE core, associated with the Type 0 record, and
+//! each patina crate it is built from, associated with the DXE core record.
+//!
+//! The Type 9 System Slots and Type 41 Onboard Devices Extended records are built from the [`PciRootBus`] the
+//! platform enumerates: one Type 9 record per PCI Express root port and one Type 41 record per integrated function.
+//!
+//! The configuration also selects the SMBIOS version, from 2.7, and the [`SmbiosEntryPoint`] the table is published
+//! through. Records are cut to the length their type has in that version, and the Type 45 records, added in SMBIOS
+//! 3.5, are only built for SMBIOS 3.5 and later.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{format, string::String, vec, vec::Vec};
+use core::{ffi::c_void, ops::Range};
+
+pub mod boot_status;
+pub mod compliance;
+#[coverage(off)]
+pub mod compliance_test;
+pub mod entry_point;
+pub mod golden;
+#[coverage(off)]
+pub mod oem_strings_test;
+pub mod pci;
+#[coverage(off)]
+pub mod protocol_test;
+pub mod qemu;
+pub mod record;
+pub mod table;
+pub mod uuid;
+pub use entry_point::SmbiosEntryPoint;
+pub use pci::PciRootBus;
+pub use qemu::QemuSmbiosTables;
+pub use uuid::SystemUuid;
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, protocol_handler::HandleSearchType},
+    component::service::Service,
+    guids,
+    pi::hob::{self, Hob, HobList},
+};
+use patina_smbios::{
+    component::SmbiosProvider,
+    service::{SMBIOS_HANDLE_PI_RESERVED, Smbios, SmbiosHandle, SmbiosTableHeader},
+    smbios_record::{
+        SmbiosRecordStructure, Type0PlatformFirmwareInformation, Type1SystemInformation, Type2BaseboardInformation,
+        Type7CacheInformation, Type16PhysicalMemoryArray, Type17MemoryDevice, Type19MemoryArrayMappedAddress,
+    },
+    smbios_types::{
+        AssociativityField, BiosCharacteristics, BiosCharacteristicsExt1, BiosCharacteristicsExt2, BoardType,
+        BootUpState, CacheConfiguration, CacheErrorCorrectionType, CacheSize, CacheSize2, CacheSramTypeData,
+        CurrentUsage, DeviceFunctionNumber, ExtendedBiosRomSize, FeatureFlags, MemoryArrayErrorCorrectionType,
+        MemoryArrayLocation, MemoryArrayUse, MemoryCapability, MemoryDeviceAttributes, MemoryDeviceTechnology,
+        MemoryDeviceType, MemoryDeviceTypeDetails, MemoryFormFactor, PowerSupplyState, SecurityStatus,
+        SlotCharacteristics1, SlotCharacteristics2, SlotLength, SystemCacheType, ThermalState, WakeUpType,
+    },
+};
+use r_efi::{efi, protocols::loaded_image};
+
+use pci::{PciFunction, PcieCapability};
+use record::{
+    Type3SystemEnclosure, Type9SystemSlots, Type11OemStrings, Type12SystemConfigurationOptions,
+    Type32SystemBootInformation, Type41OnboardDevicesExtended, Type45FirmwareInventoryInformation,
+};
+
+/// Returns the address of the table with `guid` in the UEFI Configuration Table, or `None` if it is not installed.
+///
+/// The Configuration Table is reached through the system table of a loaded image, as components do not receive the
+/// system table.
+pub fn configuration_table(
+    boot_services: &StandardBootServices,
+    guid: &efi::Guid,
+) -> Result<Option<*const c_void>, &'static str> {
+    let handles = boot_services
+        .locate_handle_buffer(HandleSearchType::ByProtocol(&loaded_image::PROTOCOL_GUID))
+        .map_err(|_| "Failed to locate a loaded image")?;
+    let handle = *handles.first().ok_or("Failed to locate a loaded image")?;
+
+    // SAFETY: The handle supports the Loaded Image protocol, whose system table is valid during boot services.
+    let configuration_table = unsafe {
+        let image = boot_services
+            .handle_protocol_unchecked(handle, &loaded_image::PROTOCOL_GUID)
+            .map_err(|_| "Failed to open the Loaded Image protocol")?
+            as *const loaded_image::Protocol;
+        let system_table = &*(*image).system_table;
+        core::slice::from_raw_parts(system_table.configuration_table, system_table.number_of_table_entries)
+    };
+
+    Ok(configuration_table
+        .iter()
+        .find(|table| table.vendor_guid == *guid)
+        .map(|table| table.vendor_table as *const c_void))
+}
+
+/// Handle value for a record reference that is not provided.
+pub const SMBIOS_HANDLE_NONE: SmbiosHandle = 0xFFFF;
+
+/// Handle value for an error information reference that is not provided.
+const SMBIOS_HANDLE_NO_ERROR_INFORMATION: SmbiosHandle = 0xFFFE;
+
+/// Type 16 `Maximum Capacity` value that defers to `Extended Maximum Capacity` (2 TB in KB)
+const MAXIMUM_CAPACITY_USE_EXTENDED: u32 = 0x8000_0000;
+/// Type 17 `Size` value that defers to `Extended Size` (32 GB - 1 MB in MB)
+const MEMORY_SIZE_USE_EXTENDED: u16 = 0x7FFF;
+/// Type 19 address value that defers to the extended address fields
+const MAPPED_ADDRESS_USE_EXTENDED: u32 = 0xFFFF_FFFF;
+/// Type 17 width value for an unknown width
+const MEMORY_WIDTH_UNKNOWN: u16 = 0xFFFF;
+/// Type 45 `Image Size` value for an unknown size
+const FIRMWARE_IMAGE_SIZE_UNKNOWN: u64 = u64::MAX;
+/// Type 45 `State` value for an enabled firmware component
+const FIRMWARE_STATE_ENABLED: u8 = 0x04;
+/// Type 45 `Version Format` and `Firmware ID Format` value for free-form strings
+const FIRMWARE_FORMAT_FREE_FORM: u8 = 0x00;
+
+/// Name and version of each patina crate the binaries are built from, from the build script
+const PATINA_CRATE_VERSIONS: &str = env!("PATINA_CRATE_VERSIONS");
+/// Manufacturer of the patina crates
+const PATINA_MANUFACTURER: &str = "Open Device Partnership";
+/// Firmware release date in `MM/DD/YYYY` form, from the build environment. Unset in tests so the records do not depend
+/// on the build.
+const BUILD_DATE: Option<&str> = if cfg!(test) { None } else { option_env!("BUILD_DATE") };
+
+/// Type 9 `Slot Height` value for an unknown height
+const SLOT_HEIGHT_UNKNOWN: u8 = 0x02;
+/// Type 41 `Device Type` bit that reports the device as enabled
+const ONBOARD_DEVICE_ENABLED: u8 = 0x80;
+
+/// Types built by the platform. QEMU structures of these types only contribute their strings, or are ignored.
+///
+/// Type 20 Memory Device Mapped Address structures reference the QEMU Type 17 handles, and the platform adds its own
+/// Type 32 System Boot Information record, so QEMU structures of both types are ignored.
+const PLATFORM_TYPES: &[u8] = &[0, 1, 2, 3, 4, 7, 16, 17, 19, 20, 32];
+
+/// Returns the string count and string pool of a record that holds a list of strings, or `None` if `strings` holds no
+/// non-empty strings. Strings past the 255th are dropped.
+fn string_list(strings: &[&str]) -> Option<(u8, Vec<String>)> {
+    let mut pool = StringPool::new();
+    let mut dropped = 0;
+    for string in strings.iter().filter(|string| !string.is_empty()) {
+        if pool.0.len() == u8::MAX as usize {
+            dropped += 1;
+            continue;
+        }
+        pool.add(string);
+    }
+    if dropped > 0 {
+        log::warn!("  Dropped {dropped} SMBIOS string(s) past the 255th");
+    }
+
+    let strings = pool.into_strings();
+    (!strings.is_empty()).then_some((strings.len() as u8, strings))
+}
+
+/// Converts a Type 0 `MM/DD/YYYY` release date to the Type 45 `YYYY-MM-DD` form.
+fn iso_release_date(date: &str) -> Option<String> {
+    let mut fields = date.split('/');
+    let (month, day, year) = (fields.next()?, fields.next()?, fields.next()?);
+    let digits = |field: &str, len| field.len() == len && field.bytes().all(|b| b.is_ascii_digit());
+    (fields.next().is_none() && digits(month, 2) && digits(day, 2) && digits(year, 4))
+        .then(|| format!("{year}-{month}-{day}"))
+}
+
+/// Returns the SMBIOS device and function number of a PCI function.
+fn device_function_number(function: &PciFunction) -> DeviceFunctionNumber {
+    DeviceFunctionNumber::new().with_device_number(function.device).with_function_number(function.function)
+}
+
+/// Encodes a cache size in the 16-bit Type 7 size field, saturating it when the 32-bit field is needed.
+fn cache_size(size_kb: u64) -> CacheSize {
+    match size_kb {
+        0..0x8000 => CacheSize::new().with_max_size(size_kb as u16),
+        _ if size_kb / 64 < 0x8000 => CacheSize::new().with_max_size((size_kb / 64) as u16).with_granularity(true),
+        _ => CacheSize::new().with_max_size(0x7FFF).with_granularity(true),
+    }
+}
+
+/// Encodes a cache size in the 32-bit Type 7 size field.
+fn cache_size2(size_kb: u64) -> CacheSize2 {
+    match size_kb {
+        0..0x8000_0000 => CacheSize2::new().with_max_size(size_kb as u32),
+        _ => CacheSize2::new().with_max_size((size_kb / 64).min(0x7FFF_FFFF) as u32).with_granularity(true),
+    }
+}
+
+/// SMBIOS system enclosure types (Type 3 `Type` field)
+pub mod chassis_type {
+    /// Other
+    pub const OTHER: u8 = 0x01;
+    /// Unknown
+    pub const UNKNOWN: u8 = 0x02;
+    /// Desktop
+    pub const DESKTOP: u8 = 0x03;
+    /// Main Server Chassis
+    pub const MAIN_SERVER_CHASSIS: u8 = 0x11;
+    /// Rack Mount Chassis
+    pub const RACK_MOUNT_CHASSIS: u8 = 0x17;
+}
+
+/// SMBIOS platform identity.
+///
+/// Empty strings are omitted from the records. Strings must not exceed 64 bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct SmbiosPlatformConfig {
+    /// System, baseboard and chassis manufacturer.
+    pub manufacturer: &'static str,
+    /// System product name.
+    pub product_name: &'static str,
+    /// System, baseboard and chassis version.
+    pub version: &'static str,
+    /// System serial number.
+    pub serial_number: &'static str,
+    /// System SKU number.
+    pub sku_number: &'static str,
+    /// System family.
+    pub family: &'static str,
+    /// Baseboard product name.
+    pub baseboard_product: &'static str,
+    /// Baseboard serial number.
+    pub baseboard_serial_number: &'static str,
+    /// Baseboard asset tag.
+    pub baseboard_asset_tag: &'static str,
+    /// Chassis type, one of [`chassis_type`].
+    pub chassis_type: u8,
+    /// Chassis serial number.
+    pub chassis_serial_number: &'static str,
+    /// Chassis asset tag.
+    pub chassis_asset_tag: &'static str,
+    /// Type 11 OEM strings, such as build metadata. No Type 11 record is built if there are none.
+    pub oem_strings: &'static [&'static str],
+    /// Type 12 system configuration options. No Type 12 record is built if there are none.
+    pub configuration_options: &'static [&'static str],
+    /// SMBIOS major and minor version, from [`entry_point::MINIMUM_VERSION`].
+    pub smbios_version: (u8, u8),
+    /// Entry points the table is published through. The 64-bit entry point requires SMBIOS 3.0 or later.
+    pub entry_point: SmbiosEntryPoint,
+}
+
+impl Default for SmbiosPlatformConfig {
+    fn default() -> Self {
+        Self {
+            manufacturer: "QEMU",
+            product_name: "Virtual Machine",
+            version: "1.0",
+            serial_number: "",
+            sku_number: "",
+            family: "Virtual Machine",
+            baseboard_product: "Virtual Machine",
+            baseboard_serial_number: "",
+            baseboard_asset_tag: "",
+            chassis_type: chassis_type::OTHER,
+            chassis_serial_number: "",
+            chassis_asset_tag: "",
+            oem_strings: &[],
+            configuration_options: &[],
+            smbios_version: (3, 9),
+            entry_point: SmbiosEntryPoint::Bits64,
+        }
+    }
+}
+
+impl SmbiosPlatformConfig {
+    /// Returns the SMBIOS provider component for the configured version.
+    ///
+    /// The provider only accepts SMBIOS 3.x, so it is created for SMBIOS 3.0 when an SMBIOS 2.x table is configured.
+    /// Its version is then only reported by the SMBIOS protocol, as the 64-bit entry point is not published.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the version is older than [`entry_point::MINIMUM_VERSION`], or if the 64-bit entry point is
+    /// configured for an SMBIOS 2.x table.
+    pub fn provider(&self) -> SmbiosProvider {
+        let (major, minor) = self.smbios_version;
+        assert!(self.smbios_version >= entry_point::MINIMUM_VERSION, "SMBIOS {major}.{minor} is not supported");
+        assert!(
+            major >= 3 || !self.entry_point.has_64_bit(),
+            "The SMBIOS 64-bit entry point requires SMBIOS 3.0 or later"
+        );
+        if major >= 3 { SmbiosProvider::new(major, minor) } else { SmbiosProvider::new(3, 0) }
+    }
+}
+
+/// System memory ranges described by the resource descriptor HOBs.
+///
+/// Adjacent and overlapping ranges are merged, so each range is one contiguous block of system memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmbiosMemoryMap {
+    ranges: Vec<Range<u64>>,
+}
+
+impl SmbiosMemoryMap {
+    /// Creates a memory map from system memory ranges in any order.
+    pub fn new(ranges: impl IntoIterator<Item = Range<u64>>) -> Self {
+        let mut sorted: Vec<Range<u64>> = ranges.into_iter().filter(|range| !range.is_empty()).collect();
+        sorted.sort_by_key(|range| range.start);
+
+        let mut ranges: Vec<Range<u64>> = Vec::with_capacity(sorted.len());
+        for range in sorted {
+            match ranges.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => ranges.push(range),
+            }
+        }
+        Self { ranges }
+    }
+
+    /// Creates a memory map from the system memory resource descriptor HOBs in `hobs`.
+    pub fn from_hobs<'a>(hobs: impl IntoIterator<Item = &'a Hob<'a>>) -> Self {
+        Self::new(hobs.into_iter().filter_map(|hob| {
+            let descriptor = match hob {
+                Hob::ResourceDescriptor(descriptor) => *descriptor,
+                Hob::ResourceDescriptorV2(descriptor) => &descriptor.v1,
+                _ => return None,
+            };
+            (descriptor.resource_type == hob::EFI_RESOURCE_SYSTEM_MEMORY).then(|| {
+                descriptor.physical_start..descriptor.physical_start.saturating_add(descriptor.resource_length)
+            })
+        }))
+    }
+
+    /// Creates a memory map from the HOB list passed to the DXE core entry point.
+    ///
+    /// Returns an empty map if `hob_list` is null.
+    pub fn from_hob_list(hob_list: *const c_void) -> Self {
+        if hob_list.is_null() {
+            return Self::default();
+        }
+        let mut hobs = HobList::new();
+        hobs.discover_hobs(hob_list);
+        Self::from_hobs(&hobs)
+    }
+
+    /// Creates a memory map from the HOB list the DXE core installs in the UEFI Configuration Table.
+    ///
+    /// Returns an empty map if the HOB list is not installed.
+    pub fn from_configuration_table(boot_services: &StandardBootServices) -> Self {
+        match configuration_table(boot_services, &guids::HOB_LIST) {
+            Ok(Some(hob_list)) => Self::from_hob_list(hob_list),
+            Ok(None) => {
+                log::warn!("No HOB list in the Configuration Table, memory records are not built");
+                Self::default()
+            }
+            Err(err) => {
+                log::warn!("{err}, memory records are not built");
+                Self::default()
+            }
+        }
+    }
+
+    /// Returns the contiguous system memory ranges in ascending order.
+    pub fn ranges(&self) -> &[Range<u64>] {
+        &self.ranges
+    }
+
+    /// Returns the total system memory size in bytes.
+    pub fn total_size(&self) -> u64 {
+        self.ranges.iter().map(|range| range.end - range.start).sum()
+    }
+}
+
+/// Builds the string pool of a record, skipping empty strings that SMBIOS does not allow in the pool.
+#[derive(Debug, Default)]
+pub struct StringPool(Vec<String>);
+
+impl StringPool {
+    /// Creates an empty string pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a string and returns its 1-based string number, or 0 if the string is empty.
+    pub fn add(&mut self, string: &str) -> u8 {
+        if string.is_empty() {
+            return 0;
+        }
+        self.0.push(String::from(string));
+        self.0.len() as u8
+    }
+
+    /// Returns the strings for a record's `string_pool`.
+    pub fn into_strings(self) -> Vec<String> {
+        self.0
+    }
+}
+
+/// Handles of the system records added by [`SmbiosPlatformBuilder::add_system_records`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemRecordHandles {
+    /// Type 0 Platform Firmware Information
+    pub firmware: SmbiosHandle,
+    /// Type 1 System Information
+    pub system: SmbiosHandle,
+    /// Type 3 System Enclosure, or [`SMBIOS_HANDLE_NONE`] if it could not be added
+    pub chassis: SmbiosHandle,
+    /// Type 2 Baseboard Information, or [`SMBIOS_HANDLE_NONE`] if it could not be added
+    pub baseboard: SmbiosHandle,
+}
+
+/// A processor cache, as described by a Type 7 Cache Information record.
+#[derive(Debug, Clone, Copy)]
+pub struct SmbiosCache {
+    /// Cache level, starting at 1
+    pub level: u8,
+    /// Kind of data held by the cache
+    pub cache_type: SystemCacheType,
+    /// Size of the cache in bytes, over all of its instances in one socket
+    pub size: u64,
+    /// Associativity of the cache
+    pub associativity: AssociativityField,
+}
+
+impl SmbiosCache {
+    /// Returns the Type 7 associativity of a set-associative cache with `ways` ways.
+    pub fn set_associativity(ways: u32) -> AssociativityField {
+        match ways {
+            1 => AssociativityField::DirectMapped,
+            2 => AssociativityField::SetAssociative2Way,
+            4 => AssociativityField::SetAssociative4Way,
+            8 => AssociativityField::SetAssociative8Way,
+            12 => AssociativityField::SetAssociative12Way,
+            16 => AssociativityField::SetAssociative16Way,
+            20 => AssociativityField::SetAssociative20Way,
+            24 => AssociativityField::SetAssociative24Way,
+            32 => AssociativityField::SetAssociative32Way,
+            48 => AssociativityField::SetAssociative48Way,
+            64 => AssociativityField::SetAssociative64Way,
+            _ => AssociativityField::Other,
+        }
+    }
+}
+
+/// Builds and adds SMBIOS records for a QEMU platform.
+pub struct SmbiosPlatformBuilder<'a> {
+    smbios: &'a Service<dyn Smbios>,
+    config: &'a SmbiosPlatformConfig,
+    qemu: Option<&'a QemuSmbiosTables>,
+    uuid: Option<SystemUuid>,
+}
+
+impl<'a> SmbiosPlatformBuilder<'a> {
+    /// Creates a builder that adds records to `smbios` using the identity in `config`.
+    pub fn new(smbios: &'a Service<dyn Smbios>, config: &'a SmbiosPlatformConfig) -> Self {
+        Self { smbios, config, qemu: None, uuid: None }
+    }
+
+    /// Merges the SMBIOS tables provided by QEMU into the records.
+    pub fn with_qemu_tables(mut self, tables: &'a QemuSmbiosTables) -> Self {
+        self.qemu = Some(tables);
+        self
+    }
+
+    /// Sets the Type 1 system UUID. Without one, the UUID is reported as not present.
+    pub fn with_uuid(mut self, uuid: Option<SystemUuid>) -> Self {
+        self.uuid = uuid;
+        self
+    }
+
+    /// Returns the string QEMU provides for the field at `offset` of its `record_type` structure, or `default`.
+    fn qemu_string<'s>(&'s self, record_type: u8, offset: usize, default: &'s str) -> &'s str {
+        self.qemu_instance_string(record_type, 0, offset, default)
+    }
+
+    /// Returns the string QEMU provides for the field at `offset` of its `instance`th `record_type` structure, or
+    /// `default`. Platform components use it to merge the QEMU strings into the records they build, such as Type 4.
+    pub fn qemu_instance_string<'s>(
+        &'s self,
+        record_type: u8,
+        instance: usize,
+        offset: usize,
+        default: &'s str,
+    ) -> &'s str {
+        self.qemu
+            .and_then(|tables| tables.find_instance(record_type, instance))
+            .and_then(|record| record.string(offset))
+            .unwrap_or(default)
+    }
+
+    /// Returns the Type 0 Platform Firmware Information record.
+    pub fn firmware_information(&self) -> Type0PlatformFirmwareInformation {
+        let mut strings = StringPool::new();
+        Type0PlatformFirmwareInformation {
+            header: SmbiosTableHeader::new(0, 0, SMBIOS_HANDLE_PI_RESERVED),
+            vendor: strings.add(self.qemu_string(0, 0x04, "Patina Firmware")),
+            firmware_version: strings.add(self.qemu_string(0, 0x05, env!("CARGO_PKG_VERSION"))),
+            bios_starting_address_segment: 0xE800,
+            firmware_release_date: strings.add(self.qemu_string(0, 0x08, BUILD_DATE.unwrap_or("01/01/1970"))),
+            firmware_rom_size: 0xFF, // Use the extended ROM size
+            characteristics: BiosCharacteristics::new().with_pci_supported(true),
+            characteristics_ext1: BiosCharacteristicsExt1::new()
+                .with_acpi_supported(true)
+                .with_usb_legacy_supported(true)
+                .with_smart_battery_supported(true),
+            characteristics_ext2: BiosCharacteristicsExt2::new()
+                .with_bios_boot_specification_supported(true)
+                .with_uefi_spec_supported(true),
+            system_bios_major_release: 1,
+            system_bios_minor_release: 0,
+            embedded_controller_major_release: 0xFF,
+            embedded_controller_minor_release: 0xFF,
+            extended_bios_rom_size: ExtendedBiosRomSize::new(),
+            string_pool: strings.into_strings(),
+        }
+    }
+
+    /// Returns the Type 1 System Information record.
+    pub fn system_information(&self) -> Type1SystemInformation {
+        let config = self.config;
+        let mut strings = StringPool::new();
+        Type1SystemInformation {
+            header: SmbiosTableHeader::new(1, 0, SMBIOS_HANDLE_PI_RESERVED),
+            manufacturer: strings.add(self.qemu_string(1, 0x04, config.manufacturer)),
+            product_name: strings.add(self.qemu_string(1, 0x05, config.product_name)),
+            version: strings.add(self.qemu_string(1, 0x06, config.version)),
+            serial_number: strings.add(self.qemu_string(1, 0x07, config.serial_number)),
+            uuid: self.uuid.map_or([0; 16], |uuid| uuid.to_smbios_bytes()),
+            wake_up_type: WakeUpType::PowerSwitch,
+            sku_number: strings.add(self.qemu_string(1, 0x19, config.sku_number)),
+            family: strings.add(self.qemu_string(1, 0x1A, config.family)),
+            string_pool: strings.into_strings(),
+        }
+    }
+
+    /// Returns the Type 3 System Enclosure record.
+    pub fn system_enclosure(&self) -> Type3SystemEnclosure {
+        let config = self.config;
+        let mut strings = StringPool::new();
+        Type3SystemEnclosure {
+            header: SmbiosTableHeader::new(3, 0, SMBIOS_HANDLE_PI_RESERVED),
+            manufacturer: strings.add(self.qemu_string(3, 0x04, config.manufacturer)),
+            enclosure_type: config.chassis_type,
+            version: strings.add(self.qemu_string(3, 0x06, config.version)),
+            serial_number: strings.add(self.qemu_string(3, 0x07, config.chassis_serial_number)),
+            asset_tag_number: strings.add(self.qemu_string(3, 0x08, config.chassis_asset_tag)),
+            bootup_state: BootUpState::Safe,
+            power_supply_state: PowerSupplyState::Safe,
+            thermal_state: ThermalState::Safe,
+            security_status: SecurityStatus::Unknown,
+            oem_defined: 0,
+            height: 0,
+            number_of_power_cords: 1,
+            contained_element_count: 0,
+            contained_element_record_length: 0,
+            sku_number: strings.add(self.qemu_string(3, 0x15, "")),
+            string_pool: strings.into_strings(),
+        }
+    }
+
+    /// Returns the Type 2 Baseboard Information record for a baseboard in the chassis with `chassis_handle`.
+    pub fn baseboard_information(&self, chassis_handle: SmbiosHandle) -> Type2BaseboardInformation {
+        let config = self.config;
+        let mut strings = StringPool::new();
+        Type2BaseboardInformation {
+            header: SmbiosTableHeader::new(2, 0, SMBIOS_HANDLE_PI_RESERVED),
+            manufacturer: strings.add(self.qemu_string(2, 0x04, config.manufacturer)),
+            product: strings.add(self.qemu_string(2, 0x05, config.baseboard_product)),
+            version: strings.add(self.qemu_string(2, 0x06, config.version)),
+            serial_number: strings.add(self.qemu_string(2, 0x07, config.baseboard_serial_number)),
+            asset_tag: strings.add(self.qemu_string(2, 0x08, config.baseboard_asset_tag)),
+            feature_flags: FeatureFlags::new().with_hosting_board(true),
+            location_in_chassis: 0,
+            chassis_handle,
+            board_type: BoardType::Motherboard,
+            contained_object_handles: 0,
+            string_pool: strings.into_strings(),
+        }
+    }
+
+    /// Returns the Type 7 Cache Information record of a cache.
+    pub fn cache_information(&self, cache: &SmbiosCache) -> Type7CacheInformation {
+        let mut strings = StringPool::new();
+        let designation = match cache.cache_type {
+            SystemCacheType::Data => format!("L{} Data Cache", cache.level),
+            SystemCacheType::Instruction => format!("L{} Instruction Cache", cache.level),
+            _ => format!("L{} Cache", cache.level),
+        };
+        let size_kb = cache.size / 1024;
+
+        Type7CacheInformation {
+            header: SmbiosTableHeader::new(7, 0, SMBIOS_HANDLE_PI_RESERVED),
+            socket_designation: strings.add(&designation),
+            cache_configuration: CacheConfiguration::new()
+                .with_cache_level(cache.level.saturating_sub(1))
+                .with_enabled_disabled(true)
+                .with_operational_mode(1), // Write-back
+            maximum_cache_size: cache_size(size_kb),
+            installed_size: cache_size(size_kb),
+            supported_sram_type: CacheSramTypeData::new().with_unknown(true),
+            current_sram_type: CacheSramTypeData::new().with_unknown(true),
+            cache_speed: 0,
+            error_correction_type: CacheErrorCorrectionType::Unknown,
+            system_cache_type: cache.cache_type,
+            associativity: cache.associativity,
+            maximum_cache_size2: cache_size2(size_kb),
+            installed_size2: cache_size2(size_kb),
+            string_pool: strings.into_strings(),
+        }
+    }
+
+    /// Returns the Type 16 Physical Memory Array record for the system memory in `memory`.
+    pub fn physical_memory_array(&self, memory: &SmbiosMemoryMap) -> Type16PhysicalMemoryArray {
+        let total_kb = memory.total_size() / 1024;
+        let (maximum_capacity, extended_maximum_capacity) = match u32::try_from(total_kb) {
+            Ok(kb) if kb < MAXIMUM_CAPACITY_USE_EXTENDED => (kb, 0),
+            _ => (MAXIMUM_CAPACITY_USE_EXTENDED, memory.total_size()),
+        };
+        Type16PhysicalMemoryArray {
+            header: SmbiosTableHeader::new(16, 0, SMBIOS_HANDLE_PI_RESERVED),
+            location: MemoryArrayLocation::SystemBoard,
+            use_field: MemoryArrayUse::SystemMemory,
+            memory_error_correction: MemoryArrayErrorCorrectionType::NoEcc,
+            maximum_capacity,
+            memory_error_information_handle: SMBIOS_HANDLE_NO_ERROR_INFORMATION,
+            number_of_memory_devices: 1,
+            extended_maximum_capacity,
+            string_pool: Vec::new(),
+        }
+    }
+
+    /// Returns the Type 17 Memory Device record for a device holding all system memory in `memory`, in the array
+    /// with `array_handle`.
+    pub fn memory_device(&self, memory: &SmbiosMemoryMap, array_handle: SmbiosHandle) -> Type17MemoryDevice {
+        let mut strings = StringPool::new();
+        let total_size = memory.total_size();
+        let total_mb = total_size / (1024 * 1024);
+        let (size, extended_size) = match u16::try_from(total_mb) {
+            Ok(mb) if mb < MEMORY_SIZE_USE_EXTENDED => (mb, 0),
+            _ => (MEMORY_SIZE_USE_EXTENDED, total_mb.min(u32::MAX as u64) as u32),
+        };
+        Type17MemoryDevice {
+            header: SmbiosTableHeader::new(17, 0, SMBIOS_HANDLE_PI_RESERVED),
+            physical_memory_array_handle: array_handle,
+            memory_error_information_handle: SMBIOS_HANDLE_NO_ERROR_INFORMATION,
+            total_width: MEMORY_WIDTH_UNKNOWN,
+            data_width: MEMORY_WIDTH_UNKNOWN,
+            size,
+            form_factor: MemoryFormFactor::Dimm,
+            device_set: 0,
+            device_locator: strings.add(self.qemu_string(17, 0x10, "DIMM 0")),
+            bank_locator: strings.add(self.qemu_string(17, 0x11, "")),
+            memory_type: MemoryDeviceType::Ram,
+            type_detail: MemoryDeviceTypeDetails::new().with_other(true),
+            speed: 0, // Unknown
+            manufacturer: strings.add(self.qemu_string(17, 0x17, self.config.manufacturer)),
+            serial_number: strings.add(self.qemu_string(17, 0x18, "")),
+            asset_tag: strings.add(self.qemu_string(17, 0x19, "")),
+            part_number: strings.add(self.qemu_string(17, 0x1A, "")),
+            attributes: MemoryDeviceAttributes::new(),
+            extended_size,
+            configured_memory_clock_speed: 0,
+            minimum_voltage: 0,
+            maximum_voltage: 0,
+            configured_voltage: 0,
+            memory_technology: MemoryDeviceTechnology::Dram,
+            memory_operating_mode_capability: MemoryCapability::new().with_volatile_memory(true),
+            firmware_version: 0,
+            module_manufacturer_id: 0,
+            module_product_id: 0,
+            memory_subsystem_controller_manufacturer_id: 0,
+            memory_subsystem_controller_product_id: 0,
+            non_volatile_size: 0,
+            volatile_size: total_size,
+            cache_size: 0,
+            logical_size: 0,
+            extended_speed: 0,
+            extended_configured_memory_speed: 0,
+            pmic0_manufacturer_id: 0,
+            pmic0_revision_number: 0,
+            rcd_manufacturer_id: 0,
+            rcd_revision_number: 0,
+            string_pool: strings.into_strings(),
+        }
+    }
+
+    /// Returns the Type 19 Memory Array Mapped Address record for one contiguous range of the array with
+    /// `array_handle`.
+    pub fn memory_array_mapped_address(
+        &self,
+        range: &Range<u64>,
+        array_handle: SmbiosHandle,
+    ) -> Type19MemoryArrayMappedAddress {
+        // The addresses are in KB and the ending address is that of the last KB in the range
+        let start_kb = range.start / 1024;
+        let end_kb = (range.end - 1) / 1024;
+        let (starting_address, ending_address, extended_starting_address, extended_ending_address) =
+            match (u32::try_from(start_kb), u32::try_from(end_kb)) {
+                (Ok(start), Ok(end)) if end < MAPPED_ADDRESS_USE_EXTENDED => (start, end, 0, 0),
+                _ => (MAPPED_ADDRESS_USE_EXTENDED, MAPPED_ADDRESS_USE_EXTENDED, range.start, range.end - 1),
+            };
+        Type19MemoryArrayMappedAddress {
+            header: SmbiosTableHeader::new(19, 0, SMBIOS_HANDLE_PI_RESERVED),
+            starting_address,
+            ending_address,
+            memory_array_handle: array_handle,
+            partition_width: 1,
+            extended_starting_address,
+            extended_ending_address,
+            string_pool: Vec::new(),
+        }
+    }
+
+    /// Returns the Type 11 OEM Strings record for the configured OEM strings, or `None` if there are none.
+    pub fn oem_strings(&self) -> Option<Type11OemStrings> {
+        let (count, string_pool) = string_list(self.config.oem_strings)?;
+        Some(Type11OemStrings { header: SmbiosTableHeader::new(11, 0, SMBIOS_HANDLE_PI_RESERVED), count, string_pool })
+    }
+
+    /// Returns the Type 12 System Configuration Options record for the configured options, or `None` if there are
+    /// none.
+    pub fn system_configuration_options(&self) -> Option<Type12SystemConfigurationOptions> {
+        let (count, string_pool) = string_list(self.config.configuration_options)?;
+        Some(Type12SystemConfigurationOptions {
+            header: SmbiosTableHeader::new(12, 0, SMBIOS_HANDLE_PI_RESERVED),
+            count,
+            string_pool,
+        })
+    }
+
+    /// Returns the Type 32 System Boot Information record for `status`, one of [`boot_status`].
+    pub fn system_boot_information(&self, status: u8) -> Type32SystemBootInformation {
+        Type32SystemBootInformation {
+            header: SmbiosTableHeader::new(32, 0, SMBIOS_HANDLE_PI_RESERVED),
+            reserved: [0; 6],
+            boot_status: status,
+            string_pool: Vec::new(),
+        }
+    }
+
+    /// Returns a Type 45 Firmware Inventory Information record for a firmware component that cannot be updated on its
+    /// own.
+    pub fn firmware_inventory(
+        &self,
+        name: &str,
+        id: &str,
+        version: &str,
+        release_date: &str,
+        manufacturer: &str,
+        associated_components: Vec<SmbiosHandle>,
+    ) -> Type45FirmwareInventoryInformation {
+        let mut strings = StringPool::new();
+        Type45FirmwareInventoryInformation {
+            header: SmbiosTableHeader::new(45, 0, SMBIOS_HANDLE_PI_RESERVED),
+            firmware_component_name: strings.add(name),
+            firmware_version: strings.add(version),
+            version_format: FIRMWARE_FORMAT_FREE_FORM,
+            firmware_id: strings.add(id),
+            firmware_id_format: FIRMWARE_FORMAT_FREE_FORM,
+            release_date: strings.add(release_date),
+            manufacturer: strings.add(manufacturer),
+            lowest_supported_version: 0,
+            image_size: FIRMWARE_IMAGE_SIZE_UNKNOWN,
+            characteristics: 0,
+            state: FIRMWARE_STATE_ENABLED,
+            associated_components,
+            string_pool: strings.into_strings(),
+        }
+    }
+
+    /// Returns the Type 9 System Slots record for a PCI Express root port.
+    ///
+    /// The slot ID is the physical slot number of the port, or `ordinal` if the slot is not numbered.
+    pub fn system_slot(&self, function: &PciFunction, port: &PcieCapability, ordinal: u16) -> Type9SystemSlots {
+        let slot_id = match port.physical_slot_number() {
+            0 => ordinal,
+            number => number,
+        };
+        let current_usage = match (port.slot_implemented(), port.presence_detected()) {
+            (true, true) => CurrentUsage::InUse,
+            (true, false) => CurrentUsage::Available,
+            (false, _) => CurrentUsage::Unknown,
+        };
+
+        let mut strings = StringPool::new();
+        Type9SystemSlots {
+            header: SmbiosTableHeader::new(9, 0, SMBIOS_HANDLE_PI_RESERVED),
+            slot_designation: strings.add(&format!("PCIe Slot {slot_id}")),
+            slot_type: port.slot_type(),
+            slot_data_bus_width: port.slot_width(),
+            current_usage,
+            slot_length: SlotLength::Unknown,
+            slot_id,
+            slot_characteristics1: SlotCharacteristics1::new().with_provides_3_volts(true),
+            slot_characteristics2: SlotCharacteristics2::new()
+                .with_pci_supports_pme(true)
+                .with_supports_hotplug(port.hot_plug_capable())
+                .with_supports_async_removal(port.hot_plug_surprise()),
+            segment_group_number: pci::SEGMENT,
+            bus_number: pci::ROOT_BUS,
+            device_function_number: device_function_number(function),
+            data_bus_width: port.max_link_width(),
+            peer_grouping_count: 0,
+            slot_information: port.max_link_speed(),
+            slot_physical_width: port.slot_width(),
+            slot_pitch: 0,
+            slot_height: SLOT_HEIGHT_UNKNOWN,
+            string_pool: strings.into_strings(),
+        }
+    }
+
+    /// Returns the Type 41 Onboard Devices Extended Information record for an integrated function.
+    ///
+    /// Returns `None` for bridges, which are not onboard devices.
+    pub fn onboard_device(&self, function: &PciFunction, instance: u8) -> Option<Type41OnboardDevicesExtended> {
+        let (device_type, name) = function.onboard_device_type()?;
+
+        let mut strings = StringPool::new();
+        Some(Type41OnboardDevicesExtended {
+            header: SmbiosTableHeader::new(41, 0, SMBIOS_HANDLE_PI_RESERVED),
+            reference_designation: strings.add(&format!("Onboard {name} {instance}")),
+            device_type: ONBOARD_DEVICE_ENABLED | device_type,
+            device_type_instance: instance,
+            segment_group_number: pci::SEGMENT,
+            bus_number: pci::ROOT_BUS,
+            device_function_number: device_function_number(function),
+            string_pool: strings.into_strings(),
+        })
+    }
+
+    /// Serializes `record` for the configured SMBIOS version, dropping the fields later versions add.
+    ///
+    /// The dropped fields must not reference strings.
+    pub fn record_bytes<T: SmbiosRecordStructure>(&self, record: &T) -> Vec<u8> {
+        let mut bytes = record.to_bytes();
+        let length = bytes[1] as usize;
+        if let Some(version_length) = compliance::structure_length(self.config.smbios_version, &bytes[..length])
+            && version_length < length
+        {
+            bytes.drain(version_length..length);
+            bytes[1] = version_length as u8;
+        }
+        bytes
+    }
+
+    /// Adds a record, logging `name` and the assigned handle.
+    ///
+    /// Returns `None` if the record could not be added. Use [`Self::add_required_record`] for records the table must
+    /// not be published without.
+    pub fn add_record<T: SmbiosRecordStructure>(&self, name: &str, record: &T) -> Option<SmbiosHandle> {
+        match self.smbios.add_from_bytes(None, &self.record_bytes(record)) {
+            Ok(handle) => {
+                log::trace!("  {name} - Handle 0x{handle:04X}");
+                Some(handle)
+            }
+            Err(e) => {
+                log::warn!("  Failed to add {name}: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Adds a record that is required by the SMBIOS specification.
+    ///
+    /// ## Errors
+    ///
+    /// - The error returned by the SMBIOS service if the record could not be added.
+    pub fn add_required_record<T: SmbiosRecordStructure>(
+        &self,
+        name: &str,
+        record: &T,
+    ) -> patina::error::Result<SmbiosHandle> {
+        let handle = self.smbios.add_from_bytes(None, &self.record_bytes(record)).inspect_err(|e| {
+            log::error!("Failed to add required {name}: {e:?}");
+        })?;
+        log::trace!("  {name} - Handle 0x{handle:04X}");
+        Ok(handle)
+    }
+
+    /// Adds the Type 0, 1, 3 and 2 records that describe the system.
+    ///
+    /// ## Errors
+    ///
+    /// - The error returned by the SMBIOS service if the required Type 0 or Type 1 record could not be added
+    ///   (SMBIOS specification section 6.2).
+    pub fn add_system_records(&self) -> patina::error::Result<SystemRecordHandles> {
+        let firmware = self.add_required_record("Type 0 (BIOS Info)", &self.firmware_information())?;
+        let system = self.add_required_record("Type 1 (System Info)", &self.system_information())?;
+        let chassis =
+            self.add_record("Type 3 (System Enclosure)", &self.system_enclosure()).unwrap_or(SMBIOS_HANDLE_NONE);
+        let baseboard = self
+            .add_record("Type 2 (Base Board Info)", &self.baseboard_information(chassis))
+            .unwrap_or(SMBIOS_HANDLE_NONE);
+
+        Ok(SystemRecordHandles { firmware, system, chassis, baseboard })
+    }
+
+    /// Adds the Type 7 records of the caches of a socket.
+    ///
+    /// Returns the handles of the L1, L2 and L3 caches for the Type 4 record of the socket, which links one cache per
+    /// level. A level with several caches, such as a split L1, is linked through its first cache.
+    pub fn add_cache_records(&self, socket: u8, caches: &[SmbiosCache]) -> [SmbiosHandle; 3] {
+        let mut cache_handles = [SMBIOS_HANDLE_NONE; 3];
+        for cache in caches {
+            let name = format!("Type 7 (CPU {socket} L{} Cache)", cache.level);
+            if let Some(handle) = self.add_record(&name, &self.cache_information(cache))
+                && let Some(slot) = cache_handles.get_mut((cache.level as usize).wrapping_sub(1))
+                && *slot == SMBIOS_HANDLE_NONE
+            {
+                *slot = handle;
+            }
+        }
+        cache_handles
+    }
+
+    /// Adds the Type 16 record for the system memory in `memory`, its Type 17 memory device and a Type 19 record for
+    /// each contiguous range.
+    ///
+    /// Nothing is added if `memory` is empty.
+    pub fn add_memory_records(&self, memory: &SmbiosMemoryMap) {
+        if memory.ranges().is_empty() {
+            log::warn!("  No system memory ranges, skipping the memory records");
+            return;
+        }
+        log::debug!("System memory: {:#X} bytes in {} range(s)", memory.total_size(), memory.ranges().len());
+
+        let Some(array_handle) =
+            self.add_record("Type 16 (Physical Memory Array)", &self.physical_memory_array(memory))
+        else {
+            log::warn!("  Skipping Type 17 and Type 19 because Type 16 was not added");
+            return;
+        };
+        self.add_record("Type 17 (Memory Device)", &self.memory_device(memory, array_handle));
+        for range in memory.ranges() {
+            self.add_record(
+                "Type 19 (Memory Array Mapped Address)",
+                &self.memory_array_mapped_address(range, array_handle),
+            );
+        }
+    }
+
+    /// Adds the Type 11 and Type 12 records for the configured strings.
+    pub fn add_oem_records(&self) {
+        if let Some(record) = self.oem_strings() {
+            self.add_record("Type 11 (OEM Strings)", &record);
+        }
+        if let Some(record) = self.system_configuration_options() {
+            self.add_record("Type 12 (System Configuration Options)", &record);
+        }
+    }
+
+    /// Adds the Type 32 record reporting `status`, one of [`boot_status`], for the previous boot.
+    pub fn add_boot_information(&self, status: u8) {
+        self.add_record("Type 32 (System Boot Info)", &self.system_boot_information(status));
+    }
+
+    /// Adds the Type 45 records for the DXE core, associated with the Type 0 record with handle `firmware`, and for
+    /// each patina crate, associated with the DXE core record.
+    ///
+    /// Nothing is added for SMBIOS versions before 3.5, which introduced Type 45.
+    pub fn add_firmware_inventory(&self, firmware: SmbiosHandle) {
+        if self.config.smbios_version < (3, 5) {
+            return;
+        }
+        let release_date = BUILD_DATE.and_then(iso_release_date).unwrap_or_default();
+        let vendor = self.qemu_string(0, 0x04, "Patina Firmware");
+        let core = self.firmware_inventory(
+            "Patina DXE Core",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            &release_date,
+            vendor,
+            vec![firmware],
+        );
+        let Some(core) = self.add_record("Type 45 (DXE Core Firmware Inventory)", &core) else {
+            return;
+        };
+
+        for (name, version) in PATINA_CRATE_VERSIONS.split(',').filter_map(|entry| entry.split_once(' ')) {
+            let record = self.firmware_inventory(name, name, version, "", PATINA_MANUFACTURER, vec![core]);
+            self.add_record(&format!("Type 45 ({name} Firmware Inventory)"), &record);
+        }
+    }
+
+    /// Adds a Type 9 record for each PCI Express root port and a Type 41 record for each integrated function on the
+    /// root bus.
+    ///
+    /// If QEMU provides records of either type, for example through `-smbios type=41`, the platform does not build
+    /// records of that type and [`Self::add_qemu_records`] adds the QEMU ones instead.
+    pub fn add_pci_records(&self, bus: &PciRootBus) {
+        let qemu_provides = |record_type| self.qemu.and_then(|tables| tables.find(record_type)).is_some();
+
+        if qemu_provides(9) {
+            log::debug!("  QEMU provides Type 9, skipping the root port slots");
+        } else {
+            for (ordinal, (function, port)) in (1..).zip(bus.root_ports()) {
+                self.add_record("Type 9 (System Slot)", &self.system_slot(function, port, ordinal));
+            }
+        }
+
+        if qemu_provides(41) {
+            log::debug!("  QEMU provides Type 41, skipping the onboard devices");
+            return;
+        }
+        let mut instances = [0u8; 0x80];
+        for function in bus.functions() {
+            let Some((device_type, _)) = function.onboard_device_type() else {
+                continue;
+            };
+            let instance = &mut instances[device_type as usize];
+            *instance = instance.saturating_add(1);
+            if let Some(record) = self.onboard_device(function, *instance) {
+                self.add_record("Type 41 (Onboard Device)", &record);
+            }
+        }
+    }
+
+    /// Adds the QEMU structures of types the platform does not build, such as Type 11 OEM Strings.
+    ///
+    /// Each structure gets a new handle. Structures the SMBIOS service rejects are skipped.
+    pub fn add_qemu_records(&self) {
+        let Some(tables) = self.qemu else {
+            return;
+        };
+        for record in tables.records() {
+            let record_type = record.record_type();
+            if PLATFORM_TYPES.contains(&record_type) {
+                continue;
+            }
+            let mut bytes = record.bytes().to_vec();
+            bytes[2..4].copy_from_slice(&SMBIOS_HANDLE_PI_RESERVED.to_le_bytes());
+            match self.smbios.add_from_bytes(None, &bytes) {
+                Ok(handle) => log::trace!("  QEMU Type {record_type} - Handle 0x{handle:04X}"),
+                Err(e) => log::warn!("  Failed to add QEMU Type {record_type}: {e:?}"),
+            }
+        }
+    }
+
+    /// Publishes the SMBIOS table to the UEFI Configuration Table through the configured entry points.
+    ///
+    /// ## Errors
+    ///
+    /// - The error returned by the SMBIOS service if the table could not be published.
+    pub fn publish(&self, boot_services: &StandardBootServices) -> patina::error::Result<()> {
+        log::debug!("Publishing SMBIOS table...");
+        let (table_addr, entry_point_addr) = self.smbios.publish_table().inspect_err(|e| {
+            log::error!("Failed to publish SMBIOS table: {e:?}");
+        })?;
+        log::debug!("SMBIOS table published successfully");
+        log::debug!("  Entry Point: 0x{entry_point_addr:X}");
+        log::debug!("  Table Data: 0x{table_addr:X}");
+        entry_point::publish(boot_services, self.smbios, self.config.smbios_version, self.config.entry_point);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use std::boxed::Box;
+
+    use patina_smbios::{error::SmbiosError, smbios_types::SlotType};
+
+    use super::*;
+
+    /// An SMBIOS service that rejects every request. The record builders do not use the service.
+    struct UnusedSmbios;
+
+    impl Smbios for UnusedSmbios {
+        fn version(&self) -> (u8, u8) {
+            (3, 9)
+        }
+
+        fn publish_table(&self) -> Result<(u64, u64), SmbiosError> {
+            Err(SmbiosError::NoRecordsAvailable)
+        }
+
+        fn update_string(
+            &self,
+            _handle: SmbiosHandle,
+            _string_number: usize,
+            _string: &str,
+        ) -> Result<(), SmbiosError> {
+            Err(SmbiosError::RecordNotFound)
+        }
+
+        fn remove(&self, _handle: SmbiosHandle) -> Result<(), SmbiosError> {
+            Err(SmbiosError::RecordNotFound)
+        }
+
+        fn add_from_bytes(
+            &self,
+            _producer: Option<r_efi::efi::Handle>,
+            _bytes: &[u8],
+        ) -> Result<SmbiosHandle, SmbiosError> {
+            Err(SmbiosError::RecordTooSmall)
+        }
+    }
+
+    fn smbios() -> Service<dyn Smbios> {
+        Service::mock(Box::new(UnusedSmbios))
+    }
+
+    #[test]
+    fn test_string_pool_skips_empty_strings() {
+        let mut strings = StringPool::new();
+
+        assert_eq!(strings.add("QEMU"), 1);
+        assert_eq!(strings.add(""), 0);
+        assert_eq!(strings.add("Q35"), 2);
+        assert_eq!(strings.into_strings(), ["QEMU", "Q35"]);
+    }
+
+    #[test]
+    fn test_cache_information() {
+        let config = SmbiosPlatformConfig::default();
+        let smbios = smbios();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+
+        let cache = SmbiosCache {
+            level: 1,
+            cache_type: SystemCacheType::Data,
+            size: 4 * 48 * 1024,
+            associativity: SmbiosCache::set_associativity(12),
+        };
+        let l1d = builder.cache_information(&cache);
+        assert_eq!(l1d.cache_configuration.cache_level(), 0);
+        assert_eq!(l1d.installed_size.max_size(), 4 * 48);
+        assert_eq!(l1d.installed_size2.max_size(), 4 * 48);
+        assert!(matches!(l1d.system_cache_type, SystemCacheType::Data));
+        assert!(matches!(l1d.associativity, AssociativityField::SetAssociative12Way));
+        assert!(matches!(l1d.error_correction_type, CacheErrorCorrectionType::Unknown));
+        assert_eq!(l1d.string_pool, ["L1 Data Cache"]);
+
+        let cache = SmbiosCache {
+            level: 3,
+            cache_type: SystemCacheType::Unified,
+            size: 64 * 1024 * 1024,
+            associativity: SmbiosCache::set_associativity(15),
+        };
+        let l3 = builder.cache_information(&cache);
+        assert_eq!(l3.cache_configuration.cache_level(), 2);
+        assert!(l3.installed_size.granularity());
+        assert_eq!(l3.installed_size.max_size(), 1024);
+        assert!(!l3.installed_size2.granularity());
+        assert_eq!(l3.installed_size2.max_size(), 64 * 1024);
+        assert!(matches!(l3.associativity, AssociativityField::Other));
+        assert_eq!(l3.string_pool, ["L3 Cache"]);
+    }
+
+    #[test]
+    fn test_cache_size_encoding() {
+        assert_eq!(cache_size(0x7FFF).max_size(), 0x7FFF);
+        assert!(!cache_size(0x7FFF).granularity());
+        assert_eq!(cache_size(0x8000).max_size(), 0x200);
+        assert!(cache_size(0x8000).granularity());
+        assert_eq!(cache_size(u64::MAX / 1024).max_size(), 0x7FFF);
+
+        assert!(!cache_size2(0x7FFF_FFFF).granularity());
+        assert_eq!(cache_size2(0x8000_0000).max_size(), 0x0200_0000);
+        assert!(cache_size2(0x8000_0000).granularity());
+    }
+
+    #[test]
+    fn test_system_information_uses_config() {
+        let config = SmbiosPlatformConfig {
+            product_name: "Test Machine",
+            serial_number: "SN-1",
+            sku_number: "",
+            family: "Test Family",
+            ..Default::default()
+        };
+        let smbios = smbios();
+        let system = SmbiosPlatformBuilder::new(&smbios, &config).system_information();
+
+        assert_eq!(system.string_pool, ["QEMU", "Test Machine", "1.0", "SN-1", "Test Family"]);
+        assert_eq!((system.product_name, system.serial_number, system.sku_number, system.family), (2, 4, 0, 5));
+        assert!(system.validate().is_ok());
+    }
+
+    #[test]
+    fn test_system_uuid_uses_smbios_byte_order() {
+        let config = SmbiosPlatformConfig::default();
+        let smbios = smbios();
+        assert_eq!(SmbiosPlatformBuilder::new(&smbios, &config).system_information().uuid, [0; 16]);
+
+        let uuid = SystemUuid::from_bytes([
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF,
+        ]);
+        let system = SmbiosPlatformBuilder::new(&smbios, &config).with_uuid(uuid).system_information();
+        assert_eq!(
+            system.uuid,
+            [0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_chassis_and_baseboard_use_config() {
+        let config = SmbiosPlatformConfig {
+            chassis_type: chassis_type::RACK_MOUNT_CHASSIS,
+            chassis_asset_tag: "ASSET-1",
+            baseboard_product: "Test Board",
+            baseboard_serial_number: "MB-1",
+            ..Default::default()
+        };
+        let smbios = smbios();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+
+        let chassis = builder.system_enclosure();
+        assert_eq!(chassis.enclosure_type, chassis_type::RACK_MOUNT_CHASSIS);
+        assert_eq!((chassis.serial_number, chassis.asset_tag_number), (0, 3));
+        assert_eq!(chassis.string_pool, ["QEMU", "1.0", "ASSET-1"]);
+
+        let baseboard = builder.baseboard_information(0x10);
+        assert_eq!(baseboard.chassis_handle, 0x10);
+        assert_eq!(baseboard.string_pool, ["QEMU", "Test Board", "1.0", "MB-1"]);
+        assert_eq!(baseboard.asset_tag, 0);
+    }
+
+    #[test]
+    fn test_qemu_strings_override_config() {
+        let type1 = qemu::tests::structure(
+            1,
+            0x100,
+            &[
+                1, 2, 0, 3, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF,
+                0, 6, 0, 0,
+            ],
+            &["QEMU", "Standard PC (Q35 + ICH9, 2009)", "CI-SERIAL-7"],
+        );
+        let type3 = qemu::tests::structure(3, 0x300, &[0, 1, 0, 0, 1], &["CHASSIS-1"]);
+        let tables = [type1, type3].concat();
+        let tables = QemuSmbiosTables::parse(&qemu::tests::anchor_64(tables.len() as u32), &tables);
+        let config = SmbiosPlatformConfig { product_name: "Test Machine", sku_number: "SKU-1", ..Default::default() };
+        let smbios = smbios();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config).with_qemu_tables(&tables);
+
+        let system = builder.system_information();
+        assert_eq!(
+            system.string_pool,
+            ["QEMU", "Standard PC (Q35 + ICH9, 2009)", "1.0", "CI-SERIAL-7", "SKU-1", "Virtual Machine"]
+        );
+
+        let chassis = builder.system_enclosure();
+        assert_eq!(chassis.string_pool, ["QEMU", "1.0", "CHASSIS-1"]);
+        assert_eq!((chassis.serial_number, chassis.asset_tag_number), (0, 3));
+
+        // QEMU did not provide Types 0 and 2, so they keep the platform strings
+        assert_eq!(builder.firmware_information().string_pool[0], "Patina Firmware");
+        assert_eq!(builder.baseboard_information(0x10).string_pool, ["QEMU", "Virtual Machine", "1.0"]);
+    }
+
+    #[test]
+    fn test_oem_and_configuration_option_records() {
+        let smbios = smbios();
+        let config = SmbiosPlatformConfig::default();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+        assert!(builder.oem_strings().is_none());
+        assert!(builder.system_configuration_options().is_none());
+
+        let config = SmbiosPlatformConfig {
+            oem_strings: &["commit=0123abc", "", "job=42"],
+            configuration_options: &["profile=smoke"],
+            ..Default::default()
+        };
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+
+        let oem = builder.oem_strings().unwrap();
+        assert_eq!(oem.count, 2);
+        assert_eq!(oem.string_pool, ["commit=0123abc", "job=42"]);
+        assert_eq!(oem.to_bytes(), b"\x0B\x05\xFE\xFF\x02commit=0123abc\0job=42\0\0");
+
+        let options = builder.system_configuration_options().unwrap();
+        assert_eq!(options.count, 1);
+        assert_eq!(options.to_bytes(), b"\x0C\x05\xFE\xFF\x01profile=smoke\0\0");
+
+        let many = [std::vec!["option"; 300], std::vec![""]].concat();
+        let (count, strings) = string_list(&many).unwrap();
+        assert_eq!((count, strings.len()), (255, 255));
+    }
+
+    #[test]
+    fn test_boot_information_and_firmware_inventory_records() {
+        let smbios = smbios();
+        let config = SmbiosPlatformConfig::default();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+
+        let boot = builder.system_boot_information(boot_status::WATCHDOG_TIMER_EXPIRED);
+        assert_eq!(boot.to_bytes(), b"\x20\x0B\xFE\xFF\0\0\0\0\0\0\x08\0\0");
+
+        let inventory =
+            builder.firmware_inventory("core", "qemu_dxe_core", "1.2.3", "", "Vendor", std::vec![0x10, 0x20]);
+        assert!(inventory.validate().is_ok());
+        let bytes = inventory.to_bytes();
+        assert_eq!(&bytes[..2], b"\x2D\x1C");
+        assert_eq!(&bytes[4..12], &[1, 2, FIRMWARE_FORMAT_FREE_FORM, 3, FIRMWARE_FORMAT_FREE_FORM, 0, 4, 0]);
+        assert_eq!(&bytes[0x0C..0x14], &u64::MAX.to_le_bytes());
+        assert_eq!(&bytes[0x16..0x1C], &[FIRMWARE_STATE_ENABLED, 2, 0x10, 0, 0x20, 0]);
+        assert_eq!(&bytes[0x1C..], b"core\x001.2.3\0qemu_dxe_core\0Vendor\0\0");
+
+        let crates: std::vec::Vec<_> =
+            PATINA_CRATE_VERSIONS.split(',').filter_map(|entry| entry.split_once(' ')).collect();
+        assert!(crates.iter().any(|(name, _)| *name == "patina_dxe_core"));
+        assert!(crates.iter().all(|(name, version)| name.starts_with("patina") && !version.is_empty()));
+    }
+
+    #[test]
+    fn test_iso_release_date() {
+        assert_eq!(iso_release_date("10/18/2026").as_deref(), Some("2026-10-18"));
+        assert_eq!(iso_release_date("2026-10-18"), None);
+        assert_eq!(iso_release_date("1/2/2026"), None);
+        assert_eq!(iso_release_date("10/18/2026/1"), None);
+    }
+
+    #[test]
+    fn test_pci_records() {
+        let mut config = pci::tests::FakeConfigSpace::default();
+        config.add_function(1, 0, 0x02_00_00);
+        config.add_function(2, 0, 0x02_00_00);
+        config.add_root_port(3, 0, (2, 8), 0, true);
+        config.add_root_port(3, 1, (4, 16), 7, false);
+        let bus = config.scan();
+        let smbios = smbios();
+        let config = SmbiosPlatformConfig::default();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+
+        let ethernet = builder.onboard_device(&bus.functions()[1], 2).unwrap();
+        assert_eq!(ethernet.device_type, 0x80 | pci::onboard_device_type::ETHERNET);
+        assert_eq!(ethernet.device_type_instance, 2);
+        assert_eq!((ethernet.segment_group_number, ethernet.bus_number), (0, 0));
+        assert_eq!(ethernet.device_function_number.device_number(), 2);
+        assert_eq!(ethernet.string_pool, ["Onboard Ethernet 2"]);
+        assert_eq!(ethernet.to_bytes()[1], 0x0B);
+
+        let ports: Vec<_> = bus.root_ports().collect();
+        let (function, port) = ports[0];
+        assert!(builder.onboard_device(function, 1).is_none());
+        let slot = builder.system_slot(function, port, 1);
+        assert_eq!(slot.slot_id, 1);
+        assert_eq!(slot.string_pool, ["PCIe Slot 1"]);
+        assert!(matches!(slot.slot_type, SlotType::PciExpressGen2x8));
+        assert!(matches!(slot.current_usage, CurrentUsage::InUse));
+        assert_eq!((slot.data_bus_width, slot.slot_information), (8, 2));
+        assert_eq!(slot.device_function_number.device_number(), 3);
+        assert!(slot.slot_characteristics2.supports_hotplug());
+        assert_eq!(slot.to_bytes()[1], 0x18);
+
+        let (function, port) = ports[1];
+        let slot = builder.system_slot(function, port, 2);
+        assert_eq!(slot.slot_id, 7);
+        assert_eq!(slot.string_pool, ["PCIe Slot 7"]);
+        assert!(matches!(slot.slot_type, SlotType::PciExpressGen4x16));
+        assert!(matches!(slot.current_usage, CurrentUsage::Available));
+        assert_eq!(slot.device_function_number.function_number(), 1);
+    }
+
+    fn resource_descriptor(resource_type: u32, physical_start: u64, resource_length: u64) -> hob::ResourceDescriptor {
+        hob::ResourceDescriptor {
+            header: hob::header::Hob {
+                r#type: hob::RESOURCE_DESCRIPTOR,
+                length: core::mem::size_of::<hob::ResourceDescriptor>() as u16,
+                reserved: 0,
+            },
+            owner: patina::guids::ZERO,
+            resource_type,
+            resource_attribute: 0,
+            physical_start,
+            resource_length,
+        }
+    }
+
+    #[test]
+    fn test_memory_map_merges_contiguous_ranges() {
+        let memory = SmbiosMemoryMap::new([0x1_0000_0000..0x1_8000_0000, 0x10_0000..0x8000_0000, 0..0xA_0000, 0..0]);
+        assert_eq!(memory.ranges(), [0..0xA_0000, 0x10_0000..0x8000_0000, 0x1_0000_0000..0x1_8000_0000]);
+
+        let memory = SmbiosMemoryMap::new([0x10_0000..0x4000_0000, 0..0x10_0000, 0x2000_0000..0x3000_0000]);
+        assert_eq!(memory.ranges().len(), 1);
+        assert_eq!(memory.ranges()[0], 0..0x4000_0000);
+        assert_eq!(memory.total_size(), 0x4000_0000);
+    }
+
+    #[test]
+    fn test_memory_map_uses_system_memory_descriptors() {
+        let low = resource_descriptor(hob::EFI_RESOURCE_SYSTEM_MEMORY, 0, 0x8000_0000);
+        let mmio = resource_descriptor(hob::EFI_RESOURCE_MEMORY_MAPPED_IO, 0xE000_0000, 0x1000_0000);
+        let high = hob::ResourceDescriptorV2::from(resource_descriptor(
+            hob::EFI_RESOURCE_SYSTEM_MEMORY,
+            0x1_0000_0000,
+            0x8000_0000,
+        ));
+        let hobs = [Hob::ResourceDescriptor(&low), Hob::ResourceDescriptor(&mmio), Hob::ResourceDescriptorV2(&high)];
+
+        let memory = SmbiosMemoryMap::from_hobs(&hobs);
+        assert_eq!(memory.ranges(), [0..0x8000_0000, 0x1_0000_0000..0x1_8000_0000]);
+        assert_eq!(memory.total_size(), 0x1_0000_0000);
+    }
+
+    #[test]
+    fn test_memory_records_describe_the_memory_map() {
+        let config = SmbiosPlatformConfig::default();
+        let smbios = smbios();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+        let memory = SmbiosMemoryMap::new([0..0x8000_0000, 0x1_0000_0000..0x1_8000_0000]);
+
+        let array = builder.physical_memory_array(&memory);
+        assert_eq!((array.maximum_capacity, array.extended_maximum_capacity), (0x40_0000, 0));
+        assert!(array.validate().is_ok());
+
+        let device = builder.memory_device(&memory, 0x20);
+        assert_eq!(device.physical_memory_array_handle, 0x20);
+        assert_eq!((device.size, device.extended_size, device.volatile_size), (4096, 0, 0x1_0000_0000));
+        assert_eq!(device.string_pool, ["DIMM 0", "QEMU"]);
+
+        let high = builder.memory_array_mapped_address(&memory.ranges()[1], 0x20);
+        assert_eq!((high.starting_address, high.ending_address), (0x40_0000, 0x5F_FFFF));
+        assert_eq!((high.extended_starting_address, high.extended_ending_address), (0, 0));
+    }
+
+    #[test]
+    fn test_memory_records_use_extended_fields_for_large_memory() {
+        const TB: u64 = 1 << 40;
+        let config = SmbiosPlatformConfig::default();
+        let smbios = smbios();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+        let memory = SmbiosMemoryMap::new([0..0x8000_0000, 0x1_0000_0000..0x1_0000_0000 + 5 * TB]);
+
+        let array = builder.physical_memory_array(&memory);
+        assert_eq!(array.maximum_capacity, MAXIMUM_CAPACITY_USE_EXTENDED);
+        assert_eq!(array.extended_maximum_capacity, 5 * TB + 0x8000_0000);
+
+        let device = builder.memory_device(&memory, 0x20);
+        assert_eq!(device.size, MEMORY_SIZE_USE_EXTENDED);
+        assert_eq!(device.extended_size, (5 * TB / (1024 * 1024)) as u32 + 2048);
+
+        let low = builder.memory_array_mapped_address(&memory.ranges()[0], 0x20);
+        assert_eq!((low.starting_address, low.ending_address), (0, 0x1F_FFFF));
+
+        let high = builder.memory_array_mapped_address(&memory.ranges()[1], 0x20);
+        assert_eq!(
+            (high.starting_address, high.ending_address),
+            (MAPPED_ADDRESS_USE_EXTENDED, MAPPED_ADDRESS_USE_EXTENDED)
+        );
+        assert_eq!(high.extended_starting_address, 0x1_0000_0000);
+        assert_eq!(high.extended_ending_address, 0x1_0000_0000 + 5 * TB - 1);
+    }
+
+    #[test]
+    fn test_records_are_cut_to_the_configured_version() {
+        let config = SmbiosPlatformConfig { smbios_version: (2, 8), ..Default::default() };
+        let smbios = smbios();
+        let builder = SmbiosPlatformBuilder::new(&smbios, &config);
+        let memory = SmbiosMemoryMap::new(Some(0..0x8000_0000));
+
+        let device = builder.memory_device(&memory, 0x20);
+        let (full, cut) = (device.to_bytes(), builder.record_bytes(&device));
+        assert_eq!((full[1], cut[1]), (0x64, 0x28));
+        assert_eq!(cut[2..0x28], full[2..0x28]);
+        assert_eq!(cut[0x28..], full[0x64..]);
+
+        // Types without version-specific layouts are unchanged
+        let record = builder.system_boot_information(0);
+        assert_eq!(builder.record_bytes(&record), record.to_bytes());
+    }
+
+    #[test]
+    fn test_provider_accepts_supported_versions() {
+        let config = SmbiosPlatformConfig {
+            smbios_version: (2, 8),
+            entry_point: SmbiosEntryPoint::Bits32,
+            ..Default::default()
+        };
+        config.provider();
+        SmbiosPlatformConfig { entry_point: SmbiosEntryPoint::Both, ..Default::default() }.provider();
+    }
+
+    #[test]
+    #[should_panic(expected = "requires SMBIOS 3.0")]
+    fn test_provider_rejects_64_bit_entry_point_for_smbios_2() {
+        SmbiosPlatformConfig { smbios_version: (2, 8), entry_point: SmbiosEntryPoint::Both, ..Default::default() }
+            .provider();
+    }
+
+    #[test]
+    #[should_panic(expected = "SMBIOS 2.6 is not supported")]
+    fn test_provider_rejects_old_versions() {
+        SmbiosPlatformConfig { smbios_version: (2, 6), entry_point: SmbiosEntryPoint::Bits32, ..Default::default() }
+            .provider();
+    }
+}
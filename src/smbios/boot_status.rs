@@ -0,0 +1,170 @@
+//! SMBIOS System Boot Status
+//!
+//! The Type 32 boot status reports why the previous boot did not complete. Neither QEMU machine reports a reset
+//! reason, so the platforms track the boot in a UEFI variable instead: it is set when the SMBIOS component starts,
+//! advanced when ReadyToBoot is signaled and deleted when the OS calls ExitBootServices. If the variable is still set
+//! on the next boot, the previous boot was reset before the OS took over:
+//!
+//! - Before ReadyToBoot, the firmware failed, which is reported as a firmware-detected failure.
+//! - After ReadyToBoot, the boot option never exited boot services. Unless the user reset the system, the UEFI
+//!   watchdog timer armed for the boot option expired, which is reported as a watchdog expiration.
+//!
+//! The variable is read and written through the runtime services, so tracking starts once the Variable Write
+//! architectural protocol is installed, and the variable is deleted from the `EVENT_GROUP_BEFORE_EXIT_BOOT_SERVICES`
+//! notification, while the variable services still serve boot services callers. On Q35 with MM variables, the
+//! variable services are switched to their runtime stub from a notification of the same group at `TPL_CALLBACK`,
+//! which is created when they are installed. The DXE core queues the members of an event group at the same TPL from
+//! the most recently created, so the notification created by [`track_boot`] runs before the switch.
+//!
+//! ## References
+//!
+//! - DMTF SMBIOS Reference Specification 3.9, section 7.33 (System Boot Information)
+//! - UEFI Specification 2.10, section 3.1.2 (Load Option Processing)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{boxed::Box, vec::Vec};
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    guids::EVENT_READY_TO_BOOT,
+    runtime_services::{RuntimeServices, StandardRuntimeServices},
+};
+use r_efi::efi;
+
+use crate::variable_services;
+
+/// Type 32 `Boot Status`: No errors detected
+pub const NO_ERRORS: u8 = 0x00;
+/// Type 32 `Boot Status`: No bootable media
+pub const NO_BOOTABLE_MEDIA: u8 = 0x01;
+/// Type 32 `Boot Status`: "Normal" operating system failed to load
+pub const OS_FAILED_TO_LOAD: u8 = 0x02;
+/// Type 32 `Boot Status`: Firmware-detected hardware failure
+pub const FIRMWARE_DETECTED_FAILURE: u8 = 0x03;
+/// Type 32 `Boot Status`: Operating system-detected hardware failure
+pub const OS_DETECTED_FAILURE: u8 = 0x04;
+/// Type 32 `Boot Status`: User-requested boot
+pub const USER_REQUESTED_BOOT: u8 = 0x05;
+/// Type 32 `Boot Status`: System security violation
+pub const SECURITY_VIOLATION: u8 = 0x06;
+/// Type 32 `Boot Status`: Previously-requested image
+pub const PREVIOUSLY_REQUESTED_IMAGE: u8 = 0x07;
+/// Type 32 `Boot Status`: System watchdog timer expired
+pub const WATCHDOG_TIMER_EXPIRED: u8 = 0x08;
+
+/// Name of the variable tracking the boot phase
+const PHASE_VARIABLE_NAME: [u16; 16] = variable_services::ucs2_name(b"SmbiosBootPhase\0");
+/// Vendor GUID of the variable tracking the boot phase
+pub const PHASE_VARIABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x2d5e8a17, 0x4c3b, 0x49f0, 0x8e, 0x62, &[0x1a, 0xb7, 0x3d, 0x95, 0xc0, 0x4f]);
+/// Attributes of the variable tracking the boot phase
+const PHASE_VARIABLE_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS;
+
+/// Boot phase recorded in the phase variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BootPhase {
+    /// The SMBIOS component has run
+    Dxe = 1,
+    /// ReadyToBoot was signaled
+    ReadyToBoot = 2,
+}
+
+/// Returns the boot status for the phase the previous boot left in the phase variable, `None` if it was deleted.
+pub fn status_from_phase(phase: Option<u8>) -> u8 {
+    match phase {
+        None => NO_ERRORS,
+        Some(phase) if phase == BootPhase::ReadyToBoot as u8 => WATCHDOG_TIMER_EXPIRED,
+        Some(_) => FIRMWARE_DETECTED_FAILURE,
+    }
+}
+
+/// Returns the status of the previous boot and starts tracking this one.
+///
+/// Must be called once the Variable Write architectural protocol is installed, see
+/// [`variable_services::on_variable_write`], so the notification deleting the phase variable runs before the variable
+/// services are switched to their runtime stub.
+///
+/// Tracking is best effort: if the phase variable cannot be read, the previous boot is reported as successful, and if
+/// it cannot be written, the next boot is.
+pub fn track_boot(boot_services: &StandardBootServices, runtime_services: &StandardRuntimeServices) -> u8 {
+    let phase = match runtime_services.get_variable::<Vec<u8>>(&PHASE_VARIABLE_NAME, &PHASE_VARIABLE_GUID, Some(1)) {
+        Ok((data, _)) => data.first().copied(),
+        Err(efi::Status::NOT_FOUND) => None,
+        Err(status) => {
+            log::warn!("Failed to read the boot phase: {status:?}");
+            None
+        }
+    };
+    let status = status_from_phase(phase);
+
+    set_phase(runtime_services, BootPhase::Dxe);
+
+    let runtime_services: &'static StandardRuntimeServices = Box::leak(Box::new(runtime_services.clone()));
+    if let Err(status) = boot_services.create_event_ex(
+        EventType::NOTIFY_SIGNAL,
+        Tpl::CALLBACK,
+        Some(ready_to_boot_callback),
+        runtime_services,
+        &EVENT_READY_TO_BOOT,
+    ) {
+        log::warn!("Failed to register the boot phase ReadyToBoot notification: {status:?}");
+    }
+    if let Err(status) = boot_services.create_event_ex(
+        EventType::NOTIFY_SIGNAL,
+        Tpl::CALLBACK,
+        Some(exit_boot_services_callback),
+        runtime_services,
+        &efi::EVENT_GROUP_BEFORE_EXIT_BOOT_SERVICES,
+    ) {
+        log::warn!("Failed to register the boot phase ExitBootServices notification: {status:?}");
+    }
+
+    status
+}
+
+/// Records `phase` in the phase variable.
+fn set_phase(runtime_services: &StandardRuntimeServices, phase: BootPhase) {
+    if let Err(status) = runtime_services.set_variable(
+        &PHASE_VARIABLE_NAME,
+        &PHASE_VARIABLE_GUID,
+        PHASE_VARIABLE_ATTRIBUTES,
+        &[phase as u8],
+    ) {
+        log::warn!("Failed to record the boot phase {phase:?}: {status:?}");
+    }
+}
+
+/// Event notification that records that ReadyToBoot was signaled. ReadyToBoot is signaled again for each boot option.
+extern "efiapi" fn ready_to_boot_callback(_event: efi::Event, runtime_services: &'static StandardRuntimeServices) {
+    set_phase(runtime_services, BootPhase::ReadyToBoot);
+}
+
+/// Event notification that deletes the phase variable once the OS is about to take over.
+extern "efiapi" fn exit_boot_services_callback(_event: efi::Event, runtime_services: &'static StandardRuntimeServices) {
+    if let Err(status) = runtime_services.set_variable(&PHASE_VARIABLE_NAME, &PHASE_VARIABLE_GUID, 0, &Vec::<u8>::new())
+    {
+        log::warn!("Failed to clear the boot phase: {status:?}");
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_from_phase() {
+        assert_eq!(status_from_phase(None), NO_ERRORS);
+        assert_eq!(status_from_phase(Some(BootPhase::Dxe as u8)), FIRMWARE_DETECTED_FAILURE);
+        assert_eq!(status_from_phase(Some(BootPhase::ReadyToBoot as u8)), WATCHDOG_TIMER_EXPIRED);
+        assert_eq!(status_from_phase(Some(0xFF)), FIRMWARE_DETECTED_FAILURE);
+    }
+}
@@ -0,0 +1,568 @@
+//! SMBIOS Table Compliance
+//!
+//! Checks a published SMBIOS table against the rules of the SMBIOS specification that a platform can get wrong: the
+//! 32-bit or 64-bit entry point, the required structure types, the structure framing and handles, handle references to
+//! other structures, string numbers against the string sets, and the minimum structure lengths for the table version.
+//!
+//! The checks only cover the fields of the structure types the QEMU platforms publish.
+//!
+//! ## References
+//!
+//! - DMTF SMBIOS Reference Specification 3.9, sections 5.2 (entry points), 6.1 (structure format) and 6.2 (required
+//!   structures)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt;
+
+use patina_smbios::service::SmbiosHandle;
+
+use super::{
+    entry_point::{ANCHOR_32, ANCHOR_64, ENTRY_POINT_32_LENGTH, ENTRY_POINT_64_LENGTH, INTERMEDIATE_ANCHOR, checksum},
+    table::{self, END_OF_TABLE},
+};
+
+/// Structure types the SMBIOS specification requires
+///
+/// Type 9 System Slots is only required on platforms with slots, see [`validate_table`].
+pub const REQUIRED_TYPES: &[u8] = &[0, 1, 3, 4, 7, 16, 17, 19, 32];
+/// Type of the System Slots structure
+const SYSTEM_SLOTS: u8 = 9;
+
+/// Revision of the SMBIOS 3.0 entry point structure
+const ENTRY_POINT_REVISION: u8 = 0x01;
+
+/// Handle reference value that means the referenced information is not provided
+const HANDLE_NOT_PROVIDED: SmbiosHandle = 0xFFFF;
+/// Memory error information handle value that means no error information structure is provided
+const HANDLE_NO_ERROR_INFORMATION: SmbiosHandle = 0xFFFE;
+
+/// A rule of the SMBIOS specification the table does not follow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The entry point is invalid.
+    EntryPoint(&'static str),
+    /// The structure at `offset` is truncated or its string set is not terminated.
+    Malformed {
+        /// Offset of the structure in the table.
+        offset: usize,
+    },
+    /// The table has no End-of-Table structure, or structures follow it.
+    EndOfTable,
+    /// More than one structure has `handle`.
+    DuplicateHandle {
+        /// The duplicated handle.
+        handle: SmbiosHandle,
+    },
+    /// No structure of the required `record_type` is present.
+    MissingType {
+        /// The missing structure type.
+        record_type: u8,
+    },
+    /// The structure with `handle` is shorter than `minimum` bytes, the length its type has in the table version.
+    TooShort {
+        /// Handle of the structure.
+        handle: SmbiosHandle,
+        /// Type of the structure.
+        record_type: u8,
+        /// Length of the formatted area of the structure.
+        length: u8,
+        /// Minimum length of the type in the table version.
+        minimum: usize,
+    },
+    /// The field at `offset` of the structure with `handle` references `reference`, which is not the handle of a
+    /// structure of the expected type.
+    DanglingHandle {
+        /// Handle of the structure.
+        handle: SmbiosHandle,
+        /// Offset of the field in the structure.
+        offset: usize,
+        /// The referenced handle.
+        reference: SmbiosHandle,
+    },
+    /// The field at `offset` of the structure with `handle` references string `number`, but the string set holds
+    /// `count` strings.
+    StringOutOfRange {
+        /// Handle of the structure.
+        handle: SmbiosHandle,
+        /// Offset of the field in the structure.
+        offset: usize,
+        /// The referenced string number.
+        number: u8,
+        /// Number of strings in the string set.
+        count: usize,
+    },
+    /// The `Count` field of the structure with `handle` does not match the `strings` in its string set.
+    StringCount {
+        /// Handle of the structure.
+        handle: SmbiosHandle,
+        /// Value of the `Count` field.
+        count: u8,
+        /// Number of strings in the string set.
+        strings: usize,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EntryPoint(reason) => write!(f, "entry point: {reason}"),
+            Self::Malformed { offset } => write!(f, "malformed structure at offset {offset:#X}"),
+            Self::EndOfTable => write!(f, "the End-of-Table structure is missing or not last"),
+            Self::DuplicateHandle { handle } => write!(f, "handle {handle:#06X} is used more than once"),
+            Self::MissingType { record_type } => write!(f, "required Type {record_type} is missing"),
+            Self::TooShort { handle, record_type, length, minimum } => {
+                write!(f, "Type {record_type} {handle:#06X} is {length:#X} bytes, expected at least {minimum:#X}")
+            }
+            Self::DanglingHandle { handle, offset, reference } => {
+                write!(f, "{handle:#06X} field {offset:#X} references missing handle {reference:#06X}")
+            }
+            Self::StringOutOfRange { handle, offset, number, count } => {
+                write!(f, "{handle:#06X} field {offset:#X} references string {number} of {count}")
+            }
+            Self::StringCount { handle, count, strings } => {
+                write!(f, "{handle:#06X} declares {count} strings but holds {strings}")
+            }
+        }
+    }
+}
+
+/// The fields of an entry point the table checks depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryPoint {
+    /// SMBIOS major and minor version
+    pub version: (u8, u8),
+    /// Maximum size of the structure table in bytes, its exact size for a 32-bit entry point
+    pub table_maximum_size: u32,
+    /// Physical address of the structure table
+    pub table_address: u64,
+}
+
+impl EntryPoint {
+    /// Parses and checks an SMBIOS 2.1 32-bit or SMBIOS 3.0 64-bit entry point, selected by its anchor string.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Violation> {
+        if bytes.starts_with(ANCHOR_32) {
+            Self::parse_32(bytes)
+        } else if bytes.starts_with(ANCHOR_64) {
+            Self::parse_64(bytes)
+        } else {
+            Err(Violation::EntryPoint("the anchor string is not _SM_ or _SM3_"))
+        }
+    }
+
+    fn parse_32(bytes: &[u8]) -> Result<Self, Violation> {
+        let length = *bytes.get(0x05).ok_or(Violation::EntryPoint("the entry point is truncated"))? as usize;
+        if length < ENTRY_POINT_32_LENGTH || bytes.len() < length {
+            return Err(Violation::EntryPoint("the entry point length is invalid"));
+        }
+        if checksum(&bytes[..length]) != 0 {
+            return Err(Violation::EntryPoint("the checksum is invalid"));
+        }
+        if !bytes[0x10..].starts_with(INTERMEDIATE_ANCHOR) {
+            return Err(Violation::EntryPoint("the intermediate anchor string is not _DMI_"));
+        }
+        if checksum(&bytes[0x10..ENTRY_POINT_32_LENGTH]) != 0 {
+            return Err(Violation::EntryPoint("the intermediate checksum is invalid"));
+        }
+        if bytes[0x06] < 2 {
+            return Err(Violation::EntryPoint("the major version is older than 2"));
+        }
+
+        Ok(Self {
+            version: (bytes[0x06], bytes[0x07]),
+            table_maximum_size: u16::from_le_bytes([bytes[0x16], bytes[0x17]]) as u32,
+            table_address: u32::from_le_bytes(bytes[0x18..0x1C].try_into().unwrap()) as u64,
+        })
+    }
+
+    fn parse_64(bytes: &[u8]) -> Result<Self, Violation> {
+        let length = *bytes.get(0x06).ok_or(Violation::EntryPoint("the entry point is truncated"))? as usize;
+        if length < ENTRY_POINT_64_LENGTH || bytes.len() < length {
+            return Err(Violation::EntryPoint("the entry point length is invalid"));
+        }
+        if checksum(&bytes[..length]) != 0 {
+            return Err(Violation::EntryPoint("the checksum is invalid"));
+        }
+        if bytes[0x07] < 3 {
+            return Err(Violation::EntryPoint("the major version is older than 3"));
+        }
+        if bytes[0x0A] != ENTRY_POINT_REVISION {
+            return Err(Violation::EntryPoint("the entry point revision is not 1"));
+        }
+
+        Ok(Self {
+            version: (bytes[0x07], bytes[0x08]),
+            table_maximum_size: u32::from_le_bytes(bytes[0x0C..0x10].try_into().unwrap()),
+            table_address: u64::from_le_bytes(bytes[0x10..0x18].try_into().unwrap()),
+        })
+    }
+}
+
+/// A structure of the table, split into its formatted area and strings.
+struct Structure<'a> {
+    formatted: &'a [u8],
+    strings: Vec<&'a [u8]>,
+}
+
+impl Structure<'_> {
+    fn record_type(&self) -> u8 {
+        self.formatted[0]
+    }
+
+    fn handle(&self) -> SmbiosHandle {
+        self.word(0x02).unwrap_or_default()
+    }
+
+    fn byte(&self, offset: usize) -> Option<u8> {
+        self.formatted.get(offset).copied()
+    }
+
+    fn word(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_le_bytes([self.byte(offset)?, self.byte(offset + 1)?]))
+    }
+}
+
+/// Checks the structure table of an SMBIOS `version` table and returns the rules it does not follow.
+///
+/// `has_slots` reports whether the platform has expansion slots, which Type 9 System Slots structures must describe.
+pub fn validate_table(version: (u8, u8), table: &[u8], has_slots: bool) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let structures = split(table, &mut violations);
+
+    let mut types = BTreeMap::new();
+    for structure in &structures {
+        if types.insert(structure.handle(), structure.record_type()).is_some() {
+            violations.push(Violation::DuplicateHandle { handle: structure.handle() });
+        }
+    }
+    let slots: &[u8] = if has_slots { &[SYSTEM_SLOTS] } else { &[] };
+    for &record_type in REQUIRED_TYPES.iter().chain(slots) {
+        if !structures.iter().any(|structure| structure.record_type() == record_type) {
+            violations.push(Violation::MissingType { record_type });
+        }
+    }
+
+    for structure in &structures {
+        let handle = structure.handle();
+        if let Some(minimum) = structure_length(version, structure.formatted)
+            && structure.formatted.len() < minimum
+        {
+            violations.push(Violation::TooShort {
+                handle,
+                record_type: structure.record_type(),
+                length: structure.formatted.len() as u8,
+                minimum,
+            });
+        }
+
+        for (offset, expected, allowed) in handle_references(structure) {
+            let Some(reference) = structure.word(offset) else {
+                continue;
+            };
+            let resolves = match types.get(&reference) {
+                Some(record_type) => expected.is_none_or(|expected| expected == *record_type),
+                None => allowed.contains(&reference),
+            };
+            if !resolves {
+                violations.push(Violation::DanglingHandle { handle, offset, reference });
+            }
+        }
+
+        let count = structure.strings.len();
+        for offset in string_fields(structure) {
+            if let Some(number) = structure.byte(offset)
+                && number as usize > count
+            {
+                violations.push(Violation::StringOutOfRange { handle, offset, number, count });
+            }
+        }
+        if matches!(structure.record_type(), 11 | 12)
+            && let Some(declared) = structure.byte(0x04)
+            && declared as usize != count
+        {
+            violations.push(Violation::StringCount { handle, count: declared, strings: count });
+        }
+    }
+
+    violations
+}
+
+/// Splits `table` into structures up to the End-of-Table structure, recording framing violations.
+fn split<'a>(table: &'a [u8], violations: &mut Vec<Violation>) -> Vec<Structure<'a>> {
+    let mut structures = Vec::new();
+    for structure in table::structures(table) {
+        let structure = match structure {
+            Ok(structure) => structure,
+            Err(offset) => {
+                violations.push(Violation::Malformed { offset });
+                return structures;
+            }
+        };
+        structures.push(Structure { formatted: structure.formatted(), strings: structure.strings().collect() });
+
+        if structure.record_type() == END_OF_TABLE {
+            if table[structure.end()..].iter().any(|&b| b != 0) {
+                violations.push(Violation::EndOfTable);
+            }
+            return structures;
+        }
+    }
+    violations.push(Violation::EndOfTable);
+    structures
+}
+
+/// Returns the length the formatted area of a `version` structure has, from its `formatted` area, for the types the
+/// platforms publish and versions from SMBIOS 2.7.
+///
+/// Structures may be longer, with fields of later versions, so this is also the minimum length in a `version` table.
+pub fn structure_length(version: (u8, u8), formatted: &[u8]) -> Option<usize> {
+    let count = |offset| formatted.get(offset).copied().unwrap_or_default() as usize;
+    Some(match *formatted.first()? {
+        0 if version >= (3, 1) => 0x1A,
+        0 => 0x18,
+        1 => 0x1B,
+        2 => 0x0F + 2 * count(0x0E),
+        3 => 0x16 + count(0x13) * count(0x14),
+        4 if version >= (3, 8) => 0x33,
+        4 if version >= (3, 6) => 0x32,
+        4 if version >= (3, 0) => 0x30,
+        4 => 0x2A,
+        7 if version >= (3, 1) => 0x1B,
+        7 => 0x13,
+        9 if version >= (3, 5) => 0x18 + 5 * count(0x12),
+        9 if version >= (3, 4) => 0x17 + 5 * count(0x12),
+        9 if version >= (3, 2) => 0x13 + 5 * count(0x12),
+        9 => 0x11,
+        11 | 12 => 0x05,
+        16 => 0x17,
+        17 if version >= (3, 7) => 0x64,
+        17 if version >= (3, 3) => 0x5C,
+        17 if version >= (3, 2) => 0x54,
+        17 if version >= (2, 8) => 0x28,
+        17 => 0x22,
+        19 => 0x1F,
+        32 => 0x0B,
+        41 => 0x0B,
+        45 => 0x18 + 2 * count(0x17),
+        _ => return None,
+    })
+}
+
+/// Returns the offset of each handle reference in `structure`, with the type of structure it must reference, if
+/// fixed, and the values that reference no structure.
+fn handle_references(structure: &Structure) -> Vec<(usize, Option<u8>, &'static [SmbiosHandle])> {
+    let count = |offset| structure.byte(offset).unwrap_or_default() as usize;
+    const NOT_PROVIDED: &[SmbiosHandle] = &[HANDLE_NOT_PROVIDED];
+    const NO_ERROR_INFORMATION: &[SmbiosHandle] = &[HANDLE_NO_ERROR_INFORMATION, HANDLE_NOT_PROVIDED];
+
+    match structure.record_type() {
+        2 => [(0x0B, Some(3), &[][..])]
+            .into_iter()
+            .chain((0..count(0x0E)).map(|i| (0x0F + 2 * i, None, &[][..])))
+            .collect(),
+        4 => [0x1A, 0x1C, 0x1E].into_iter().map(|offset| (offset, Some(7), NOT_PROVIDED)).collect(),
+        16 => [(0x0B, None, NO_ERROR_INFORMATION)].into(),
+        17 => [(0x04, Some(16), &[][..]), (0x06, None, NO_ERROR_INFORMATION)].into(),
+        19 => [(0x0C, Some(16), &[][..])].into(),
+        45 => (0..count(0x17)).map(|i| (0x18 + 2 * i, None, &[][..])).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the offset of each string number field in `structure`, for the types the platforms publish.
+fn string_fields(structure: &Structure) -> Vec<usize> {
+    let count = |offset| structure.byte(offset).unwrap_or_default() as usize;
+    match structure.record_type() {
+        0 => [0x04, 0x05, 0x08].into(),
+        1 => [0x04, 0x05, 0x06, 0x07, 0x19, 0x1A].into(),
+        2 => [0x04, 0x05, 0x06, 0x07, 0x08, 0x0A].into(),
+        3 => [0x04, 0x06, 0x07, 0x08, 0x15 + count(0x13) * count(0x14)].into(),
+        4 => [0x04, 0x07, 0x10, 0x20, 0x21, 0x22, 0x32].into(),
+        7 | 9 | 41 => [0x04].into(),
+        17 => [0x10, 0x11, 0x17, 0x18, 0x19, 0x1A, 0x2C].into(),
+        45 => [0x04, 0x05, 0x07, 0x09, 0x0A, 0x0B].into(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use std::vec;
+
+    use super::*;
+    use crate::smbios::qemu::tests::structure;
+
+    /// Builds a valid SMBIOS 3.0 entry point for a table of `length` bytes at `address`.
+    fn entry_point(length: u32, address: u64) -> Vec<u8> {
+        let mut bytes = vec![0u8; ENTRY_POINT_64_LENGTH];
+        bytes[..5].copy_from_slice(ANCHOR_64);
+        bytes[0x06] = ENTRY_POINT_64_LENGTH as u8;
+        bytes[0x07] = 3;
+        bytes[0x08] = 9;
+        bytes[0x0A] = ENTRY_POINT_REVISION;
+        bytes[0x0C..0x10].copy_from_slice(&length.to_le_bytes());
+        bytes[0x10..0x18].copy_from_slice(&address.to_le_bytes());
+        bytes[0x05] = checksum(&bytes);
+        bytes
+    }
+
+    /// Builds a formatted area of `length` bytes after the header, with `fields` written at their offsets.
+    fn formatted(length: usize, fields: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut bytes = vec![0u8; length - 4];
+        for (offset, value) in fields {
+            bytes[offset - 4..offset - 4 + value.len()].copy_from_slice(value);
+        }
+        bytes
+    }
+
+    /// Builds a table holding one structure of each required type that follows every rule.
+    fn compliant_table() -> Vec<Vec<u8>> {
+        vec![
+            structure(0, 0x0000, &formatted(0x1A, &[(0x04, &[1])]), &["Vendor"]),
+            structure(1, 0x0001, &formatted(0x1B, &[]), &[]),
+            structure(3, 0x0003, &formatted(0x16, &[(0x15, &[1])]), &["SKU"]),
+            structure(2, 0x0002, &formatted(0x0F, &[(0x0B, &3u16.to_le_bytes())]), &[]),
+            structure(7, 0x0007, &formatted(0x1B, &[]), &[]),
+            structure(
+                4,
+                0x0004,
+                &formatted(0x33, &[(0x1A, &7u16.to_le_bytes()), (0x1C, &[0xFF, 0xFF]), (0x1E, &[0xFF, 0xFF])]),
+                &[],
+            ),
+            structure(9, 0x0009, &formatted(0x18, &[]), &[]),
+            structure(16, 0x0010, &formatted(0x17, &[(0x0B, &[0xFE, 0xFF])]), &[]),
+            structure(17, 0x0011, &formatted(0x64, &[(0x04, &0x10u16.to_le_bytes()), (0x06, &[0xFE, 0xFF])]), &[]),
+            structure(19, 0x0013, &formatted(0x1F, &[(0x0C, &0x10u16.to_le_bytes())]), &[]),
+            structure(32, 0x0020, &formatted(0x0B, &[]), &[]),
+            structure(11, 0x000B, &[2], &["a", "b"]),
+            structure(127, 0xFEFF, &[], &[]),
+        ]
+    }
+
+    #[test]
+    fn test_entry_point() {
+        let bytes = entry_point(0x100, 0x1234_5000);
+        let entry_point = EntryPoint::parse(&bytes).unwrap();
+        assert_eq!(entry_point.version, (3, 9));
+        assert_eq!(entry_point.table_maximum_size, 0x100);
+        assert_eq!(entry_point.table_address, 0x1234_5000);
+
+        let mut corrupted = bytes.clone();
+        corrupted[0x10] ^= 1;
+        assert_eq!(EntryPoint::parse(&corrupted), Err(Violation::EntryPoint("the checksum is invalid")));
+        assert!(EntryPoint::parse(b"_SM_").is_err());
+        assert!(EntryPoint::parse(b"_DMI_").is_err());
+        assert!(EntryPoint::parse(&bytes[..0x10]).is_err());
+    }
+
+    #[test]
+    fn test_entry_point_32() {
+        let table = compliant_table().concat();
+        let bytes = crate::smbios::entry_point::entry_point_32((2, 8), &table, 0x7FF0_0000).unwrap();
+        let entry_point = EntryPoint::parse(&bytes).unwrap();
+        assert_eq!(entry_point.version, (2, 8));
+        assert_eq!(entry_point.table_maximum_size as usize, table.len());
+        assert_eq!(entry_point.table_address, 0x7FF0_0000);
+
+        let mut corrupted = bytes;
+        corrupted[0x16] ^= 1;
+        corrupted[0x04] ^= 1;
+        assert_eq!(EntryPoint::parse(&corrupted), Err(Violation::EntryPoint("the intermediate checksum is invalid")));
+        let mut corrupted = bytes;
+        corrupted[0x10] = b'X';
+        corrupted[0x15] = corrupted[0x15].wrapping_add(b'_' - b'X');
+        assert_eq!(
+            EntryPoint::parse(&corrupted),
+            Err(Violation::EntryPoint("the intermediate anchor string is not _DMI_"))
+        );
+    }
+
+    #[test]
+    fn test_compliant_table() {
+        assert_eq!(validate_table((3, 9), &compliant_table().concat(), true), []);
+    }
+
+    #[test]
+    fn test_framing_violations() {
+        let mut records = compliant_table();
+        records.remove(records.len() - 1);
+        records[1][2] = 0x00; // Type 1 takes the Type 0 handle
+        let table = records.concat();
+        let violations = validate_table((3, 9), &table, true);
+        assert!(violations.contains(&Violation::EndOfTable));
+        assert!(violations.contains(&Violation::DuplicateHandle { handle: 0 }));
+
+        let truncated = &compliant_table().concat()[..0x10];
+        assert_eq!(validate_table((3, 9), truncated, true)[0], Violation::Malformed { offset: 0 });
+    }
+
+    #[test]
+    fn test_required_types_and_lengths() {
+        let mut records = compliant_table();
+        records.retain(|record| record[0] != 32);
+        let violations = validate_table((3, 9), &records.concat(), true);
+        assert_eq!(violations, [Violation::MissingType { record_type: 32 }]);
+
+        // Type 9 is only required on platforms with slots
+        let mut records = compliant_table();
+        records.retain(|record| record[0] != 9);
+        assert_eq!(validate_table((3, 9), &records.concat(), false), []);
+        assert_eq!(validate_table((3, 9), &records.concat(), true), [Violation::MissingType { record_type: 9 }]);
+
+        // The SMBIOS 3.0 Type 4 layout is complete in a 3.5 table, but not in a 3.9 table
+        let mut records = compliant_table();
+        records[5] = structure(4, 0x0004, &formatted(0x30, &[(0x1A, &[0xFF; 6])]), &[]);
+        let table = records.concat();
+        assert_eq!(validate_table((3, 5), &table, true), []);
+        assert_eq!(
+            validate_table((3, 9), &table, true),
+            [Violation::TooShort { handle: 0x0004, record_type: 4, length: 0x30, minimum: 0x33 }]
+        );
+
+        // SMBIOS 2.x structures are shorter
+        assert_eq!(structure_length((2, 8), &records[5]), Some(0x2A));
+        assert_eq!(structure_length((2, 8), &compliant_table()[8]), Some(0x28));
+        assert_eq!(structure_length((2, 7), &compliant_table()[8]), Some(0x22));
+        assert_eq!(structure_length((2, 8), &compliant_table()[0]), Some(0x18));
+        assert_eq!(structure_length((3, 9), &[127, 4, 0xFF, 0xFE]), None);
+    }
+
+    #[test]
+    fn test_handle_references() {
+        let mut records = compliant_table();
+        records[3] = structure(2, 0x0002, &formatted(0x0F, &[(0x0B, &[0xFF, 0xFF])]), &[]);
+        records[9] = structure(19, 0x0013, &formatted(0x1F, &[(0x0C, &0x11u16.to_le_bytes())]), &[]);
+        let violations = validate_table((3, 9), &records.concat(), true);
+        assert_eq!(
+            violations,
+            [
+                Violation::DanglingHandle { handle: 0x0002, offset: 0x0B, reference: 0xFFFF },
+                Violation::DanglingHandle { handle: 0x0013, offset: 0x0C, reference: 0x0011 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_violations() {
+        let mut records = compliant_table();
+        records[0] = structure(0, 0x0000, &formatted(0x1A, &[(0x04, &[1]), (0x08, &[2])]), &["Vendor"]);
+        records[11] = structure(11, 0x000B, &[3], &["a", "b"]);
+        let violations = validate_table((3, 9), &records.concat(), true);
+        assert_eq!(
+            violations,
+            [
+                Violation::StringOutOfRange { handle: 0x0000, offset: 0x08, number: 2, count: 1 },
+                Violation::StringCount { handle: 0x000B, count: 3, strings: 2 },
+            ]
+        );
+    }
+}
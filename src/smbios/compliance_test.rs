@@ -0,0 +1,116 @@
+//! SMBIOS Compliance Test
+//!
+//! Walks the published SMBIOS table from each entry point in the UEFI Configuration Table, the SMBIOS 2.1 32-bit and
+//! the SMBIOS 3.0 64-bit one, and checks it with the [`compliance`](super::compliance) rules.
+//!
+//! The platforms describe the PCI Express root ports of the root bus as slots, so Type 9 System Slots is only required
+//! if the PCI bus driver reports root ports on the root bus.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::collections::BTreeMap;
+use core::ffi::c_void;
+
+// used in the macro, but not directly referenced; causes a warning if patina tests not enabled.
+#[allow(unused)]
+use patina::BinaryGuid;
+use patina::boot_services::{BootServices, StandardBootServices, protocol_handler::HandleSearchType};
+use patina_test::{patina_test, u_assert};
+use r_efi::{efi, protocols::pci_io};
+
+use super::{
+    compliance::{EntryPoint, validate_table},
+    configuration_table,
+    entry_point::{ENTRY_POINT_32_LENGTH, ENTRY_POINT_64_LENGTH},
+    pci::{self, PciRootBus},
+};
+
+/// Checks the SMBIOS table once every record has been added.
+#[patina_test]
+#[on(event = BinaryGuid(efi::EVENT_GROUP_READY_TO_BOOT))]
+fn smbios_compliance_test(boot_services: StandardBootServices) -> patina_test::error::Result {
+    log::debug!("SMBIOS Compliance Test - Validating the published table");
+
+    let has_slots = has_root_ports(&boot_services);
+    log::trace!("  Root bus slots: {has_slots}");
+
+    let mut published = false;
+    for (guid, length) in
+        [(efi::SMBIOS_TABLE_GUID, ENTRY_POINT_32_LENGTH), (efi::SMBIOS3_TABLE_GUID, ENTRY_POINT_64_LENGTH)]
+    {
+        let Some(entry_point_address) = configuration_table(&boot_services, &guid)? else {
+            continue;
+        };
+        published = true;
+
+        // SAFETY: The SMBIOS configuration tables point to an entry point of their length.
+        let entry_point = unsafe { core::slice::from_raw_parts(entry_point_address as *const u8, length) };
+        let entry_point = EntryPoint::parse(entry_point).map_err(|violation| {
+            log::error!("  {violation}");
+            "The SMBIOS entry point is invalid"
+        })?;
+        log::trace!(
+            "  SMBIOS {}.{} table at {:#X}",
+            entry_point.version.0,
+            entry_point.version.1,
+            entry_point.table_address
+        );
+
+        // SAFETY: The entry point was checked, and the structure table it describes is at most its maximum size.
+        let table = unsafe {
+            core::slice::from_raw_parts(entry_point.table_address as *const u8, entry_point.table_maximum_size as usize)
+        };
+        let violations = validate_table(entry_point.version, table, has_slots);
+        for violation in &violations {
+            log::error!("  {violation}");
+        }
+        u_assert!(violations.is_empty(), "The SMBIOS table should follow the SMBIOS specification");
+    }
+    u_assert!(published, "An SMBIOS entry point should be installed");
+
+    log::debug!("SMBIOS Compliance Test complete");
+    Ok(())
+}
+
+/// Returns whether the PCI I/O instances of the root bus include a PCI Express root port.
+///
+/// Functions without a PCI I/O instance, such as those on a bus the PCI bus driver has not enumerated, read as absent.
+fn has_root_ports(boot_services: &StandardBootServices) -> bool {
+    let Ok(handles) = boot_services.locate_handle_buffer(HandleSearchType::ByProtocol(&pci_io::PROTOCOL_GUID)) else {
+        return false;
+    };
+
+    let mut functions = BTreeMap::new();
+    for &handle in handles.iter() {
+        // SAFETY: The handle supports the PCI I/O protocol, whose interface is valid during boot services.
+        let Ok(pci_io) = (unsafe { boot_services.handle_protocol_unchecked(handle, &pci_io::PROTOCOL_GUID) }) else {
+            continue;
+        };
+        let pci_io = pci_io as *mut pci_io::Protocol;
+        let (mut segment, mut bus, mut device, mut function) = (0, 0, 0, 0);
+        // SAFETY: The interface is a PCI I/O protocol and the location outputs are valid for writes.
+        let status = unsafe { ((*pci_io).get_location)(pci_io, &mut segment, &mut bus, &mut device, &mut function) };
+        if status == efi::Status::SUCCESS && segment == 0 && bus == pci::ROOT_BUS as usize {
+            functions.insert((device as u8, function as u8), pci_io);
+        }
+    }
+
+    let bus = PciRootBus::scan(|device, function, offset| {
+        let Some(&pci_io) = functions.get(&(device, function)) else {
+            return u32::MAX;
+        };
+        let mut value = u32::MAX;
+        // SAFETY: The interface is a PCI I/O protocol and the buffer holds one 32-bit value.
+        let status = unsafe {
+            ((*pci_io).pci.read)(pci_io, pci_io::WIDTH_UINT32, offset as u32, 1, &mut value as *mut u32 as *mut c_void)
+        };
+        if status == efi::Status::SUCCESS { value } else { u32::MAX }
+    });
+    bus.root_ports().next().is_some()
+}
@@ -0,0 +1,291 @@
+//! SMBIOS Entry Points
+//!
+//! The SMBIOS provider builds the structure table and publishes it through the 64-bit SMBIOS 3.0 entry point (`_SM3_`)
+//! only. Guest tooling that predates SMBIOS 3.0 looks for the 32-bit SMBIOS 2.1 entry point (`_SM_`) instead, which
+//! can only describe a table below 4 GB of at most 64 KB.
+//!
+//! [`publish`] installs the entry points the [`SmbiosEntryPoint`] configuration selects. The 32-bit entry point
+//! describes a copy of the table in memory below 4 GB, as the provider may place its table anywhere. The copy is
+//! refreshed when ReadyToBoot is signaled, so it includes the records drivers add after the platform component.
+//!
+//! ## References
+//!
+//! - DMTF SMBIOS Reference Specification 3.9, sections 5.2.1 (32-bit entry point) and 5.2.2 (64-bit entry point)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::boxed::Box;
+use core::ffi::c_void;
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, allocation::AllocType, event::EventType, tpl::Tpl},
+    component::service::Service,
+    efi_types::EfiMemoryType,
+    guids::EVENT_READY_TO_BOOT,
+    uefi_size_to_pages,
+};
+use patina_smbios::service::Smbios;
+use r_efi::efi;
+
+use super::table::{self, END_OF_TABLE};
+
+/// Anchor string of the SMBIOS 2.1 32-bit entry point
+pub const ANCHOR_32: &[u8] = b"_SM_";
+/// Intermediate anchor string of the SMBIOS 2.1 32-bit entry point
+pub const INTERMEDIATE_ANCHOR: &[u8] = b"_DMI_";
+/// Length of the SMBIOS 2.1 32-bit entry point
+pub const ENTRY_POINT_32_LENGTH: usize = 0x1F;
+/// Anchor string of the SMBIOS 3.0 64-bit entry point
+pub const ANCHOR_64: &[u8] = b"_SM3_";
+/// Length of the SMBIOS 3.0 64-bit entry point
+pub const ENTRY_POINT_64_LENGTH: usize = 0x18;
+
+/// Oldest SMBIOS version the platforms build records for. The memory records use the SMBIOS 2.7 extended fields.
+pub const MINIMUM_VERSION: (u8, u8) = (2, 7);
+/// Highest address the 32-bit entry point and its table can be at
+const MAXIMUM_ADDRESS_32: usize = 0xFFFF_FFFF;
+/// Offset of the table copy in the 32-bit entry point buffer
+const TABLE_32_OFFSET: usize = 0x1000;
+/// Size of the table copy, the largest table the 32-bit entry point can describe
+const TABLE_32_SIZE: usize = 0x10000;
+
+/// Entry points the SMBIOS table is published through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SmbiosEntryPoint {
+    /// The SMBIOS 2.1 32-bit entry point (`_SM_`), in the `SMBIOS_TABLE_GUID` configuration table.
+    Bits32,
+    /// The SMBIOS 3.0 64-bit entry point (`_SM3_`), in the `SMBIOS3_TABLE_GUID` configuration table. Requires an
+    /// SMBIOS 3.x table.
+    #[default]
+    Bits64,
+    /// Both entry points, describing the same structures.
+    Both,
+}
+
+impl SmbiosEntryPoint {
+    /// Returns whether the 32-bit entry point is published.
+    pub fn has_32_bit(self) -> bool {
+        matches!(self, Self::Bits32 | Self::Both)
+    }
+
+    /// Returns whether the 64-bit entry point is published.
+    pub fn has_64_bit(self) -> bool {
+        matches!(self, Self::Bits64 | Self::Both)
+    }
+}
+
+/// Returns the 32-bit entry point of the `version` structure table `table` at `address`, or `None` if `table` is not
+/// a complete structure table of at most 64 KB.
+pub fn entry_point_32(version: (u8, u8), table: &[u8], address: u32) -> Option<[u8; ENTRY_POINT_32_LENGTH]> {
+    let table_length = u16::try_from(table.len()).ok()?;
+
+    let mut count = 0u16;
+    let mut maximum_size = 0usize;
+    let mut end_of_table = false;
+    for structure in table::structures(table) {
+        let structure = structure.ok()?;
+        count += 1;
+        maximum_size = maximum_size.max(structure.bytes.len());
+        end_of_table = structure.record_type() == END_OF_TABLE;
+    }
+    if !end_of_table {
+        return None;
+    }
+
+    let mut bytes = [0u8; ENTRY_POINT_32_LENGTH];
+    bytes[0x00..0x04].copy_from_slice(ANCHOR_32);
+    bytes[0x05] = ENTRY_POINT_32_LENGTH as u8;
+    (bytes[0x06], bytes[0x07]) = version;
+    bytes[0x08..0x0A].copy_from_slice(&(maximum_size as u16).to_le_bytes());
+    bytes[0x10..0x15].copy_from_slice(INTERMEDIATE_ANCHOR);
+    bytes[0x16..0x18].copy_from_slice(&table_length.to_le_bytes());
+    bytes[0x18..0x1C].copy_from_slice(&address.to_le_bytes());
+    bytes[0x1C..0x1E].copy_from_slice(&count.to_le_bytes());
+    // The BCD revision can only hold single-digit versions; zero defers to the version fields
+    if version.0 < 10 && version.1 < 10 {
+        bytes[0x1E] = (version.0 << 4) | version.1;
+    }
+
+    bytes[0x15] = checksum(&bytes[0x10..]);
+    bytes[0x04] = checksum(&bytes);
+    Some(bytes)
+}
+
+/// Returns the byte that makes the sum of `bytes` zero, or zero if they already sum to zero.
+pub fn checksum(bytes: &[u8]) -> u8 {
+    0u8.wrapping_sub(bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)))
+}
+
+/// Publication state shared with the ReadyToBoot notification.
+struct Publication {
+    boot_services: StandardBootServices,
+    smbios: Service<dyn Smbios>,
+    version: (u8, u8),
+    entry_point: SmbiosEntryPoint,
+    /// Pages below 4 GB for the 32-bit entry point, followed by the copy of the table
+    buffer: usize,
+}
+
+/// Installs the entry points `entry_point` selects for the published `version` table, and refreshes them when
+/// ReadyToBoot is signaled.
+///
+/// Publication is best effort: failures are logged, and the 64-bit entry point is left installed if the 32-bit one
+/// cannot be.
+pub fn publish(
+    boot_services: &StandardBootServices,
+    smbios: &Service<dyn Smbios>,
+    version: (u8, u8),
+    entry_point: SmbiosEntryPoint,
+) {
+    if !entry_point.has_32_bit() {
+        return;
+    }
+
+    let buffer = match boot_services.allocate_pages(
+        AllocType::MaxAddress(MAXIMUM_ADDRESS_32),
+        EfiMemoryType::ACPIReclaimMemory,
+        uefi_size_to_pages!(TABLE_32_OFFSET + TABLE_32_SIZE),
+    ) {
+        Ok(buffer) => buffer,
+        Err(status) => {
+            log::error!("Failed to allocate the SMBIOS 32-bit entry point below 4 GB: {status:?}");
+            return;
+        }
+    };
+
+    let publication: &'static Publication = Box::leak(Box::new(Publication {
+        boot_services: boot_services.clone(),
+        smbios: smbios.clone(),
+        version,
+        entry_point,
+        buffer,
+    }));
+    publication.refresh();
+
+    if let Err(status) = boot_services.create_event_ex(
+        EventType::NOTIFY_SIGNAL,
+        Tpl::CALLBACK,
+        Some(ready_to_boot_callback),
+        publication,
+        &EVENT_READY_TO_BOOT,
+    ) {
+        log::warn!("Failed to register the SMBIOS entry point ReadyToBoot notification: {status:?}");
+    }
+}
+
+impl Publication {
+    /// Republishes the table, copies it below 4 GB and installs the 32-bit entry point, removing the 64-bit one if it
+    /// is not configured.
+    fn refresh(&self) {
+        let (table_address, entry_point_64) = match self.smbios.publish_table() {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                log::error!("Failed to republish the SMBIOS table: {e:?}");
+                return;
+            }
+        };
+
+        // SAFETY: The SMBIOS service returned the address of its 64-bit entry point, and the table it describes.
+        let table = unsafe {
+            let entry_point = core::slice::from_raw_parts(entry_point_64 as *const u8, ENTRY_POINT_64_LENGTH);
+            let table_size = u32::from_le_bytes(entry_point[0x0C..0x10].try_into().unwrap()) as usize;
+            core::slice::from_raw_parts(table_address as *const u8, table_size)
+        };
+        if table.len() > TABLE_32_SIZE {
+            log::error!("The SMBIOS table is too large for the 32-bit entry point: {:#X} bytes", table.len());
+            return;
+        }
+
+        // SAFETY: The buffer was allocated below 4 GB for the entry point and a table of TABLE_32_SIZE bytes.
+        let (entry_point, copy) = unsafe {
+            let entry_point = core::slice::from_raw_parts_mut(self.buffer as *mut u8, ENTRY_POINT_32_LENGTH);
+            let copy = core::slice::from_raw_parts_mut((self.buffer + TABLE_32_OFFSET) as *mut u8, table.len());
+            (entry_point, copy)
+        };
+        copy.copy_from_slice(table);
+        let Some(bytes) = entry_point_32(self.version, copy, copy.as_ptr() as u32) else {
+            log::error!("The SMBIOS table is malformed, skipping the 32-bit entry point");
+            return;
+        };
+        entry_point.copy_from_slice(&bytes);
+
+        // SAFETY: The buffer holds a 32-bit entry point and is never freed.
+        if let Err(status) = unsafe {
+            self.boot_services
+                .install_configuration_table_unchecked(&efi::SMBIOS_TABLE_GUID, self.buffer as *mut c_void)
+        } {
+            log::error!("Failed to install the SMBIOS 32-bit entry point: {status:?}");
+            return;
+        }
+        log::debug!("  32-bit Entry Point: {:#X}", self.buffer);
+
+        if !self.entry_point.has_64_bit() {
+            // SAFETY: A null table removes the configuration table.
+            if let Err(status) = unsafe {
+                self.boot_services
+                    .install_configuration_table_unchecked(&efi::SMBIOS3_TABLE_GUID, core::ptr::null_mut())
+            } {
+                log::error!("Failed to remove the SMBIOS 64-bit entry point: {status:?}");
+            }
+        }
+    }
+}
+
+/// Event notification that refreshes the 32-bit entry point. ReadyToBoot is signaled again for each boot option.
+extern "efiapi" fn ready_to_boot_callback(_event: efi::Event, publication: &'static Publication) {
+    publication.refresh();
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::smbios::{compliance::EntryPoint, qemu::tests::structure};
+
+    #[test]
+    fn test_entry_point_32_describes_the_table() {
+        let table = [
+            structure(0, 0x0000, &[0; 0x16], &["Vendor", "1.0"]),
+            structure(1, 0x0001, &[0; 0x17], &[]),
+            structure(127, 0xFEFF, &[], &[]),
+        ]
+        .concat();
+        let bytes = entry_point_32((2, 8), &table, 0x7FF0_0000).unwrap();
+
+        assert_eq!(&bytes[..4], ANCHOR_32);
+        assert_eq!(bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)), 0);
+        assert_eq!(u16::from_le_bytes([bytes[0x08], bytes[0x09]]), 0x1A + 12);
+        assert_eq!(u16::from_le_bytes([bytes[0x1C], bytes[0x1D]]), 3);
+        assert_eq!(bytes[0x1E], 0x28);
+
+        let entry_point = EntryPoint::parse(&bytes).unwrap();
+        assert_eq!(entry_point.version, (2, 8));
+        assert_eq!(entry_point.table_maximum_size as usize, table.len());
+        assert_eq!(entry_point.table_address, 0x7FF0_0000);
+    }
+
+    #[test]
+    fn test_entry_point_32_rejects_invalid_tables() {
+        let end = structure(127, 0xFEFF, &[], &[]);
+        assert!(entry_point_32((3, 9), &end, 0).is_some());
+        assert_eq!(entry_point_32((3, 10), &end, 0).unwrap()[0x1E], 0);
+        // Truncated, unterminated and larger than 64 KB
+        assert!(entry_point_32((3, 9), &end[..3], 0).is_none());
+        assert!(entry_point_32((3, 9), &structure(1, 0x0001, &[0; 0x17], &[]), 0).is_none());
+        assert!(entry_point_32((3, 9), &[end.as_slice(), &[0; 0x10000]].concat(), 0).is_none());
+    }
+
+    #[test]
+    fn test_entry_point_style() {
+        assert_eq!(SmbiosEntryPoint::default(), SmbiosEntryPoint::Bits64);
+        assert!(SmbiosEntryPoint::Bits32.has_32_bit() && !SmbiosEntryPoint::Bits32.has_64_bit());
+        assert!(!SmbiosEntryPoint::Bits64.has_32_bit() && SmbiosEntryPoint::Bits64.has_64_bit());
+        assert!(SmbiosEntryPoint::Both.has_32_bit() && SmbiosEntryPoint::Both.has_64_bit());
+    }
+}
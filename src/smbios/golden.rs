@@ -0,0 +1,174 @@
+//! SMBIOS Golden Record Dumps
+//!
+//! A host-only [`RecordingSmbios`] service that captures the records a platform component adds, and a text dump of
+//! them that platform tests compare against a dump checked in next to the test with [`assert_golden`]. Each record is
+//! dumped as the hex bytes of its formatted area followed by its strings, so a change to the encoding of a record
+//! shows up as a reviewable diff of the dump.
+//!
+//! Crate versions, which change with every release, are replaced with placeholders. `BUILD_DATE` is ignored in tests,
+//! so the release dates are always the defaults.
+//!
+//! Run the tests with `SMBIOS_GOLDEN_UPDATE=1` to rewrite the dumps instead of comparing them.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg(test)]
+
+extern crate std;
+use std::{
+    boxed::Box,
+    cell::RefCell,
+    fmt::Write,
+    format,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use patina::component::service::Service;
+use patina_smbios::{
+    error::SmbiosError,
+    service::{SMBIOS_HANDLE_PI_RESERVED, Smbios, SmbiosHandle},
+};
+
+/// Environment variable that rewrites the golden dumps instead of comparing them
+const UPDATE_VARIABLE: &str = "SMBIOS_GOLDEN_UPDATE";
+
+/// An SMBIOS service that records the bytes of every added record.
+///
+/// Handles are assigned in order from 1, as the SMBIOS provider assigns handle 0 to the End-of-Table structure.
+/// Records are only checked for the framing the provider rejects.
+#[derive(Default)]
+pub struct RecordingSmbios {
+    records: Rc<RefCell<Vec<Vec<u8>>>>,
+}
+
+impl RecordingSmbios {
+    /// Creates a service with no records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a handle to the recorded records that remains valid after the service is created.
+    pub fn records(&self) -> Rc<RefCell<Vec<Vec<u8>>>> {
+        self.records.clone()
+    }
+
+    /// Converts the recorder into a service.
+    pub fn into_service(self) -> Service<dyn Smbios> {
+        Service::mock(Box::new(self))
+    }
+}
+
+impl Smbios for RecordingSmbios {
+    fn version(&self) -> (u8, u8) {
+        (3, 9)
+    }
+
+    fn publish_table(&self) -> Result<(u64, u64), SmbiosError> {
+        Err(SmbiosError::NotInitialized)
+    }
+
+    fn update_string(&self, _handle: SmbiosHandle, _string_number: usize, _string: &str) -> Result<(), SmbiosError> {
+        Err(SmbiosError::RecordNotFound)
+    }
+
+    fn remove(&self, _handle: SmbiosHandle) -> Result<(), SmbiosError> {
+        Err(SmbiosError::RecordNotFound)
+    }
+
+    fn add_from_bytes(&self, _producer: Option<r_efi::efi::Handle>, bytes: &[u8]) -> Result<SmbiosHandle, SmbiosError> {
+        let length = *bytes.get(1).ok_or(SmbiosError::RecordTooSmall)? as usize;
+        if length < 4 || bytes.len() < length + 2 {
+            return Err(SmbiosError::RecordTooSmall);
+        }
+        if !bytes.ends_with(&[0, 0]) {
+            return Err(SmbiosError::InvalidStringPoolTermination);
+        }
+
+        let mut records = self.records.borrow_mut();
+        let requested = u16::from_le_bytes([bytes[2], bytes[3]]);
+        let handle = if requested == SMBIOS_HANDLE_PI_RESERVED { records.len() as SmbiosHandle + 1 } else { requested };
+        if records.iter().any(|record| record[2..4] == handle.to_le_bytes()) {
+            return Err(SmbiosError::HandleInUse);
+        }
+
+        let mut record = bytes.to_vec();
+        record[2..4].copy_from_slice(&handle.to_le_bytes());
+        records.push(record);
+        Ok(handle)
+    }
+}
+
+/// Returns the crate version strings, with their placeholders.
+fn build_strings() -> Vec<(String, &'static str)> {
+    let mut strings = Vec::from([(env!("CARGO_PKG_VERSION").to_string(), "<version>")]);
+    for (_, version) in env!("PATINA_CRATE_VERSIONS").split(',').filter_map(|entry| entry.split_once(' ')) {
+        strings.push((version.to_string(), "<version>"));
+    }
+    strings
+}
+
+/// Dumps `records` as text: a line naming the type and handle of each record, the bytes of its formatted area, 16 per
+/// line, and its strings, with a blank line between records.
+pub fn dump(records: &[Vec<u8>]) -> String {
+    let build_strings = build_strings();
+    let mut dump = String::new();
+    for record in records {
+        if !dump.is_empty() {
+            dump.push('\n');
+        }
+        let length = record[1] as usize;
+        writeln!(dump, "Type {} Handle 0x{:04X}", record[0], u16::from_le_bytes([record[2], record[3]])).unwrap();
+        for (line, bytes) in record[..length].chunks(16).enumerate() {
+            let bytes: Vec<_> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            writeln!(dump, "  {:02X}: {}", line * 16, bytes.join(" ")).unwrap();
+        }
+        let strings = &record[length..record.len() - 2];
+        for string in strings.split(|&b| b == 0).filter(|string| !string.is_empty()) {
+            let string = String::from_utf8_lossy(string);
+            let string = build_strings.iter().find(|(value, _)| *value == string).map_or(&*string, |(_, p)| *p);
+            writeln!(dump, "  {string:?}").unwrap();
+        }
+    }
+    dump
+}
+
+/// Compares `actual` with the golden dump at `path`, relative to the crate root, or rewrites the dump if
+/// `SMBIOS_GOLDEN_UPDATE` is set.
+pub fn assert_golden(path: &str, actual: &str) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    if std::env::var_os(UPDATE_VARIABLE).is_some() {
+        std::fs::write(&path, actual).expect("Failed to write the golden dump");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        expected == actual,
+        "The SMBIOS records differ from {}. If the change is intended, rerun with {UPDATE_VARIABLE}=1 and review the \
+         diff.\n{}",
+        path.display(),
+        diff(&expected, actual)
+    );
+}
+
+/// Returns the lines of `expected` and `actual` that differ, in order.
+fn diff(expected: &str, actual: &str) -> String {
+    let (mut expected, mut actual) = (expected.lines(), actual.lines());
+    let mut diff = String::new();
+    loop {
+        match (expected.next(), actual.next()) {
+            (None, None) => return diff,
+            (e, a) if e == a => {}
+            (e, a) => {
+                e.into_iter().for_each(|line| writeln!(diff, "- {line}").unwrap());
+                a.into_iter().for_each(|line| writeln!(diff, "+ {line}").unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! SMBIOS OEM Strings Test
+//!
+//! Reads the Type 11 OEM Strings and Type 12 System Configuration Options records back through the SMBIOS protocol
+//! and verifies they carry the strings in the [`SmbiosPlatformConfig`].
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{string::String, vec::Vec};
+use core::ffi::{CStr, c_char};
+
+// used in the macro, but not directly referenced; causes a warning if patina tests not enabled.
+#[allow(unused)]
+use patina::BinaryGuid;
+use patina::{boot_services::StandardBootServices, component::params::Config};
+use patina_smbios::service::{SMBIOS_HANDLE_PI_RESERVED, SmbiosHandle, SmbiosTableHeader};
+use patina_test::{patina_test, u_assert, u_assert_eq};
+use r_efi::efi;
+
+use super::{
+    SmbiosPlatformConfig,
+    protocol_test::{self, SmbiosProtocol},
+};
+
+/// Offset of the `Count` field in the Type 11 and Type 12 structures
+const COUNT_OFFSET: usize = 0x04;
+
+/// Verifies the configured OEM strings and configuration options are published once everything has been added.
+#[patina_test]
+#[on(event = BinaryGuid(efi::EVENT_GROUP_READY_TO_BOOT))]
+fn smbios_oem_strings_test(
+    boot_services: StandardBootServices,
+    config: Config<SmbiosPlatformConfig>,
+) -> patina_test::error::Result {
+    log::debug!("SMBIOS OEM Strings Test - Reading Type 11 and Type 12 records");
+
+    let protocol = protocol_test::locate(&boot_services)?;
+
+    let oem_strings = read_strings(protocol, 11)?;
+    for string in config.oem_strings.iter().filter(|string| !string.is_empty()) {
+        u_assert!(oem_strings.iter().any(|s| s == string), "Configured OEM string should be published");
+    }
+    log::trace!("  OEM strings: {:?}", oem_strings);
+
+    let options = read_strings(protocol, 12)?;
+    for option in config.configuration_options.iter().filter(|option| !option.is_empty()) {
+        u_assert!(options.iter().any(|s| s == option), "Configured configuration option should be published");
+    }
+    log::trace!("  Configuration options: {:?}", options);
+
+    log::debug!("SMBIOS OEM Strings Test complete");
+    Ok(())
+}
+
+/// Returns the strings of every record of `record_type`, checking each record's `Count` field against its strings.
+fn read_strings(protocol: &SmbiosProtocol, record_type: u8) -> Result<Vec<String>, &'static str> {
+    let mut strings = Vec::new();
+    let mut handle: SmbiosHandle = SMBIOS_HANDLE_PI_RESERVED;
+    let mut filter = record_type;
+    let mut record: *mut SmbiosTableHeader = core::ptr::null_mut();
+    let mut producer: efi::Handle = core::ptr::null_mut();
+
+    while (protocol.get_next)(protocol, &mut handle, &mut filter, &mut record, &mut producer) == efi::Status::SUCCESS {
+        // SAFETY: GetNext returns a complete record: the formatted area followed by its double-null-terminated
+        // string set.
+        let (count, record_strings) = unsafe {
+            let base = record as *const u8;
+            let length = (*record).length as usize;
+            u_assert!(length > COUNT_OFFSET, "Record should hold a Count field");
+
+            let mut record_strings = Vec::new();
+            let mut next = base.add(length) as *const c_char;
+            while *next != 0 {
+                let string = CStr::from_ptr(next);
+                next = next.add(string.count_bytes() + 1);
+                record_strings.push(String::from_utf8_lossy(string.to_bytes()).into_owned());
+            }
+            (*base.add(COUNT_OFFSET), record_strings)
+        };
+        u_assert_eq!(count as usize, record_strings.len(), "Count should match the number of strings");
+        strings.extend(record_strings);
+    }
+    Ok(strings)
+}
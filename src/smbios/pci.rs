@@ -0,0 +1,460 @@
+//! PCI Root Bus Enumeration for SMBIOS
+//!
+//! Enumerates the functions on the PCI root bus through a configuration space accessor provided by the platform, so
+//! the SMBIOS platform builder can describe the integrated devices (Type 41) and the PCI Express root port slots
+//! (Type 9).
+//!
+//! Only the root bus is scanned. The SMBIOS platform components run before the PCI bus driver assigns bus numbers
+//! behind the root ports, and the integrated devices and root ports both live on the root bus on the QEMU machines.
+//!
+//! ## References
+//!
+//! - PCI Local Bus Specification 3.0, section 6.2 (configuration space header)
+//! - PCI Express Base Specification 6.0, section 7.5.3 (PCI Express capability structure)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use patina_smbios::smbios_types::{SlotType, SlotWidth};
+
+/// PCI segment group of the root bus
+pub const SEGMENT: u16 = 0;
+/// Bus number of the root bus
+pub const ROOT_BUS: u8 = 0;
+
+/// Number of devices on a bus
+const DEVICES: u8 = 32;
+/// Number of functions of a multi-function device
+const FUNCTIONS: u8 = 8;
+/// Maximum number of capabilities followed in a capability list, which guards against malformed lists
+const MAX_CAPABILITIES: usize = 48;
+
+/// Configuration space header registers (dword offsets)
+mod register {
+    pub const ID: u16 = 0x00;
+    pub const COMMAND_STATUS: u16 = 0x04;
+    pub const CLASS_REVISION: u16 = 0x08;
+    pub const HEADER_TYPE: u16 = 0x0C;
+    pub const CAPABILITIES_POINTER: u16 = 0x34;
+
+    /// Capabilities List bit of the Status register
+    pub const STATUS_CAPABILITIES_LIST: u32 = 1 << 20;
+    /// Multi-function bit of the Header Type register
+    pub const HEADER_TYPE_MULTI_FUNCTION: u32 = 1 << 23;
+}
+
+/// PCI Express capability registers (dword offsets from the capability)
+mod pcie {
+    pub const CAPABILITY_ID: u32 = 0x10;
+    pub const LINK_CAPABILITIES: u16 = 0x0C;
+    pub const SLOT_CAPABILITIES: u16 = 0x14;
+    pub const SLOT_CONTROL_STATUS: u16 = 0x18;
+
+    /// Slot Implemented bit of the PCI Express Capabilities register
+    pub const SLOT_IMPLEMENTED: u32 = 1 << 24;
+    /// Device/Port Type of a Root Port
+    pub const PORT_TYPE_ROOT_PORT: u8 = 0x4;
+
+    /// Hot-Plug Surprise bit of the Slot Capabilities register
+    pub const SLOT_HOT_PLUG_SURPRISE: u32 = 1 << 5;
+    /// Hot-Plug Capable bit of the Slot Capabilities register
+    pub const SLOT_HOT_PLUG_CAPABLE: u32 = 1 << 6;
+    /// Presence Detect State bit of the Slot Status register
+    pub const SLOT_PRESENCE_DETECT_STATE: u16 = 1 << 6;
+}
+
+/// PCI base class codes
+mod class {
+    pub const MASS_STORAGE: u8 = 0x01;
+    pub const NETWORK: u8 = 0x02;
+    pub const DISPLAY: u8 = 0x03;
+    pub const MULTIMEDIA: u8 = 0x04;
+    pub const BRIDGE: u8 = 0x06;
+    pub const WIRELESS: u8 = 0x0D;
+}
+
+/// SMBIOS onboard device types (Type 41 `Device Type` bits 6:0)
+pub mod onboard_device_type {
+    /// Other
+    pub const OTHER: u8 = 0x01;
+    /// Video
+    pub const VIDEO: u8 = 0x03;
+    /// SCSI Controller
+    pub const SCSI: u8 = 0x04;
+    /// Ethernet
+    pub const ETHERNET: u8 = 0x05;
+    /// Sound
+    pub const SOUND: u8 = 0x07;
+    /// PATA Controller
+    pub const PATA: u8 = 0x08;
+    /// SATA Controller
+    pub const SATA: u8 = 0x09;
+    /// SAS Controller
+    pub const SAS: u8 = 0x0A;
+    /// Wireless LAN
+    pub const WIRELESS_LAN: u8 = 0x0B;
+    /// Bluetooth
+    pub const BLUETOOTH: u8 = 0x0C;
+    /// NVMe Controller
+    pub const NVME: u8 = 0x0F;
+}
+
+/// The PCI Express capability of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcieCapability {
+    /// PCI Express Capabilities register, in the upper half of the capability header
+    pub capabilities: u32,
+    /// Link Capabilities register
+    pub link_capabilities: u32,
+    /// Slot Capabilities register
+    pub slot_capabilities: u32,
+    /// Slot Status register
+    pub slot_status: u16,
+}
+
+impl PcieCapability {
+    /// Returns the Device/Port Type.
+    pub fn port_type(&self) -> u8 {
+        ((self.capabilities >> 20) & 0xF) as u8
+    }
+
+    /// Returns whether the port is connected to a slot.
+    pub fn slot_implemented(&self) -> bool {
+        self.capabilities & pcie::SLOT_IMPLEMENTED != 0
+    }
+
+    /// Returns the maximum link speed, as the PCI Express generation.
+    pub fn max_link_speed(&self) -> u8 {
+        (self.link_capabilities & 0xF) as u8
+    }
+
+    /// Returns the maximum link width, in lanes.
+    pub fn max_link_width(&self) -> u8 {
+        ((self.link_capabilities >> 4) & 0x3F) as u8
+    }
+
+    /// Returns the physical slot number, or 0 if the slot is not numbered.
+    pub fn physical_slot_number(&self) -> u16 {
+        (self.slot_capabilities >> 19) as u16
+    }
+
+    /// Returns whether the slot supports hot-plug.
+    pub fn hot_plug_capable(&self) -> bool {
+        self.slot_capabilities & pcie::SLOT_HOT_PLUG_CAPABLE != 0
+    }
+
+    /// Returns whether a device can be removed from the slot without prior notification.
+    pub fn hot_plug_surprise(&self) -> bool {
+        self.slot_capabilities & pcie::SLOT_HOT_PLUG_SURPRISE != 0
+    }
+
+    /// Returns whether a card is present in the slot.
+    pub fn presence_detected(&self) -> bool {
+        self.slot_status & pcie::SLOT_PRESENCE_DETECT_STATE != 0
+    }
+
+    /// Returns the SMBIOS slot type for the maximum link speed and width.
+    pub fn slot_type(&self) -> SlotType {
+        let lanes = [1, 2, 4, 8, 16].iter().position(|&lanes| lanes == self.max_link_width());
+        const GEN1: [SlotType; 5] = [
+            SlotType::PciExpressx1,
+            SlotType::PciExpressx2,
+            SlotType::PciExpressx4,
+            SlotType::PciExpressx8,
+            SlotType::PciExpressx16,
+        ];
+        const GEN2: [SlotType; 5] = [
+            SlotType::PciExpressGen2x1,
+            SlotType::PciExpressGen2x2,
+            SlotType::PciExpressGen2x4,
+            SlotType::PciExpressGen2x8,
+            SlotType::PciExpressGen2x16,
+        ];
+        const GEN3: [SlotType; 5] = [
+            SlotType::PciExpressGen3x1,
+            SlotType::PciExpressGen3x2,
+            SlotType::PciExpressGen3x4,
+            SlotType::PciExpressGen3x8,
+            SlotType::PciExpressGen3x16,
+        ];
+        const GEN4: [SlotType; 5] = [
+            SlotType::PciExpressGen4x1,
+            SlotType::PciExpressGen4x2,
+            SlotType::PciExpressGen4x4,
+            SlotType::PciExpressGen4x8,
+            SlotType::PciExpressGen4x16,
+        ];
+        const GEN5: [SlotType; 5] = [
+            SlotType::PciExpressGen5x1,
+            SlotType::PciExpressGen5x2,
+            SlotType::PciExpressGen5x4,
+            SlotType::PciExpressGen5x8,
+            SlotType::PciExpressGen5x16,
+        ];
+
+        match (self.max_link_speed(), lanes) {
+            (1, Some(lanes)) => GEN1[lanes],
+            (2, Some(lanes)) => GEN2[lanes],
+            (3, Some(lanes)) => GEN3[lanes],
+            (4, Some(lanes)) => GEN4[lanes],
+            (5, Some(lanes)) => GEN5[lanes],
+            (2, None) => SlotType::PciExpressGen2,
+            (3, None) => SlotType::PciExpressGen3,
+            (4, None) => SlotType::PciExpressGen4,
+            (5, None) => SlotType::PciExpressGen5,
+            (6.., _) => SlotType::PciExpressGen6,
+            _ => SlotType::PciExpress,
+        }
+    }
+
+    /// Returns the SMBIOS slot width for the maximum link width.
+    pub fn slot_width(&self) -> SlotWidth {
+        match self.max_link_width() {
+            1 => SlotWidth::X1,
+            2 => SlotWidth::X2,
+            4 => SlotWidth::X4,
+            8 => SlotWidth::X8,
+            12 => SlotWidth::X12,
+            16 => SlotWidth::X16,
+            32 => SlotWidth::X32,
+            _ => SlotWidth::Unknown,
+        }
+    }
+}
+
+/// A function on the PCI root bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciFunction {
+    /// Device number
+    pub device: u8,
+    /// Function number
+    pub function: u8,
+    /// Vendor ID
+    pub vendor_id: u16,
+    /// Device ID
+    pub device_id: u16,
+    /// Base class code
+    pub base_class: u8,
+    /// Subclass code
+    pub sub_class: u8,
+    /// Programming interface
+    pub prog_if: u8,
+    /// PCI Express capability, if the function has one
+    pub pcie: Option<PcieCapability>,
+}
+
+impl PciFunction {
+    /// Returns the PCI Express capability if the function is a root port.
+    pub fn root_port(&self) -> Option<&PcieCapability> {
+        self.pcie.as_ref().filter(|pcie| pcie.port_type() == pcie::PORT_TYPE_ROOT_PORT)
+    }
+
+    /// Returns the SMBIOS onboard device type and its name, or `None` for bridges, which are not onboard devices.
+    pub fn onboard_device_type(&self) -> Option<(u8, &'static str)> {
+        use onboard_device_type::*;
+
+        Some(match (self.base_class, self.sub_class, self.prog_if) {
+            (class::BRIDGE, _, _) => return None,
+            (class::DISPLAY, _, _) => (VIDEO, "Video"),
+            (class::MASS_STORAGE, 0x00, _) => (SCSI, "SCSI"),
+            (class::MASS_STORAGE, 0x01, _) => (PATA, "IDE"),
+            (class::MASS_STORAGE, 0x06, _) => (SATA, "SATA"),
+            (class::MASS_STORAGE, 0x07, _) => (SAS, "SAS"),
+            (class::MASS_STORAGE, 0x08, 0x02) => (NVME, "NVMe"),
+            (class::NETWORK, 0x00, _) => (ETHERNET, "Ethernet"),
+            (class::MULTIMEDIA, 0x01 | 0x03, _) => (SOUND, "Sound"),
+            (class::WIRELESS, 0x11, _) => (BLUETOOTH, "Bluetooth"),
+            (class::WIRELESS, 0x20 | 0x21, _) => (WIRELESS_LAN, "Wireless LAN"),
+            _ => (OTHER, "Device"),
+        })
+    }
+}
+
+/// The functions on the PCI root bus.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PciRootBus {
+    functions: Vec<PciFunction>,
+}
+
+impl PciRootBus {
+    /// Enumerates the root bus.
+    ///
+    /// `read` returns the configuration space dword at a register offset of a device and function on the root bus.
+    /// A function that is not present reads all ones, or all zeros on some configuration space emulations.
+    pub fn scan(read: impl Fn(u8, u8, u16) -> u32) -> Self {
+        let mut functions = Vec::new();
+        for device in 0..DEVICES {
+            for function in 0..FUNCTIONS {
+                let id = read(device, function, register::ID);
+                if matches!(id as u16, 0xFFFF | 0x0000) {
+                    if function == 0 {
+                        break;
+                    }
+                    continue;
+                }
+
+                let class = read(device, function, register::CLASS_REVISION);
+                functions.push(PciFunction {
+                    device,
+                    function,
+                    vendor_id: id as u16,
+                    device_id: (id >> 16) as u16,
+                    base_class: (class >> 24) as u8,
+                    sub_class: (class >> 16) as u8,
+                    prog_if: (class >> 8) as u8,
+                    pcie: pcie_capability(&|offset| read(device, function, offset)),
+                });
+
+                if function == 0 && read(device, 0, register::HEADER_TYPE) & register::HEADER_TYPE_MULTI_FUNCTION == 0 {
+                    break;
+                }
+            }
+        }
+        Self { functions }
+    }
+
+    /// Returns the functions in device and function order.
+    pub fn functions(&self) -> &[PciFunction] {
+        &self.functions
+    }
+
+    /// Returns the PCI Express root ports.
+    pub fn root_ports(&self) -> impl Iterator<Item = (&PciFunction, &PcieCapability)> {
+        self.functions.iter().filter_map(|function| Some((function, function.root_port()?)))
+    }
+}
+
+/// Finds and reads the PCI Express capability of a function. `read` reads a dword of its configuration space.
+fn pcie_capability(read: &dyn Fn(u16) -> u32) -> Option<PcieCapability> {
+    if read(register::COMMAND_STATUS) & register::STATUS_CAPABILITIES_LIST == 0 {
+        return None;
+    }
+
+    let mut offset = (read(register::CAPABILITIES_POINTER) & 0xFC) as u16;
+    for _ in 0..MAX_CAPABILITIES {
+        if offset < 0x40 {
+            return None;
+        }
+        let header = read(offset);
+        if header & 0xFF == pcie::CAPABILITY_ID {
+            return Some(PcieCapability {
+                capabilities: header,
+                link_capabilities: read(offset + pcie::LINK_CAPABILITIES),
+                slot_capabilities: read(offset + pcie::SLOT_CAPABILITIES),
+                slot_status: (read(offset + pcie::SLOT_CONTROL_STATUS) >> 16) as u16,
+            });
+        }
+        offset = ((header >> 8) & 0xFC) as u16;
+    }
+    None
+}
+
+#[cfg(test)]
+#[coverage(off)]
+pub(crate) mod tests {
+    extern crate std;
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// A configuration space for a root bus, in dwords by device, function and register offset.
+    #[derive(Default)]
+    pub(crate) struct FakeConfigSpace(BTreeMap<(u8, u8, u16), u32>);
+
+    impl FakeConfigSpace {
+        /// Adds a function with the given class code (base class, subclass and programming interface).
+        pub(crate) fn add_function(&mut self, device: u8, function: u8, class_code: u32) {
+            self.0.insert((device, function, register::ID), 0x1234_1B36);
+            self.0.insert((device, function, register::CLASS_REVISION), class_code << 8);
+            if function != 0 {
+                *self.0.entry((device, 0, register::HEADER_TYPE)).or_default() |= register::HEADER_TYPE_MULTI_FUNCTION;
+            }
+        }
+
+        /// Adds a PCI Express root port with a slot.
+        pub(crate) fn add_root_port(&mut self, device: u8, function: u8, link: (u8, u8), slot: u16, present: bool) {
+            self.add_function(device, function, 0x06_04_00);
+            let (speed, width) = link;
+            self.0.insert((device, function, register::COMMAND_STATUS), register::STATUS_CAPABILITIES_LIST);
+            self.0.insert((device, function, register::CAPABILITIES_POINTER), 0x40);
+            // A power management capability precedes the PCI Express capability
+            self.0.insert((device, function, 0x40), 0x0000_6001);
+            self.0.insert(
+                (device, function, 0x60),
+                pcie::SLOT_IMPLEMENTED | (pcie::PORT_TYPE_ROOT_PORT as u32) << 20 | 0x0002_0010,
+            );
+            self.0.insert((device, function, 0x60 + pcie::LINK_CAPABILITIES), (width as u32) << 4 | speed as u32);
+            self.0.insert(
+                (device, function, 0x60 + pcie::SLOT_CAPABILITIES),
+                (slot as u32) << 19 | pcie::SLOT_HOT_PLUG_CAPABLE,
+            );
+            let status = if present { pcie::SLOT_PRESENCE_DETECT_STATE } else { 0 };
+            self.0.insert((device, function, 0x60 + pcie::SLOT_CONTROL_STATUS), (status as u32) << 16);
+        }
+
+        /// Reads a configuration dword. Absent functions read as all ones.
+        pub(crate) fn read(&self, device: u8, function: u8, offset: u16) -> u32 {
+            match self.0.get(&(device, function, register::ID)) {
+                Some(_) => self.0.get(&(device, function, offset)).copied().unwrap_or_default(),
+                None => u32::MAX,
+            }
+        }
+
+        pub(crate) fn scan(&self) -> PciRootBus {
+            PciRootBus::scan(|device, function, offset| self.read(device, function, offset))
+        }
+    }
+
+    #[test]
+    fn test_scan_finds_functions() {
+        let mut config = FakeConfigSpace::default();
+        config.add_function(0, 0, 0x06_00_00);
+        config.add_function(1, 0, 0x03_00_00);
+        config.add_function(0x1F, 0, 0x06_01_00);
+        config.add_function(0x1F, 2, 0x01_06_01);
+        config.add_function(0x1F, 3, 0x0C_05_00);
+        // Function 1 of a single-function device is not scanned
+        config.add_function(2, 0, 0x02_00_00);
+        config.0.insert((2, 1, register::ID), 0x1234_1B36);
+
+        let bus = config.scan();
+        let functions: Vec<_> = bus.functions().iter().map(|f| (f.device, f.function)).collect();
+        assert_eq!(functions, [(0, 0), (1, 0), (2, 0), (0x1F, 0), (0x1F, 2), (0x1F, 3)]);
+        assert_eq!(bus.functions()[4].onboard_device_type(), Some((onboard_device_type::SATA, "SATA")));
+        assert_eq!(bus.functions()[0].onboard_device_type(), None);
+        assert!(bus.functions().iter().all(|f| f.pcie.is_none()));
+        assert_eq!(bus.root_ports().count(), 0);
+    }
+
+    #[test]
+    fn test_scan_reads_root_port_capability() {
+        let mut config = FakeConfigSpace::default();
+        config.add_root_port(2, 0, (3, 4), 5, true);
+        config.add_root_port(2, 1, (1, 1), 0, false);
+
+        let bus = config.scan();
+        let ports: Vec<_> = bus.root_ports().collect();
+        assert_eq!(ports.len(), 2);
+
+        let (function, port) = ports[0];
+        assert_eq!((function.device, function.function), (2, 0));
+        assert!(port.slot_implemented());
+        assert_eq!((port.max_link_speed(), port.max_link_width()), (3, 4));
+        assert_eq!(port.physical_slot_number(), 5);
+        assert!(port.hot_plug_capable());
+        assert!(port.presence_detected());
+        assert!(matches!(port.slot_type(), SlotType::PciExpressGen3x4));
+        assert!(matches!(port.slot_width(), SlotWidth::X4));
+
+        let (_, port) = ports[1];
+        assert!(!port.presence_detected());
+        assert!(matches!(port.slot_type(), SlotType::PciExpressx1));
+    }
+}
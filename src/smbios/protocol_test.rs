@@ -0,0 +1,489 @@
+//! SMBIOS Protocol Test
+//!
+//! Exercises the EDK II SMBIOS protocol, the C FFI layer of the SMBIOS component, through its Add, UpdateString,
+//! Remove and GetNext functions. Each platform runs [`run`] from its own `patina_test`.
+//!
+//! Besides the happy paths, the test checks that the protocol rejects malformed records, out of range and duplicate
+//! handles, oversized strings and out of range string numbers, that GetNext honors its type filter, and that the
+//! producer handle of a record is reported back. Every record the test adds uses an OEM type and is removed again.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{ffi::CString, vec, vec::Vec};
+use core::ffi::{CStr, c_char};
+
+use patina::boot_services::{BootServices, StandardBootServices, protocol_handler::HandleSearchType};
+use patina_smbios::service::{SMBIOS_HANDLE_PI_RESERVED, SMBIOS_STRING_MAX_LENGTH, SmbiosHandle, SmbiosTableHeader};
+use patina_test::{u_assert, u_assert_eq};
+use r_efi::{efi, protocols::loaded_image};
+
+/// EDK II SMBIOS protocol GUID
+pub const SMBIOS_PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x03583ff6, 0xcb36, 0x4940, 0x94, 0x7e, &[0xb9, 0xb3, 0x9f, 0x4a, 0xfa, 0xf7]);
+
+/// OEM-specific types of the records the test adds, which no platform publishes
+const TEST_TYPE: u8 = 0xF0;
+const OTHER_TEST_TYPE: u8 = 0xF1;
+
+/// Handle outside the range the protocol assigns or accepts
+const HANDLE_OUT_OF_RANGE: SmbiosHandle = 0xFFFF;
+
+/// Bytes the protocol scans for the end of a string pool before it gives up
+const STRING_POOL_SCAN_LIMIT: usize = 4096;
+
+/// EDK II SMBIOS protocol, as laid out by the C protocol layer
+#[repr(C)]
+pub struct SmbiosProtocol {
+    /// Adds a record
+    pub add: extern "efiapi" fn(
+        *const SmbiosProtocol,
+        efi::Handle,
+        *mut SmbiosHandle,
+        *const SmbiosTableHeader,
+    ) -> efi::Status,
+    /// Replaces a string of a record
+    pub update_string:
+        extern "efiapi" fn(*const SmbiosProtocol, *mut SmbiosHandle, *mut usize, *const c_char) -> efi::Status,
+    /// Removes a record
+    pub remove: extern "efiapi" fn(*const SmbiosProtocol, SmbiosHandle) -> efi::Status,
+    /// Returns the record after a handle, optionally of one type
+    pub get_next: extern "efiapi" fn(
+        *const SmbiosProtocol,
+        *mut SmbiosHandle,
+        *mut u8,
+        *mut *mut SmbiosTableHeader,
+        *mut efi::Handle,
+    ) -> efi::Status,
+    /// SMBIOS major version
+    pub major_version: u8,
+    /// SMBIOS minor version
+    pub minor_version: u8,
+}
+
+/// A record returned by GetNext
+struct NextRecord {
+    handle: SmbiosHandle,
+    record_type: u8,
+    strings: Vec<Vec<u8>>,
+    producer: efi::Handle,
+}
+
+/// Locates the SMBIOS protocol.
+pub fn locate(boot_services: &StandardBootServices) -> Result<&'static SmbiosProtocol, &'static str> {
+    // SAFETY: The SMBIOS component installs this protocol with the layout of `SmbiosProtocol`.
+    unsafe {
+        let ptr =
+            boot_services.locate_protocol_unchecked(&SMBIOS_PROTOCOL_GUID, core::ptr::null_mut()).map_err(|e| {
+                log::error!("Failed to locate SMBIOS protocol: {:?}", e);
+                "Failed to locate SMBIOS protocol"
+            })?;
+        Ok(&*(ptr as *const SmbiosProtocol))
+    }
+}
+
+/// Runs every protocol check.
+pub fn run(boot_services: &StandardBootServices) -> patina_test::error::Result {
+    log::debug!("SMBIOS FFI Test - Testing C Protocol FFI Layer");
+    let protocol = locate(boot_services)?;
+
+    log::trace!("  Add, UpdateString and Remove...");
+    test_add_update_remove(protocol)?;
+    log::trace!("  Malformed records...");
+    test_malformed_records(protocol)?;
+    log::trace!("  Invalid handles...");
+    test_invalid_handles(protocol)?;
+    log::trace!("  Duplicate handles...");
+    test_duplicate_handles(protocol)?;
+    log::trace!("  String updates...");
+    test_string_updates(protocol)?;
+    log::trace!("  GetNext type filter...");
+    test_get_next_type_filter(protocol)?;
+    log::trace!("  Producer handles...");
+    test_producer_handles(protocol, boot_services)?;
+
+    log::debug!("SMBIOS FFI Test complete");
+    Ok(())
+}
+
+/// Adds, updates and removes a Type 2 record.
+fn test_add_update_remove(protocol: &SmbiosProtocol) -> patina_test::error::Result {
+    let baseboard =
+        record(2, SMBIOS_HANDLE_PI_RESERVED, &[1, 2, 3, 4], &["Test Manufacturer", "Test Product", "1.0", "SN-12345"]);
+    let handle = add(protocol, core::ptr::null_mut(), &baseboard)?;
+
+    u_assert_eq!(
+        update_string(protocol, handle, 1, c"Updated via C Protocol"),
+        efi::Status::SUCCESS,
+        "Protocol UpdateString should succeed"
+    );
+    let updated = find(protocol, handle, Some(2))?;
+    u_assert_eq!(updated.record_type, 2, "GetNext should return the added record");
+    u_assert!(
+        updated.strings.iter().map(Vec::as_slice).eq([
+            b"Updated via C Protocol".as_slice(),
+            b"Test Product",
+            b"1.0",
+            b"SN-12345"
+        ]),
+        "UpdateString should replace only the updated string"
+    );
+
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+    u_assert_eq!(
+        update_string(protocol, handle, 1, c"Removed"),
+        efi::Status::NOT_FOUND,
+        "UpdateString after removal should not find the record"
+    );
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::NOT_FOUND, "A removed record cannot be removed");
+    u_assert!(find(protocol, handle, None).is_err(), "GetNext should not return a removed record");
+    Ok(())
+}
+
+/// Checks that Add rejects records the SMBIOS specification does not allow.
+fn test_malformed_records(protocol: &SmbiosProtocol) -> patina_test::error::Result {
+    let mut short_header = record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[], &["Test"]);
+    short_header[1] = 3;
+    u_assert_eq!(
+        add_status(protocol, &short_header),
+        efi::Status::INVALID_PARAMETER,
+        "Add should reject a length shorter than the header"
+    );
+
+    let end_of_table = record(127, SMBIOS_HANDLE_PI_RESERVED, &[], &[]);
+    u_assert_eq!(
+        add_status(protocol, &end_of_table),
+        efi::Status::INVALID_PARAMETER,
+        "Add should reject a Type 127 End-of-Table record"
+    );
+
+    // The pool starts with a terminator, so the first string is empty
+    let empty_string = [&[TEST_TYPE, 5, 0xFE, 0xFF, 1][..], b"\0Test\0\0"].concat();
+    u_assert_eq!(
+        add_status(protocol, &empty_string),
+        efi::Status::INVALID_PARAMETER,
+        "Add should reject an empty string in the string pool"
+    );
+
+    let mut unterminated = vec![TEST_TYPE, 4, 0xFE, 0xFF];
+    unterminated.resize(4 + STRING_POOL_SCAN_LIMIT, b'A');
+    unterminated.extend_from_slice(&[0, 0]);
+    u_assert_eq!(
+        add_status(protocol, &unterminated),
+        efi::Status::INVALID_PARAMETER,
+        "Add should reject a string pool without a terminator"
+    );
+
+    let long = "A".repeat(SMBIOS_STRING_MAX_LENGTH + 1);
+    let oversized = record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[1], &[&long]);
+    u_assert_eq!(
+        add_status(protocol, &oversized),
+        efi::Status::INVALID_PARAMETER,
+        "Add should reject a string longer than the SMBIOS maximum"
+    );
+
+    let longest = record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[1], &[&long[..SMBIOS_STRING_MAX_LENGTH]]);
+    let handle = add(protocol, core::ptr::null_mut(), &longest)?;
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+
+    let mut handle = SMBIOS_HANDLE_PI_RESERVED;
+    u_assert_eq!(
+        (protocol.add)(protocol, core::ptr::null_mut(), &mut handle, core::ptr::null()),
+        efi::Status::INVALID_PARAMETER,
+        "Add should reject a missing record"
+    );
+    u_assert_eq!(
+        (protocol.add)(
+            protocol,
+            core::ptr::null_mut(),
+            core::ptr::null_mut(),
+            longest.as_ptr() as *const SmbiosTableHeader
+        ),
+        efi::Status::INVALID_PARAMETER,
+        "Add should reject a missing handle"
+    );
+    Ok(())
+}
+
+/// Checks that handles outside the valid range or without a record are rejected.
+fn test_invalid_handles(protocol: &SmbiosProtocol) -> patina_test::error::Result {
+    let out_of_range = record(TEST_TYPE, HANDLE_OUT_OF_RANGE, &[], &[]);
+    u_assert_eq!(
+        add_status(protocol, &out_of_range),
+        efi::Status::INVALID_PARAMETER,
+        "Add should reject a handle out of range"
+    );
+    for handle in [SMBIOS_HANDLE_PI_RESERVED, HANDLE_OUT_OF_RANGE] {
+        u_assert_eq!(
+            (protocol.remove)(protocol, handle),
+            efi::Status::INVALID_PARAMETER,
+            "Remove should reject a handle out of range"
+        );
+    }
+
+    // The handle is known to be free once its record is removed
+    let handle = add(protocol, core::ptr::null_mut(), &record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[1], &["Test"]))?;
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::NOT_FOUND, "Remove should not find a free handle");
+    u_assert_eq!(
+        update_string(protocol, handle, 1, c"Test"),
+        efi::Status::NOT_FOUND,
+        "UpdateString should not find a free handle"
+    );
+
+    let mut handle = handle;
+    let mut record_ptr = core::ptr::null_mut();
+    u_assert_eq!(
+        (protocol.get_next)(protocol, &mut handle, core::ptr::null_mut(), &mut record_ptr, core::ptr::null_mut()),
+        efi::Status::NOT_FOUND,
+        "GetNext should not continue from a free handle"
+    );
+    u_assert_eq!(
+        (protocol.get_next)(
+            protocol,
+            core::ptr::null_mut(),
+            core::ptr::null_mut(),
+            &mut record_ptr,
+            core::ptr::null_mut()
+        ),
+        efi::Status::INVALID_PARAMETER,
+        "GetNext should reject a missing handle"
+    );
+    Ok(())
+}
+
+/// Checks that a handle can only be requested while no record uses it.
+fn test_duplicate_handles(protocol: &SmbiosProtocol) -> patina_test::error::Result {
+    let handle = add(protocol, core::ptr::null_mut(), &record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[1], &["First"]))?;
+
+    let duplicate = record(TEST_TYPE, handle, &[1], &["Second"]);
+    u_assert_eq!(add_status(protocol, &duplicate), efi::Status::INVALID_PARAMETER, "Add should reject a handle in use");
+    u_assert!(
+        find(protocol, handle, Some(TEST_TYPE))?.strings.iter().map(Vec::as_slice).eq([b"First".as_slice()]),
+        "A rejected duplicate should not replace the record"
+    );
+
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+    u_assert_eq!(
+        add(protocol, core::ptr::null_mut(), &duplicate)?,
+        handle,
+        "Add should assign a requested handle once it is free"
+    );
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+    Ok(())
+}
+
+/// Checks UpdateString with out of range string numbers and invalid strings.
+fn test_string_updates(protocol: &SmbiosProtocol) -> patina_test::error::Result {
+    let handle =
+        add(protocol, core::ptr::null_mut(), &record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[1, 2], &["One", "Two"]))?;
+
+    for string_number in [0, 3] {
+        u_assert_eq!(
+            update_string(protocol, handle, string_number, c"Test"),
+            efi::Status::INVALID_PARAMETER,
+            "UpdateString should reject a string number out of range"
+        );
+    }
+
+    let long = CString::new("A".repeat(SMBIOS_STRING_MAX_LENGTH + 1)).unwrap();
+    u_assert_eq!(
+        update_string(protocol, handle, 2, &long),
+        efi::Status::INVALID_PARAMETER,
+        "UpdateString should reject a string longer than the SMBIOS maximum"
+    );
+    u_assert_eq!(
+        update_string(protocol, handle, 2, c"\xFF\xFE"),
+        efi::Status::INVALID_PARAMETER,
+        "UpdateString should reject a string that is not UTF-8"
+    );
+
+    u_assert_eq!(
+        update_string(protocol, handle, 2, c"A longer second string"),
+        efi::Status::SUCCESS,
+        "Protocol UpdateString should succeed"
+    );
+    u_assert!(
+        find(protocol, handle, Some(TEST_TYPE))?
+            .strings
+            .iter()
+            .map(Vec::as_slice)
+            .eq([b"One".as_slice(), b"A longer second string"]),
+        "UpdateString should resize the string pool"
+    );
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+
+    let handle = add(protocol, core::ptr::null_mut(), &record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[0], &[]))?;
+    u_assert_eq!(
+        update_string(protocol, handle, 1, c"Test"),
+        efi::Status::INVALID_PARAMETER,
+        "UpdateString should reject a record without strings"
+    );
+    u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+    Ok(())
+}
+
+/// Checks that GetNext returns only records of the requested type, and every record without a filter.
+fn test_get_next_type_filter(protocol: &SmbiosProtocol) -> patina_test::error::Result {
+    let mut handles = Vec::new();
+    for (record_type, string) in [(TEST_TYPE, "First"), (OTHER_TEST_TYPE, "Other"), (TEST_TYPE, "Second")] {
+        let record = record(record_type, SMBIOS_HANDLE_PI_RESERVED, &[1], &[string]);
+        handles.push(add(protocol, core::ptr::null_mut(), &record)?);
+    }
+
+    let filtered = records(protocol, Some(TEST_TYPE))?;
+    u_assert!(
+        filtered.iter().map(|record| record.handle).eq([handles[0], handles[2]]),
+        "GetNext should return the records of the requested type in order"
+    );
+    u_assert!(
+        filtered.iter().all(|record| record.record_type == TEST_TYPE),
+        "GetNext should only return records of the requested type"
+    );
+
+    let all = records(protocol, None)?;
+    u_assert!(
+        handles.iter().all(|handle| all.iter().any(|record| record.handle == *handle)),
+        "GetNext without a filter should return every record"
+    );
+    u_assert_eq!(
+        all.last().map(|record| record.record_type),
+        Some(127),
+        "GetNext should return the End-of-Table record last"
+    );
+
+    for handle in handles {
+        u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+    }
+    u_assert!(records(protocol, Some(TEST_TYPE))?.is_empty(), "GetNext should not return removed records");
+    Ok(())
+}
+
+/// Checks that GetNext reports the producer handle each record was added with.
+fn test_producer_handles(
+    protocol: &SmbiosProtocol,
+    boot_services: &StandardBootServices,
+) -> patina_test::error::Result {
+    let producer = *boot_services
+        .locate_handle_buffer(HandleSearchType::ByProtocol(&loaded_image::PROTOCOL_GUID))
+        .map_err(|_| "Failed to locate a loaded image")?
+        .first()
+        .ok_or("Failed to locate a loaded image")?;
+
+    let produced = add(protocol, producer, &record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[1], &["Produced"]))?;
+    let anonymous =
+        add(protocol, core::ptr::null_mut(), &record(TEST_TYPE, SMBIOS_HANDLE_PI_RESERVED, &[1], &["Anonymous"]))?;
+
+    u_assert_eq!(
+        find(protocol, produced, Some(TEST_TYPE))?.producer,
+        producer,
+        "GetNext should report the producer handle"
+    );
+    u_assert!(
+        find(protocol, anonymous, Some(TEST_TYPE))?.producer.is_null(),
+        "GetNext should report no producer for a record added without one"
+    );
+
+    for handle in [produced, anonymous] {
+        u_assert_eq!((protocol.remove)(protocol, handle), efi::Status::SUCCESS, "Protocol Remove should succeed");
+    }
+    Ok(())
+}
+
+/// Returns the bytes of a structure with `formatted` following the header, and `strings` in its string pool.
+fn record(record_type: u8, handle: SmbiosHandle, formatted: &[u8], strings: &[&str]) -> Vec<u8> {
+    let mut bytes = vec![record_type, (4 + formatted.len()) as u8];
+    bytes.extend_from_slice(&handle.to_le_bytes());
+    bytes.extend_from_slice(formatted);
+    for string in strings {
+        bytes.extend_from_slice(string.as_bytes());
+        bytes.push(0);
+    }
+    if strings.is_empty() {
+        bytes.push(0);
+    }
+    bytes.push(0);
+    bytes
+}
+
+/// Adds `record` with `producer` and returns its handle.
+fn add(protocol: &SmbiosProtocol, producer: efi::Handle, record: &[u8]) -> Result<SmbiosHandle, &'static str> {
+    let mut handle = SMBIOS_HANDLE_PI_RESERVED;
+    let status = (protocol.add)(protocol, producer, &mut handle, record.as_ptr() as *const SmbiosTableHeader);
+    u_assert_eq!(status, efi::Status::SUCCESS, "Protocol Add should succeed");
+    Ok(handle)
+}
+
+/// Adds `record`, which is expected to be rejected, and returns the status.
+fn add_status(protocol: &SmbiosProtocol, record: &[u8]) -> efi::Status {
+    let mut handle = SMBIOS_HANDLE_PI_RESERVED;
+    (protocol.add)(protocol, core::ptr::null_mut(), &mut handle, record.as_ptr() as *const SmbiosTableHeader)
+}
+
+/// Updates string `string_number` of the record with `handle` and returns the status.
+fn update_string(protocol: &SmbiosProtocol, handle: SmbiosHandle, string_number: usize, string: &CStr) -> efi::Status {
+    let (mut handle, mut string_number) = (handle, string_number);
+    (protocol.update_string)(protocol, &mut handle, &mut string_number, string.as_ptr())
+}
+
+/// Returns every record of `record_type`, or every record if it is `None`, in GetNext order.
+fn records(protocol: &SmbiosProtocol, record_type: Option<u8>) -> Result<Vec<NextRecord>, &'static str> {
+    let mut records = Vec::new();
+    let mut handle = SMBIOS_HANDLE_PI_RESERVED;
+    loop {
+        let mut filter = record_type.unwrap_or_default();
+        let filter_ptr = if record_type.is_some() { &mut filter as *mut u8 } else { core::ptr::null_mut() };
+        let mut record_ptr: *mut SmbiosTableHeader = core::ptr::null_mut();
+        let mut producer: efi::Handle = core::ptr::null_mut();
+
+        match (protocol.get_next)(protocol, &mut handle, filter_ptr, &mut record_ptr, &mut producer) {
+            efi::Status::NOT_FOUND => return Ok(records),
+            efi::Status::SUCCESS => {}
+            status => {
+                log::error!("GetNext failed: {:?}", status);
+                return Err("Protocol GetNext should succeed or find no more records");
+            }
+        }
+        u_assert!(!record_ptr.is_null(), "GetNext should return the record");
+        u_assert_eq!(filter, record_type.unwrap_or_default(), "GetNext should not change the type filter");
+
+        // SAFETY: GetNext returned a record in the published table, which stays valid until the next change.
+        let (record_type, record_handle, strings) = unsafe { read_record(record_ptr as *const u8) };
+        u_assert_eq!(record_handle, handle, "GetNext should return the record with the returned handle");
+        records.push(NextRecord { handle, record_type, strings, producer });
+    }
+}
+
+/// Returns the record with `handle`, searching the records of `record_type`.
+fn find(protocol: &SmbiosProtocol, handle: SmbiosHandle, record_type: Option<u8>) -> Result<NextRecord, &'static str> {
+    records(protocol, record_type)?
+        .into_iter()
+        .find(|record| record.handle == handle)
+        .ok_or("GetNext should return the record")
+}
+
+/// Returns the type, handle and strings of the record at `record`.
+///
+/// ## Safety
+///
+/// `record` must point to a structure in the published SMBIOS table.
+unsafe fn read_record(record: *const u8) -> (u8, SmbiosHandle, Vec<Vec<u8>>) {
+    // SAFETY: The caller guarantees that the structure is complete, so the string pool ends with a double null.
+    unsafe {
+        let header = record.cast::<SmbiosTableHeader>().read_unaligned();
+        let mut strings = Vec::new();
+        let mut string = record.add(header.length as usize);
+        while *string != 0 {
+            let bytes = CStr::from_ptr(string as *const c_char).to_bytes();
+            strings.push(bytes.to_vec());
+            string = string.add(bytes.len() + 1);
+        }
+        (header.record_type, header.handle, strings)
+    }
+}
@@ -0,0 +1,221 @@
+//! QEMU-Provided SMBIOS Tables
+//!
+//! QEMU builds SMBIOS structures from its defaults and the `-smbios` command line options, and exposes them through
+//! the fw_cfg files `etc/smbios/smbios-tables` (the structure table) and `etc/smbios/smbios-anchor` (the entry point
+//! that describes it). The platform components read both files and hand the parsed tables to
+//! [`SmbiosPlatformBuilder`](super::SmbiosPlatformBuilder), which merges them with the platform records.
+//!
+//! ## References
+//!
+//! - [QEMU Firmware Configuration (fw_cfg) Device](https://www.qemu.org/docs/master/specs/fw_cfg.html)
+//! - DMTF SMBIOS Reference Specification 3.9, section 5.2 (entry point structures)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use super::{
+    entry_point::{ANCHOR_32, ANCHOR_64},
+    table::{self, END_OF_TABLE},
+};
+
+/// fw_cfg file holding the SMBIOS structure table
+pub const TABLES_FILE: &str = "etc/smbios/smbios-tables";
+/// fw_cfg file holding the SMBIOS entry point structure
+pub const ANCHOR_FILE: &str = "etc/smbios/smbios-anchor";
+
+/// A structure from the QEMU SMBIOS table, including its string set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QemuRecord {
+    bytes: Vec<u8>,
+}
+
+impl QemuRecord {
+    /// Returns the structure type.
+    pub fn record_type(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// Returns the structure as QEMU provides it: the formatted area followed by the string set.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the formatted area of the structure.
+    pub fn formatted(&self) -> &[u8] {
+        &self.bytes[..self.bytes[1] as usize]
+    }
+
+    /// Returns the string referenced by the string number at `offset` in the formatted area.
+    ///
+    /// Returns `None` if the field is outside the structure, the string number is 0, or the string is empty or not
+    /// valid UTF-8.
+    pub fn string(&self, offset: usize) -> Option<&str> {
+        let number = *self.formatted().get(offset)? as usize;
+        if number == 0 {
+            return None;
+        }
+        let strings = &self.bytes[self.formatted().len()..];
+        let string = strings.split(|&b| b == 0).nth(number - 1)?;
+        core::str::from_utf8(string).ok().filter(|string| !string.is_empty())
+    }
+}
+
+/// The SMBIOS tables provided by QEMU.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QemuSmbiosTables {
+    version: Option<(u8, u8)>,
+    records: Vec<QemuRecord>,
+}
+
+impl QemuSmbiosTables {
+    /// Parses the contents of the anchor and tables files.
+    ///
+    /// The entry point in `anchor` limits the structure table to its declared length. Parsing stops at the
+    /// End-of-Table structure or at the first malformed structure, keeping the structures before it.
+    pub fn parse(anchor: &[u8], tables: &[u8]) -> Self {
+        let (version, table_length) = if anchor.starts_with(ANCHOR_64) && anchor.len() >= 0x10 {
+            let length = u32::from_le_bytes([anchor[0x0C], anchor[0x0D], anchor[0x0E], anchor[0x0F]]);
+            (Some((anchor[0x07], anchor[0x08])), length as usize)
+        } else if anchor.starts_with(ANCHOR_32) && anchor.len() >= 0x18 {
+            let length = u16::from_le_bytes([anchor[0x16], anchor[0x17]]);
+            (Some((anchor[0x06], anchor[0x07])), length as usize)
+        } else {
+            (None, tables.len())
+        };
+        let tables = &tables[..table_length.min(tables.len())];
+
+        let mut records = Vec::new();
+        for structure in table::structures(tables) {
+            let structure = match structure {
+                Ok(structure) => structure,
+                Err(offset) => {
+                    log::warn!("Malformed QEMU SMBIOS structure at offset {offset:#X}");
+                    break;
+                }
+            };
+            if structure.record_type() == END_OF_TABLE {
+                break;
+            }
+            records.push(QemuRecord { bytes: structure.bytes.to_vec() });
+        }
+
+        Self { version, records }
+    }
+
+    /// Returns the SMBIOS version declared by the QEMU entry point, if it was provided.
+    pub fn version(&self) -> Option<(u8, u8)> {
+        self.version
+    }
+
+    /// Returns the structures in table order, without the End-of-Table structure.
+    pub fn records(&self) -> &[QemuRecord] {
+        &self.records
+    }
+
+    /// Returns the first structure of `record_type`.
+    pub fn find(&self, record_type: u8) -> Option<&QemuRecord> {
+        self.find_instance(record_type, 0)
+    }
+
+    /// Returns the structure of `record_type` at `instance`, counting from 0 in table order.
+    pub fn find_instance(&self, record_type: u8, instance: usize) -> Option<&QemuRecord> {
+        self.records.iter().filter(|record| record.record_type() == record_type).nth(instance)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+pub(crate) mod tests {
+    extern crate std;
+    use std::vec;
+
+    use super::*;
+
+    /// Builds a structure from its type, the formatted area after the header and its strings.
+    pub(crate) fn structure(record_type: u8, handle: u16, formatted: &[u8], strings: &[&str]) -> Vec<u8> {
+        let mut bytes = vec![record_type, (4 + formatted.len()) as u8];
+        bytes.extend_from_slice(&handle.to_le_bytes());
+        bytes.extend_from_slice(formatted);
+        for string in strings {
+            bytes.extend_from_slice(string.as_bytes());
+            bytes.push(0);
+        }
+        if strings.is_empty() {
+            bytes.push(0);
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    /// Builds a SMBIOS 3.0 entry point for a table of `length` bytes.
+    pub(crate) fn anchor_64(length: u32) -> Vec<u8> {
+        let mut anchor = vec![0u8; 0x18];
+        anchor[..5].copy_from_slice(ANCHOR_64);
+        anchor[0x07] = 3;
+        anchor[0x08] = 0;
+        anchor[0x0C..0x10].copy_from_slice(&length.to_le_bytes());
+        anchor
+    }
+
+    #[test]
+    fn test_parse_structures() {
+        let type1 = structure(1, 0x100, &[1, 2, 0, 3], &["QEMU", "Standard PC", "SN-42"]);
+        let type11 = structure(11, 0x1100, &[2], &["OEM-1", "OEM-2"]);
+        let type32 = structure(32, 0x2000, &[0; 7], &[]);
+        let end = structure(127, 0x7F00, &[], &[]);
+        let tables = [type1.clone(), type11.clone(), type32.clone(), end].concat();
+
+        let parsed = QemuSmbiosTables::parse(&anchor_64(tables.len() as u32), &tables);
+        assert_eq!(parsed.version(), Some((3, 0)));
+        assert_eq!(parsed.records().len(), 3);
+        assert_eq!(parsed.records()[1].bytes(), type11);
+        assert_eq!(parsed.find(32).unwrap().bytes(), type32);
+
+        let system = parsed.find(1).unwrap();
+        assert_eq!(system.formatted().len(), 8);
+        assert_eq!(system.string(4), Some("QEMU"));
+        assert_eq!(system.string(5), Some("Standard PC"));
+        assert_eq!(system.string(6), None);
+        assert_eq!(system.string(7), Some("SN-42"));
+        assert_eq!(system.string(8), None);
+    }
+
+    #[test]
+    fn test_parse_honors_the_entry_point_length() {
+        let type1 = structure(1, 0x100, &[1], &["QEMU"]);
+        let type11 = structure(11, 0x1100, &[1], &["OEM"]);
+        let tables = [type1.clone(), type11].concat();
+
+        let parsed = QemuSmbiosTables::parse(&anchor_64(type1.len() as u32), &tables);
+        assert_eq!(parsed.records().len(), 1);
+
+        let mut anchor_32 = vec![0u8; 0x1F];
+        anchor_32[..4].copy_from_slice(ANCHOR_32);
+        anchor_32[0x06] = 2;
+        anchor_32[0x07] = 8;
+        anchor_32[0x16..0x18].copy_from_slice(&(type1.len() as u16).to_le_bytes());
+        let parsed = QemuSmbiosTables::parse(&anchor_32, &tables);
+        assert_eq!(parsed.version(), Some((2, 8)));
+        assert_eq!(parsed.records().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_stops_at_malformed_structures() {
+        let type1 = structure(1, 0x100, &[1], &["QEMU"]);
+
+        let truncated = [type1.clone(), vec![11, 0x20, 0, 0, 1]].concat();
+        assert_eq!(QemuSmbiosTables::parse(&[], &truncated).records().len(), 1);
+
+        let unterminated = [type1.clone(), vec![11, 5, 0, 0, 1, b'O']].concat();
+        assert_eq!(QemuSmbiosTables::parse(&[], &unterminated).records().len(), 1);
+
+        assert_eq!(QemuSmbiosTables::parse(&[], &[]), QemuSmbiosTables::default());
+    }
+}
@@ -0,0 +1,353 @@
+//! SMBIOS Record Structures
+//!
+//! Record structures for the SMBIOS types the QEMU platforms publish that `patina_smbios` does not define, or defines
+//! with a layout older than the table version. They use the same `SmbiosRecord` derive as the `patina_smbios` records,
+//! so they are added through [`SmbiosPlatformBuilder::add_record`](super::SmbiosPlatformBuilder::add_record) like any
+//! other record.
+//!
+//! Type 45 ends in a variable-length list of handles, which the derive cannot serialize, so it implements
+//! `SmbiosRecordStructure` by hand.
+//!
+//! ## References
+//!
+//! - DMTF SMBIOS Reference Specification 3.9
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{string::String, vec::Vec};
+
+use patina_macro::SmbiosRecord;
+use patina_smbios::{
+    error::SmbiosError,
+    service::{SMBIOS_STRING_MAX_LENGTH, SmbiosHandle, SmbiosTableHeader},
+    smbios_record::SmbiosRecordStructure,
+    smbios_types::{
+        BootUpState, CurrentUsage, DeviceFunctionNumber, PowerSupplyState, ProcessorCharacteristics,
+        ProcessorFamilyData, ProcessorInformationStatus, ProcessorTypeData, ProcessorUpgrade, ProcessorVoltage,
+        SecurityStatus, SlotCharacteristics1, SlotCharacteristics2, SlotLength, SlotType, SlotWidth, ThermalState,
+    },
+};
+
+/// Type 3: System Enclosure (SMBIOS 2.7 layout, without contained elements)
+#[derive(SmbiosRecord)]
+#[smbios(record_type = 3)]
+pub struct Type3SystemEnclosure {
+    /// SMBIOS table header
+    pub header: SmbiosTableHeader,
+    /// Manufacturer string index
+    pub manufacturer: u8,
+    /// Enclosure type
+    pub enclosure_type: u8,
+    /// Version string index
+    pub version: u8,
+    /// Serial number string index
+    pub serial_number: u8,
+    /// Asset tag number string index
+    pub asset_tag_number: u8,
+    /// Boot-up state
+    pub bootup_state: BootUpState,
+    /// Power supply state
+    pub power_supply_state: PowerSupplyState,
+    /// Thermal state
+    pub thermal_state: ThermalState,
+    /// Security status
+    pub security_status: SecurityStatus,
+    /// OEM-defined
+    pub oem_defined: u32,
+    /// Height in rack units, or 0 if unspecified
+    pub height: u8,
+    /// Number of power cords
+    pub number_of_power_cords: u8,
+    /// Contained element count. Always 0, as the record has no contained elements.
+    pub contained_element_count: u8,
+    /// Contained element record length
+    pub contained_element_record_length: u8,
+    /// SKU number string index (SMBIOS 2.7)
+    pub sku_number: u8,
+
+    /// String pool (NOT part of binary SMBIOS format)
+    #[string_pool]
+    pub string_pool: Vec<String>,
+}
+
+/// Type 4: Processor Information (SMBIOS 3.8 layout)
+#[derive(SmbiosRecord)]
+#[smbios(record_type = 4)]
+pub struct Type4ProcessorInformation {
+    /// SMBIOS table header
+    pub header: SmbiosTableHeader,
+    /// Socket designation string index
+    pub socket_designation: u8,
+    /// Processor type
+    pub processor_type: ProcessorTypeData,
+    /// Processor family, or 0xFE to use `processor_family2`
+    pub processor_family: u8,
+    /// Processor manufacturer string index
+    pub processor_manufacturer: u8,
+    /// Processor ID
+    pub processor_id: [u8; 8],
+    /// Processor version string index
+    pub processor_version: u8,
+    /// Voltage
+    pub voltage: ProcessorVoltage,
+    /// External clock frequency in MHz
+    pub external_clock: u16,
+    /// Max speed in MHz
+    pub max_speed: u16,
+    /// Current speed in MHz
+    pub current_speed: u16,
+    /// Status
+    pub status: ProcessorInformationStatus,
+    /// Processor upgrade
+    pub processor_upgrade: ProcessorUpgrade,
+    /// L1 cache handle
+    pub l1_cache_handle: u16,
+    /// L2 cache handle
+    pub l2_cache_handle: u16,
+    /// L3 cache handle
+    pub l3_cache_handle: u16,
+    /// Serial number string index
+    pub serial_number: u8,
+    /// Asset tag string index
+    pub asset_tag: u8,
+    /// Part number string index
+    pub part_number: u8,
+    /// Core count
+    pub core_count: u8,
+    /// Core enabled
+    pub core_enabled: u8,
+    /// Thread count
+    pub thread_count: u8,
+    /// Processor characteristics
+    pub processor_characteristics: ProcessorCharacteristics,
+    /// Processor family 2
+    pub processor_family2: ProcessorFamilyData,
+    /// Core count 2 (SMBIOS 3.0)
+    pub core_count2: u16,
+    /// Core enabled 2 (SMBIOS 3.0)
+    pub core_enabled2: u16,
+    /// Thread count 2 (SMBIOS 3.0)
+    pub thread_count2: u16,
+    /// Thread enabled (SMBIOS 3.6)
+    pub thread_enabled: u16,
+    /// Socket type string index (SMBIOS 3.8)
+    pub socket_type: u8,
+
+    /// String pool (NOT part of binary SMBIOS format)
+    #[string_pool]
+    pub string_pool: Vec<String>,
+}
+
+/// Type 9: System Slots (SMBIOS 3.5 layout, without peer groups)
+#[derive(SmbiosRecord)]
+#[smbios(record_type = 9)]
+pub struct Type9SystemSlots {
+    /// SMBIOS table header
+    pub header: SmbiosTableHeader,
+    /// Slot designation string index
+    pub slot_designation: u8,
+    /// Slot type
+    pub slot_type: SlotType,
+    /// Slot data bus width
+    pub slot_data_bus_width: SlotWidth,
+    /// Current usage
+    pub current_usage: CurrentUsage,
+    /// Slot length
+    pub slot_length: SlotLength,
+    /// Slot ID
+    pub slot_id: u16,
+    /// Slot characteristics 1
+    pub slot_characteristics1: SlotCharacteristics1,
+    /// Slot characteristics 2
+    pub slot_characteristics2: SlotCharacteristics2,
+    /// Segment group number
+    pub segment_group_number: u16,
+    /// Bus number
+    pub bus_number: u8,
+    /// Device and function number
+    pub device_function_number: DeviceFunctionNumber,
+    /// Data bus width, in lanes (SMBIOS 3.2)
+    pub data_bus_width: u8,
+    /// Peer grouping count. Always 0, as the record has no peer groups.
+    pub peer_grouping_count: u8,
+    /// Slot information, the PCI Express generation for PCI Express slots (SMBIOS 3.4)
+    pub slot_information: u8,
+    /// Slot physical width (SMBIOS 3.4)
+    pub slot_physical_width: SlotWidth,
+    /// Slot pitch in 1/100 mm, or 0 if unknown (SMBIOS 3.4)
+    pub slot_pitch: u16,
+    /// Slot height (SMBIOS 3.5)
+    pub slot_height: u8,
+
+    /// String pool (NOT part of binary SMBIOS format)
+    #[string_pool]
+    pub string_pool: Vec<String>,
+}
+
+/// Type 41: Onboard Devices Extended Information
+#[derive(SmbiosRecord)]
+#[smbios(record_type = 41)]
+pub struct Type41OnboardDevicesExtended {
+    /// SMBIOS table header
+    pub header: SmbiosTableHeader,
+    /// Reference designation string index
+    pub reference_designation: u8,
+    /// Device type in bits 6:0 and device status (enabled) in bit 7
+    pub device_type: u8,
+    /// Device type instance
+    pub device_type_instance: u8,
+    /// Segment group number
+    pub segment_group_number: u16,
+    /// Bus number
+    pub bus_number: u8,
+    /// Device and function number
+    pub device_function_number: DeviceFunctionNumber,
+
+    /// String pool (NOT part of binary SMBIOS format)
+    #[string_pool]
+    pub string_pool: Vec<String>,
+}
+
+/// Type 11: OEM Strings
+#[derive(SmbiosRecord)]
+#[smbios(record_type = 11)]
+pub struct Type11OemStrings {
+    /// SMBIOS table header
+    pub header: SmbiosTableHeader,
+    /// Number of strings
+    pub count: u8,
+
+    /// String pool (NOT part of binary SMBIOS format)
+    #[string_pool]
+    pub string_pool: Vec<String>,
+}
+
+/// Type 12: System Configuration Options
+#[derive(SmbiosRecord)]
+#[smbios(record_type = 12)]
+pub struct Type12SystemConfigurationOptions {
+    /// SMBIOS table header
+    pub header: SmbiosTableHeader,
+    /// Number of strings
+    pub count: u8,
+
+    /// String pool (NOT part of binary SMBIOS format)
+    #[string_pool]
+    pub string_pool: Vec<String>,
+}
+
+/// Type 32: System Boot Information
+#[derive(SmbiosRecord)]
+#[smbios(record_type = 32)]
+pub struct Type32SystemBootInformation {
+    /// SMBIOS table header
+    pub header: SmbiosTableHeader,
+    /// Reserved, all zeros
+    pub reserved: [u8; 6],
+    /// Boot status, one of [`boot_status`](super::boot_status)
+    pub boot_status: u8,
+
+    /// String pool (NOT part of binary SMBIOS format)
+    #[string_pool]
+    pub string_pool: Vec<String>,
+}
+
+/// Type 45: Firmware Inventory Information (SMBIOS 3.5)
+pub struct Type45FirmwareInventoryInformation {
+    /// SMBIOS table header
+    pub header: SmbiosTableHeader,
+    /// Firmware component name string index
+    pub firmware_component_name: u8,
+    /// Firmware version string index
+    pub firmware_version: u8,
+    /// Format of the firmware version string
+    pub version_format: u8,
+    /// Firmware ID string index
+    pub firmware_id: u8,
+    /// Format of the firmware ID string
+    pub firmware_id_format: u8,
+    /// Release date string index, in `YYYY-MM-DD` form
+    pub release_date: u8,
+    /// Manufacturer string index
+    pub manufacturer: u8,
+    /// Lowest supported firmware version string index
+    pub lowest_supported_version: u8,
+    /// Image size in bytes, or `u64::MAX` if unknown
+    pub image_size: u64,
+    /// Characteristics: bit 0 updatable, bit 1 write-protected
+    pub characteristics: u16,
+    /// State of the firmware component
+    pub state: u8,
+    /// Handles of the structures the firmware component is associated with
+    pub associated_components: Vec<SmbiosHandle>,
+
+    /// String pool (NOT part of binary SMBIOS format)
+    pub string_pool: Vec<String>,
+}
+
+impl Type45FirmwareInventoryInformation {
+    /// Length of the structure without the associated component handles
+    const BASE_LENGTH: usize = 0x18;
+}
+
+impl SmbiosRecordStructure for Type45FirmwareInventoryInformation {
+    const RECORD_TYPE: u8 = 45;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.push(Self::RECORD_TYPE);
+        bytes.push((Self::BASE_LENGTH + self.associated_components.len() * 2) as u8);
+        bytes.extend_from_slice(&self.header.handle.to_le_bytes());
+        bytes.extend_from_slice(&[
+            self.firmware_component_name,
+            self.firmware_version,
+            self.version_format,
+            self.firmware_id,
+            self.firmware_id_format,
+            self.release_date,
+            self.manufacturer,
+            self.lowest_supported_version,
+        ]);
+        bytes.extend_from_slice(&self.image_size.to_le_bytes());
+        bytes.extend_from_slice(&self.characteristics.to_le_bytes());
+        bytes.push(self.state);
+        bytes.push(self.associated_components.len() as u8);
+        for handle in &self.associated_components {
+            bytes.extend_from_slice(&handle.to_le_bytes());
+        }
+
+        if self.string_pool.is_empty() {
+            bytes.push(0);
+        }
+        for string in &self.string_pool {
+            bytes.extend_from_slice(string.as_bytes());
+            bytes.push(0);
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    fn validate(&self) -> Result<(), SmbiosError> {
+        if Self::BASE_LENGTH + self.associated_components.len() * 2 > u8::MAX as usize {
+            return Err(SmbiosError::MalformedRecordHeader);
+        }
+        if self.string_pool.iter().any(|string| string.len() > SMBIOS_STRING_MAX_LENGTH) {
+            return Err(SmbiosError::StringTooLong);
+        }
+        Ok(())
+    }
+
+    fn string_pool(&self) -> &[String] {
+        &self.string_pool
+    }
+
+    fn string_pool_mut(&mut self) -> &mut Vec<String> {
+        &mut self.string_pool
+    }
+}
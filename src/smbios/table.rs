@@ -0,0 +1,142 @@
+//! SMBIOS Structure Table
+//!
+//! Splits an SMBIOS structure table into its structures. Each structure is a formatted area, whose length is in its
+//! header, followed by a string set that ends in a double null. The table ends with the End-of-Table structure.
+//!
+//! The QEMU tables, the 32-bit entry point and the compliance checks all walk structure tables with [`structures`].
+//!
+//! ## References
+//!
+//! - DMTF SMBIOS Reference Specification 3.9, section 6.1 (structure format)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+/// Type of the End-of-Table structure
+pub const END_OF_TABLE: u8 = 127;
+
+/// A structure of a structure table, including its string set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableStructure<'a> {
+    /// Offset of the structure in the table
+    pub offset: usize,
+    /// The formatted area followed by the string set and its double-null terminator
+    pub bytes: &'a [u8],
+}
+
+impl<'a> TableStructure<'a> {
+    /// Returns the structure type.
+    pub fn record_type(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// Returns the formatted area of the structure.
+    pub fn formatted(&self) -> &'a [u8] {
+        &self.bytes[..self.bytes[1] as usize]
+    }
+
+    /// Returns the strings of the string set, without their null terminators.
+    ///
+    /// A string set without strings is a lone double null, otherwise each string ends in a null. Strings cannot be
+    /// empty, as an empty string would end the set.
+    pub fn strings(&self) -> impl Iterator<Item = &'a [u8]> {
+        let string_set = &self.bytes[self.formatted().len()..self.bytes.len() - 2];
+        string_set.split(|&b| b == 0).filter(move |_| !string_set.is_empty())
+    }
+
+    /// Returns the offset of the first byte after the structure.
+    pub fn end(&self) -> usize {
+        self.offset + self.bytes.len()
+    }
+}
+
+/// Iterator over the structures of a structure table, created by [`structures`].
+#[derive(Debug, Clone)]
+pub struct Structures<'a> {
+    table: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> Iterator for Structures<'a> {
+    /// The structure, or the offset of a structure that is truncated or whose string set is not terminated
+    type Item = Result<TableStructure<'a>, usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let offset = self.offset;
+        let &[record_type, length, ..] = self.table.get(offset..)? else {
+            return None;
+        };
+
+        let length = length as usize;
+        let terminator =
+            self.table.get(offset + length..).and_then(|strings| strings.windows(2).position(|w| w == [0, 0]));
+        let Some(terminator) = terminator.filter(|_| length >= 4) else {
+            self.done = true;
+            return Some(Err(offset));
+        };
+
+        self.offset = offset + length + terminator + 2;
+        self.done = record_type == END_OF_TABLE;
+        Some(Ok(TableStructure { offset, bytes: &self.table[offset..self.offset] }))
+    }
+}
+
+/// Returns the structures of `table`, in table order.
+///
+/// The iterator ends after the End-of-Table structure, after the first malformed structure, or when fewer than two
+/// bytes are left. Bytes after the End-of-Table structure are not read.
+pub fn structures(table: &[u8]) -> Structures<'_> {
+    Structures { table, offset: 0, done: false }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::smbios::qemu::tests::structure;
+
+    #[test]
+    fn test_structures() {
+        let table = [
+            structure(1, 0x0001, &[1, 2], &["Product", "Version"]),
+            structure(2, 0x0002, &[], &[]),
+            structure(END_OF_TABLE, 0xFEFF, &[], &[]),
+            structure(3, 0x0003, &[], &[]),
+        ]
+        .concat();
+
+        let structures: Vec<_> = structures(&table).map(Result::unwrap).collect();
+        assert_eq!(structures.len(), 3);
+        assert_eq!(structures[0].record_type(), 1);
+        assert_eq!(structures[0].formatted(), &[1, 6, 1, 0, 1, 2]);
+        assert_eq!(structures[0].strings().collect::<Vec<_>>(), [b"Product".as_slice(), b"Version"]);
+        assert_eq!(structures[1].offset, structures[0].end());
+        assert_eq!(structures[1].strings().count(), 0);
+        assert_eq!(structures[2].record_type(), END_OF_TABLE);
+    }
+
+    #[test]
+    fn test_structures_stop_at_malformed_structures() {
+        // The second structure is shorter than its header
+        let table = [structure(1, 0x0001, &[], &[]), [2, 3, 0, 0, 0, 0].to_vec()].concat();
+        let mut walker = structures(&table);
+        assert!(walker.next().unwrap().is_ok());
+        assert_eq!(walker.next(), Some(Err(6)));
+        assert_eq!(walker.next(), None);
+
+        let unterminated = structure(1, 0x0001, &[], &["Product"]);
+        assert_eq!(structures(&unterminated[..unterminated.len() - 1]).next(), Some(Err(0)));
+        assert_eq!(structures(&[]).next(), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! SMBIOS System UUID
+//!
+//! The Type 1 UUID identifies the system to asset management and OS activation. QEMU passes its `-uuid` option to the
+//! firmware through the fw_cfg UUID item on both platforms, in RFC 4122 byte order, and repeats it in the Type 1
+//! structure of its SMBIOS tables. The device tree QEMU generates for the Arm Virt machine does not carry it.
+//!
+//! When QEMU is started without `-uuid`, the fw_cfg item is all zeros. The platforms then use a random UUID generated
+//! on the first boot and persisted in a UEFI variable, so the system keeps its identity across boots.
+//!
+//! SMBIOS stores the `time_low`, `time_mid` and `time_hi_and_version` fields little-endian, while RFC 4122 stores every
+//! field big-endian. [`SystemUuid`] keeps the RFC 4122 order and converts when the record is built.
+//!
+//! ## References
+//!
+//! - DMTF SMBIOS Reference Specification 3.9, section 7.2.1 (System UUID)
+//! - [RFC 4122](https://www.rfc-editor.org/rfc/rfc4122)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use patina::{pi::error_codes::EFI_NOT_AVAILABLE_YET, runtime_services::RuntimeServices};
+use r_efi::efi;
+
+use super::QemuSmbiosTables;
+use crate::variable_services;
+
+/// Offset of the UUID in the Type 1 structure
+const TYPE1_UUID_OFFSET: usize = 0x08;
+
+/// Name of the variable holding the generated fallback UUID
+const FALLBACK_VARIABLE_NAME: [u16; 17] = variable_services::ucs2_name(b"SmbiosSystemUuid\0");
+/// Vendor GUID of the variable holding the generated fallback UUID
+pub const FALLBACK_VARIABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x6b1f2a4c, 0x93d7, 0x4e15, 0xa8, 0x3b, &[0x5c, 0x0e, 0x71, 0xd2, 0x94, 0x6f]);
+/// Attributes of the variable holding the generated fallback UUID
+const FALLBACK_VARIABLE_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS;
+
+/// A system UUID, stored in RFC 4122 byte order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemUuid([u8; 16]);
+
+impl SystemUuid {
+    /// Returns the UUID with the given bytes in RFC 4122 order.
+    ///
+    /// Returns `None` for all zeros and all ones, which SMBIOS reserves for a UUID that is not present and not set.
+    pub fn from_bytes(bytes: [u8; 16]) -> Option<Self> {
+        (bytes != [0; 16] && bytes != [0xFF; 16]).then_some(Self(bytes))
+    }
+
+    /// Returns the UUID with the given bytes in SMBIOS order, or `None` if it is not present or not set.
+    pub fn from_smbios_bytes(bytes: [u8; 16]) -> Option<Self> {
+        Self::from_bytes(swap_fields(bytes))
+    }
+
+    /// Generates a random (version 4) UUID by expanding `seed`.
+    pub fn generate(seed: u64) -> Self {
+        let mut state = seed;
+        let mut bytes = [0u8; 16];
+        for chunk in bytes.chunks_exact_mut(8) {
+            chunk.copy_from_slice(&splitmix64(&mut state).to_be_bytes());
+        }
+        bytes[6] = (bytes[6] & 0x0F) | 0x40; // Version 4
+        bytes[8] = (bytes[8] & 0x3F) | 0x80; // RFC 4122 variant
+        Self(bytes)
+    }
+
+    /// Returns the bytes in RFC 4122 order.
+    pub fn bytes(&self) -> [u8; 16] {
+        self.0
+    }
+
+    /// Returns the bytes in SMBIOS order, for the Type 1 `UUID` field.
+    pub fn to_smbios_bytes(&self) -> [u8; 16] {
+        swap_fields(self.0)
+    }
+}
+
+impl fmt::Display for SystemUuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reverses the byte order of the first three fields, converting between RFC 4122 and SMBIOS order.
+fn swap_fields(mut bytes: [u8; 16]) -> [u8; 16] {
+    bytes[0..4].reverse();
+    bytes[4..6].reverse();
+    bytes[6..8].reverse();
+    bytes
+}
+
+/// Advances a SplitMix64 generator and returns its next output.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Returns the system UUID QEMU was started with, or `fallback` if QEMU does not provide one.
+///
+/// The fw_cfg UUID item, `fw_cfg_uuid`, takes precedence over the UUID of the QEMU Type 1 structure.
+pub fn resolve(
+    fw_cfg_uuid: [u8; 16],
+    tables: &QemuSmbiosTables,
+    fallback: impl FnOnce() -> Option<SystemUuid>,
+) -> Option<SystemUuid> {
+    SystemUuid::from_bytes(fw_cfg_uuid)
+        .or_else(|| {
+            let uuid = tables.find(1)?.formatted().get(TYPE1_UUID_OFFSET..TYPE1_UUID_OFFSET + 16)?;
+            SystemUuid::from_smbios_bytes(uuid.try_into().ok()?)
+        })
+        .or_else(fallback)
+}
+
+/// Returns the fallback UUID persisted in a UEFI variable.
+///
+/// On the first boot, or if the variable does not hold a valid UUID, generates a UUID from `seed` and stores it. If
+/// the variable cannot be written, the generated UUID only identifies the system until the next boot.
+///
+/// Returns `None`, without generating a UUID, if the variable services are not installed yet. The caller waits for the
+/// Variable Write architectural protocol, see [`variable_services`].
+pub fn persisted_fallback(runtime_services: &impl RuntimeServices, seed: impl FnOnce() -> u64) -> Option<SystemUuid> {
+    match runtime_services.get_variable::<Vec<u8>>(&FALLBACK_VARIABLE_NAME, &FALLBACK_VARIABLE_GUID, Some(16)) {
+        Ok((data, _)) => match <[u8; 16]>::try_from(data.as_slice()).ok().and_then(SystemUuid::from_bytes) {
+            Some(uuid) => return Some(uuid),
+            None => log::warn!("Persisted system UUID is invalid, generating a new one"),
+        },
+        Err(efi::Status::NOT_FOUND) => {}
+        Err(status) if status == efi::Status::from_usize(EFI_NOT_AVAILABLE_YET) => {
+            log::error!("Variable services are not available, the system UUID is not reported");
+            return None;
+        }
+        Err(status) => log::warn!("Failed to read the persisted system UUID: {status:?}"),
+    }
+
+    let uuid = SystemUuid::generate(seed());
+    match runtime_services.set_variable(
+        &FALLBACK_VARIABLE_NAME,
+        &FALLBACK_VARIABLE_GUID,
+        FALLBACK_VARIABLE_ATTRIBUTES,
+        &uuid.bytes(),
+    ) {
+        Ok(()) => log::info!("Generated system UUID {uuid}"),
+        Err(status) => log::warn!("Failed to persist the generated system UUID {uuid}: {status:?}"),
+    }
+    Some(uuid)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use core::cell::{Cell, RefCell};
+    use std::string::ToString;
+
+    use patina::runtime_services::variable_services::{GetVariableStatus, VariableInfo};
+
+    use super::*;
+    use crate::smbios::qemu;
+
+    const RFC_4122: [u8; 16] =
+        [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+    const SMBIOS: [u8; 16] =
+        [0x78, 0x56, 0x34, 0x12, 0xBC, 0x9A, 0xF0, 0xDE, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+
+    #[test]
+    fn test_smbios_encoding_is_mixed_endian() {
+        let uuid = SystemUuid::from_bytes(RFC_4122).unwrap();
+        assert_eq!(uuid.to_smbios_bytes(), SMBIOS);
+        assert_eq!(SystemUuid::from_smbios_bytes(SMBIOS), Some(uuid));
+        assert_eq!(uuid.to_string(), "12345678-9abc-def0-0123-456789abcdef");
+
+        assert_eq!(SystemUuid::from_bytes([0; 16]), None);
+        assert_eq!(SystemUuid::from_bytes([0xFF; 16]), None);
+    }
+
+    #[test]
+    fn test_generated_uuid_is_version_4() {
+        let uuid = SystemUuid::generate(0x1234).bytes();
+        assert_eq!(uuid[6] >> 4, 4);
+        assert_eq!(uuid[8] >> 6, 0b10);
+        assert_eq!(SystemUuid::generate(0x1234).bytes(), uuid);
+        assert_ne!(SystemUuid::generate(0x1235).bytes(), uuid);
+    }
+
+    #[test]
+    fn test_resolve_prefers_qemu() {
+        let fallback = SystemUuid::generate(1);
+        let mut formatted = [0u8; 0x17];
+        formatted[TYPE1_UUID_OFFSET - 4..TYPE1_UUID_OFFSET + 12].copy_from_slice(&SMBIOS);
+        let type1 = qemu::tests::structure(1, 0x100, &formatted, &[]);
+        let tables = QemuSmbiosTables::parse(&qemu::tests::anchor_64(type1.len() as u32), &type1);
+
+        let other = SystemUuid::generate(2);
+        assert_eq!(resolve(other.bytes(), &tables, || Some(fallback)), Some(other));
+        assert_eq!(resolve([0; 16], &tables, || Some(fallback)).map(|uuid| uuid.bytes()), Some(RFC_4122));
+        assert_eq!(resolve([0; 16], &QemuSmbiosTables::default(), || Some(fallback)), Some(fallback));
+        assert_eq!(resolve([0; 16], &QemuSmbiosTables::default(), || None), None);
+    }
+
+    /// Runtime services with a single variable slot and a fixed status for reads of a missing variable.
+    struct FakeVariables {
+        data: RefCell<Option<Vec<u8>>>,
+        missing: efi::Status,
+        writes: Cell<usize>,
+    }
+
+    impl FakeVariables {
+        fn new(data: Option<Vec<u8>>, missing: efi::Status) -> Self {
+            Self { data: RefCell::new(data), missing, writes: Cell::new(0) }
+        }
+    }
+
+    impl RuntimeServices for FakeVariables {
+        unsafe fn set_variable_unchecked(
+            &self,
+            _name: &mut [u16],
+            namespace: &efi::Guid,
+            _attributes: u32,
+            data: &[u8],
+        ) -> Result<(), efi::Status> {
+            assert_eq!(*namespace, FALLBACK_VARIABLE_GUID);
+            self.writes.set(self.writes.get() + 1);
+            *self.data.borrow_mut() = Some(data.to_vec());
+            Ok(())
+        }
+
+        unsafe fn get_variable_unchecked(
+            &self,
+            _name: &mut [u16],
+            _namespace: &efi::Guid,
+            data: Option<&mut [u8]>,
+        ) -> GetVariableStatus {
+            let Some(stored) = self.data.borrow().clone() else {
+                return GetVariableStatus::Error(self.missing);
+            };
+            match data {
+                Some(data) if data.len() >= stored.len() => {
+                    data[..stored.len()].copy_from_slice(&stored);
+                    GetVariableStatus::Success { data_size: stored.len(), attributes: FALLBACK_VARIABLE_ATTRIBUTES }
+                }
+                _ => GetVariableStatus::BufferTooSmall {
+                    data_size: stored.len(),
+                    attributes: FALLBACK_VARIABLE_ATTRIBUTES,
+                },
+            }
+        }
+
+        unsafe fn get_next_variable_name_unchecked(
+            &self,
+            _prev_name: &[u16],
+            _prev_namespace: &efi::Guid,
+            _next_name: &mut Vec<u16>,
+            _next_namespace: &mut efi::Guid,
+        ) -> Result<(), efi::Status> {
+            unimplemented!()
+        }
+
+        fn query_variable_info(&self, _attributes: u32) -> Result<VariableInfo, efi::Status> {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_persisted_fallback_generates_and_stores_once() {
+        let variables = FakeVariables::new(None, efi::Status::NOT_FOUND);
+
+        let uuid = persisted_fallback(&variables, || 0x1234).unwrap();
+        assert_eq!(uuid, SystemUuid::generate(0x1234));
+        assert_eq!(variables.writes.get(), 1);
+
+        assert_eq!(persisted_fallback(&variables, || panic!("The persisted UUID should be used")), Some(uuid));
+        assert_eq!(variables.writes.get(), 1);
+    }
+
+    #[test]
+    fn test_persisted_fallback_replaces_an_invalid_uuid() {
+        let variables = FakeVariables::new(Some(std::vec![0; 16]), efi::Status::NOT_FOUND);
+
+        assert_eq!(persisted_fallback(&variables, || 7), Some(SystemUuid::generate(7)));
+        assert_eq!(variables.writes.get(), 1);
+    }
+
+    #[test]
+    fn test_persisted_fallback_waits_for_the_variable_services() {
+        let variables = FakeVariables::new(None, efi::Status::from_usize(EFI_NOT_AVAILABLE_YET));
+
+        assert_eq!(persisted_fallback(&variables, || panic!("No UUID should be generated")), None);
+        assert_eq!(variables.writes.get(), 0);
+    }
+}
@@ -0,0 +1,90 @@
+//! UEFI Variable Services Availability
+//!
+//! Components that store state in UEFI variables can be dispatched before the variable services are installed. Until
+//! then, the runtime services table holds the DXE core placeholders, which return `EFI_NOT_AVAILABLE_YET`. The
+//! variable services driver installs the Variable architectural protocol once variables can be read, and the Variable
+//! Write architectural protocol once non-volatile variables can be written. [`on_variable_write`] defers the work
+//! that needs variables until the latter is installed.
+//!
+//! ## References
+//!
+//! - UEFI Platform Initialization Specification 1.8, Volume 2, section 12.9 (Variable Architectural Protocols)
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::boxed::Box;
+
+use patina::boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl};
+use r_efi::efi;
+
+/// Variable Architectural Protocol GUID
+pub const VARIABLE_ARCH_PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x1e5668e2, 0x8481, 0x11d4, 0xbc, 0xf1, &[0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81]);
+/// Variable Write Architectural Protocol GUID
+pub const VARIABLE_WRITE_ARCH_PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x6441f818, 0x6362, 0x4e44, 0xb5, 0x70, &[0x7d, 0xba, 0x31, 0xdd, 0x24, 0x53]);
+
+/// Returns the null-terminated UCS-2 variable name of the null-terminated ASCII `name`.
+pub const fn ucs2_name<const N: usize>(name: &[u8; N]) -> [u16; N] {
+    let mut ucs2 = [0u16; N];
+    let mut index = 0;
+    while index < N {
+        ucs2[index] = name[index] as u16;
+        index += 1;
+    }
+    ucs2
+}
+
+/// Work waiting for the Variable Write architectural protocol.
+struct Pending<T: 'static> {
+    boot_services: StandardBootServices,
+    context: T,
+    callback: fn(&'static T),
+}
+
+/// Calls `callback` with `context` once the Variable Write architectural protocol is installed.
+///
+/// `callback` is called before returning if the protocol is already installed, and otherwise from the notification
+/// of its installation, at `TPL_CALLBACK`. The context is kept for the lifetime of the firmware.
+///
+/// ## Errors
+///
+/// - The status returned by the boot services if the protocol notification could not be registered.
+pub fn on_variable_write<T: 'static>(
+    boot_services: &StandardBootServices,
+    context: T,
+    callback: fn(&'static T),
+) -> Result<(), efi::Status> {
+    let pending: &'static Pending<T> =
+        Box::leak(Box::new(Pending { boot_services: boot_services.clone(), context, callback }));
+
+    if boot_services.locate_protocol_marker(&VARIABLE_WRITE_ARCH_PROTOCOL_GUID, None).is_ok() {
+        callback(&pending.context);
+        return Ok(());
+    }
+
+    log::debug!("Variable services are not installed yet, waiting for the Variable Write Arch Protocol");
+    let event =
+        boot_services.create_event(EventType::NOTIFY_SIGNAL, Tpl::CALLBACK, Some(variable_write_callback), pending)?;
+    if let Err(status) = boot_services.register_protocol_notify(&VARIABLE_WRITE_ARCH_PROTOCOL_GUID, event) {
+        let _ = boot_services.close_event(event);
+        return Err(status);
+    }
+    Ok(())
+}
+
+/// Event notification that runs the pending work once the Variable Write architectural protocol is installed.
+extern "efiapi" fn variable_write_callback<T: 'static>(event: efi::Event, pending: &'static Pending<T>) {
+    if pending.boot_services.locate_protocol_marker(&VARIABLE_WRITE_ARCH_PROTOCOL_GUID, None).is_err() {
+        return;
+    }
+    let _ = pending.boot_services.close_event(event);
+
+    (pending.callback)(&pending.context);
+}